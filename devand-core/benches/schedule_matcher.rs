@@ -0,0 +1,133 @@
+//! Compare the bitset DayScheduleMatrix with the previous implementation,
+//! a Vec<bool> indexed by user id, on users with random schedules.
+//!
+//! Run with `cargo bench -p devand-core`
+
+use devand_core::schedule_matcher::{DayScheduleMatrix, RESOLUTION};
+use devand_core::{DaySchedule, UserId};
+use std::time::{Duration, Instant};
+
+const SLOTS_IN_DAY: usize = RESOLUTION.slots_in_day();
+const SLOTS_IN_HOUR: usize = RESOLUTION.slots_per_hour();
+
+/// Previous implementation: one bool for each slot of each user id, from 0
+/// to the max user id
+struct NaiveDayScheduleMatrix {
+    data: Vec<bool>,
+}
+
+impl From<Vec<(UserId, DaySchedule)>> for NaiveDayScheduleMatrix {
+    fn from(us: Vec<(UserId, DaySchedule)>) -> Self {
+        let max_user_id = us.iter().map(|x| (x.0).0).max().unwrap_or(0);
+        let size = (1 + max_user_id as usize) * SLOTS_IN_DAY;
+
+        let mut data = vec![false; size];
+
+        for (UserId(i), day) in us.iter() {
+            let day = day.to_resolution(RESOLUTION);
+            for (s, &in_schedule) in day.slots.iter().enumerate() {
+                data[(*i as usize) * SLOTS_IN_DAY + s] = in_schedule;
+            }
+        }
+
+        Self { data }
+    }
+}
+
+impl NaiveDayScheduleMatrix {
+    fn get_available_in_slots(&self, slots: std::ops::Range<usize>) -> Vec<UserId> {
+        (0..self.data.len() / SLOTS_IN_DAY)
+            .filter(|i| {
+                let offset = i * SLOTS_IN_DAY;
+                self.data[(offset + slots.start)..(offset + slots.end)]
+                    .iter()
+                    .all(|available| *available)
+            })
+            .map(|i| UserId(i as i32))
+            .collect()
+    }
+}
+
+/// Small linear congruential generator, to have reproducible schedules
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self) -> u64 {
+        self.0 = self
+            .0
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        self.0 >> 33
+    }
+}
+
+/// Users available for a few random hours per day. Ids are sparse, like
+/// in a database where some users have been deleted.
+fn random_schedules(users: usize) -> Vec<(UserId, DaySchedule)> {
+    let mut rng = Lcg(users as u64);
+
+    (0..users)
+        .map(|i| {
+            let mut day = DaySchedule::empty(RESOLUTION);
+            let start = (rng.next() as usize) % (SLOTS_IN_DAY - 4 * SLOTS_IN_HOUR);
+            let len = 1 + (rng.next() as usize) % (4 * SLOTS_IN_HOUR);
+            for slot in day.slots.iter_mut().skip(start).take(len) {
+                *slot = true;
+            }
+            (UserId((i * 3) as i32), day)
+        })
+        .collect()
+}
+
+fn measure<T>(f: impl Fn() -> T) -> Duration {
+    const RUNS: u32 = 5;
+
+    let t0 = Instant::now();
+    for _ in 0..RUNS {
+        // Volatile read, so the result is not optimized away
+        let result = f();
+        unsafe { std::ptr::read_volatile(&result as *const T as *const u8) };
+    }
+    t0.elapsed() / RUNS
+}
+
+/// Find users available in each hour of the day
+fn match_all_hours<F>(available_in_slots: F) -> usize
+where
+    F: Fn(std::ops::Range<usize>) -> Vec<UserId>,
+{
+    (0..24)
+        .map(|h| available_in_slots((h * SLOTS_IN_HOUR)..((h + 1) * SLOTS_IN_HOUR)).len())
+        .sum()
+}
+
+fn main() {
+    println!(
+        "{:>8} {:>14} {:>14} {:>14} {:>14}",
+        "users", "build naive", "build bitset", "match naive", "match bitset"
+    );
+
+    for &users in [1_000, 10_000, 100_000].iter() {
+        let schedules = random_schedules(users);
+
+        let naive = NaiveDayScheduleMatrix::from(schedules.clone());
+        let bitset = DayScheduleMatrix::from(schedules.clone());
+
+        // Both implementations must agree
+        assert_eq!(
+            match_all_hours(|slots| naive.get_available_in_slots(slots)),
+            match_all_hours(|slots| bitset.get_available_in_slots(slots)),
+        );
+
+        let build_naive = measure(|| NaiveDayScheduleMatrix::from(schedules.clone()));
+        let build_bitset = measure(|| DayScheduleMatrix::from(schedules.clone()));
+        let match_naive = measure(|| match_all_hours(|slots| naive.get_available_in_slots(slots)));
+        let match_bitset =
+            measure(|| match_all_hours(|slots| bitset.get_available_in_slots(slots)));
+
+        println!(
+            "{:>8} {:>14?} {:>14?} {:>14?} {:>14?}",
+            users, build_naive, build_bitset, match_naive, match_bitset
+        );
+    }
+}
@@ -1,28 +1,38 @@
+use crate::Intent;
 use crate::Language;
 use crate::LanguagePreference;
 use crate::Languages;
 use crate::Level;
 use crate::Priority;
+use crate::WeekSchedule;
+use crate::{spoken_languages_score, SpokenLanguages};
+use crate::{topics_score, Topic, Topics};
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
+use strum_macros::{Display, EnumIter, EnumString};
 
 #[derive(Ord, PartialOrd, Eq, PartialEq)]
 struct PairPriority(i32);
 
 impl PairPriority {
-    const MAX: Self = PairPriority(4);
+    const LOW: i32 = 1;
+    const HIGH: i32 = 2;
 
-    fn new(a: Priority, b: Priority) -> Self {
-        let a = Self::priority_score(a);
-        let b = Self::priority_score(b);
+    fn new(a: Priority, b: Priority, config: &AffinityConfig) -> Self {
+        let a = Self::priority_score(a, config);
+        let b = Self::priority_score(b, config);
         Self(a * b)
     }
 
-    fn priority_score(p: Priority) -> i32 {
+    fn max(config: &AffinityConfig) -> Self {
+        Self::new(Priority::High, Priority::High, config)
+    }
+
+    fn priority_score(p: Priority, config: &AffinityConfig) -> i32 {
         match p {
             Priority::No => 0,
-            Priority::Low => 1,
-            Priority::High => 2,
+            Priority::Low => config.low_priority_score,
+            Priority::High => config.high_priority_score,
         }
     }
 }
@@ -35,19 +45,104 @@ impl PairLevel {
 
     #[allow(dead_code)]
     const MEDIUM: Self = PairLevel(2);
-    #[allow(dead_code)]
-    const MIN: Self = PairLevel(1);
 
-    fn new(a: Level, b: Level) -> Self {
+    /// Peers should have the same level
+    fn new(a: Level, b: Level, config: &AffinityConfig) -> Self {
         let diff = ((a.as_number() as i32) - (b.as_number() as i32)).abs();
-        let score = Self::MAX.0 - (diff as i32);
-        Self(score)
+        let score = config.max_level_score - diff;
+        Self(score.max(0))
+    }
+
+    fn max(config: &AffinityConfig) -> Self {
+        Self(config.max_level_score)
+    }
+
+    /// Score of the most distant levels
+    fn min(config: &AffinityConfig) -> Self {
+        Self::new(Level::Novice, Level::Expert, config)
+    }
+
+    /// A mentor should have an higher level than the mentee
+    fn mentoring(mentor: Level, mentee: Level, config: &AffinityConfig) -> Self {
+        match mentor.cmp(&mentee) {
+            std::cmp::Ordering::Greater => Self::max(config),
+            std::cmp::Ordering::Equal => Self::min(config),
+            std::cmp::Ordering::Less => PairLevel(0),
+        }
+    }
+
+    fn from_preferences(
+        a: &LanguagePreference,
+        b: &LanguagePreference,
+        config: &AffinityConfig,
+    ) -> Self {
+        match Role::from_intents(a.intent, b.intent) {
+            (Role::Mentor, _) => Self::mentoring(a.level, b.level, config),
+            (Role::Mentee, _) => Self::mentoring(b.level, a.level, config),
+            _ => Self::new(a.level, b.level, config),
+        }
+    }
+}
+
+/// Role of an user in a pair, for a language
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Peer,
+    Mentor,
+    Mentee,
+}
+
+impl Role {
+    /// Roles of two users, given their intents. Only a learner and a teacher
+    /// are complementary, any other combination makes them peers.
+    fn from_intents(a: Intent, b: Intent) -> (Role, Role) {
+        match (a, b) {
+            (Intent::Learn, Intent::Teach) => (Role::Mentee, Role::Mentor),
+            (Intent::Teach, Intent::Learn) => (Role::Mentor, Role::Mentee),
+            _ => (Role::Peer, Role::Peer),
+        }
+    }
+}
+
+/// Roles of both users in a pair, for a language
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct PairRoles {
+    pub language: Language,
+    /// Role of the user affinities are calculated for
+    pub user: Role,
+    /// Role of the other user
+    pub other: Role,
+}
+
+impl PairRoles {
+    /// Roles for the language with the best affinity, if any
+    pub fn from_params(
+        a: &AffinityParams,
+        b: &AffinityParams,
+        config: &AffinityConfig,
+    ) -> Option<Self> {
+        let matching_languages = find_matching_languages(&a.languages, &b.languages);
+        let (language, _) = matching_languages.find_max_affinity(config)?;
+        let (a_pref, b_pref) = matching_languages.0.get(&language)?;
+        let (user, other) = Role::from_intents(a_pref.intent, b_pref.intent);
+
+        Some(PairRoles {
+            language,
+            user,
+            other,
+        })
     }
 }
 
 #[derive(Default, Clone, Debug)]
 pub struct AffinityParams {
     languages: Languages,
+    /// Weekly schedule in UTC, projected on the same week for all users
+    schedule: Option<WeekSchedule>,
+    spoken_languages: SpokenLanguages,
+    topics: Topics,
 }
 
 impl AffinityParams {
@@ -59,36 +154,315 @@ impl AffinityParams {
         self.languages = languages;
         self
     }
+
+    pub fn with_schedule(mut self, schedule: WeekSchedule) -> Self {
+        self.schedule = Some(schedule);
+        self
+    }
+
+    pub fn with_spoken_languages(mut self, spoken_languages: SpokenLanguages) -> Self {
+        self.spoken_languages = spoken_languages;
+        self
+    }
+
+    pub fn with_topics(mut self, topics: Topics) -> Self {
+        self.topics = topics;
+        self
+    }
+
+    pub(crate) fn schedule(&self) -> Option<&WeekSchedule> {
+        self.schedule.as_ref()
+    }
+
+    pub(crate) fn languages(&self) -> &Languages {
+        &self.languages
+    }
+
+    pub(crate) fn spoken_languages(&self) -> &SpokenLanguages {
+        &self.spoken_languages
+    }
 }
 
 impl From<Vec<(Language, LanguagePreference)>> for AffinityParams {
     fn from(v: Vec<(Language, LanguagePreference)>) -> Self {
         let languages = Languages(v.into_iter().collect());
-        Self { languages }
+        Self {
+            languages,
+            ..Self::default()
+        }
+    }
+}
+
+/// Scoring functions which can be selected by configuration
+#[derive(
+    Debug,
+    Default,
+    Serialize,
+    Deserialize,
+    Copy,
+    Clone,
+    EnumIter,
+    Display,
+    EnumString,
+    Eq,
+    PartialEq,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum AffinityStrategyKind {
+    /// Best language and ratio of matching languages, see `WeightedStrategy`
+    #[default]
+    Weighted,
+    /// Only the best language, see `BestLanguageStrategy`
+    BestLanguage,
+}
+
+impl AffinityStrategyKind {
+    pub fn strategy(&self) -> &'static dyn AffinityStrategy {
+        match self {
+            AffinityStrategyKind::Weighted => &WeightedStrategy,
+            AffinityStrategyKind::BestLanguage => &BestLanguageStrategy,
+        }
+    }
+}
+
+/// Parameters used to calculate the affinity
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct AffinityConfig {
+    pub strategy: AffinityStrategyKind,
+    /// How much the language with the best affinity counts, from 0.0 to
+    /// 1.0. The ratio of matching languages weighs the rest.
+    pub best_language_weight: f64,
+    /// How much the weekly schedule overlap counts in the affinity, from 0.0
+    /// (ignored) to 1.0 (only schedule counts). Languages weigh the rest.
+    pub schedule_weight: f64,
+    /// How much the spoken languages fit counts in the affinity, from 0.0
+    /// (ignored) to 1.0. Languages weigh the rest.
+    pub spoken_languages_weight: f64,
+    /// How much the topics overlap counts in the affinity, from 0.0 (ignored)
+    /// to 1.0. Languages weigh the rest.
+    pub topics_weight: f64,
+    pub low_priority_score: i32,
+    pub high_priority_score: i32,
+    /// Score of two users with the same level, decreased by one for each
+    /// level of distance
+    pub max_level_score: i32,
+    /// Minimum normalized affinity to be `AffinityLevel::Medium`
+    pub medium_affinity_threshold: f64,
+    /// Minimum normalized affinity to be `AffinityLevel::High`
+    pub high_affinity_threshold: f64,
+}
+
+impl Default for AffinityConfig {
+    fn default() -> Self {
+        Self {
+            strategy: AffinityStrategyKind::default(),
+            best_language_weight: 0.8,
+            schedule_weight: 0.3,
+            spoken_languages_weight: 0.2,
+            topics_weight: 0.2,
+            low_priority_score: PairPriority::LOW,
+            high_priority_score: PairPriority::HIGH,
+            max_level_score: PairLevel::MAX.0,
+            medium_affinity_threshold: 0.3,
+            high_affinity_threshold: 0.6,
+        }
+    }
+}
+
+impl AffinityConfig {
+    /// Scores are multiplied together, so they are limited to avoid
+    /// overflows
+    const MAX_SCORE: i32 = 100;
+
+    /// Weights and thresholds must be in [0..1], with the medium threshold
+    /// not above the high one. Scores must be positive, at most MAX_SCORE,
+    /// with the low priority score not above the high one.
+    pub fn is_valid(&self) -> bool {
+        let ratio = |x: f64| (0.0..=1.0).contains(&x);
+        let score = |x: i32| (1..=Self::MAX_SCORE).contains(&x);
+
+        ratio(self.best_language_weight)
+            && ratio(self.schedule_weight)
+            && ratio(self.spoken_languages_weight)
+            && ratio(self.topics_weight)
+            && ratio(self.medium_affinity_threshold)
+            && ratio(self.high_affinity_threshold)
+            && self.medium_affinity_threshold <= self.high_affinity_threshold
+            && score(self.low_priority_score)
+            && score(self.high_priority_score)
+            && self.low_priority_score <= self.high_priority_score
+            && score(self.max_level_score)
+    }
+}
+
+/// A scoring function. Implement it to compare different ways of
+/// calculating the affinity.
+pub trait AffinityStrategy {
+    fn breakdown(
+        &self,
+        a: &AffinityParams,
+        b: &AffinityParams,
+        config: &AffinityConfig,
+    ) -> AffinityBreakdown;
+}
+
+/// The language with the best affinity weighs `best_language_weight`, the
+/// ratio of matching languages weighs the rest
+pub struct WeightedStrategy;
+
+impl AffinityStrategy for WeightedStrategy {
+    fn breakdown(
+        &self,
+        a: &AffinityParams,
+        b: &AffinityParams,
+        config: &AffinityConfig,
+    ) -> AffinityBreakdown {
+        weighted_breakdown(a, b, config, config.best_language_weight)
     }
 }
 
+/// Only the language with the best affinity counts, so users are not
+/// penalized for having many languages
+pub struct BestLanguageStrategy;
+
+impl AffinityStrategy for BestLanguageStrategy {
+    fn breakdown(
+        &self,
+        a: &AffinityParams,
+        b: &AffinityParams,
+        config: &AffinityConfig,
+    ) -> AffinityBreakdown {
+        weighted_breakdown(a, b, config, 1.0)
+    }
+}
+
+fn weighted_breakdown(
+    a: &AffinityParams,
+    b: &AffinityParams,
+    config: &AffinityConfig,
+    best_language_weight: f64,
+) -> AffinityBreakdown {
+    let matching_languages = find_matching_languages(&a.languages, &b.languages);
+    let best_lang = matching_languages.find_max_affinity(config);
+
+    let (best_language, best_lang_score) = if let Some((lang, aff)) = best_lang {
+        (lang, aff)
+    } else {
+        return AffinityBreakdown::default();
+    };
+
+    let best_language_weight = best_language_weight.clamp(0.0, 1.0);
+    let best_lang_score = best_lang_score.normalize() * best_language_weight;
+
+    let matching_languages_count = matching_languages.len();
+    let total_languages_count = a.languages.union(&b.languages).len();
+    let matching_ratio = (matching_languages_count as f64) / (total_languages_count as f64);
+    let matching_ratio_score = matching_ratio * (1.0 - best_language_weight);
+
+    let score = best_lang_score + matching_ratio_score;
+
+    let schedule_overlap = match (&a.schedule, &b.schedule) {
+        (Some(a_schedule), Some(b_schedule)) => {
+            Some(schedule_overlap_score(a_schedule, b_schedule))
+        }
+        _ => None,
+    };
+
+    let spoken_languages = spoken_languages_score(&a.spoken_languages, &b.spoken_languages);
+    let topics = topics_score(&a.topics, &b.topics);
+
+    // Schedule, spoken languages and topics are weighted only if known for
+    // both users
+    let weight_of = |factor: Option<f64>, weight: f64| match factor {
+        Some(_) => weight.clamp(0.0, 1.0),
+        None => 0.0,
+    };
+    let mut others_weights = [
+        weight_of(schedule_overlap, config.schedule_weight),
+        weight_of(spoken_languages, config.spoken_languages_weight),
+        weight_of(topics, config.topics_weight),
+    ];
+
+    // Total weight cannot exceed 1, so other factors are scaled down if too
+    // heavy
+    let others_weight: f64 = others_weights.iter().sum();
+    if others_weight > 1.0 {
+        others_weights.iter_mut().for_each(|w| *w /= others_weight);
+    }
+    let [schedule_weight, spoken_languages_weight, topics_weight] = others_weights;
+    let languages_weight = 1.0 - schedule_weight - spoken_languages_weight - topics_weight;
+
+    let score = score * languages_weight
+        + schedule_overlap.unwrap_or_default() * schedule_weight
+        + spoken_languages.unwrap_or_default() * spoken_languages_weight
+        + topics.unwrap_or_default() * topics_weight;
+
+    let weights = AffinityWeights {
+        best_language: best_language_weight * languages_weight,
+        matching_ratio: (1.0 - best_language_weight) * languages_weight,
+        schedule: schedule_weight,
+        spoken_languages: spoken_languages_weight,
+        topics: topics_weight,
+    };
+
+    AffinityBreakdown {
+        languages: matching_languages.affinities(config),
+        best_language: Some(best_language),
+        matching_ratio,
+        schedule_overlap,
+        spoken_languages,
+        topics,
+        common_topics: a.topics.common(&b.topics),
+        weights,
+        affinity: Affinity::from_number(score),
+    }
+}
+
+/// Score of the weekly overlap between two schedules, in [0..1]
+fn schedule_overlap_score(a: &WeekSchedule, b: &WeekSchedule) -> f64 {
+    /// Hours of weekly overlap considered enough for pairing
+    const ENOUGH_OVERLAP_HOURS: f64 = 4.0;
+
+    let hours = a.overlap_minutes(b) as f64 / 60.0;
+    (hours / ENOUGH_OVERLAP_HOURS).min(1.0)
+}
+
 struct MatchingLanguages(BTreeMap<Language, (LanguagePreference, LanguagePreference)>);
 
-#[derive(Ord, PartialOrd, Eq, PartialEq)]
-struct LanguageAffinity(i32);
+/// Affinity of two users for a single language, given their priorities and
+/// levels
+#[derive(Ord, PartialOrd, Eq, PartialEq, Serialize, Deserialize, Copy, Clone, Debug)]
+pub struct LanguageAffinity(i32);
 
 impl LanguageAffinity {
-    const MAX: Self = Self(PairPriority::MAX.0 * PairLevel::MAX.0);
+    const MAX: Self = Self(1000);
+
+    fn new(a: &LanguagePreference, b: &LanguagePreference, config: &AffinityConfig) -> Self {
+        let pair_prio = PairPriority::new(a.priority, b.priority, config);
+        let pair_level = PairLevel::from_preferences(a, b, config);
+        let max = PairPriority::max(config).0 * PairLevel::max(config).0;
+        LanguageAffinity(pair_prio.0 * pair_level.0 * Self::MAX.0 / max.max(1))
+    }
 
-    fn new(a: &LanguagePreference, b: &LanguagePreference) -> Self {
-        let pair_prio = PairPriority::new(a.priority, b.priority);
-        let pair_level = PairLevel::new(a.level, b.level);
-        LanguageAffinity(pair_prio.0 * pair_level.0)
+    pub fn normalize(&self) -> f64 {
+        (self.0 as f64) / (Self::MAX.0 as f64)
+    }
+}
+
+impl std::fmt::Display for LanguageAffinity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.0}%", self.normalize() * 100.0)
     }
 }
 
 impl MatchingLanguages {
-    fn find_max_affinity(&self) -> Option<(Language, LanguageAffinity)> {
+    fn find_max_affinity(&self, config: &AffinityConfig) -> Option<(Language, LanguageAffinity)> {
         self.0
             .iter()
             .map(|(lang, (a, b))| {
-                let aff = LanguageAffinity::new(a, b);
+                let aff = LanguageAffinity::new(a, b, config);
                 (lang, aff)
             })
             .max_by(|(_, l), (_, r)| l.cmp(r))
@@ -98,6 +472,13 @@ impl MatchingLanguages {
     fn len(&self) -> usize {
         self.0.len()
     }
+
+    fn affinities(&self, config: &AffinityConfig) -> BTreeMap<Language, LanguageAffinity> {
+        self.0
+            .iter()
+            .map(|(&lang, (a, b))| (lang, LanguageAffinity::new(a, b, config)))
+            .collect()
+    }
 }
 
 /// Find the intersection between the two collections a and b, extracting
@@ -120,6 +501,63 @@ fn find_matching_languages(a: &Languages, b: &Languages) -> MatchingLanguages {
 #[derive(Default, Ord, PartialOrd, Eq, PartialEq, Serialize, Deserialize, Copy, Clone, Debug)]
 pub struct Affinity(i32);
 
+/// Weights applied to each score when calculating the affinity. They sum
+/// up to 1.0.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub struct AffinityWeights {
+    pub best_language: f64,
+    pub matching_ratio: f64,
+    pub schedule: f64,
+    #[serde(default)]
+    pub spoken_languages: f64,
+    #[serde(default)]
+    pub topics: f64,
+}
+
+/// Explains how the affinity between two users has been calculated
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub struct AffinityBreakdown {
+    /// Affinity for each language both users are interested in
+    pub languages: BTreeMap<Language, LanguageAffinity>,
+    /// The language with the best affinity, which counts the most
+    pub best_language: Option<Language>,
+    /// Matching languages over all languages of both users, in [0..1]
+    pub matching_ratio: f64,
+    /// Score of the weekly schedule overlap in [0..1], only if both users
+    /// have a schedule
+    pub schedule_overlap: Option<f64>,
+    /// Score of the spoken languages fit in [0..1], only if both users have
+    /// spoken languages
+    #[serde(default)]
+    pub spoken_languages: Option<f64>,
+    /// Score of the topics overlap in [0..1], only if both users have topics
+    #[serde(default)]
+    pub topics: Option<f64>,
+    /// Topics both users are interested in
+    #[serde(default)]
+    pub common_topics: Vec<Topic>,
+    pub weights: AffinityWeights,
+    pub affinity: Affinity,
+}
+
+impl AffinityBreakdown {
+    /// Calculate affinity with the strategy selected in `config`
+    pub fn from_params(a: &AffinityParams, b: &AffinityParams, config: &AffinityConfig) -> Self {
+        Self::from_params_with_strategy(a, b, config.strategy.strategy(), config)
+    }
+
+    pub fn from_params_with_strategy(
+        a: &AffinityParams,
+        b: &AffinityParams,
+        strategy: &dyn AffinityStrategy,
+        config: &AffinityConfig,
+    ) -> Self {
+        strategy.breakdown(a, b, config)
+    }
+}
+
 #[derive(Copy, Clone)]
 pub enum AffinityLevel {
     Low,
@@ -134,27 +572,9 @@ impl Affinity {
     const MIN: i32 = 0;
     const MAX: i32 = 1000;
 
-    const BEST_LANG_SCORE_WEIGHT: f64 = 0.8;
-
-    pub fn from_params(a: &AffinityParams, b: &AffinityParams) -> Self {
-        let matching_languages = find_matching_languages(&a.languages, &b.languages);
-        let best_lang = matching_languages.find_max_affinity();
-
-        if let Some((_, best_lang_score)) = best_lang {
-            let best_lang_score = (best_lang_score.0 as f64) / (LanguageAffinity::MAX.0 as f64);
-            let best_lang_score = best_lang_score * Self::BEST_LANG_SCORE_WEIGHT;
-
-            let matching_languages_count = matching_languages.len();
-            let total_languages_count = a.languages.union(&b.languages).len();
-            let matching_ratio = (matching_languages_count as f64) / (total_languages_count as f64);
-            let matching_ratio = matching_ratio * (1.0 - Self::BEST_LANG_SCORE_WEIGHT);
-
-            let score = best_lang_score + matching_ratio;
-
-            Self((score * (Self::MAX as f64)) as i32)
-        } else {
-            Self::NONE
-        }
+    /// See [`AffinityBreakdown::from_params`]
+    pub fn from_params(a: &AffinityParams, b: &AffinityParams, config: &AffinityConfig) -> Self {
+        AffinityBreakdown::from_params(a, b, config).affinity
     }
 
     pub fn normalize(&self) -> f64 {
@@ -174,20 +594,20 @@ impl Affinity {
     }
 }
 
-impl From<Affinity> for AffinityLevel {
-    fn from(affinity: Affinity) -> Self {
+impl AffinityLevel {
+    pub fn new(affinity: Affinity, config: &AffinityConfig) -> Self {
         let x = affinity.normalize();
         match x {
-            x if x >= 0.6 => AffinityLevel::High,
-            x if x >= 0.3 => AffinityLevel::Medium,
+            x if x >= config.high_affinity_threshold => AffinityLevel::High,
+            x if x >= config.medium_affinity_threshold => AffinityLevel::Medium,
             _ => AffinityLevel::Low,
         }
     }
 }
 
-impl ToString for Affinity {
-    fn to_string(&self) -> String {
-        format!("{:.0}%", self.normalize() * 100.0)
+impl std::fmt::Display for Affinity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.0}%", self.normalize() * 100.0)
     }
 }
 
@@ -195,12 +615,16 @@ impl ToString for Affinity {
 mod tests {
     use super::*;
 
+    fn config() -> AffinityConfig {
+        AffinityConfig::default()
+    }
+
     #[test]
     fn default_params_have_no_affinity() {
         let a = AffinityParams::new();
         let b = AffinityParams::new();
 
-        assert!(Affinity::from_params(&a, &b) == Affinity::NONE);
+        assert!(Affinity::from_params(&a, &b, &config()) == Affinity::NONE);
     }
 
     #[test]
@@ -211,12 +635,13 @@ mod tests {
             LanguagePreference {
                 level: Level::Expert,
                 priority: Priority::High,
+                intent: Intent::Peer,
             },
         );
         let a = AffinityParams::new().with_languages(languages.clone());
         let b = AffinityParams::new().with_languages(languages.clone());
 
-        let affinity = Affinity::from_params(&a, &b);
+        let affinity = Affinity::from_params(&a, &b, &config());
 
         assert!(affinity == Affinity::FULL);
     }
@@ -229,12 +654,13 @@ mod tests {
             LanguagePreference {
                 level: Level::Expert,
                 priority: Priority::Low,
+                intent: Intent::Peer,
             },
         );
         let a = AffinityParams::new().with_languages(languages.clone());
         let b = AffinityParams::new().with_languages(languages.clone());
 
-        let affinity = Affinity::from_params(&a, &b);
+        let affinity = Affinity::from_params(&a, &b, &config());
 
         assert!(affinity < Affinity::FULL);
         assert!(affinity > Affinity::NONE);
@@ -250,6 +676,7 @@ mod tests {
                 LanguagePreference {
                     level: Level::Expert,
                     priority: Priority::High,
+                    intent: Intent::Peer,
                 },
             );
             AffinityParams::new().with_languages(languages)
@@ -262,18 +689,263 @@ mod tests {
                 LanguagePreference {
                     level: Level::Novice,
                     priority: Priority::High,
+                    intent: Intent::Peer,
                 },
             );
             AffinityParams::new().with_languages(languages)
         };
 
-        let affinity = Affinity::from_params(&a, &b);
+        let affinity = Affinity::from_params(&a, &b, &config());
 
         assert!(affinity < Affinity::FULL);
         assert!(affinity > Affinity::NONE);
         // assert!(affinity.0 == 12);
     }
 
+    #[test]
+    fn schedule_overlap_increases_affinity() {
+        use std::convert::TryFrom;
+
+        let mut languages = Languages::default();
+        languages.insert(
+            Language::Rust,
+            LanguagePreference {
+                level: Level::Expert,
+                priority: Priority::High,
+                intent: Intent::Peer,
+            },
+        );
+
+        let schedule = |mon: &str| WeekSchedule {
+            mon: crate::DaySchedule::try_from(mon).unwrap(),
+            ..WeekSchedule::default()
+        };
+
+        let params = |mon| {
+            AffinityParams::new()
+                .with_languages(languages.clone())
+                .with_schedule(schedule(mon))
+        };
+
+        let a = params("18,19,20,21");
+        let full_overlap = Affinity::from_params(&a, &params("18,19,20,21"), &config());
+        let partial_overlap = Affinity::from_params(&a, &params("20,21,22"), &config());
+        let no_overlap = Affinity::from_params(&a, &params("9,10"), &config());
+
+        assert_eq!(full_overlap, Affinity::FULL);
+        assert!(partial_overlap < full_overlap);
+        assert!(no_overlap < partial_overlap);
+        assert!(no_overlap > Affinity::NONE);
+
+        // Schedule is ignored if weight is zero
+        let no_schedule = AffinityConfig {
+            schedule_weight: 0.0,
+            ..config()
+        };
+        let no_overlap = Affinity::from_params(&a, &params("9,10"), &no_schedule);
+        assert_eq!(no_overlap, Affinity::FULL);
+    }
+
+    fn rust(level: Level, intent: Intent) -> AffinityParams {
+        let mut languages = Languages::default();
+        languages.insert(
+            Language::Rust,
+            LanguagePreference {
+                level,
+                priority: Priority::High,
+                intent,
+            },
+        );
+        AffinityParams::new().with_languages(languages)
+    }
+
+    #[test]
+    fn learner_and_teacher_are_complementary() {
+        let learner = rust(Level::Novice, Intent::Learn);
+        let teacher = rust(Level::Expert, Intent::Teach);
+        let peer = rust(Level::Expert, Intent::Peer);
+
+        assert_eq!(
+            Affinity::from_params(&learner, &teacher, &config()),
+            Affinity::FULL
+        );
+        assert_eq!(
+            Affinity::from_params(&teacher, &learner, &config()),
+            Affinity::FULL
+        );
+        assert!(Affinity::from_params(&learner, &peer, &config()) < Affinity::FULL);
+
+        let roles = PairRoles::from_params(&learner, &teacher, &config()).unwrap();
+        assert_eq!(roles.language, Language::Rust);
+        assert_eq!(roles.user, Role::Mentee);
+        assert_eq!(roles.other, Role::Mentor);
+
+        let roles = PairRoles::from_params(&learner, &peer, &config()).unwrap();
+        assert_eq!(roles.user, Role::Peer);
+        assert_eq!(roles.other, Role::Peer);
+    }
+
+    #[test]
+    fn teacher_should_have_higher_level() {
+        let learner = rust(Level::Proficient, Intent::Learn);
+        let better_teacher = rust(Level::Expert, Intent::Teach);
+        let same_level_teacher = rust(Level::Proficient, Intent::Teach);
+        let worse_teacher = rust(Level::Novice, Intent::Teach);
+
+        let better = Affinity::from_params(&learner, &better_teacher, &config());
+        let same_level = Affinity::from_params(&learner, &same_level_teacher, &config());
+        let worse = Affinity::from_params(&learner, &worse_teacher, &config());
+
+        assert!(better > same_level);
+        assert!(same_level > worse);
+    }
+
+    #[test]
+    fn breakdown_explains_affinity() {
+        let mut languages = Languages::default();
+        languages.insert(
+            Language::Rust,
+            LanguagePreference {
+                level: Level::Expert,
+                priority: Priority::High,
+                intent: Intent::Peer,
+            },
+        );
+        let a = AffinityParams::new().with_languages(languages.clone());
+
+        languages.insert(
+            Language::Go,
+            LanguagePreference {
+                level: Level::Novice,
+                priority: Priority::Low,
+                intent: Intent::Peer,
+            },
+        );
+        let b = AffinityParams::new().with_languages(languages);
+
+        let breakdown = AffinityBreakdown::from_params(&a, &b, &config());
+
+        assert_eq!(breakdown.affinity, Affinity::from_params(&a, &b, &config()));
+        assert_eq!(breakdown.best_language, Some(Language::Rust));
+        assert_eq!(breakdown.languages.len(), 1);
+        assert_eq!(breakdown.languages[&Language::Rust].normalize(), 1.0);
+        assert_eq!(breakdown.matching_ratio, 0.5);
+        assert_eq!(breakdown.schedule_overlap, None);
+        assert_eq!(breakdown.weights.schedule, 0.0);
+        assert_eq!(breakdown.affinity, Affinity(900));
+    }
+
+    #[test]
+    fn breakdown_without_matching_languages_is_empty() {
+        let breakdown = AffinityBreakdown::from_params(
+            &AffinityParams::new(),
+            &AffinityParams::new(),
+            &config(),
+        );
+        assert_eq!(breakdown, AffinityBreakdown::default());
+        assert_eq!(breakdown.affinity, Affinity::NONE);
+    }
+
+    #[test]
+    fn spoken_languages_fit_increases_affinity() {
+        use crate::{Proficiency, SpokenLanguage, SpokenLanguagePreference};
+        use maplit::btreemap;
+
+        let speaks = |proficiency| {
+            rust(Level::Expert, Intent::Peer).with_spoken_languages(SpokenLanguages(btreemap! {
+                SpokenLanguage::English => SpokenLanguagePreference { proficiency, preferred: true },
+            }))
+        };
+
+        let native = speaks(Proficiency::Native);
+        let basic = speaks(Proficiency::Basic);
+        let italian = rust(Level::Expert, Intent::Peer).with_spoken_languages(
+            SpokenLanguages::from(maplit::btreeset![SpokenLanguage::Italian]),
+        );
+
+        let best = AffinityBreakdown::from_params(&native, &native, &config());
+        let worse = AffinityBreakdown::from_params(&native, &basic, &config());
+        let incompatible = AffinityBreakdown::from_params(&native, &italian, &config());
+
+        assert_eq!(best.affinity, Affinity::FULL);
+        assert!(worse.affinity < best.affinity);
+        assert!(incompatible.affinity < worse.affinity);
+        assert!(incompatible.affinity > Affinity::NONE);
+        assert_eq!(incompatible.spoken_languages, Some(0.0));
+        assert_eq!(incompatible.weights.spoken_languages, 0.2);
+    }
+
+    #[test]
+    fn common_topics_increase_affinity() {
+        let interested_in = |v: &[&str]| {
+            let topics = Topics(v.iter().map(|x| x.parse().unwrap()).collect());
+            rust(Level::Expert, Intent::Peer).with_topics(topics)
+        };
+
+        let a = interested_in(&["embedded", "tokio"]);
+        let b = interested_in(&["tokio", "react"]);
+        let c = interested_in(&["react"]);
+
+        let common = AffinityBreakdown::from_params(&a, &b, &config());
+        let nothing_in_common = AffinityBreakdown::from_params(&a, &c, &config());
+        let unknown =
+            AffinityBreakdown::from_params(&a, &rust(Level::Expert, Intent::Peer), &config());
+
+        assert!(common.affinity > nothing_in_common.affinity);
+        assert_eq!(common.topics, Some(0.5));
+        assert_eq!(common.common_topics, vec!["tokio".parse().unwrap()]);
+        assert_eq!(unknown.topics, None);
+        assert_eq!(unknown.weights.topics, 0.0);
+        assert_eq!(unknown.affinity, Affinity::FULL);
+    }
+
+    #[test]
+    fn best_language_strategy_ignores_other_languages() {
+        let a = rust(Level::Expert, Intent::Peer);
+        let mut b = rust(Level::Expert, Intent::Peer);
+        b.languages
+            .insert(Language::Go, LanguagePreference::default());
+
+        let weighted = Affinity::from_params(&a, &b, &config());
+        assert!(weighted < Affinity::FULL);
+
+        let best_language = AffinityConfig {
+            strategy: AffinityStrategyKind::BestLanguage,
+            ..config()
+        };
+        let best_language = AffinityBreakdown::from_params(&a, &b, &best_language);
+        assert_eq!(best_language.affinity, Affinity::FULL);
+        assert_eq!(best_language.weights.matching_ratio, 0.0);
+    }
+
+    #[test]
+    fn affinity_level_thresholds_are_configurable() {
+        let affinity = Affinity::from_number(0.5);
+        assert!(matches!(
+            AffinityLevel::new(affinity, &config()),
+            AffinityLevel::Medium
+        ));
+
+        let config = AffinityConfig {
+            high_affinity_threshold: 0.5,
+            ..config()
+        };
+        assert!(matches!(
+            AffinityLevel::new(affinity, &config),
+            AffinityLevel::High
+        ));
+    }
+
+    #[test]
+    fn parse_strategy_kind() {
+        use std::str::FromStr;
+        assert_eq!(
+            AffinityStrategyKind::from_str("best_language"),
+            Ok(AffinityStrategyKind::BestLanguage)
+        );
+        assert!(AffinityStrategyKind::from_str("unknown").is_err());
+    }
+
     #[test]
     fn find_matching_languages_ok() {
         let mut languages = Languages::default();
@@ -282,6 +954,7 @@ mod tests {
             LanguagePreference {
                 level: Level::Expert,
                 priority: Priority::Low,
+                intent: Intent::Peer,
             },
         );
         let a = languages.clone();
@@ -290,7 +963,7 @@ mod tests {
         let matching = find_matching_languages(&a, &b);
 
         assert!(matching.0.len() == 1);
-        assert!(matching.0.get(&Language::Rust).is_some());
+        assert!(matching.0.contains_key(&Language::Rust));
     }
 
     #[test]
@@ -316,15 +989,71 @@ mod tests {
 
     #[test]
     fn same_level_is_max() {
-        assert!(PairLevel::new(Level::Expert, Level::Expert) == PairLevel::MAX);
-        assert!(PairLevel::new(Level::Proficient, Level::Proficient) == PairLevel::MAX);
-        assert!(PairLevel::new(Level::Novice, Level::Novice) == PairLevel::MAX);
+        assert!(
+            PairLevel::new(Level::Expert, Level::Expert, &config()) == PairLevel::max(&config())
+        );
+        assert!(
+            PairLevel::new(Level::Proficient, Level::Proficient, &config())
+                == PairLevel::max(&config())
+        );
+        assert!(
+            PairLevel::new(Level::Novice, Level::Novice, &config()) == PairLevel::max(&config())
+        );
     }
 
     #[test]
     fn distant_level_is_min() {
-        assert!(PairLevel::new(Level::Expert, Level::Novice) == PairLevel::MIN);
-        assert!(PairLevel::new(Level::Novice, Level::Expert) == PairLevel::MIN);
+        let config = config();
+        let min = PairLevel(1);
+        assert!(PairLevel::new(Level::Expert, Level::Novice, &config) == min);
+        assert!(PairLevel::new(Level::Novice, Level::Expert, &config) == min);
+        assert!(PairLevel::new(Level::Novice, Level::Proficient, &config) > min);
+        assert!(PairLevel::min(&config) == min);
+
+        // Scores are never negative
+        let config = AffinityConfig {
+            max_level_score: 1,
+            ..config
+        };
+        assert!(PairLevel::min(&config) == PairLevel(0));
+    }
+
+    #[test]
+    fn affinity_config_is_validated() {
+        assert!(config().is_valid());
+
+        let invalid = [
+            AffinityConfig {
+                schedule_weight: -0.1,
+                ..config()
+            },
+            AffinityConfig {
+                topics_weight: f64::NAN,
+                ..config()
+            },
+            AffinityConfig {
+                medium_affinity_threshold: 0.7,
+                high_affinity_threshold: 0.6,
+                ..config()
+            },
+            AffinityConfig {
+                low_priority_score: 3,
+                high_priority_score: 2,
+                ..config()
+            },
+            AffinityConfig {
+                max_level_score: 0,
+                ..config()
+            },
+            AffinityConfig {
+                high_priority_score: i32::MAX,
+                ..config()
+            },
+        ];
+
+        for config in invalid.iter() {
+            assert!(!config.is_valid(), "{:?}", config);
+        }
     }
 
     #[test]
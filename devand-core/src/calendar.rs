@@ -0,0 +1,472 @@
+use crate::schedule_matcher::MatchingSession;
+use crate::{Availability, UserId};
+use chrono::prelude::*;
+use chrono::Duration;
+use chrono_tz::{OffsetComponents, OffsetName, Tz};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+mod import;
+
+pub use import::import_week_schedule;
+
+/// Domain of events uid, so they are globally unique
+const UID_DOMAIN: &str = "devand.dev";
+
+/// Lines longer than this (in octets) must be folded
+const MAX_LINE_LEN: usize = 75;
+
+/// Time zone transitions are exported for this number of years from the
+/// first event. Afterwards, clients keep the last offset.
+const TIME_ZONE_YEARS: i64 = 10;
+
+/// A secret url users can subscribe to with a calendar client
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct CalendarFeed {
+    /// The feed can be subscribed
+    pub enabled: bool,
+    /// Part of the url: urls with a different key are not valid
+    pub key: u32,
+}
+
+impl CalendarFeed {
+    /// Enable the feed with a new key, so old urls are not valid anymore
+    pub fn regenerate(&mut self) {
+        self.enabled = true;
+        self.key = self.key.wrapping_add(1);
+    }
+
+    /// Disable the feed. The key is changed too, so old urls are still not
+    /// valid when the feed is enabled again.
+    pub fn revoke(&mut self) {
+        self.enabled = false;
+        self.key = self.key.wrapping_add(1);
+    }
+
+    pub fn accepts(&self, key: u32) -> bool {
+        self.enabled && self.key == key
+    }
+}
+
+/// An event of an iCalendar feed
+#[derive(Debug, Clone, PartialEq)]
+pub struct CalendarEvent {
+    pub uid: String,
+    pub summary: String,
+    pub start: DateTime<Tz>,
+    pub end: DateTime<Tz>,
+    /// Repeated every week, on the same weekday and time
+    pub weekly: bool,
+    /// Free time, which does not make the user busy
+    pub free: bool,
+}
+
+/// A weekly recurring event for each range of consecutive available slots.
+/// Events are in `tz` local time, so they follow daylight saving time like
+/// the schedule does. First occurrences are in the 7 days starting from
+/// `date`. Exceptions on specific dates are not exported.
+pub fn availability_events(
+    user: UserId,
+    availability: &Availability,
+    tz: Tz,
+    date: DateTime<Utc>,
+    summary: &str,
+) -> Vec<CalendarEvent> {
+    let week_schedule = match availability {
+        Availability::Weekly(week_schedule) => week_schedule,
+        Availability::Never => return Vec::new(),
+    };
+
+    let resolution = week_schedule.resolution();
+    let minutes = resolution.minutes();
+    let first_day = date.with_timezone(&tz).naive_local().date();
+
+    let mut events = Vec::new();
+
+    for day in (0..7).map(|i| first_day + Duration::days(i)) {
+        let day_schedule = week_schedule[day.weekday()].to_resolution(resolution);
+
+        for range in available_ranges(&day_schedule.slots) {
+            let start = local_time(tz, day, range.start * minutes);
+            let end = local_time(tz, day, range.end * minutes);
+
+            // Skip times which do not exist, because of daylight saving time
+            if let (Some(start), Some(end)) = (start, end) {
+                events.push(CalendarEvent {
+                    uid: format!(
+                        "availability-{}-{}-{}@{}",
+                        user.0,
+                        day.weekday(),
+                        range.start * minutes,
+                        UID_DOMAIN
+                    ),
+                    summary: summary.to_string(),
+                    start,
+                    end,
+                    weekly: true,
+                    free: true,
+                });
+            }
+        }
+    }
+
+    events
+}
+
+/// An event for each matched session, with the summary given by `summary`
+/// for the other user
+pub fn session_events<F>(
+    user: UserId,
+    sessions: &[MatchingSession],
+    summary: F,
+) -> Vec<CalendarEvent>
+where
+    F: Fn(UserId) -> String,
+{
+    sessions
+        .iter()
+        .map(|session| CalendarEvent {
+            uid: format!(
+                "session-{}-{}-{}@{}",
+                user.0,
+                session.user.0,
+                session.start.timestamp(),
+                UID_DOMAIN
+            ),
+            summary: summary(session.user),
+            start: session.start.with_timezone(&Tz::UTC),
+            end: session.end.with_timezone(&Tz::UTC),
+            weekly: false,
+            free: false,
+        })
+        .collect()
+}
+
+/// Serialize events as an iCalendar (RFC 5545) feed
+pub fn to_ical(name: &str, events: &[CalendarEvent], now: DateTime<Utc>) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//DevAndDev//Calendar feed//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        format!("X-WR-CALNAME:{}", escape(name)),
+    ];
+
+    lines.extend(time_zones(events));
+
+    for event in events {
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}", event.uid));
+        lines.push(format!("DTSTAMP:{}", now.format("%Y%m%dT%H%M%SZ")));
+        lines.push(format_time("DTSTART", &event.start));
+        lines.push(format_time("DTEND", &event.end));
+        if event.weekly {
+            lines.push("RRULE:FREQ=WEEKLY".to_string());
+        }
+        if event.free {
+            lines.push("TRANSP:TRANSPARENT".to_string());
+        }
+        lines.push(format!("SUMMARY:{}", escape(&event.summary)));
+        lines.push("END:VEVENT".to_string());
+    }
+
+    lines.push("END:VCALENDAR".to_string());
+
+    lines
+        .iter()
+        .map(|line| fold(line) + "\r\n")
+        .collect::<String>()
+}
+
+/// Ranges of consecutive available slots
+fn available_ranges(slots: &[bool]) -> Vec<std::ops::Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = None;
+
+    for (i, &available) in slots.iter().chain(std::iter::once(&false)).enumerate() {
+        match (start, available) {
+            (None, true) => start = Some(i),
+            (Some(s), false) => {
+                ranges.push(s..i);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+
+    ranges
+}
+
+/// Local time of a minute of the day. The end of the day is midnight of the
+/// next day.
+fn local_time(tz: Tz, day: NaiveDate, minute_of_day: usize) -> Option<DateTime<Tz>> {
+    let t = day.and_hms(0, 0, 0) + Duration::minutes(minute_of_day as i64);
+    tz.from_local_datetime(&t).earliest()
+}
+
+/// Times in UTC are absolute, others refer to the time zone by its IANA name
+fn format_time(property: &str, t: &DateTime<Tz>) -> String {
+    let tz = t.timezone();
+    if tz == Tz::UTC {
+        format!("{}:{}", property, t.format("%Y%m%dT%H%M%SZ"))
+    } else {
+        format!(
+            "{};TZID={}:{}",
+            property,
+            tz.name(),
+            t.format("%Y%m%dT%H%M%S")
+        )
+    }
+}
+
+/// A VTIMEZONE component for each time zone events refer to, because
+/// clients are not required to know time zones by their IANA name
+fn time_zones(events: &[CalendarEvent]) -> Vec<String> {
+    let mut first_times: BTreeMap<&str, (Tz, DateTime<Utc>)> = BTreeMap::new();
+
+    for t in events.iter().flat_map(|event| vec![event.start, event.end]) {
+        let tz = t.timezone();
+        if tz != Tz::UTC {
+            let t = t.with_timezone(&Utc);
+            let first = first_times.entry(tz.name()).or_insert((tz, t));
+            first.1 = first.1.min(t);
+        }
+    }
+
+    first_times
+        .values()
+        .flat_map(|&(tz, from)| vtimezone(tz, from))
+        .collect()
+}
+
+/// The offset of `tz` at `from`, followed by its transitions in the next
+/// TIME_ZONE_YEARS
+fn vtimezone(tz: Tz, from: DateTime<Utc>) -> Vec<String> {
+    let offset_at = |t: DateTime<Utc>| tz.offset_from_utc_datetime(&t.naive_utc());
+
+    let mut lines = vec!["BEGIN:VTIMEZONE".to_string(), format!("TZID:{}", tz.name())];
+
+    let mut offset = offset_at(from);
+    lines.extend(observance(from, &offset, &offset));
+
+    let until = from + Duration::days(365 * TIME_ZONE_YEARS);
+    let mut day = from;
+
+    while day < until {
+        let next_day = day + Duration::days(1);
+        let next_offset = offset_at(next_day);
+
+        if next_offset.fix() != offset.fix() {
+            // The transition is the first second with the new offset
+            let (mut before, mut after) = (day, next_day);
+            while after - before > Duration::seconds(1) {
+                let middle = before + (after - before) / 2;
+                if offset_at(middle).fix() == offset.fix() {
+                    before = middle;
+                } else {
+                    after = middle;
+                }
+            }
+
+            lines.extend(observance(after, &offset, &next_offset));
+            offset = next_offset;
+        }
+
+        day = next_day;
+    }
+
+    lines.push("END:VTIMEZONE".to_string());
+    lines
+}
+
+/// A time zone observance starting at `t`, whose local time is given with
+/// the previous offset
+fn observance(
+    t: DateTime<Utc>,
+    from: &<Tz as TimeZone>::Offset,
+    to: &<Tz as TimeZone>::Offset,
+) -> Vec<String> {
+    let kind = if to.dst_offset().is_zero() {
+        "STANDARD"
+    } else {
+        "DAYLIGHT"
+    };
+
+    let from_seconds = from.fix().local_minus_utc();
+    let local = t.naive_utc() + Duration::seconds(from_seconds as i64);
+
+    vec![
+        format!("BEGIN:{}", kind),
+        format!("DTSTART:{}", local.format("%Y%m%dT%H%M%S")),
+        format!("TZOFFSETFROM:{}", format_offset(from_seconds)),
+        format!("TZOFFSETTO:{}", format_offset(to.fix().local_minus_utc())),
+        format!("TZNAME:{}", escape(to.abbreviation())),
+        format!("END:{}", kind),
+    ]
+}
+
+/// UTC offsets are in `+hhmm` format, seconds are added only if needed
+fn format_offset(seconds: i32) -> String {
+    let sign = if seconds < 0 { '-' } else { '+' };
+    let seconds = seconds.abs();
+    let (h, m, s) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+
+    if s == 0 {
+        format!("{}{:02}{:02}", sign, h, m)
+    } else {
+        format!("{}{:02}{:02}{:02}", sign, h, m, s)
+    }
+}
+
+/// Escape a text value. Any line break becomes an escaped newline.
+fn escape(text: &str) -> String {
+    text.replace("\r\n", "\n")
+        .replace('\r', "\n")
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Split long lines, continuation lines start with a space
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len());
+    let mut len = 0;
+
+    for c in line.chars() {
+        if len + c.len_utf8() > MAX_LINE_LEN {
+            folded.push_str("\r\n ");
+            // The leading space counts
+            len = 1;
+        }
+        folded.push(c);
+        len += c.len_utf8();
+    }
+
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Affinity, DaySchedule, WeekSchedule};
+    use std::convert::TryFrom;
+
+    #[test]
+    fn consecutive_slots_are_merged() {
+        let day = DaySchedule::try_from("8,9,10,14,22,23").unwrap();
+        assert_eq!(available_ranges(&day.slots), vec![8..11, 14..15, 22..24]);
+    }
+
+    #[test]
+    fn availability_is_exported_as_weekly_events() {
+        let week = WeekSchedule {
+            mon: DaySchedule::try_from("21,22,23").unwrap(),
+            ..WeekSchedule::default()
+        };
+        let availability = Availability::Weekly(week);
+
+        // Wednesday, first monday is 2020-01-13
+        let date = Utc.ymd(2020, 1, 8).and_hms(12, 0, 0);
+        let tz = Tz::Europe__Rome;
+        let events = availability_events(UserId(1), &availability, tz, date, "Available");
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].start, tz.ymd(2020, 1, 13).and_hms(21, 0, 0));
+        assert_eq!(events[0].end, tz.ymd(2020, 1, 14).and_hms(0, 0, 0));
+
+        let ical = to_ical("DevAndDev", &events, date);
+        assert!(ical.contains("DTSTART;TZID=Europe/Rome:20200113T210000\r\n"));
+        assert!(ical.contains("DTEND;TZID=Europe/Rome:20200114T000000\r\n"));
+        assert_eq!(
+            ical.matches("BEGIN:VTIMEZONE\r\nTZID:Europe/Rome\r\n")
+                .count(),
+            1
+        );
+        assert!(ical.contains("RRULE:FREQ=WEEKLY\r\n"));
+        assert!(ical.contains("TRANSP:TRANSPARENT\r\n"));
+        assert!(ical.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(ical.ends_with("END:VCALENDAR\r\n"));
+    }
+
+    #[test]
+    fn sessions_are_exported_in_utc() {
+        let start = Utc.ymd(2020, 1, 6).and_hms(10, 0, 0);
+        let sessions = vec![MatchingSession {
+            user: UserId(2),
+            affinity: Affinity::FULL,
+            start,
+            end: start + Duration::hours(2),
+        }];
+
+        let events = session_events(UserId(1), &sessions, |u| format!("Pair with {}", u.0));
+        let ical = to_ical("DevAndDev", &events, start);
+
+        assert!(ical.contains("UID:session-1-2-1578304800@devand.dev\r\n"));
+        assert!(ical.contains("DTSTART:20200106T100000Z\r\n"));
+        assert!(ical.contains("DTEND:20200106T120000Z\r\n"));
+        assert!(ical.contains("SUMMARY:Pair with 2\r\n"));
+        assert!(!ical.contains("RRULE"));
+        assert!(!ical.contains("VTIMEZONE"));
+    }
+
+    #[test]
+    fn time_zones_are_exported_with_their_transitions() {
+        // Daylight saving time in Italy starts on 2020-03-29 at 02:00 and
+        // ends on 2020-10-25 at 03:00
+        let tz = Tz::Europe__Rome;
+        let lines = vtimezone(tz, Utc.ymd(2020, 1, 13).and_hms(20, 0, 0));
+        let ical = lines.join("\n");
+
+        assert!(ical.starts_with(
+            "BEGIN:VTIMEZONE\nTZID:Europe/Rome\n\
+             BEGIN:STANDARD\nDTSTART:20200113T210000\n\
+             TZOFFSETFROM:+0100\nTZOFFSETTO:+0100\nTZNAME:CET\nEND:STANDARD\n\
+             BEGIN:DAYLIGHT\nDTSTART:20200329T020000\n\
+             TZOFFSETFROM:+0100\nTZOFFSETTO:+0200\nTZNAME:CEST\nEND:DAYLIGHT\n\
+             BEGIN:STANDARD\nDTSTART:20201025T030000\n\
+             TZOFFSETFROM:+0200\nTZOFFSETTO:+0100\nTZNAME:CET\nEND:STANDARD\n"
+        ));
+        assert!(ical.ends_with("END:VTIMEZONE"));
+        assert_eq!(
+            ical.matches("BEGIN:DAYLIGHT").count(),
+            TIME_ZONE_YEARS as usize
+        );
+
+        assert_eq!(format_offset(5 * 3600 + 30 * 60), "+0530");
+        assert_eq!(format_offset(-3 * 3600 - 30 * 60), "-0330");
+        assert_eq!(format_offset(0), "+0000");
+        assert_eq!(format_offset(-(3600 + 75)), "-010115");
+    }
+
+    #[test]
+    fn long_lines_are_folded_and_text_escaped() {
+        let summary = format!("Pair, programming; {}", "x".repeat(100));
+        let line = format!("SUMMARY:{}", escape(&summary));
+        let folded = fold(&line);
+
+        assert!(line.starts_with("SUMMARY:Pair\\, programming\\;"));
+        assert_eq!(escape("a\r\nb\rc\nd"), "a\\nb\\nc\\nd");
+        assert!(folded.split("\r\n").all(|l| l.len() <= MAX_LINE_LEN));
+        assert_eq!(folded.replace("\r\n ", ""), line);
+    }
+
+    #[test]
+    fn revoked_feed_keys_are_not_accepted() {
+        let mut feed = CalendarFeed::default();
+        assert!(!feed.accepts(feed.key));
+
+        feed.regenerate();
+        let old_key = feed.key;
+        assert!(feed.accepts(old_key));
+
+        feed.revoke();
+        assert!(!feed.accepts(old_key));
+        assert!(!feed.accepts(feed.key));
+
+        feed.regenerate();
+        assert!(!feed.accepts(old_key));
+        assert!(feed.accepts(feed.key));
+    }
+}
@@ -0,0 +1,549 @@
+use crate::{DaySchedule, Resolution, WeekSchedule};
+use chrono::prelude::*;
+use chrono::Duration;
+use chrono_tz::Tz;
+
+/// Longer periods are cut, since they already cover the whole week
+const MAX_PERIOD_DAYS: i64 = 7;
+
+/// Longer durations cannot be represented by `chrono::Duration`
+const MAX_DURATION_SECONDS: i64 = i64::MAX / 1000;
+
+const WEEKDAYS: [Weekday; 7] = [
+    Weekday::Mon,
+    Weekday::Tue,
+    Weekday::Wed,
+    Weekday::Thu,
+    Weekday::Fri,
+    Weekday::Sat,
+    Weekday::Sun,
+];
+
+/// Derive a weekly schedule from an iCalendar file (`.ics` or `VFREEBUSY`),
+/// in `tz` local time and with the given resolution.
+///
+/// Recurring events marked as free (`TRANSP:TRANSPARENT`) and free periods
+/// of `FREEBUSY` are free time. Other recurring events and periods are busy
+/// time, on the same weekday and time of every week. Events which do not
+/// recur every week do not change the weekly schedule, so they are ignored.
+/// The schedule is the free time which is not busy, or the inverse of busy
+/// time if there is no free time at all. Returns None if the file is not a
+/// calendar, if it has invalid times or durations, or if it has neither free
+/// nor busy time.
+pub fn import_week_schedule(ics: &str, tz: Tz, resolution: Resolution) -> Option<WeekSchedule> {
+    let ics = unfold(ics);
+    let properties: Vec<Property> = ics.lines().filter_map(Property::parse).collect();
+
+    let is_calendar = properties
+        .first()
+        .map_or(false, |p| p.name == "BEGIN" && p.value == "VCALENDAR");
+
+    if !is_calendar {
+        return None;
+    }
+
+    let mut free = Vec::new();
+    let mut busy = Vec::new();
+
+    // Properties of nested components, like alarms and time zone rules, are
+    // not properties of the event
+    let mut components = Vec::new();
+    let mut event = Event::default();
+
+    for property in properties.iter() {
+        match property.name.as_str() {
+            "BEGIN" => {
+                if property.value == "VEVENT" {
+                    event = Event::default();
+                }
+                components.push(property.value);
+                continue;
+            }
+            "END" => {
+                let is_event = components.pop() == Some("VEVENT");
+                if is_event && event.recurring.is_some() && !event.cancelled {
+                    let periods = event.periods()?;
+                    if event.free {
+                        free.extend(periods);
+                    } else {
+                        busy.extend(periods);
+                    }
+                }
+                continue;
+            }
+            _ => {}
+        }
+
+        match components.last() {
+            Some(&"VEVENT") => event.set(property, tz)?,
+            Some(&"VFREEBUSY") if property.name == "FREEBUSY" => {
+                let is_free = property.param("FBTYPE") == Some("FREE");
+                let periods = property
+                    .value
+                    .split(',')
+                    .map(|period| parse_period(period, tz))
+                    .collect::<Option<Vec<_>>>()?;
+
+                if is_free {
+                    free.extend(periods);
+                } else {
+                    busy.extend(periods);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if free.is_empty() && busy.is_empty() {
+        return None;
+    }
+
+    let free_slots = slots(&free, resolution, true);
+    let busy_slots = slots(&busy, resolution, false);
+    let inverse = free.is_empty();
+
+    let mut week = WeekSchedule::default();
+
+    for &day in WEEKDAYS.iter() {
+        let slots = free_slots[day]
+            .slots
+            .iter()
+            .zip(busy_slots[day].slots.iter())
+            .map(|(&free, &busy)| (free || inverse) && !busy)
+            .collect();
+
+        week[day] = DaySchedule { resolution, slots };
+    }
+
+    Some(week)
+}
+
+/// A period of time, in local time
+type Period = (NaiveDateTime, NaiveDateTime);
+
+/// A content line, like `DTSTART;TZID=Europe/Rome:20200113T210000`
+struct Property<'a> {
+    name: String,
+    params: Vec<(String, &'a str)>,
+    value: &'a str,
+}
+
+impl<'a> Property<'a> {
+    fn parse(line: &'a str) -> Option<Self> {
+        let colon = line.find(':')?;
+        let mut head = line[..colon].split(';');
+        let name = head.next()?.trim().to_ascii_uppercase();
+
+        let params = head
+            .filter_map(|param| {
+                let eq = param.find('=')?;
+                let value = param[eq + 1..].trim_matches('"');
+                Some((param[..eq].to_ascii_uppercase(), value))
+            })
+            .collect();
+
+        let value = line[colon + 1..].trim();
+
+        Some(Property {
+            name,
+            params,
+            value,
+        })
+    }
+
+    fn param(&self, name: &str) -> Option<&'a str> {
+        self.params
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| *value)
+    }
+}
+
+#[derive(Default)]
+struct Event {
+    start: Option<NaiveDateTime>,
+    end: Option<NaiveDateTime>,
+    duration: Option<Duration>,
+    /// Weekdays when the event is repeated. Empty means the same weekday of
+    /// the start.
+    recurring: Option<Vec<Weekday>>,
+    free: bool,
+    cancelled: bool,
+}
+
+impl Event {
+    /// Returns None if the value of the property is not valid
+    fn set(&mut self, property: &Property, tz: Tz) -> Option<()> {
+        match property.name.as_str() {
+            "DTSTART" => self.start = Some(parse_property_time(property, tz)?),
+            "DTEND" => self.end = Some(parse_property_time(property, tz)?),
+            "DURATION" => self.duration = Some(parse_duration(property.value)?),
+            "RRULE" => self.recurring = parse_weekly_rule(property.value),
+            "TRANSP" => self.free = property.value == "TRANSPARENT",
+            "STATUS" => self.cancelled = property.value == "CANCELLED",
+            _ => {}
+        }
+        Some(())
+    }
+
+    /// A period for each weekday of the event. Returns None if the end of
+    /// the event cannot be represented.
+    fn periods(&self) -> Option<Vec<Period>> {
+        let start = match self.start {
+            Some(start) => start,
+            None => return Some(Vec::new()),
+        };
+
+        let end = match (self.end, self.duration) {
+            (Some(end), _) => end,
+            (None, Some(duration)) => start.checked_add_signed(duration)?,
+            // An event with a date only lasts the whole day
+            (None, None) if start.time() == NaiveTime::from_hms(0, 0, 0) => {
+                start + Duration::days(1)
+            }
+            (None, None) => start,
+        };
+
+        let days = match &self.recurring {
+            Some(days) if !days.is_empty() => days.clone(),
+            _ => vec![start.weekday()],
+        };
+
+        let end = end.min(start + Duration::days(MAX_PERIOD_DAYS));
+
+        let periods = days
+            .iter()
+            .map(|day| {
+                let from = start.weekday().num_days_from_monday() as i64;
+                let to = day.num_days_from_monday() as i64;
+                let shift = Duration::days((to - from + 7) % 7);
+                (start + shift, end + shift)
+            })
+            .collect();
+
+        Some(periods)
+    }
+}
+
+/// Unfold long lines, which are split with a line break and a space
+fn unfold(ics: &str) -> String {
+    ics.replace("\r\n", "\n")
+        .replace("\n ", "")
+        .replace("\n\t", "")
+}
+
+fn parse_property_time(property: &Property, tz: Tz) -> Option<NaiveDateTime> {
+    if let Ok(date) = NaiveDate::parse_from_str(property.value, "%Y%m%d") {
+        return valid_time(date.and_hms(0, 0, 0));
+    }
+
+    parse_time(property.value, property.param("TZID"), tz)
+}
+
+/// Parse a date-time as local time in `tz`. Times may be in UTC (`Z`
+/// suffix), in the time zone `tzid` or floating: floating times and times in
+/// unknown time zones are considered already local.
+fn parse_time(value: &str, tzid: Option<&str>, tz: Tz) -> Option<NaiveDateTime> {
+    const FORMAT: &str = "%Y%m%dT%H%M%S";
+
+    if let Some(utc) = value.strip_suffix('Z') {
+        let t = valid_time(NaiveDateTime::parse_from_str(utc, FORMAT).ok()?)?;
+        return Some(Utc.from_utc_datetime(&t).with_timezone(&tz).naive_local());
+    }
+
+    let t = valid_time(NaiveDateTime::parse_from_str(value, FORMAT).ok()?)?;
+
+    match tzid.and_then(|tzid| tzid.parse::<Tz>().ok()) {
+        Some(event_tz) if event_tz != tz => {
+            let t = event_tz.from_local_datetime(&t).earliest()?;
+            Some(t.with_timezone(&tz).naive_local())
+        }
+        _ => Some(t),
+    }
+}
+
+/// iCalendar years have four digits. Other times are not valid, which also
+/// keeps computations with valid times far from overflows.
+fn valid_time(t: NaiveDateTime) -> Option<NaiveDateTime> {
+    if (0..=9999).contains(&t.year()) {
+        Some(t)
+    } else {
+        None
+    }
+}
+
+/// Parse a duration, like `PT1H30M` or `P1D`. Durations too long to be
+/// represented are not valid.
+fn parse_duration(s: &str) -> Option<Duration> {
+    let s = s.strip_prefix('P')?;
+    let mut seconds: i64 = 0;
+    let mut number = String::new();
+
+    for c in s.chars() {
+        match c {
+            'T' => {}
+            '0'..='9' => number.push(c),
+            unit => {
+                let n: i64 = number.parse().ok()?;
+                number.clear();
+                let unit_seconds = match unit {
+                    'W' => 7 * 24 * 60 * 60,
+                    'D' => 24 * 60 * 60,
+                    'H' => 60 * 60,
+                    'M' => 60,
+                    'S' => 1,
+                    _ => return None,
+                };
+                let part = n.checked_mul(unit_seconds)?;
+                seconds = seconds.checked_add(part)?;
+            }
+        }
+    }
+
+    if seconds > MAX_DURATION_SECONDS {
+        return None;
+    }
+
+    Some(Duration::seconds(seconds))
+}
+
+/// Weekdays of a daily or weekly rule, like `FREQ=WEEKLY;BYDAY=MO,WE`.
+/// Other rules are not recurring every week, so they return None.
+fn parse_weekly_rule(rule: &str) -> Option<Vec<Weekday>> {
+    let mut freq = None;
+    let mut days = Vec::new();
+
+    for part in rule.split(';') {
+        let mut kv = part.splitn(2, '=');
+        match (kv.next(), kv.next()) {
+            (Some("FREQ"), Some(f)) => freq = Some(f),
+            (Some("BYDAY"), Some(byday)) => {
+                // Ordinals, like `1MO`, are only meaningful in monthly rules
+                days = byday
+                    .split(',')
+                    .filter_map(|d| {
+                        let d = d.trim_start_matches(|c: char| {
+                            c == '+' || c == '-' || c.is_ascii_digit()
+                        });
+                        parse_weekday(d)
+                    })
+                    .collect();
+            }
+            _ => {}
+        }
+    }
+
+    match freq {
+        Some("WEEKLY") => Some(days),
+        Some("DAILY") if days.is_empty() => Some(WEEKDAYS.to_vec()),
+        Some("DAILY") => Some(days),
+        _ => None,
+    }
+}
+
+fn parse_weekday(s: &str) -> Option<Weekday> {
+    let day = match s {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return None,
+    };
+    Some(day)
+}
+
+/// Parse a period of `FREEBUSY`, like `19970308T160000Z/PT8H30M` or
+/// `19970308T160000Z/19970308T230000Z`
+fn parse_period(s: &str, tz: Tz) -> Option<Period> {
+    let mut parts = s.trim().splitn(2, '/');
+    let start = parse_time(parts.next()?, None, tz)?;
+    let end = parts.next()?;
+
+    let end = if end.starts_with('P') {
+        start.checked_add_signed(parse_duration(end)?)?
+    } else {
+        parse_time(end, None, tz)?
+    };
+
+    Some((start, end))
+}
+
+/// Slots of the week in the periods. If `whole` is set, only slots entirely
+/// in a period are included, otherwise any overlap is enough.
+fn slots(periods: &[Period], resolution: Resolution, whole: bool) -> WeekSchedule {
+    let minutes = resolution.minutes() as i64;
+    let minute_of_day = |t: NaiveDateTime| (t.hour() * 60 + t.minute()) as i64;
+
+    let mut week = WeekSchedule::default();
+    for &day in WEEKDAYS.iter() {
+        week[day] = DaySchedule::empty(resolution);
+    }
+
+    for &(start, end) in periods.iter() {
+        let end = end.min(start + Duration::days(MAX_PERIOD_DAYS));
+
+        // The slot which contains the start
+        let mut slot_start = start.date().and_hms(0, 0, 0)
+            + Duration::minutes(minute_of_day(start) / minutes * minutes);
+
+        while slot_start < end {
+            let slot_end = slot_start + Duration::minutes(minutes);
+
+            if !whole || (slot_start >= start && slot_end <= end) {
+                let slot = (minute_of_day(slot_start) / minutes) as usize;
+                week[slot_start.weekday()].slots[slot] = true;
+            }
+
+            slot_start = slot_end;
+        }
+    }
+
+    week
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calendar::{availability_events, to_ical};
+    use crate::{Availability, UserId};
+    use std::convert::TryFrom;
+
+    fn ics(lines: &[&str]) -> String {
+        lines.join("\r\n")
+    }
+
+    #[test]
+    fn exported_availability_is_imported_back() {
+        let week = WeekSchedule {
+            mon: DaySchedule::try_from("9,10,21,22,23").unwrap(),
+            thu: DaySchedule::try_from("14,15").unwrap(),
+            ..WeekSchedule::default()
+        };
+
+        let tz = Tz::Europe__Rome;
+        let date = Utc.ymd(2020, 1, 8).and_hms(12, 0, 0);
+        let availability = Availability::Weekly(week.clone());
+        let events = availability_events(UserId(1), &availability, tz, date, "Available");
+        let ics = to_ical("DevAndDev", &events, date);
+
+        let imported = import_week_schedule(&ics, tz, Resolution::Hour).unwrap();
+        assert_eq!(imported, week);
+    }
+
+    #[test]
+    fn busy_time_is_inverted() {
+        let ics = ics(&[
+            "BEGIN:VCALENDAR",
+            "BEGIN:VFREEBUSY",
+            "DTSTART:20200106T000000Z",
+            "DTEND:20200113T000000Z",
+            // Monday, 9:00-17:30 in Rome
+            "FREEBUSY;FBTYPE=BUSY:20200106T080000Z/PT8H30M",
+            // Tuesday, from 23:00 to midnight in Rome
+            "FREEBUSY:20200107T220000Z/20200107T230000Z",
+            "END:VFREEBUSY",
+            "END:VCALENDAR",
+        ]);
+
+        let week = import_week_schedule(&ics, Tz::Europe__Rome, Resolution::Hour).unwrap();
+
+        let not = |s: &str| {
+            let busy = DaySchedule::try_from(s).unwrap();
+            let slots = busy.slots.iter().map(|b| !b).collect();
+            DaySchedule {
+                resolution: Resolution::Hour,
+                slots,
+            }
+        };
+
+        // The partially busy hour is not available
+        assert_eq!(week.mon, not("9-18"));
+        assert_eq!(week.tue, not("23"));
+        assert_eq!(week.wed, DaySchedule::always());
+    }
+
+    #[test]
+    fn busy_events_are_removed_from_free_time() {
+        let ics = ics(&[
+            "BEGIN:VCALENDAR",
+            "BEGIN:VTIMEZONE",
+            "TZID:Europe/London",
+            "BEGIN:STANDARD",
+            "DTSTART:19701025T020000",
+            "END:STANDARD",
+            "END:VTIMEZONE",
+            "BEGIN:VEVENT",
+            "DTSTART;TZID=Europe/London:20200106T170000",
+            "DURATION:PT3H",
+            "RRULE:FREQ=WEEKLY;BYDAY=MO,WE",
+            "TRANSP:TRANSPARENT",
+            "BEGIN:VALARM",
+            "DURATION:PT15M",
+            "END:VALARM",
+            "END:VEVENT",
+            "BEGIN:VEVENT",
+            "DTSTART:20200108T180000",
+            "DTEND:20200108T190000",
+            "RRULE:FREQ=WEEKLY",
+            "SUMMARY:Dinner",
+            "END:VEVENT",
+            "BEGIN:VEVENT",
+            "DTSTART:20200113T170000",
+            "DTEND:20200113T180000",
+            "SUMMARY:Dentist",
+            "END:VEVENT",
+            "END:VCALENDAR",
+        ]);
+
+        let week = import_week_schedule(&ics, Tz::Europe__London, Resolution::Hour).unwrap();
+
+        // The dentist is only on a Monday, so the other Mondays are still free
+        assert_eq!(week.mon, DaySchedule::try_from("17,18,19").unwrap());
+        assert_eq!(week.wed, DaySchedule::try_from("17,19").unwrap());
+        assert_eq!(week.tue, DaySchedule::never());
+    }
+
+    #[test]
+    fn invalid_durations_and_times() {
+        let event = |duration: &str| {
+            ics(&[
+                "BEGIN:VCALENDAR",
+                "BEGIN:VEVENT",
+                "DTSTART:20200106T170000",
+                duration,
+                "RRULE:FREQ=WEEKLY",
+                "END:VEVENT",
+                "END:VCALENDAR",
+            ])
+        };
+
+        let import = |ics: &str| import_week_schedule(ics, Tz::UTC, Resolution::Hour);
+
+        assert!(import(&event("DURATION:PT1H")).is_some());
+        assert!(import(&event("DURATION:P99999999D")).is_none());
+        assert!(import(&event("DURATION:P99999999999999999W")).is_none());
+        assert!(import(&event("DURATION:PT1X")).is_none());
+
+        let freebusy = ics(&[
+            "BEGIN:VCALENDAR",
+            "BEGIN:VFREEBUSY",
+            "FREEBUSY:20200106T080000Z/P99999999D",
+            "END:VFREEBUSY",
+            "END:VCALENDAR",
+        ]);
+        assert!(import(&freebusy).is_none());
+
+        let far_future = event("DTEND:+1000000106T180000");
+        assert!(import(&far_future).is_none());
+    }
+
+    #[test]
+    fn not_a_calendar() {
+        assert!(import_week_schedule("hello", Tz::UTC, Resolution::Hour).is_none());
+        let empty = ics(&["BEGIN:VCALENDAR", "END:VCALENDAR"]);
+        assert!(import_week_schedule(&empty, Tz::UTC, Resolution::Hour).is_none());
+    }
+}
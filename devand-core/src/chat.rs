@@ -1,16 +1,132 @@
 use crate::UserId;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
 
+pub mod highlight;
+pub mod markdown;
+
+/// Messages loaded at once, when no limit is given
+pub const HISTORY_PAGE_SIZE: usize = 50;
+
+/// Maximum number of messages loaded at once
+pub const MAX_HISTORY_PAGE_SIZE: usize = 200;
+
+/// Group chats are created with at least this number of members, the creator
+/// included. Direct chats have two members.
+pub const MIN_GROUP_CHAT_MEMBERS: usize = 3;
+
+/// Maximum length of the name of a group chat, in characters
+pub const MAX_GROUP_CHAT_NAME_LEN: usize = 64;
+
+/// Authors can edit or delete their messages for this time, unless the
+/// server is configured otherwise
+pub const DEFAULT_EDIT_WINDOW_MINUTES: i64 = 15;
+
+/// Longest edit window the server can be configured with, a hundred years
+pub const MAX_EDIT_WINDOW_MINUTES: i64 = 60 * 24 * 365 * 100;
+
+/// Maximum length of a message, in characters
+pub const MAX_MESSAGE_LEN: usize = 512;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ChatMessage {
     pub id: uuid::Uuid,
     pub created_at: DateTime<Utc>,
     pub author: UserId,
+    /// Empty when the message is deleted
+    pub txt: String,
+    /// Last time the author changed the text
+    #[serde(default)]
+    pub edited_at: Option<DateTime<Utc>>,
+    /// Deleted messages are kept in the history, without their text
+    #[serde(default)]
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+impl ChatMessage {
+    pub fn is_deleted(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+
+    /// Last time the message was created, edited or deleted
+    pub fn changed_at(&self) -> DateTime<Utc> {
+        self.created_at
+            .max(self.edited_at.unwrap_or(self.created_at))
+            .max(self.deleted_at.unwrap_or(self.created_at))
+    }
+
+    /// Only the author can edit or delete a message, for a while after it
+    /// was sent. Deleted messages cannot be changed anymore.
+    pub fn can_be_changed_by(&self, user: UserId, now: DateTime<Utc>, config: ChatConfig) -> bool {
+        self.author == user && !self.is_deleted() && now - self.created_at <= config.edit_window()
+    }
+}
+
+/// Where polling for changes continues, after the given changes. Without
+/// changes, it continues from the same time. Cursors are only computed by the
+/// server, because its clock is the only one that matters.
+pub fn next_changes_cursor(after: DateTime<Utc>, changes: &[ChatMessage]) -> DateTime<Utc> {
+    changes
+        .iter()
+        .map(ChatMessage::changed_at)
+        .fold(after, DateTime::max)
+}
+
+/// New messages, followed by the ones changed after the cursor
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ChatUpdates {
+    pub messages: Vec<ChatMessage>,
+    /// Cursor of the next poll for changes
+    #[serde(default)]
+    pub changed_after: Option<DateTime<Utc>>,
+}
+
+/// A previous text of an edited message
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ChatMessageEdit {
+    /// When this text was replaced
+    pub edited_at: DateTime<Utc>,
     pub txt: String,
 }
 
-#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+/// Settings of all chats, which can be changed by the server
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ChatConfig {
+    /// Minutes after sending, when authors can edit or delete a message
+    pub edit_window_minutes: i64,
+}
+
+impl Default for ChatConfig {
+    fn default() -> Self {
+        Self {
+            edit_window_minutes: DEFAULT_EDIT_WINDOW_MINUTES,
+        }
+    }
+}
+
+impl ChatConfig {
+    /// The edit window must not be negative nor longer than
+    /// `MAX_EDIT_WINDOW_MINUTES`
+    pub fn is_valid(&self) -> bool {
+        (0..=MAX_EDIT_WINDOW_MINUTES).contains(&self.edit_window_minutes)
+    }
+
+    pub fn edit_window(&self) -> Duration {
+        Duration::minutes(self.edit_window_minutes)
+    }
+}
+
+/// The new text of a message, if it is not blank and not longer than
+/// `MAX_MESSAGE_LEN`. Blank messages must be deleted instead.
+pub fn valid_edit(txt: &str) -> Option<String> {
+    if txt.trim().is_empty() || txt.chars().count() > MAX_MESSAGE_LEN {
+        None
+    } else {
+        Some(txt.to_string())
+    }
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ChatId(pub uuid::Uuid);
 
 impl std::fmt::Display for ChatId {
@@ -19,10 +135,71 @@ impl std::fmt::Display for ChatId {
     }
 }
 
+impl std::str::FromStr for ChatId {
+    type Err = uuid::ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        uuid::Uuid::parse_str(s).map(ChatId)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Chat {
     pub id: ChatId,
     pub members: Vec<UserId>,
+    /// Only group chats have a name
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+impl Chat {
+    /// Group chats are found by id and their members can change. Direct
+    /// chats are found by their members.
+    pub fn is_group(&self) -> bool {
+        self.name.is_some()
+    }
+}
+
+/// Chats are keyed by their set of members, so order and repetitions of
+/// members do not matter. Members are sorted and unique.
+pub fn canonical_members(members: &[UserId]) -> Vec<UserId> {
+    let mut members = members.to_vec();
+    members.sort();
+    members.dedup();
+    members
+}
+
+/// Request to create a group chat
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct NewGroupChat {
+    pub name: String,
+    /// Other members, the creator is added anyway
+    pub members: Vec<UserId>,
+}
+
+impl NewGroupChat {
+    /// The trimmed name, if it is not empty and not too long
+    pub fn valid_name(&self) -> Option<String> {
+        let name = self.name.trim();
+        let len = name.chars().count();
+        if len > 0 && len <= MAX_GROUP_CHAT_NAME_LEN {
+            Some(name.to_string())
+        } else {
+            None
+        }
+    }
+
+    /// All members of the group, creator included, if they are enough
+    pub fn valid_members(&self, creator: UserId) -> Option<Vec<UserId>> {
+        let mut members = self.members.clone();
+        members.push(creator);
+        let members = canonical_members(&members);
+        if members.len() >= MIN_GROUP_CHAT_MEMBERS {
+            Some(members)
+        } else {
+            None
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -36,8 +213,50 @@ pub struct ChatMemberInfo {
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ChatInfo {
+    /// Only group chats have a name
+    #[serde(default)]
+    pub name: Option<String>,
     pub members_info: Vec<ChatMemberInfo>,
     pub messages: Vec<ChatMessage>,
+    #[serde(default)]
+    pub config: ChatConfig,
+    /// Cursor of the first poll for changes
+    #[serde(default)]
+    pub changed_after: Option<DateTime<Utc>>,
+}
+
+/// Position of a page in the chat history. Messages are sorted by creation
+/// time and pages are always in chronological order.
+#[derive(Debug, Default, Copy, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryCursor {
+    /// The newest messages
+    #[default]
+    Latest,
+    /// Messages older than the one with this id
+    Before(uuid::Uuid),
+    /// Messages newer than the one with this id
+    After(uuid::Uuid),
+}
+
+/// Number of messages in a page, given the one requested
+pub fn history_page_size(limit: Option<usize>) -> usize {
+    limit
+        .unwrap_or(HISTORY_PAGE_SIZE)
+        .clamp(1, MAX_HISTORY_PAGE_SIZE)
+}
+
+/// Pushed to chat members as soon as something changes, so they do not need
+/// to poll
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatEvent {
+    /// A message was added to the chat
+    NewMessage { chat: Chat, message: ChatMessage },
+    /// A message was edited or deleted by its author
+    MessageChanged { chat: Chat, message: ChatMessage },
+    /// Unread messages in all chats of the user
+    UnreadMessages(usize),
 }
 
 #[cfg(test)]
@@ -49,5 +268,128 @@ mod test {
         let s = "554119d7-554a-438a-ac33-7a0b1bf99437";
         let u = uuid::Uuid::parse_str(s).unwrap();
         assert_eq!(s, format!("{}", ChatId(u)));
+        assert_eq!(s.parse::<ChatId>().unwrap(), ChatId(u));
+    }
+
+    #[test]
+    fn canonical_members_ignore_order() {
+        let members = canonical_members(&[UserId(72), UserId(5), UserId(72)]);
+        assert_eq!(members, vec![UserId(5), UserId(72)]);
+        assert_eq!(canonical_members(&[UserId(5), UserId(72)]), members);
+    }
+
+    #[test]
+    fn new_group_chat_validation() {
+        let group = NewGroupChat {
+            name: "  Rustaceans ".into(),
+            members: vec![UserId(3), UserId(2)],
+        };
+        assert_eq!(group.valid_name(), Some("Rustaceans".into()));
+        assert_eq!(
+            group.valid_members(UserId(1)),
+            Some(vec![UserId(1), UserId(2), UserId(3)])
+        );
+        // The creator is not counted twice
+        assert_eq!(group.valid_members(UserId(2)), None);
+
+        let group = NewGroupChat {
+            name: " ".into(),
+            ..group
+        };
+        assert_eq!(group.valid_name(), None);
+
+        let group = NewGroupChat {
+            name: "x".repeat(MAX_GROUP_CHAT_NAME_LEN + 1),
+            ..group
+        };
+        assert_eq!(group.valid_name(), None);
+    }
+
+    #[test]
+    fn history_page_size_is_limited() {
+        assert_eq!(history_page_size(None), HISTORY_PAGE_SIZE);
+        assert_eq!(history_page_size(Some(10)), 10);
+        assert_eq!(history_page_size(Some(0)), 1);
+        assert_eq!(history_page_size(Some(1000)), MAX_HISTORY_PAGE_SIZE);
+    }
+
+    #[test]
+    fn chat_event_json() {
+        let event = ChatEvent::UnreadMessages(3);
+        let json = serde_json::to_string(&event).unwrap();
+        assert_eq!(json, r#"{"unread_messages":3}"#);
+    }
+
+    fn message(created_at: DateTime<Utc>) -> ChatMessage {
+        ChatMessage {
+            id: uuid::Uuid::nil(),
+            created_at,
+            author: UserId(1),
+            txt: "Hello".into(),
+            edited_at: None,
+            deleted_at: None,
+        }
+    }
+
+    #[test]
+    fn message_changes() {
+        use chrono::TimeZone;
+        let t0 = Utc.ymd(2020, 10, 1).and_hms(10, 0, 0);
+        let config = ChatConfig::default();
+        let window = config.edit_window();
+
+        let mut msg = message(t0);
+        assert_eq!(msg.changed_at(), t0);
+        assert!(msg.can_be_changed_by(UserId(1), t0 + window, config));
+        assert!(!msg.can_be_changed_by(UserId(1), t0 + window + Duration::seconds(1), config));
+        assert!(!msg.can_be_changed_by(UserId(2), t0, config));
+
+        msg.edited_at = Some(t0 + Duration::minutes(1));
+        assert_eq!(msg.changed_at(), t0 + Duration::minutes(1));
+
+        msg.deleted_at = Some(t0 + Duration::minutes(2));
+        assert_eq!(msg.changed_at(), t0 + Duration::minutes(2));
+        assert!(!msg.can_be_changed_by(UserId(1), t0, config));
+
+        let other = message(t0 + Duration::minutes(3));
+        let cursor = next_changes_cursor(t0, &[msg.clone(), other]);
+        assert_eq!(cursor, t0 + Duration::minutes(3));
+        let later = t0 + Duration::hours(1);
+        assert_eq!(next_changes_cursor(later, &[msg]), later);
+        assert_eq!(next_changes_cursor(t0, &[]), t0);
+    }
+
+    #[test]
+    fn message_without_changes_json() {
+        let json = r#"{"id":"00000000-0000-0000-0000-000000000000","created_at":"2020-10-01T10:00:00Z","author":1,"txt":"Hello"}"#;
+        let msg: ChatMessage = serde_json::from_str(json).unwrap();
+        assert!(msg.edited_at.is_none());
+        assert!(!msg.is_deleted());
+    }
+
+    #[test]
+    fn blank_edits_are_invalid() {
+        assert_eq!(valid_edit(" fixed "), Some(" fixed ".into()));
+        assert_eq!(valid_edit(" \n"), None);
+    }
+
+    #[test]
+    fn long_edits_are_invalid() {
+        let longest = "è".repeat(MAX_MESSAGE_LEN);
+        assert_eq!(valid_edit(&longest), Some(longest.clone()));
+        assert_eq!(valid_edit(&format!("{}a", longest)), None);
+    }
+
+    #[test]
+    fn edit_window_is_validated() {
+        let config = |edit_window_minutes| ChatConfig {
+            edit_window_minutes,
+        };
+        assert!(ChatConfig::default().is_valid());
+        assert!(config(0).is_valid());
+        assert!(config(MAX_EDIT_WINDOW_MINUTES).is_valid());
+        assert!(!config(-1).is_valid());
+        assert!(!config(MAX_EDIT_WINDOW_MINUTES + 1).is_valid());
+        assert!(!config(i64::MAX).is_valid());
     }
 }
@@ -0,0 +1,406 @@
+use crate::Language;
+use strum::IntoEnumIterator;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TokenKind {
+    Plain,
+    Keyword,
+    String,
+    Number,
+    Comment,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub text: String,
+}
+
+/// What is highlighted in a language. This is far from a lexer, but good
+/// enough for the short snippets shared in a chat.
+struct Syntax {
+    /// Separated by spaces
+    keywords: &'static str,
+    line_comments: &'static [&'static str],
+    block_comments: &'static [(&'static str, &'static str)],
+    quotes: &'static [char],
+    /// Keywords are matched ignoring case
+    ignore_case: bool,
+}
+
+impl Default for Syntax {
+    fn default() -> Self {
+        Self {
+            keywords: "",
+            line_comments: &["//"],
+            block_comments: &[("/*", "*/")],
+            quotes: &['"', '\''],
+            ignore_case: false,
+        }
+    }
+}
+
+/// Highlight code written in the given language. Concatenating the text
+/// of all tokens gives the code back.
+pub fn highlight(code: &str, language: Language) -> Vec<Token> {
+    let syntax = syntax(language);
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut rest = code;
+
+    while !rest.is_empty() {
+        let (kind, len) = next_token(rest, &syntax);
+        let text = &rest[..len];
+        rest = &rest[len..];
+
+        match tokens.last_mut() {
+            Some(last) if last.kind == kind => last.text.push_str(text),
+            _ => tokens.push(Token {
+                kind,
+                text: text.to_string(),
+            }),
+        }
+    }
+
+    tokens
+}
+
+/// The language of a fenced code block, from its info string, e.g. `rust`,
+/// `rs` or `C++`
+pub fn language_from_info(info: &str) -> Option<Language> {
+    let name = info.split_whitespace().next()?.to_lowercase();
+
+    let alias = match name.as_str() {
+        "sh" | "shell" | "zsh" | "console" => Some(Language::Bash),
+        "h" => Some(Language::C),
+        "ocaml" | "ml" => Some(Language::Caml),
+        "cpp" | "cxx" | "cc" | "hpp" => Some(Language::CPlusPlus),
+        "cs" | "csharp" => Some(Language::CSharp),
+        "clj" | "cljs" => Some(Language::Clojure),
+        "ex" | "exs" => Some(Language::Elixir),
+        "erl" => Some(Language::Erlang),
+        "fs" | "fsharp" => Some(Language::FSharp),
+        "golang" => Some(Language::Go),
+        "hs" => Some(Language::Haskell),
+        "js" | "jsx" => Some(Language::JavaScript),
+        "kt" | "kts" => Some(Language::Kotlin),
+        "el" | "elisp" | "scheme" => Some(Language::Lisp),
+        "objc" | "objective-c" => Some(Language::ObjectiveC),
+        "py" => Some(Language::Python),
+        "rb" => Some(Language::Ruby),
+        "rs" => Some(Language::Rust),
+        "ts" | "tsx" => Some(Language::TypeScript),
+        "vb" => Some(Language::VBA),
+        _ => None,
+    };
+
+    alias.or_else(|| Language::iter().find(|language| language.to_string().to_lowercase() == name))
+}
+
+fn next_token(s: &str, syntax: &Syntax) -> (TokenKind, usize) {
+    if let Some(len) = comment_len(s, syntax) {
+        return (TokenKind::Comment, len);
+    }
+
+    let c = s.chars().next().unwrap_or_default();
+
+    if syntax.quotes.contains(&c) {
+        (TokenKind::String, string_len(s, c))
+    } else if c.is_ascii_digit() {
+        // Also suffixes and digits of other bases, e.g. 0xffu8
+        (TokenKind::Number, word_len(s))
+    } else if is_word_char(c) {
+        let len = word_len(s);
+        let word = &s[..len];
+        let is_keyword = syntax.keywords.split_whitespace().any(|k| {
+            if syntax.ignore_case {
+                k.eq_ignore_ascii_case(word)
+            } else {
+                k == word
+            }
+        });
+
+        if is_keyword {
+            (TokenKind::Keyword, len)
+        } else {
+            (TokenKind::Plain, len)
+        }
+    } else {
+        (TokenKind::Plain, c.len_utf8())
+    }
+}
+
+fn comment_len(s: &str, syntax: &Syntax) -> Option<usize> {
+    if syntax
+        .line_comments
+        .iter()
+        .any(|start| s.starts_with(start))
+    {
+        return Some(s.find('\n').unwrap_or(s.len()));
+    }
+
+    let (start, end) = syntax
+        .block_comments
+        .iter()
+        .find(|(start, _)| s.starts_with(start))?;
+
+    let len = match s[start.len()..].find(end) {
+        Some(i) => start.len() + i + end.len(),
+        None => s.len(),
+    };
+
+    Some(len)
+}
+
+/// Strings end at the closing quote, not escaped, or at the end of the line.
+/// Only backticks quote strings of many lines.
+fn string_len(s: &str, quote: char) -> usize {
+    let mut chars = s.char_indices().skip(1);
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '\n' if quote != '`' => return i,
+            c if c == quote => return i + c.len_utf8(),
+            _ => {}
+        }
+    }
+
+    s.len()
+}
+
+fn word_len(s: &str) -> usize {
+    s.find(|c| !is_word_char(c)).unwrap_or(s.len())
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn syntax(language: Language) -> Syntax {
+    match language {
+        Language::Ada => Syntax {
+            keywords: "\
+                begin end procedure function is if then else elsif loop while for in out return \
+                declare package body type record with use null and or not case when",
+            line_comments: &["--"],
+            block_comments: &[],
+            quotes: &['"'],
+            ignore_case: true,
+        },
+        Language::Bash => Syntax {
+            keywords: "\
+                if then else elif fi for while until do done case esac in function return local \
+                export echo",
+            line_comments: &["#"],
+            block_comments: &[],
+            ..Syntax::default()
+        },
+        Language::C | Language::CPlusPlus | Language::ObjectiveC => Syntax {
+            keywords: "\
+                auto break case char const continue default do double else enum extern float for \
+                goto if int long return short signed sizeof static struct switch typedef union \
+                unsigned void volatile while class namespace template typename public private \
+                protected virtual new delete this true false nullptr bool using self nil YES NO",
+            ..Syntax::default()
+        },
+        Language::Caml | Language::FSharp => Syntax {
+            keywords: "\
+                let rec in fun function match with if then else type of module open begin end \
+                true false mutable member val",
+            line_comments: if language == Language::FSharp {
+                &["//"]
+            } else {
+                &[]
+            },
+            block_comments: &[("(*", "*)")],
+            quotes: &['"'],
+            ignore_case: false,
+        },
+        Language::Clojure | Language::Lisp => Syntax {
+            keywords: "\
+                def defn defun defmacro defvar fn lambda let if cond when do progn loop recur \
+                nil true false",
+            line_comments: &[";"],
+            block_comments: &[],
+            quotes: &['"'],
+            ignore_case: false,
+        },
+        Language::CSharp | Language::Java => Syntax {
+            keywords: "\
+                abstract boolean bool break case catch class const continue default do double \
+                else enum extends final finally float for foreach if implements import in int \
+                interface long namespace new null package private protected public return static \
+                string super switch this throw throws true false try using var void while",
+            ..Syntax::default()
+        },
+        Language::Dart => Syntax {
+            keywords: "\
+                abstract async await break case class const else extends final for if import in \
+                new null return static switch this true false var void while",
+            ..Syntax::default()
+        },
+        Language::Elixir => Syntax {
+            keywords: "\
+                def defp defmodule do end fn if else case cond with when true false nil import \
+                alias use",
+            line_comments: &["#"],
+            block_comments: &[],
+            ..Syntax::default()
+        },
+        Language::Erlang => Syntax {
+            keywords: "after begin case catch end fun if of receive try when module export",
+            line_comments: &["%"],
+            block_comments: &[],
+            quotes: &['"'],
+            ignore_case: false,
+        },
+        Language::Go => Syntax {
+            keywords: "\
+                break case chan const continue default defer else for func go if import \
+                interface map package range return select struct switch type var nil true false",
+            quotes: &['"', '\'', '`'],
+            ..Syntax::default()
+        },
+        Language::Groovy | Language::Kotlin | Language::Scala => Syntax {
+            keywords: "\
+                class def else false for fun if import in interface is match new null object \
+                override package private return this true val var when while case trait extends",
+            ..Syntax::default()
+        },
+        Language::Haskell => Syntax {
+            keywords: "\
+                case class data deriving do else if import in instance let module newtype of \
+                then type where",
+            line_comments: &["--"],
+            block_comments: &[("{-", "-}")],
+            quotes: &['"'],
+            ignore_case: false,
+        },
+        Language::JavaScript | Language::TypeScript => Syntax {
+            keywords: "\
+                async await break case catch class const continue default else export extends \
+                false for function if import in instanceof interface let new null of return \
+                switch this throw true try type typeof undefined var while",
+            quotes: &['"', '\'', '`'],
+            ..Syntax::default()
+        },
+        Language::PHP => Syntax {
+            keywords: "\
+                echo function if else elseif for foreach as while return class public private \
+                protected new null true false array namespace use",
+            line_comments: &["//", "#"],
+            ..Syntax::default()
+        },
+        Language::Python => Syntax {
+            keywords: "\
+                and as async await break class continue def elif else except False finally for \
+                from if import in is lambda None not or pass raise return True try while with \
+                yield",
+            line_comments: &["#"],
+            block_comments: &[],
+            ..Syntax::default()
+        },
+        Language::R => Syntax {
+            keywords: "if else for while repeat function return next break TRUE FALSE NULL NA in",
+            line_comments: &["#"],
+            block_comments: &[],
+            ..Syntax::default()
+        },
+        Language::Ruby => Syntax {
+            keywords: "\
+                begin class def do else elsif end false for if in module nil require return self \
+                true unless until when while yield",
+            line_comments: &["#"],
+            block_comments: &[],
+            ..Syntax::default()
+        },
+        Language::Rust => Syntax {
+            keywords: "\
+                as async await break const continue crate dyn else enum false fn for if impl in \
+                let loop match mod move mut pub ref return self Self static struct super trait \
+                true type unsafe use where while",
+            // Single quotes are also lifetimes
+            quotes: &['"'],
+            ..Syntax::default()
+        },
+        Language::Swift => Syntax {
+            keywords: "\
+                class func let var if else guard for in while return struct enum protocol \
+                extension import nil true false self switch case",
+            quotes: &['"'],
+            ..Syntax::default()
+        },
+        Language::VBA => Syntax {
+            keywords: "\
+                Dim As Sub Function End If Then Else ElseIf For Each Next Do Loop While Wend Set \
+                Call Return True False Nothing And Or Not",
+            line_comments: &["'"],
+            block_comments: &[],
+            quotes: &['"'],
+            ignore_case: true,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(kind: TokenKind, text: &str) -> Token {
+        Token {
+            kind,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn highlight_rust() {
+        let code = "let x = \"a\\\"b\"; // 'a\nx + 0xffu8";
+        let tokens = highlight(code, Language::Rust);
+        assert_eq!(
+            tokens,
+            vec![
+                token(TokenKind::Keyword, "let"),
+                token(TokenKind::Plain, " x = "),
+                token(TokenKind::String, "\"a\\\"b\""),
+                token(TokenKind::Plain, "; "),
+                token(TokenKind::Comment, "// 'a"),
+                token(TokenKind::Plain, "\nx + "),
+                token(TokenKind::Number, "0xffu8"),
+            ]
+        );
+
+        let text: String = tokens.iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(text, code);
+    }
+
+    #[test]
+    fn highlight_by_language() {
+        let tokens = highlight("if x: # comment", Language::Python);
+        assert_eq!(tokens[0], token(TokenKind::Keyword, "if"));
+        assert_eq!(tokens[2], token(TokenKind::Comment, "# comment"));
+
+        let tokens = highlight("dim x ' comment", Language::VBA);
+        assert_eq!(tokens[0], token(TokenKind::Keyword, "dim"));
+        assert_eq!(tokens[2], token(TokenKind::Comment, "' comment"));
+
+        // Not closed strings and comments end with the code
+        let tokens = highlight("x = \"abc\ny /* z", Language::C);
+        assert_eq!(tokens[1], token(TokenKind::String, "\"abc"));
+        assert_eq!(tokens[3], token(TokenKind::Comment, "/* z"));
+    }
+
+    #[test]
+    fn language_from_fence_info() {
+        assert_eq!(language_from_info("rust"), Some(Language::Rust));
+        assert_eq!(language_from_info(" rs ignore"), Some(Language::Rust));
+        assert_eq!(language_from_info("C++"), Some(Language::CPlusPlus));
+        assert_eq!(language_from_info("c#"), Some(Language::CSharp));
+        assert_eq!(language_from_info("JavaScript"), Some(Language::JavaScript));
+        assert_eq!(language_from_info("py"), Some(Language::Python));
+        assert_eq!(language_from_info("brainfuck"), None);
+        assert_eq!(language_from_info(""), None);
+    }
+}
@@ -0,0 +1,478 @@
+//! Chat messages use a small subset of Markdown, parsed here instead of with
+//! comrak, as devand-mailer does. This crate is also built for the UI, which
+//! renders the parsed blocks as elements and never as HTML. Comrak parses the
+//! whole CommonMark, which would have to be reduced to this subset again, and
+//! treats single new lines as spaces, while chat messages keep them.
+
+use super::highlight::language_from_info;
+use crate::Language;
+
+/// Only links with these schemes are shown as links, others are text
+const SAFE_URL_SCHEMES: [&str; 3] = ["http://", "https://", "mailto:"];
+
+/// Urls found in text are links, without their trailing punctuation
+const URL_TRAILING_PUNCTUATION: &[char] = &['.', ',', ';', ':', '!', '?', '\'', '"'];
+
+/// A block of a chat message
+#[derive(Debug, Clone, PartialEq)]
+pub enum Block {
+    Paragraph(Vec<Inline>),
+    /// Consecutive lines starting with `- `, `* ` or `+ `
+    List(Vec<Vec<Inline>>),
+    /// Consecutive lines starting with `>`
+    Quote(Vec<Inline>),
+    /// Fenced with ``` or ~~~, with the language after the opening fence
+    Code {
+        language: Option<Language>,
+        code: String,
+    },
+}
+
+/// Text in a block. Nesting is not supported, e.g. strong text cannot
+/// contain a link.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Inline {
+    Text(String),
+    Code(String),
+    Strong(String),
+    Emphasis(String),
+    /// Only http, https and mailto urls
+    Link {
+        text: String,
+        url: String,
+    },
+    /// Every new line in a paragraph is kept
+    LineBreak,
+}
+
+/// Parse a chat message, written with a small subset of Markdown: fenced
+/// code blocks, bullet lists, quotes, inline code, strong and emphasized
+/// text and links. Anything else, HTML included, is text. The result is
+/// rendered as elements, so a message can never inject HTML.
+pub fn parse(txt: &str) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut paragraph: Vec<&str> = Vec::new();
+    let mut lines = txt.lines().peekable();
+
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim_start();
+
+        if let Some(fence) = opening_fence(trimmed) {
+            flush_paragraph(&mut blocks, &mut paragraph);
+            let info = trimmed.trim_start_matches(&['`', '~'][..]);
+            let code: Vec<&str> = lines
+                .by_ref()
+                .take_while(|line| !line.trim_start().starts_with(fence))
+                .collect();
+            blocks.push(Block::Code {
+                language: language_from_info(info),
+                code: code.join("\n"),
+            });
+        } else if trimmed.is_empty() {
+            flush_paragraph(&mut blocks, &mut paragraph);
+        } else if let Some(item) = list_item(trimmed) {
+            flush_paragraph(&mut blocks, &mut paragraph);
+            let mut items = vec![parse_inline(item)];
+            while let Some(item) = lines.peek().copied().and_then(list_item) {
+                items.push(parse_inline(item));
+                lines.next();
+            }
+            blocks.push(Block::List(items));
+        } else if let Some(quoted) = quote_line(trimmed) {
+            flush_paragraph(&mut blocks, &mut paragraph);
+            let mut quoted_lines = vec![quoted];
+            while let Some(quoted) = lines.peek().copied().and_then(quote_line) {
+                quoted_lines.push(quoted);
+                lines.next();
+            }
+            blocks.push(Block::Quote(parse_lines(&quoted_lines)));
+        } else {
+            paragraph.push(line.trim());
+        }
+    }
+
+    flush_paragraph(&mut blocks, &mut paragraph);
+    blocks
+}
+
+/// The message without markup, e.g. for email notifications. Code is kept
+/// as it is and links are followed by their url.
+pub fn to_plain_text(txt: &str) -> String {
+    parse(txt)
+        .iter()
+        .map(block_to_plain_text)
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+fn block_to_plain_text(block: &Block) -> String {
+    match block {
+        Block::Paragraph(inlines) => inlines_to_plain_text(inlines),
+        Block::List(items) => items
+            .iter()
+            .map(|item| format!("- {}", inlines_to_plain_text(item)))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Block::Quote(inlines) => inlines_to_plain_text(inlines)
+            .lines()
+            .map(|line| format!("> {}", line))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Block::Code { code, .. } => code.clone(),
+    }
+}
+
+fn inlines_to_plain_text(inlines: &[Inline]) -> String {
+    inlines
+        .iter()
+        .map(|inline| match inline {
+            Inline::Text(s) | Inline::Code(s) | Inline::Strong(s) | Inline::Emphasis(s) => {
+                s.clone()
+            }
+            Inline::Link { text, url } if text == url => url.clone(),
+            Inline::Link { text, url } => format!("{} ({})", text, url),
+            Inline::LineBreak => "\n".to_string(),
+        })
+        .collect()
+}
+
+fn flush_paragraph(blocks: &mut Vec<Block>, paragraph: &mut Vec<&str>) {
+    if !paragraph.is_empty() {
+        blocks.push(Block::Paragraph(parse_lines(paragraph)));
+        paragraph.clear();
+    }
+}
+
+fn opening_fence(line: &str) -> Option<&'static str> {
+    ["```", "~~~"]
+        .iter()
+        .copied()
+        .find(|fence| line.starts_with(fence))
+}
+
+fn list_item(line: &str) -> Option<&str> {
+    let line = line.trim_start();
+    ["- ", "* ", "+ "]
+        .iter()
+        .find_map(|bullet| line.strip_prefix(bullet))
+}
+
+fn quote_line(line: &str) -> Option<&str> {
+    let quoted = line.trim_start().strip_prefix('>')?;
+    Some(quoted.strip_prefix(' ').unwrap_or(quoted))
+}
+
+fn parse_lines(lines: &[&str]) -> Vec<Inline> {
+    let mut inlines = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        if i > 0 {
+            inlines.push(Inline::LineBreak);
+        }
+        inlines.extend(parse_inline(line));
+    }
+    inlines
+}
+
+fn parse_inline(line: &str) -> Vec<Inline> {
+    let mut inlines = Vec::new();
+    let mut text = String::new();
+    let mut prev: Option<char> = None;
+    let mut rest = line;
+
+    while let Some(c) = rest.chars().next() {
+        // Escaped punctuation is always text
+        if c == '\\' {
+            if let Some(escaped) = rest[1..].chars().next().filter(char::is_ascii_punctuation) {
+                text.push(escaped);
+                prev = Some(escaped);
+                rest = &rest[1 + escaped.len_utf8()..];
+                continue;
+            }
+        }
+
+        let parsed = match c {
+            '`' => code_span(rest),
+            '*' | '_' => emphasis(rest, prev),
+            '[' => link(rest),
+            'h' | 'H' if !prev.map_or(false, char::is_alphanumeric) => autolink(rest),
+            _ => None,
+        };
+
+        match parsed {
+            Some((inline, len)) => {
+                if !text.is_empty() {
+                    inlines.push(Inline::Text(std::mem::take(&mut text)));
+                }
+                inlines.push(inline);
+                prev = rest[..len].chars().last();
+                rest = &rest[len..];
+            }
+            None => {
+                text.push(c);
+                prev = Some(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+
+    if !text.is_empty() {
+        inlines.push(Inline::Text(text));
+    }
+
+    inlines
+}
+
+/// Code between runs of backticks of the same length
+fn code_span(s: &str) -> Option<(Inline, usize)> {
+    let ticks = s.len() - s.trim_start_matches('`').len();
+    let fence = &s[..ticks];
+    let end = s[ticks..].find(fence)?;
+    let code = s[ticks..ticks + end].trim();
+
+    if code.is_empty() {
+        None
+    } else {
+        Some((Inline::Code(code.to_string()), ticks + end + ticks))
+    }
+}
+
+/// `**strong**` or `*emphasis*`, also with underscores. Underscores inside
+/// words, as in `snake_case`, are text.
+fn emphasis(s: &str, prev: Option<char>) -> Option<(Inline, usize)> {
+    let c = s.chars().next()?;
+    if c == '_' && prev.map_or(false, char::is_alphanumeric) {
+        return None;
+    }
+
+    let strong = s[1..].starts_with(c);
+    let delimiter_len = if strong { 2 } else { 1 };
+    let delimiter = &s[..delimiter_len];
+    let inner = &s[delimiter_len..];
+    let end = inner.find(delimiter)?;
+    let content = &inner[..end];
+    let after = &inner[end + delimiter_len..];
+
+    let valid_content = !content.is_empty()
+        && !content.starts_with(char::is_whitespace)
+        && !content.ends_with(char::is_whitespace);
+    let closes_word = c != '_' || !after.starts_with(char::is_alphanumeric);
+
+    if !valid_content || !closes_word {
+        return None;
+    }
+
+    let inline = if strong {
+        Inline::Strong(content.to_string())
+    } else {
+        Inline::Emphasis(content.to_string())
+    };
+
+    Some((inline, delimiter_len + end + delimiter_len))
+}
+
+/// `[text](url)`, only with a safe url
+fn link(s: &str) -> Option<(Inline, usize)> {
+    let close = s.find("](")?;
+    let text = &s[1..close];
+    let after = &s[close + 2..];
+    let end = after.find(')')?;
+    let url = safe_url(&after[..end])?;
+
+    if text.trim().is_empty() {
+        return None;
+    }
+
+    let inline = Inline::Link {
+        text: text.to_string(),
+        url,
+    };
+
+    Some((inline, close + 2 + end + 1))
+}
+
+/// Urls in text, starting with http:// or https://
+fn autolink(s: &str) -> Option<(Inline, usize)> {
+    let end = s.find(char::is_whitespace).unwrap_or(s.len());
+    let mut url = s[..end].trim_end_matches(URL_TRAILING_PUNCTUATION);
+
+    // A closing parenthesis belongs to the url only if it is balanced
+    while url.ends_with(')') && url.matches(')').count() > url.matches('(').count() {
+        url = &url[..url.len() - 1];
+    }
+
+    let scheme_len = url.find("://")? + 3;
+    if url.len() == scheme_len {
+        return None;
+    }
+
+    let url = safe_url(url)?;
+    let len = url.len();
+    let inline = Inline::Link {
+        text: url.clone(),
+        url,
+    };
+
+    Some((inline, len))
+}
+
+/// Urls with other schemes, like `javascript:`, could run code when clicked
+fn safe_url(url: &str) -> Option<String> {
+    let url = url.trim();
+    let lowercase = url.to_lowercase();
+    let safe = SAFE_URL_SCHEMES
+        .iter()
+        .any(|scheme| lowercase.starts_with(scheme) && lowercase.len() > scheme.len());
+
+    if safe && !url.contains(char::is_whitespace) {
+        Some(url.to_string())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str) -> Inline {
+        Inline::Text(s.to_string())
+    }
+
+    #[test]
+    fn parse_paragraphs_with_line_breaks() {
+        let blocks = parse("Hello\nworld\n\nBye");
+        assert_eq!(
+            blocks,
+            vec![
+                Block::Paragraph(vec![text("Hello"), Inline::LineBreak, text("world")]),
+                Block::Paragraph(vec![text("Bye")]),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_fenced_code_with_language() {
+        let blocks = parse("Look:\n```rust\nfn main() {\n    *x = 1;\n}\n```\nok");
+        assert_eq!(
+            blocks,
+            vec![
+                Block::Paragraph(vec![text("Look:")]),
+                Block::Code {
+                    language: Some(Language::Rust),
+                    code: "fn main() {\n    *x = 1;\n}".to_string(),
+                },
+                Block::Paragraph(vec![text("ok")]),
+            ]
+        );
+
+        // Not closed, until the end
+        let blocks = parse("~~~\n<b>x</b>");
+        assert_eq!(
+            blocks,
+            vec![Block::Code {
+                language: None,
+                code: "<b>x</b>".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn parse_lists_and_quotes() {
+        let blocks = parse("- one\n* `two`\n> quoted\n> text");
+        assert_eq!(
+            blocks,
+            vec![
+                Block::List(vec![
+                    vec![text("one")],
+                    vec![Inline::Code("two".to_string())],
+                ]),
+                Block::Quote(vec![text("quoted"), Inline::LineBreak, text("text")]),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_inline_markup() {
+        let inlines = parse_inline("a **b** *c* _d_ `e` snake_case_name \\*f\\*");
+        assert_eq!(
+            inlines,
+            vec![
+                text("a "),
+                Inline::Strong("b".to_string()),
+                text(" "),
+                Inline::Emphasis("c".to_string()),
+                text(" "),
+                Inline::Emphasis("d".to_string()),
+                text(" "),
+                Inline::Code("e".to_string()),
+                text(" snake_case_name *f*"),
+            ]
+        );
+
+        assert_eq!(parse_inline("2 * 3 * 4"), vec![text("2 * 3 * 4")]);
+        assert_eq!(
+            parse_inline("``a ` b``"),
+            vec![Inline::Code("a ` b".to_string())]
+        );
+    }
+
+    #[test]
+    fn parse_links() {
+        let inlines = parse_inline("See [docs](https://docs.rs/x) or https://devand.dev.");
+        assert_eq!(
+            inlines,
+            vec![
+                text("See "),
+                Inline::Link {
+                    text: "docs".to_string(),
+                    url: "https://docs.rs/x".to_string(),
+                },
+                text(" or "),
+                Inline::Link {
+                    text: "https://devand.dev".to_string(),
+                    url: "https://devand.dev".to_string(),
+                },
+                text("."),
+            ]
+        );
+
+        let inlines = parse_inline("(https://en.wikipedia.org/wiki/Rust_(programming_language))");
+        assert_eq!(
+            inlines[1],
+            Inline::Link {
+                text: "https://en.wikipedia.org/wiki/Rust_(programming_language)".to_string(),
+                url: "https://en.wikipedia.org/wiki/Rust_(programming_language)".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn unsafe_markup_is_text() {
+        let inlines = parse_inline("[click](javascript:alert(1)) <script>alert(1)</script>");
+        assert_eq!(
+            inlines,
+            vec![text(
+                "[click](javascript:alert(1)) <script>alert(1)</script>"
+            )]
+        );
+
+        assert_eq!(
+            parse_inline("[x](JavaScript:alert(1))"),
+            vec![text("[x](JavaScript:alert(1))")]
+        );
+        assert_eq!(
+            parse_inline("[x](data:text/html,x)"),
+            vec![text("[x](data:text/html,x)")]
+        );
+        assert_eq!(parse_inline("https://"), vec![text("https://")]);
+    }
+
+    #[test]
+    fn plain_text() {
+        let txt = "Try **this**:\n```rust\nlet x = 1;\n```\n- [docs](https://docs.rs)\n> `ok`";
+        assert_eq!(
+            to_plain_text(txt),
+            "Try this:\n\nlet x = 1;\n\n- docs (https://docs.rs)\n\n> ok"
+        );
+    }
+}
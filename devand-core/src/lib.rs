@@ -1,22 +1,38 @@
 mod affinity;
 pub mod auth;
+pub mod calendar;
 pub mod chat;
 mod languages;
 pub mod mock;
+pub mod pairing;
 mod schedule;
 pub mod schedule_matcher;
+pub mod session;
 mod spoken_languages;
 pub mod string_utils;
+mod time_zone;
+mod topics;
+mod vacation;
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::cmp::Ord;
 use std::collections::{BTreeMap, BTreeSet};
 use strum_macros::{Display, EnumIter, EnumString};
 
-pub use affinity::{Affinity, AffinityLevel, AffinityParams};
+pub use affinity::{
+    Affinity, AffinityBreakdown, AffinityConfig, AffinityLevel, AffinityParams, AffinityStrategy,
+    AffinityStrategyKind, AffinityWeights, BestLanguageStrategy, LanguageAffinity, PairRoles, Role,
+    WeightedStrategy,
+};
 pub use languages::Language;
-pub use schedule::{Availability, DaySchedule, WeekSchedule};
+pub use schedule::{
+    Availability, DayException, DaySchedule, Resolution, WeekSchedule, WeekScheduleDiff,
+};
 pub use spoken_languages::*;
+pub use time_zone::UserTimeZone;
+pub use topics::{count_topics, topics_score, Topic, TopicCount, Topics};
+pub use vacation::Vacation;
 
 /// Identifies univocally an user
 #[derive(Debug, Default, Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
@@ -123,9 +139,63 @@ pub struct UserSettings {
     pub schedule: Availability,
     /// User can disable all activities without losing schedule
     pub vacation_mode: bool,
+    /// When vacation mode is enabled, it is active only in this period
+    #[serde(default)]
+    pub vacation: Vacation,
     /// User can set spoken language
     #[serde(default)]
     pub spoken_languages: SpokenLanguages,
+    /// User does not want to pair with people who do not speak any of
+    /// their spoken languages
+    #[serde(default)]
+    pub require_common_spoken_language: bool,
+    /// User can set topics of interest, beyond languages
+    #[serde(default)]
+    pub topics: Topics,
+    /// Schedule is expressed in this time zone
+    #[serde(default)]
+    pub time_zone: UserTimeZone,
+    /// User wants a suggested pair every week
+    #[serde(default)]
+    pub weekly_pairing: bool,
+    /// Secret url of user's calendar feed
+    #[serde(default)]
+    pub calendar_feed: calendar::CalendarFeed,
+}
+
+impl UserSettings {
+    /// The vacation period, if vacation mode is enabled
+    pub fn enabled_vacation(&self) -> Option<Vacation> {
+        if self.vacation_mode {
+            Some(self.vacation)
+        } else {
+            None
+        }
+    }
+
+    /// Return true if user is on vacation at the given time
+    pub fn is_on_vacation(&self, now: DateTime<Utc>) -> bool {
+        self.enabled_vacation().map_or(false, |vacation| {
+            vacation.contains_time(now, *self.time_zone)
+        })
+    }
+
+    /// Remove schedule exceptions on dates which are already past in user's
+    /// time zone, so they do not pile up in stored settings
+    pub fn remove_past_exceptions(&mut self, now: DateTime<Utc>) {
+        if let Availability::Weekly(week_schedule) = &mut self.schedule {
+            let today = now.with_timezone(&*self.time_zone).date().naive_local();
+            week_schedule.remove_exceptions_before(today);
+        }
+    }
+
+    /// Return true if user can pair with someone who speaks
+    /// `spoken_languages`, that is always unless a common spoken language
+    /// is required
+    pub fn accepts_spoken_languages(&self, spoken_languages: &SpokenLanguages) -> bool {
+        !self.require_common_spoken_language
+            || are_spoken_language_compatible(&self.spoken_languages, spoken_languages)
+    }
 }
 
 #[derive(
@@ -182,11 +252,40 @@ pub enum Priority {
     High,
 }
 
+/// What an user wants to do with a language
+#[derive(
+    Debug,
+    Default,
+    Serialize,
+    Deserialize,
+    Copy,
+    Clone,
+    EnumIter,
+    Display,
+    EnumString,
+    Ord,
+    PartialOrd,
+    Eq,
+    PartialEq,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Intent {
+    /// Pair with someone with a similar level
+    #[default]
+    Peer,
+    /// Find a mentor
+    Learn,
+    /// Mentor someone
+    Teach,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub struct LanguagePreference {
     pub level: Level,
     pub priority: Priority,
+    #[serde(default)]
+    pub intent: Intent,
 }
 
 impl Default for LanguagePreference {
@@ -194,6 +293,7 @@ impl Default for LanguagePreference {
         Self {
             level: Level::Novice,
             priority: Priority::High,
+            intent: Intent::Peer,
         }
     }
 }
@@ -203,49 +303,145 @@ impl Default for LanguagePreference {
 pub struct UserAffinity {
     pub user: PublicUserProfile,
     pub affinity: Affinity,
+    /// Roles in the pair, for the language with the best affinity
+    #[serde(default)]
+    pub roles: Option<PairRoles>,
+    /// How the affinity has been calculated
+    #[serde(default)]
+    pub breakdown: Option<AffinityBreakdown>,
 }
 
 impl UserAffinity {
     pub fn new(user: PublicUserProfile, affinity: Affinity) -> Self {
-        Self { user, affinity }
+        Self {
+            user,
+            affinity,
+            roles: None,
+            breakdown: None,
+        }
+    }
+
+    pub fn with_roles(mut self, roles: Option<PairRoles>) -> Self {
+        self.roles = roles;
+        self
+    }
+
+    pub fn with_breakdown(mut self, breakdown: AffinityBreakdown) -> Self {
+        self.breakdown = Some(breakdown);
+        self
     }
 }
 
+/// Affinity params of an user, without the schedule: it must be projected on
+/// a specific week first (see `calculate_users_affinities`)
 impl From<&User> for AffinityParams {
     fn from(user: &User) -> Self {
-        let languages = user.settings.languages.clone();
-        AffinityParams::new().with_languages(languages)
+        let settings = &user.settings;
+        let languages = settings.languages.clone();
+        AffinityParams::new()
+            .with_languages(languages)
+            .with_spoken_languages(settings.spoken_languages.clone())
+            .with_topics(settings.topics.clone())
     }
 }
 
 impl From<&PublicUserProfile> for AffinityParams {
     fn from(user: &PublicUserProfile) -> Self {
         let languages = user.languages.clone();
-        AffinityParams::new().with_languages(languages)
+        AffinityParams::new()
+            .with_languages(languages)
+            .with_spoken_languages(user.spoken_languages.clone())
+            .with_topics(user.topics.clone())
     }
 }
 
-/// Calculate affinities between `user` and all `users` passed
+impl From<&User> for schedule_matcher::MatchingProfile {
+    fn from(user: &User) -> Self {
+        let settings = &user.settings;
+        let params = AffinityParams::new()
+            .with_languages(settings.languages.clone())
+            .with_spoken_languages(settings.spoken_languages.clone())
+            .with_topics(settings.topics.clone());
+        Self {
+            params,
+            require_common_spoken_language: settings.require_common_spoken_language,
+        }
+    }
+}
+
+/// Calculate affinities between `user` and all `users` passed. Only the
+/// spoken languages requirement of `user` is known, so `users` which
+/// require a common spoken language must be filtered before.
 pub fn calculate_affinities(
-    user: &PublicUserProfile,
+    user: &User,
     users: impl IntoIterator<Item = PublicUserProfile>,
+    config: &AffinityConfig,
+) -> impl Iterator<Item = UserAffinity> {
+    let settings = user.settings.clone();
+    let user = PublicUserProfile::from(user.clone());
+    let user_params = AffinityParams::from(&user);
+    let users = users
+        .into_iter()
+        .filter(move |u| settings.accepts_spoken_languages(&u.spoken_languages))
+        .map(|u| {
+            let params = AffinityParams::from(&u);
+            (u, params)
+        });
+
+    calculate_affinities_with_params(&user, user_params, users, *config)
+}
+
+/// Calculate affinities between `user` and all `users` passed, taking
+/// their schedules, as projected in `wsm`, into account
+pub fn calculate_users_affinities(
+    user: &User,
+    users: impl IntoIterator<Item = User>,
+    wsm: &schedule_matcher::WeekScheduleMatrix,
+    config: &AffinityConfig,
+) -> impl Iterator<Item = UserAffinity> {
+    let params = |u: &User| AffinityParams::from(u).with_schedule(wsm.utc_schedule(u.id));
+    let user_params = params(user);
+    let settings = &user.settings;
+    let users: Vec<_> = users
+        .into_iter()
+        // Remove users with incompatible spoken languages, if any of them
+        // requires a common spoken language
+        .filter(|u| {
+            settings.accepts_spoken_languages(&u.settings.spoken_languages)
+                && u.settings
+                    .accepts_spoken_languages(&settings.spoken_languages)
+        })
+        .map(|u| {
+            let params = params(&u);
+            (PublicUserProfile::from(u), params)
+        })
+        .collect();
+    let user = PublicUserProfile::from(user.clone());
+
+    calculate_affinities_with_params(&user, user_params, users, *config)
+}
+
+fn calculate_affinities_with_params(
+    user: &PublicUserProfile,
+    user_params: AffinityParams,
+    users: impl IntoIterator<Item = (PublicUserProfile, AffinityParams)>,
+    config: AffinityConfig,
 ) -> impl Iterator<Item = UserAffinity> {
     let username = user.username.clone();
-    let user_params = AffinityParams::from(user);
-    let spoken_languages = user.spoken_languages.clone();
 
     users
         .into_iter()
         // There may be same user in the list, just skip it
-        .filter(move |u| u.username != username)
-        // Remove users with incompatible spoken languages
-        .filter(move |u| are_spoken_language_compatible(&u.spoken_languages, &spoken_languages))
+        .filter(move |(u, _)| u.username != username)
+        // Remove users on vacation
+        .filter(|(u, _)| u.vacation.is_none())
         // Calculate the affinity
-        .map(move |u| {
-            let u_params = AffinityParams::from(&u);
-            // TODO Avoid cloning logged user params
-            let affinity = Affinity::from_params(&user_params, &u_params);
-            UserAffinity::new(u, affinity)
+        .map(move |(u, u_params)| {
+            let breakdown = AffinityBreakdown::from_params(&user_params, &u_params, &config);
+            let roles = PairRoles::from_params(&user_params, &u_params, &config);
+            UserAffinity::new(u, breakdown.affinity)
+                .with_roles(roles)
+                .with_breakdown(breakdown)
         })
         // Remove users who do not have any affinity
         .filter(|aff| aff.affinity != Affinity::NONE)
@@ -260,7 +456,12 @@ pub struct PublicUserProfile {
     pub languages: Languages,
     pub bio: String,
     pub spoken_languages: SpokenLanguages,
+    #[serde(default)]
+    pub topics: Topics,
     pub projects: Vec<String>,
+    /// Current vacation, if user is on vacation now
+    #[serde(default)]
+    pub vacation: Option<Vacation>,
 }
 
 impl PublicUserProfile {
@@ -281,6 +482,12 @@ impl PartialEq for PublicUserProfile {
 
 impl From<User> for PublicUserProfile {
     fn from(user: User) -> Self {
+        let vacation = if user.settings.is_on_vacation(Utc::now()) {
+            user.settings.enabled_vacation()
+        } else {
+            None
+        };
+
         PublicUserProfile {
             id: user.id,
             username: user.username,
@@ -288,7 +495,9 @@ impl From<User> for PublicUserProfile {
             languages: user.settings.languages,
             bio: user.bio,
             spoken_languages: user.settings.spoken_languages,
+            topics: user.settings.topics,
             projects: user.projects,
+            vacation,
         }
     }
 }
@@ -323,14 +532,120 @@ mod tests {
         let _json = serde_json::to_string(&user).unwrap();
     }
 
+    #[test]
+    fn user_settings_without_time_zone_are_utc() {
+        let json = r#"{"languages":{},"schedule":"never","vacation_mode":false}"#;
+        let settings: UserSettings = serde_json::from_str(json).unwrap();
+        assert_eq!(settings.time_zone, UserTimeZone::default());
+    }
+
+    #[test]
+    fn vacation_in_user_time_zone() {
+        use chrono::{NaiveDate, TimeZone};
+
+        let mut settings = mock::user().settings;
+        settings.vacation = Vacation {
+            start: Some(NaiveDate::from_ymd(2020, 8, 1)),
+            end: None,
+        };
+
+        // It is already August 1st in Rome
+        let now = Utc.ymd(2020, 7, 31).and_hms(23, 0, 0);
+        assert!(!settings.is_on_vacation(now));
+        settings.vacation_mode = true;
+        assert!(settings.is_on_vacation(now));
+        settings.time_zone = UserTimeZone::default();
+        assert!(!settings.is_on_vacation(now));
+    }
+
+    #[test]
+    fn users_on_vacation_have_no_affinity() {
+        let user = mock::user();
+        let mut other = mock::user_with_username("other");
+        other.id = UserId(2);
+        let available: PublicUserProfile = other.clone().into();
+        other.settings.vacation_mode = true;
+        let on_vacation: PublicUserProfile = other.into();
+        assert!(on_vacation.vacation.is_some());
+
+        let config = AffinityConfig::default();
+        assert_eq!(
+            calculate_affinities(&user, vec![available], &config).count(),
+            1
+        );
+        assert_eq!(
+            calculate_affinities(&user, vec![on_vacation], &config).count(),
+            0
+        );
+    }
+
+    #[test]
+    fn common_spoken_language_is_required_by_user() {
+        use maplit::btreeset;
+
+        let mut user = mock::user();
+        user.settings.spoken_languages = btreeset![SpokenLanguage::Italian].into();
+        let mut other = mock::user_with_username("other");
+        other.id = UserId(2);
+        other.settings.spoken_languages = btreeset![SpokenLanguage::English].into();
+        let other: PublicUserProfile = other.into();
+
+        let config = AffinityConfig::default();
+        assert_eq!(
+            calculate_affinities(&user, vec![other.clone()], &config).count(),
+            1
+        );
+        user.settings.require_common_spoken_language = true;
+        assert_eq!(calculate_affinities(&user, vec![other], &config).count(), 0);
+    }
+
+    #[test]
+    fn users_with_overlapping_schedule_rank_higher() {
+        use std::convert::TryFrom;
+
+        let user = mock::user();
+
+        let with_schedule = |id, username, mon: &str| {
+            let mut u = mock::user_with_username(username);
+            u.id = UserId(id);
+            let week = WeekSchedule {
+                mon: DaySchedule::try_from(mon).unwrap(),
+                ..WeekSchedule::default()
+            };
+            u.settings.schedule = Availability::Weekly(week);
+            u
+        };
+
+        let users = vec![
+            with_schedule(2, "never_overlapping", "9"),
+            with_schedule(3, "overlapping", "21,22,23"),
+        ];
+
+        let schedules = std::iter::once(&user)
+            .chain(users.iter())
+            .filter_map(|u| match &u.settings.schedule {
+                Availability::Weekly(week) => Some((u.id, week.clone(), *u.settings.time_zone)),
+                Availability::Never => None,
+            })
+            .collect();
+        let wsm = schedule_matcher::WeekScheduleMatrix::from_local(schedules, Utc::now());
+
+        let mut affinities: Vec<_> =
+            calculate_users_affinities(&user, users, &wsm, &AffinityConfig::default()).collect();
+        affinities.sort_by_key(|x| std::cmp::Reverse(x.affinity));
+
+        assert_eq!(affinities.len(), 2);
+        assert_eq!(affinities[0].user.username, "overlapping");
+    }
+
     #[test]
     fn sort_languages_by_priority_then_level() {
         let languages = Languages(btreemap![
-            Language::C => LanguagePreference { level: Level::Expert, priority: Priority::Low, },
-            Language::JavaScript => LanguagePreference { level: Level::Proficient, priority: Priority::Low, },
-            Language::CPlusPlus => LanguagePreference { level: Level::Novice, priority: Priority::Low, },
-            Language::Rust => LanguagePreference { level: Level::Proficient, priority: Priority::High, },
-            Language::Go => LanguagePreference { level: Level::Expert, priority: Priority::No, }
+            Language::C => LanguagePreference { level: Level::Expert, priority: Priority::Low, intent: Intent::Peer, },
+            Language::JavaScript => LanguagePreference { level: Level::Proficient, priority: Priority::Low, intent: Intent::Peer, },
+            Language::CPlusPlus => LanguagePreference { level: Level::Novice, priority: Priority::Low, intent: Intent::Peer, },
+            Language::Rust => LanguagePreference { level: Level::Proficient, priority: Priority::High, intent: Intent::Peer, },
+            Language::Go => LanguagePreference { level: Level::Expert, priority: Priority::No, intent: Intent::Peer, }
         ]);
 
         let languages = languages.into_sorted_vec();
@@ -345,17 +660,17 @@ mod tests {
     #[test]
     fn languages_intersection() {
         let a = Languages(btreemap![
-            Language::C => LanguagePreference { level: Level::Expert, priority: Priority::Low, },
-            Language::JavaScript => LanguagePreference { level: Level::Proficient, priority: Priority::Low, },
-            Language::Rust => LanguagePreference { level: Level::Novice, priority: Priority::High, },
-            Language::Go => LanguagePreference { level: Level::Expert, priority: Priority::Low, }
+            Language::C => LanguagePreference { level: Level::Expert, priority: Priority::Low, intent: Intent::Peer, },
+            Language::JavaScript => LanguagePreference { level: Level::Proficient, priority: Priority::Low, intent: Intent::Peer, },
+            Language::Rust => LanguagePreference { level: Level::Novice, priority: Priority::High, intent: Intent::Peer, },
+            Language::Go => LanguagePreference { level: Level::Expert, priority: Priority::Low, intent: Intent::Peer, }
         ]);
 
         let b = Languages(btreemap![
-            Language::C => LanguagePreference { level: Level::Expert, priority: Priority::Low, },
-            Language::Java => LanguagePreference { level: Level::Proficient, priority: Priority::Low, },
-            Language::Rust => LanguagePreference { level: Level::Proficient, priority: Priority::Low, },
-            Language::Go => LanguagePreference { level: Level::Expert, priority: Priority::High, }
+            Language::C => LanguagePreference { level: Level::Expert, priority: Priority::Low, intent: Intent::Peer, },
+            Language::Java => LanguagePreference { level: Level::Proficient, priority: Priority::Low, intent: Intent::Peer, },
+            Language::Rust => LanguagePreference { level: Level::Proficient, priority: Priority::Low, intent: Intent::Peer, },
+            Language::Go => LanguagePreference { level: Level::Expert, priority: Priority::High, intent: Intent::Peer, }
         ]);
 
         let c = a.intersection(&b);
@@ -365,17 +680,17 @@ mod tests {
     #[test]
     fn languages_no_priority() {
         let a = Languages(btreemap![
-            Language::C => LanguagePreference { level: Level::Expert, priority: Priority::Low, },
-            Language::JavaScript => LanguagePreference { level: Level::Proficient, priority: Priority::Low, },
-            Language::Rust => LanguagePreference { level: Level::Novice, priority: Priority::No, },
-            Language::Go => LanguagePreference { level: Level::Expert, priority: Priority::No, }
+            Language::C => LanguagePreference { level: Level::Expert, priority: Priority::Low, intent: Intent::Peer, },
+            Language::JavaScript => LanguagePreference { level: Level::Proficient, priority: Priority::Low, intent: Intent::Peer, },
+            Language::Rust => LanguagePreference { level: Level::Novice, priority: Priority::No, intent: Intent::Peer, },
+            Language::Go => LanguagePreference { level: Level::Expert, priority: Priority::No, intent: Intent::Peer, }
         ]);
 
         let b = Languages(btreemap![
-            Language::C => LanguagePreference { level: Level::Expert, priority: Priority::No, },
-            Language::Java => LanguagePreference { level: Level::Proficient, priority: Priority::No, },
-            Language::Rust => LanguagePreference { level: Level::Proficient, priority: Priority::Low, },
-            Language::Go => LanguagePreference { level: Level::Expert, priority: Priority::High, }
+            Language::C => LanguagePreference { level: Level::Expert, priority: Priority::No, intent: Intent::Peer, },
+            Language::Java => LanguagePreference { level: Level::Proficient, priority: Priority::No, intent: Intent::Peer, },
+            Language::Rust => LanguagePreference { level: Level::Proficient, priority: Priority::Low, intent: Intent::Peer, },
+            Language::Go => LanguagePreference { level: Level::Expert, priority: Priority::High, intent: Intent::Peer, }
         ]);
 
         let c = a.intersection(&b);
@@ -385,17 +700,17 @@ mod tests {
     #[test]
     fn languages_union() {
         let a = Languages(btreemap![
-            Language::C => LanguagePreference { level: Level::Expert, priority: Priority::Low, },
-            Language::JavaScript => LanguagePreference { level: Level::Proficient, priority: Priority::Low, },
-            Language::Rust => LanguagePreference { level: Level::Novice, priority: Priority::High, },
-            Language::Go => LanguagePreference { level: Level::Expert, priority: Priority::Low, }
+            Language::C => LanguagePreference { level: Level::Expert, priority: Priority::Low, intent: Intent::Peer, },
+            Language::JavaScript => LanguagePreference { level: Level::Proficient, priority: Priority::Low, intent: Intent::Peer, },
+            Language::Rust => LanguagePreference { level: Level::Novice, priority: Priority::High, intent: Intent::Peer, },
+            Language::Go => LanguagePreference { level: Level::Expert, priority: Priority::Low, intent: Intent::Peer, }
         ]);
 
         let b = Languages(btreemap![
-            Language::C => LanguagePreference { level: Level::Expert, priority: Priority::Low, },
-            Language::Java => LanguagePreference { level: Level::Proficient, priority: Priority::Low, },
-            Language::Rust => LanguagePreference { level: Level::Proficient, priority: Priority::Low, },
-            Language::Go => LanguagePreference { level: Level::Expert, priority: Priority::High, }
+            Language::C => LanguagePreference { level: Level::Expert, priority: Priority::Low, intent: Intent::Peer, },
+            Language::Java => LanguagePreference { level: Level::Proficient, priority: Priority::Low, intent: Intent::Peer, },
+            Language::Rust => LanguagePreference { level: Level::Proficient, priority: Priority::Low, intent: Intent::Peer, },
+            Language::Go => LanguagePreference { level: Level::Expert, priority: Priority::High, intent: Intent::Peer, }
         ]);
 
         let c = a.union(&b);
@@ -12,12 +12,13 @@ pub fn user() -> User {
         email_verified: true,
         settings: UserSettings {
             languages: Languages(btreemap! {
-                Language::C => LanguagePreference { level: Level::Expert, priority: Priority::Low },
-                Language::JavaScript => LanguagePreference { level: Level::Proficient, priority: Priority::No },
-                Language::CPlusPlus => LanguagePreference { level: Level::Expert, priority: Priority::Low },
-                Language::Rust => LanguagePreference { level: Level::Novice, priority: Priority::High },
+                Language::C => LanguagePreference { level: Level::Expert, priority: Priority::Low, intent: Intent::Peer },
+                Language::JavaScript => LanguagePreference { level: Level::Proficient, priority: Priority::No, intent: Intent::Peer },
+                Language::CPlusPlus => LanguagePreference { level: Level::Expert, priority: Priority::Low, intent: Intent::Peer },
+                Language::Rust => LanguagePreference { level: Level::Novice, priority: Priority::High, intent: Intent::Peer },
             }),
             vacation_mode: false,
+            vacation: Vacation::default(),
             schedule: Availability::Weekly(WeekSchedule {
                 mon: DaySchedule::try_from("21,22,23").unwrap(),
                 tue: DaySchedule::never(),
@@ -26,8 +27,14 @@ pub fn user() -> User {
                 fri: DaySchedule::never(),
                 sat: DaySchedule::always(),
                 sun: DaySchedule::never(),
+                exceptions: btreemap! {},
             }),
-            spoken_languages: SpokenLanguages(btreeset! {SpokenLanguage::English}),
+            spoken_languages: SpokenLanguages::from(btreeset! {SpokenLanguage::English}),
+            require_common_spoken_language: false,
+            topics: Topics::default(),
+            time_zone: UserTimeZone(chrono_tz::Tz::Europe__Rome),
+            weekly_pairing: false,
+            calendar_feed: calendar::CalendarFeed::default(),
         },
         bio: "Ciao".to_string(),
         projects: vec!["https://github.com/alepez/devand".into()],
@@ -39,3 +46,16 @@ pub fn user_with_username(username: &str) -> User {
     user.username = username.to_string();
     user
 }
+
+/// User available only on Monday, in UTC, with the given schedule
+#[cfg(test)]
+pub(crate) fn user_on_monday(id: i32, monday: &str) -> User {
+    let mut user = user_with_username(&format!("user{}", id));
+    user.id = UserId(id);
+    user.settings.time_zone = UserTimeZone::default();
+    user.settings.schedule = Availability::Weekly(WeekSchedule {
+        mon: DaySchedule::try_from(monday).unwrap(),
+        ..WeekSchedule::default()
+    });
+    user
+}
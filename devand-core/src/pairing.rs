@@ -0,0 +1,238 @@
+//! Weekly pairing proposes a globally good set of disjoint pairs among all
+//! users who opted in, so everyone gets a suggested pair of the week.
+
+mod max_weight_matching;
+
+use crate::schedule_matcher::{utc_availability, utc_availability_without_vacation};
+use crate::{
+    are_spoken_language_compatible, Affinity, AffinityConfig, AffinityParams, User, UserId,
+};
+use chrono::prelude::*;
+use chrono::Duration;
+use max_weight_matching::max_weight_matching;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct WeeklyPair {
+    pub users: (UserId, UserId),
+    pub affinity: Affinity,
+    /// Minutes both users are available in the week
+    pub overlap_minutes: usize,
+}
+
+impl WeeklyPair {
+    /// The other user in the pair, if `user` is in the pair
+    pub fn other(&self, user: UserId) -> Option<UserId> {
+        match self.users {
+            (a, b) if a == user => Some(b),
+            (a, b) if b == user => Some(a),
+            _ => None,
+        }
+    }
+}
+
+/// Suggested pairs of the week. An user is in one pair at most.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct WeeklyPairs(pub Vec<WeeklyPair>);
+
+impl WeeklyPairs {
+    /// The pair suggested to `user`, if any
+    pub fn of(&self, user: UserId) -> Option<&WeeklyPair> {
+        self.0.iter().find(|pair| pair.other(user).is_some())
+    }
+}
+
+/// Start of the week after `now`, on Monday at 00:00 UTC
+pub fn next_week(now: DateTime<Utc>) -> DateTime<Utc> {
+    let days_to_monday = 7 - now.weekday().num_days_from_monday() as i64;
+    (now.date() + Duration::days(days_to_monday)).and_hms(0, 0, 0)
+}
+
+/// Propose disjoint pairs for the 7 days starting from `week`, among users
+/// who opted in. The sum of affinities of all pairs is maximized. Only users
+/// with overlapping schedules, after removing vacations, can be paired.
+pub fn suggest_weekly_pairs(
+    users: &[User],
+    week: DateTime<Utc>,
+    config: &AffinityConfig,
+) -> WeeklyPairs {
+    let users: Vec<&User> = users.iter().filter(|u| u.settings.weekly_pairing).collect();
+
+    let params: Vec<(UserId, AffinityParams)> = users
+        .iter()
+        .map(|u| {
+            let settings = &u.settings;
+            let availability = &settings.schedule;
+            let tz = *settings.time_zone;
+            let schedule = match settings.enabled_vacation() {
+                Some(vacation) => {
+                    utc_availability_without_vacation(availability, tz, &vacation, week)
+                }
+                None => utc_availability(availability, tz, week),
+            };
+            let params = AffinityParams::new()
+                .with_languages(settings.languages.clone())
+                .with_schedule(schedule)
+                .with_spoken_languages(settings.spoken_languages.clone())
+                .with_topics(settings.topics.clone());
+            (u.id, params)
+        })
+        .collect();
+
+    // Vertices of the graph are indexes in `users`
+    let mut candidates = Vec::new();
+    for (i, (a, a_params)) in users.iter().zip(params.iter()).enumerate() {
+        for (j, (b, b_params)) in users.iter().zip(params.iter()).enumerate().skip(i + 1) {
+            let required = a.settings.require_common_spoken_language
+                || b.settings.require_common_spoken_language;
+            let a_spoken = &a.settings.spoken_languages;
+            let b_spoken = &b.settings.spoken_languages;
+            if required && !are_spoken_language_compatible(a_spoken, b_spoken) {
+                continue;
+            }
+
+            let overlap_minutes = match (a_params.1.schedule(), b_params.1.schedule()) {
+                (Some(a), Some(b)) => a.overlap_minutes(b),
+                _ => 0,
+            };
+            if overlap_minutes == 0 {
+                continue;
+            }
+
+            let affinity = Affinity::from_params(&a_params.1, &b_params.1, config);
+            if !affinity.is_zero() {
+                candidates.push((i, j, affinity, overlap_minutes));
+            }
+        }
+    }
+
+    let edges: Vec<_> = candidates
+        .iter()
+        .map(|&(i, j, affinity, _)| (i, j, (affinity.normalize() * 1000.0).round() as i64))
+        .collect();
+
+    let mate = max_weight_matching(&edges);
+
+    let pairs = candidates
+        .into_iter()
+        .filter(|&(i, j, _, _)| mate.get(i) == Some(&Some(j)))
+        .map(|(i, j, affinity, overlap_minutes)| WeeklyPair {
+            users: (users[i].id, users[j].id),
+            affinity,
+            overlap_minutes,
+        })
+        .collect();
+
+    WeeklyPairs(pairs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Topics, Vacation};
+
+    /// User who opted in weekly pairing
+    fn opted_in_user(id: i32, mon: &str) -> User {
+        let mut user = crate::mock::user_on_monday(id, mon);
+        user.settings.weekly_pairing = true;
+        user
+    }
+
+    fn monday() -> DateTime<Utc> {
+        Utc.ymd(2020, 1, 6).and_hms(0, 0, 0)
+    }
+
+    #[test]
+    fn next_week_starts_on_monday() {
+        let sunday = Utc.ymd(2020, 1, 5).and_hms(23, 0, 0);
+        assert_eq!(next_week(sunday), monday());
+        let monday_morning = Utc.ymd(2019, 12, 30).and_hms(9, 0, 0);
+        assert_eq!(next_week(monday_morning), monday());
+    }
+
+    #[test]
+    fn pair_users_with_overlapping_schedules() {
+        let users = vec![
+            opted_in_user(1, "9,10"),
+            opted_in_user(2, "20,21"),
+            opted_in_user(3, "9"),
+            opted_in_user(4, "21"),
+        ];
+
+        let pairs = suggest_weekly_pairs(&users, monday(), &AffinityConfig::default());
+
+        assert_eq!(pairs.0.len(), 2);
+        assert_eq!(
+            pairs.of(UserId(1)).unwrap().other(UserId(1)),
+            Some(UserId(3))
+        );
+        assert_eq!(
+            pairs.of(UserId(2)).unwrap().other(UserId(2)),
+            Some(UserId(4))
+        );
+        assert_eq!(pairs.of(UserId(3)).unwrap().overlap_minutes, 60);
+    }
+
+    #[test]
+    fn users_with_common_topics_are_preferred() {
+        let interested_in = |id: i32, topics: &[&str]| {
+            let mut user = opted_in_user(id, "9");
+            user.settings.topics = Topics(topics.iter().map(|x| x.parse().unwrap()).collect());
+            user
+        };
+
+        let users = vec![
+            interested_in(1, &["tokio"]),
+            interested_in(2, &["react"]),
+            interested_in(3, &["tokio"]),
+            interested_in(4, &["react"]),
+        ];
+
+        let pairs = suggest_weekly_pairs(&users, monday(), &AffinityConfig::default());
+
+        assert_eq!(
+            pairs.of(UserId(1)).unwrap().other(UserId(1)),
+            Some(UserId(3))
+        );
+    }
+
+    #[test]
+    fn users_are_paired_at_most_once() {
+        let users = vec![
+            opted_in_user(1, "9"),
+            opted_in_user(2, "9"),
+            opted_in_user(3, "9"),
+        ];
+
+        let pairs = suggest_weekly_pairs(&users, monday(), &AffinityConfig::default());
+
+        assert_eq!(pairs.0.len(), 1);
+    }
+
+    #[test]
+    fn only_opted_in_users_are_paired() {
+        let mut not_opted_in = opted_in_user(2, "9");
+        not_opted_in.settings.weekly_pairing = false;
+        let users = vec![opted_in_user(1, "9"), not_opted_in];
+
+        let pairs = suggest_weekly_pairs(&users, monday(), &AffinityConfig::default());
+
+        assert!(pairs.of(UserId(1)).is_none());
+    }
+
+    #[test]
+    fn users_on_vacation_are_not_paired() {
+        let mut on_vacation = opted_in_user(2, "9");
+        on_vacation.settings.vacation_mode = true;
+        on_vacation.settings.vacation = Vacation {
+            start: Some(NaiveDate::from_ymd(2020, 1, 1)),
+            end: Some(NaiveDate::from_ymd(2020, 1, 10)),
+        };
+        let users = vec![opted_in_user(1, "9"), on_vacation];
+
+        let pairs = suggest_weekly_pairs(&users, monday(), &AffinityConfig::default());
+
+        assert!(pairs.0.is_empty());
+    }
+}
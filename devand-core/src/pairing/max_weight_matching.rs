@@ -0,0 +1,731 @@
+//! Maximum weight matching in general graphs, with Edmonds' blossom
+//! algorithm in O(n^3). This is a port of the well known `mwmatching.py` by
+//! Joris van Rantwijk (public domain), restricted to integer weights and
+//! without the maximum cardinality option.
+
+/// An undirected edge between vertex `.0` and vertex `.1`, with weight `.2`
+pub type Edge = (usize, usize, i64);
+
+const NONE: usize = usize::MAX;
+
+/// Find a matching with the maximum total weight. Return the mate of each
+/// vertex, if any.
+pub fn max_weight_matching(edges: &[Edge]) -> Vec<Option<usize>> {
+    if edges.is_empty() {
+        return Vec::default();
+    }
+
+    let mut m = Matching::new(edges);
+    m.solve();
+
+    m.mate
+        .iter()
+        .map(|&p| if p == NONE { None } else { Some(m.endpoint[p]) })
+        .collect()
+}
+
+/// Index a blossom child list, wrapping around negative indexes
+fn at(v: &[usize], i: isize) -> usize {
+    v[i.rem_euclid(v.len() as isize) as usize]
+}
+
+struct Matching<'a> {
+    edges: &'a [Edge],
+    nvertex: usize,
+    /// Vertex at each edge endpoint: endpoint `p` of edge `k` is
+    /// `edges[k].0` if `p == 2k`, `edges[k].1` if `p == 2k + 1`
+    endpoint: Vec<usize>,
+    /// Remote endpoints of edges incident on each vertex
+    neighbend: Vec<Vec<usize>>,
+    /// Remote endpoint of the matched edge of each vertex
+    mate: Vec<usize>,
+    /// Label of each top-level blossom: 0 free, 1 S-blossom, 2 T-blossom
+    label: Vec<u8>,
+    /// Endpoint through which a labeled blossom got its label
+    labelend: Vec<usize>,
+    /// Top-level blossom containing each vertex
+    inblossom: Vec<usize>,
+    blossomparent: Vec<usize>,
+    blossomchilds: Vec<Vec<usize>>,
+    blossombase: Vec<usize>,
+    blossomendps: Vec<Vec<usize>>,
+    /// Least-slack edge to a different S-blossom
+    bestedge: Vec<usize>,
+    blossombestedges: Vec<Option<Vec<usize>>>,
+    unusedblossoms: Vec<usize>,
+    /// Dual variables, doubled so they stay integers
+    dualvar: Vec<i64>,
+    allowedge: Vec<bool>,
+    queue: Vec<usize>,
+}
+
+impl<'a> Matching<'a> {
+    fn new(edges: &'a [Edge]) -> Self {
+        let nedge = edges.len();
+        let nvertex = edges
+            .iter()
+            .map(|&(i, j, _)| i.max(j) + 1)
+            .max()
+            .unwrap_or(0);
+        let maxweight = edges.iter().map(|&(_, _, w)| w).max().unwrap_or(0).max(0);
+
+        let endpoint = (0..2 * nedge)
+            .map(|p| {
+                let (i, j, _) = edges[p / 2];
+                if p % 2 == 0 {
+                    i
+                } else {
+                    j
+                }
+            })
+            .collect();
+
+        let mut neighbend = vec![Vec::new(); nvertex];
+        for (k, &(i, j, _)) in edges.iter().enumerate() {
+            neighbend[i].push(2 * k + 1);
+            neighbend[j].push(2 * k);
+        }
+
+        let mut blossombase: Vec<usize> = (0..nvertex).collect();
+        blossombase.resize(2 * nvertex, NONE);
+
+        let mut dualvar = vec![maxweight; nvertex];
+        dualvar.resize(2 * nvertex, 0);
+
+        Self {
+            edges,
+            nvertex,
+            endpoint,
+            neighbend,
+            mate: vec![NONE; nvertex],
+            label: vec![0; 2 * nvertex],
+            labelend: vec![NONE; 2 * nvertex],
+            inblossom: (0..nvertex).collect(),
+            blossomparent: vec![NONE; 2 * nvertex],
+            blossomchilds: vec![Vec::new(); 2 * nvertex],
+            blossombase,
+            blossomendps: vec![Vec::new(); 2 * nvertex],
+            bestedge: vec![NONE; 2 * nvertex],
+            blossombestedges: vec![None; 2 * nvertex],
+            unusedblossoms: (nvertex..2 * nvertex).collect(),
+            dualvar,
+            allowedge: vec![false; nedge],
+            queue: Vec::new(),
+        }
+    }
+
+    fn slack(&self, k: usize) -> i64 {
+        let (i, j, w) = self.edges[k];
+        self.dualvar[i] + self.dualvar[j] - 2 * w
+    }
+
+    fn blossom_leaves(&self, b: usize) -> Vec<usize> {
+        if b < self.nvertex {
+            vec![b]
+        } else {
+            self.blossomchilds[b]
+                .iter()
+                .flat_map(|&t| self.blossom_leaves(t))
+                .collect()
+        }
+    }
+
+    /// Assign label `t` to the top-level blossom containing vertex `w`,
+    /// coming through endpoint `p`
+    fn assign_label(&mut self, w: usize, t: u8, p: usize) {
+        let b = self.inblossom[w];
+        debug_assert!(self.label[w] == 0 && self.label[b] == 0);
+        self.label[w] = t;
+        self.label[b] = t;
+        self.labelend[w] = p;
+        self.labelend[b] = p;
+        self.bestedge[w] = NONE;
+        self.bestedge[b] = NONE;
+        if t == 1 {
+            let leaves = self.blossom_leaves(b);
+            self.queue.extend(leaves);
+        } else if t == 2 {
+            let base = self.blossombase[b];
+            debug_assert!(self.mate[base] != NONE);
+            let mate = self.mate[base];
+            self.assign_label(self.endpoint[mate], 1, mate ^ 1);
+        }
+    }
+
+    /// Trace back from vertices `v` and `w` to discover either a new blossom
+    /// or an augmenting path. Return the base vertex of the new blossom, if
+    /// any.
+    fn scan_blossom(&mut self, mut v: usize, mut w: usize) -> Option<usize> {
+        let mut path = Vec::new();
+        let mut base = None;
+        while v != NONE || w != NONE {
+            let mut b = self.inblossom[v];
+            if self.label[b] & 4 != 0 {
+                base = Some(self.blossombase[b]);
+                break;
+            }
+            debug_assert_eq!(self.label[b], 1);
+            path.push(b);
+            self.label[b] = 5;
+            if self.labelend[b] == NONE {
+                v = NONE;
+            } else {
+                v = self.endpoint[self.labelend[b]];
+                b = self.inblossom[v];
+                debug_assert_eq!(self.label[b], 2);
+                v = self.endpoint[self.labelend[b]];
+            }
+            if w != NONE {
+                std::mem::swap(&mut v, &mut w);
+            }
+        }
+        for b in path {
+            self.label[b] = 1;
+        }
+        base
+    }
+
+    /// Construct a new blossom with given base, containing edge `k` which
+    /// connects a pair of S vertices
+    fn add_blossom(&mut self, base: usize, k: usize) {
+        let (mut v, mut w, _) = self.edges[k];
+        let bb = self.inblossom[base];
+        let mut bv = self.inblossom[v];
+        let mut bw = self.inblossom[w];
+
+        let b = self.unusedblossoms.pop().expect("unused blossom");
+        self.blossombase[b] = base;
+        self.blossomparent[b] = NONE;
+        self.blossomparent[bb] = b;
+
+        let mut path = Vec::new();
+        let mut endps = Vec::new();
+        while bv != bb {
+            self.blossomparent[bv] = b;
+            path.push(bv);
+            endps.push(self.labelend[bv]);
+            v = self.endpoint[self.labelend[bv]];
+            bv = self.inblossom[v];
+        }
+        path.push(bb);
+        path.reverse();
+        endps.reverse();
+        endps.push(2 * k);
+        while bw != bb {
+            self.blossomparent[bw] = b;
+            path.push(bw);
+            endps.push(self.labelend[bw] ^ 1);
+            w = self.endpoint[self.labelend[bw]];
+            bw = self.inblossom[w];
+        }
+
+        debug_assert_eq!(self.label[bb], 1);
+        self.label[b] = 1;
+        self.labelend[b] = self.labelend[bb];
+        self.dualvar[b] = 0;
+
+        for v in self.blossom_leaves_of(&path) {
+            if self.label[self.inblossom[v]] == 2 {
+                self.queue.push(v);
+            }
+            self.inblossom[v] = b;
+        }
+
+        let mut bestedgeto = vec![NONE; 2 * self.nvertex];
+        for &bv in &path {
+            let nblists: Vec<Vec<usize>> = match self.blossombestedges[bv].take() {
+                Some(list) => vec![list],
+                None => self
+                    .blossom_leaves(bv)
+                    .iter()
+                    .map(|&v| self.neighbend[v].iter().map(|p| p / 2).collect())
+                    .collect(),
+            };
+            for k in nblists.into_iter().flatten() {
+                let (mut i, mut j, _) = self.edges[k];
+                if self.inblossom[j] == b {
+                    std::mem::swap(&mut i, &mut j);
+                }
+                let bj = self.inblossom[j];
+                if bj != b
+                    && self.label[bj] == 1
+                    && (bestedgeto[bj] == NONE || self.slack(k) < self.slack(bestedgeto[bj]))
+                {
+                    bestedgeto[bj] = k;
+                }
+            }
+            self.bestedge[bv] = NONE;
+        }
+
+        let best_edges: Vec<usize> = bestedgeto.into_iter().filter(|&k| k != NONE).collect();
+        self.bestedge[b] = NONE;
+        for &k in &best_edges {
+            if self.bestedge[b] == NONE || self.slack(k) < self.slack(self.bestedge[b]) {
+                self.bestedge[b] = k;
+            }
+        }
+        self.blossombestedges[b] = Some(best_edges);
+
+        self.blossomchilds[b] = path;
+        self.blossomendps[b] = endps;
+    }
+
+    fn blossom_leaves_of(&self, blossoms: &[usize]) -> Vec<usize> {
+        blossoms
+            .iter()
+            .flat_map(|&b| self.blossom_leaves(b))
+            .collect()
+    }
+
+    /// Expand the given top-level blossom
+    fn expand_blossom(&mut self, b: usize, endstage: bool) {
+        let childs = self.blossomchilds[b].clone();
+        for &s in &childs {
+            self.blossomparent[s] = NONE;
+            if s < self.nvertex {
+                self.inblossom[s] = s;
+            } else if endstage && self.dualvar[s] == 0 {
+                self.expand_blossom(s, endstage);
+            } else {
+                for v in self.blossom_leaves(s) {
+                    self.inblossom[v] = s;
+                }
+            }
+        }
+
+        // If we expand a T-blossom during a stage, its sub-blossoms must be
+        // relabeled
+        if !endstage && self.label[b] == 2 {
+            let endps = self.blossomendps[b].clone();
+            let entrychild = self.inblossom[self.endpoint[self.labelend[b] ^ 1]];
+            let mut j = childs.iter().position(|&c| c == entrychild).unwrap() as isize;
+            let (jstep, endptrick) = if j & 1 != 0 {
+                j -= childs.len() as isize;
+                (1, 0)
+            } else {
+                (-1, 1)
+            };
+
+            let mut p = self.labelend[b];
+            while j != 0 {
+                self.label[self.endpoint[p ^ 1]] = 0;
+                self.label[self.endpoint[at(&endps, j - endptrick) ^ (endptrick as usize) ^ 1]] = 0;
+                self.assign_label(self.endpoint[p ^ 1], 2, p);
+                self.allowedge[at(&endps, j - endptrick) / 2] = true;
+                j += jstep;
+                p = at(&endps, j - endptrick) ^ (endptrick as usize);
+                self.allowedge[p / 2] = true;
+                j += jstep;
+            }
+
+            let bv = at(&childs, j);
+            self.label[self.endpoint[p ^ 1]] = 2;
+            self.label[bv] = 2;
+            self.labelend[self.endpoint[p ^ 1]] = p;
+            self.labelend[bv] = p;
+            self.bestedge[bv] = NONE;
+            j += jstep;
+
+            while at(&childs, j) != entrychild {
+                let bv = at(&childs, j);
+                if self.label[bv] == 1 {
+                    j += jstep;
+                    continue;
+                }
+                let labeled = self
+                    .blossom_leaves(bv)
+                    .into_iter()
+                    .find(|&v| self.label[v] != 0);
+                if let Some(v) = labeled {
+                    debug_assert_eq!(self.label[v], 2);
+                    debug_assert_eq!(self.inblossom[v], bv);
+                    self.label[v] = 0;
+                    let mate = self.mate[self.blossombase[bv]];
+                    self.label[self.endpoint[mate]] = 0;
+                    self.assign_label(v, 2, self.labelend[v]);
+                }
+                j += jstep;
+            }
+        }
+
+        self.label[b] = 0;
+        self.labelend[b] = NONE;
+        self.blossomchilds[b] = Vec::new();
+        self.blossomendps[b] = Vec::new();
+        self.blossombase[b] = NONE;
+        self.blossombestedges[b] = None;
+        self.bestedge[b] = NONE;
+        self.unusedblossoms.push(b);
+    }
+
+    /// Swap matched and unmatched edges over an alternating path through
+    /// blossom `b` between vertex `v` and the base vertex
+    fn augment_blossom(&mut self, b: usize, v: usize) {
+        let mut t = v;
+        while self.blossomparent[t] != b {
+            t = self.blossomparent[t];
+        }
+        if t >= self.nvertex {
+            self.augment_blossom(t, v);
+        }
+
+        let i = self.blossomchilds[b].iter().position(|&c| c == t).unwrap();
+        let mut j = i as isize;
+        let (jstep, endptrick) = if i & 1 != 0 {
+            j -= self.blossomchilds[b].len() as isize;
+            (1, 0)
+        } else {
+            (-1, 1)
+        };
+
+        while j != 0 {
+            j += jstep;
+            let t = at(&self.blossomchilds[b], j);
+            let p = at(&self.blossomendps[b], j - endptrick) ^ (endptrick as usize);
+            if t >= self.nvertex {
+                self.augment_blossom(t, self.endpoint[p]);
+            }
+            j += jstep;
+            let t = at(&self.blossomchilds[b], j);
+            if t >= self.nvertex {
+                self.augment_blossom(t, self.endpoint[p ^ 1]);
+            }
+            self.mate[self.endpoint[p]] = p ^ 1;
+            self.mate[self.endpoint[p ^ 1]] = p;
+        }
+
+        self.blossomchilds[b].rotate_left(i);
+        self.blossomendps[b].rotate_left(i);
+        self.blossombase[b] = self.blossombase[self.blossomchilds[b][0]];
+        debug_assert_eq!(self.blossombase[b], v);
+    }
+
+    /// Swap matched and unmatched edges over an alternating path between two
+    /// single vertices, through edge `k`
+    fn augment_matching(&mut self, k: usize) {
+        let (v, w, _) = self.edges[k];
+        for &(s, p) in &[(v, 2 * k + 1), (w, 2 * k)] {
+            let (mut s, mut p) = (s, p);
+            loop {
+                let bs = self.inblossom[s];
+                debug_assert_eq!(self.label[bs], 1);
+                if bs >= self.nvertex {
+                    self.augment_blossom(bs, s);
+                }
+                self.mate[s] = p;
+                if self.labelend[bs] == NONE {
+                    break;
+                }
+                let t = self.endpoint[self.labelend[bs]];
+                let bt = self.inblossom[t];
+                debug_assert_eq!(self.label[bt], 2);
+                s = self.endpoint[self.labelend[bt]];
+                let j = self.endpoint[self.labelend[bt] ^ 1];
+                debug_assert_eq!(self.blossombase[bt], t);
+                if bt >= self.nvertex {
+                    self.augment_blossom(bt, j);
+                }
+                self.mate[j] = self.labelend[bt];
+                p = self.labelend[bt] ^ 1;
+            }
+        }
+    }
+
+    fn solve(&mut self) {
+        let nvertex = self.nvertex;
+
+        // Each stage finds an augmenting path, there are at most n stages
+        for _ in 0..nvertex {
+            self.label.iter_mut().for_each(|x| *x = 0);
+            self.bestedge.iter_mut().for_each(|x| *x = NONE);
+            self.blossombestedges[nvertex..]
+                .iter_mut()
+                .for_each(|x| *x = None);
+            self.allowedge.iter_mut().for_each(|x| *x = false);
+            self.queue.clear();
+
+            for v in 0..nvertex {
+                if self.mate[v] == NONE && self.label[self.inblossom[v]] == 0 {
+                    self.assign_label(v, 1, NONE);
+                }
+            }
+
+            let mut augmented = false;
+            loop {
+                while let Some(v) = self.queue.pop() {
+                    debug_assert_eq!(self.label[self.inblossom[v]], 1);
+
+                    for p in self.neighbend[v].clone() {
+                        let k = p / 2;
+                        let w = self.endpoint[p];
+                        if self.inblossom[v] == self.inblossom[w] {
+                            continue;
+                        }
+                        let mut kslack = 0;
+                        if !self.allowedge[k] {
+                            kslack = self.slack(k);
+                            if kslack <= 0 {
+                                self.allowedge[k] = true;
+                            }
+                        }
+                        if self.allowedge[k] {
+                            if self.label[self.inblossom[w]] == 0 {
+                                self.assign_label(w, 2, p ^ 1);
+                            } else if self.label[self.inblossom[w]] == 1 {
+                                match self.scan_blossom(v, w) {
+                                    Some(base) => self.add_blossom(base, k),
+                                    None => {
+                                        self.augment_matching(k);
+                                        augmented = true;
+                                        break;
+                                    }
+                                }
+                            } else if self.label[w] == 0 {
+                                self.label[w] = 2;
+                                self.labelend[w] = p ^ 1;
+                            }
+                        } else if self.label[self.inblossom[w]] == 1 {
+                            let b = self.inblossom[v];
+                            if self.bestedge[b] == NONE || kslack < self.slack(self.bestedge[b]) {
+                                self.bestedge[b] = k;
+                            }
+                        } else if self.label[w] == 0
+                            && (self.bestedge[w] == NONE || kslack < self.slack(self.bestedge[w]))
+                        {
+                            self.bestedge[w] = k;
+                        }
+                    }
+
+                    if augmented {
+                        break;
+                    }
+                }
+
+                if augmented {
+                    break;
+                }
+
+                // No augmenting path found, update dual variables
+                let mut deltatype = 1;
+                let mut delta = self.dualvar[..nvertex].iter().copied().min().unwrap();
+                let mut deltaedge = NONE;
+                let mut deltablossom = NONE;
+
+                for v in 0..nvertex {
+                    if self.label[self.inblossom[v]] == 0 && self.bestedge[v] != NONE {
+                        let d = self.slack(self.bestedge[v]);
+                        if d < delta {
+                            delta = d;
+                            deltatype = 2;
+                            deltaedge = self.bestedge[v];
+                        }
+                    }
+                }
+
+                for b in 0..2 * nvertex {
+                    if self.blossomparent[b] == NONE
+                        && self.label[b] == 1
+                        && self.bestedge[b] != NONE
+                    {
+                        let kslack = self.slack(self.bestedge[b]);
+                        debug_assert_eq!(kslack % 2, 0);
+                        let d = kslack / 2;
+                        if d < delta {
+                            delta = d;
+                            deltatype = 3;
+                            deltaedge = self.bestedge[b];
+                        }
+                    }
+                }
+
+                for b in nvertex..2 * nvertex {
+                    if self.blossombase[b] != NONE
+                        && self.blossomparent[b] == NONE
+                        && self.label[b] == 2
+                        && self.dualvar[b] < delta
+                    {
+                        delta = self.dualvar[b];
+                        deltatype = 4;
+                        deltablossom = b;
+                    }
+                }
+
+                for v in 0..nvertex {
+                    match self.label[self.inblossom[v]] {
+                        1 => self.dualvar[v] -= delta,
+                        2 => self.dualvar[v] += delta,
+                        _ => {}
+                    }
+                }
+
+                for b in nvertex..2 * nvertex {
+                    if self.blossombase[b] != NONE && self.blossomparent[b] == NONE {
+                        match self.label[b] {
+                            1 => self.dualvar[b] += delta,
+                            2 => self.dualvar[b] -= delta,
+                            _ => {}
+                        }
+                    }
+                }
+
+                match deltatype {
+                    // No further improvement possible, optimum reached
+                    1 => break,
+                    2 => {
+                        self.allowedge[deltaedge] = true;
+                        let (mut i, j, _) = self.edges[deltaedge];
+                        if self.label[self.inblossom[i]] == 0 {
+                            i = j;
+                        }
+                        self.queue.push(i);
+                    }
+                    3 => {
+                        self.allowedge[deltaedge] = true;
+                        let (i, _, _) = self.edges[deltaedge];
+                        self.queue.push(i);
+                    }
+                    _ => self.expand_blossom(deltablossom, false),
+                }
+            }
+
+            if !augmented {
+                break;
+            }
+
+            // End of stage, expand all S-blossoms with zero dual
+            for b in nvertex..2 * nvertex {
+                if self.blossomparent[b] == NONE
+                    && self.blossombase[b] != NONE
+                    && self.label[b] == 1
+                    && self.dualvar[b] == 0
+                {
+                    self.expand_blossom(b, true);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Minimal deterministic pseudo-random generator, to avoid a dependency
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn gen_range(&mut self, low: usize, high: usize) -> usize {
+            self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1);
+            low + ((self.0 >> 33) as usize) % (high - low)
+        }
+    }
+
+    fn weight(edges: &[Edge], mate: &[Option<usize>]) -> i64 {
+        edges
+            .iter()
+            .filter(|&&(i, j, _)| mate[i] == Some(j))
+            .map(|&(_, _, w)| w)
+            .sum()
+    }
+
+    fn is_matching(mate: &[Option<usize>]) -> bool {
+        mate.iter()
+            .enumerate()
+            .all(|(i, m)| m.map_or(true, |j| mate[j] == Some(i)))
+    }
+
+    /// Try all matchings, it works only for very small graphs
+    fn brute_force(edges: &[Edge], used: &mut Vec<bool>, k: usize) -> i64 {
+        if k == edges.len() {
+            return 0;
+        }
+        let skip = brute_force(edges, used, k + 1);
+        let (i, j, w) = edges[k];
+        if used[i] || used[j] {
+            return skip;
+        }
+        used[i] = true;
+        used[j] = true;
+        let take = w + brute_force(edges, used, k + 1);
+        used[i] = false;
+        used[j] = false;
+        skip.max(take)
+    }
+
+    #[test]
+    fn empty_graph() {
+        assert!(max_weight_matching(&[]).is_empty());
+    }
+
+    #[test]
+    fn single_edge() {
+        assert_eq!(max_weight_matching(&[(0, 1, 1)]), vec![Some(1), Some(0)]);
+    }
+
+    #[test]
+    fn prefer_heavier_pairs_over_single_best_edge() {
+        // Greedy would take 1-2 only
+        let edges = [(0, 1, 5), (1, 2, 6), (2, 3, 5)];
+        let mate = max_weight_matching(&edges);
+        assert_eq!(mate, vec![Some(1), Some(0), Some(3), Some(2)]);
+    }
+
+    #[test]
+    fn blossom() {
+        // Odd cycle 1-2-3 with a pendant on 1 and 3
+        let edges = [(1, 2, 8), (1, 3, 9), (2, 3, 10), (3, 4, 7)];
+        let mate = max_weight_matching(&edges);
+        assert_eq!(weight(&edges, &mate), 15);
+        assert_eq!(mate[1], Some(2));
+        assert_eq!(mate[3], Some(4));
+    }
+
+    #[test]
+    fn nested_blossoms_expansion() {
+        let edges = [
+            (1, 2, 23),
+            (1, 5, 22),
+            (1, 6, 15),
+            (2, 3, 25),
+            (3, 4, 22),
+            (4, 5, 25),
+            (4, 8, 14),
+            (5, 7, 13),
+        ];
+        let mate = max_weight_matching(&edges);
+        assert_eq!(
+            mate,
+            vec![
+                None,
+                Some(6),
+                Some(3),
+                Some(2),
+                Some(8),
+                Some(7),
+                Some(1),
+                Some(5),
+                Some(4)
+            ]
+        );
+    }
+
+    #[test]
+    fn same_as_brute_force_on_random_graphs() {
+        let mut rng = Lcg(42);
+        for _ in 0..300 {
+            let n = rng.gen_range(2, 9);
+            let mut edges = Vec::new();
+            for i in 0..n {
+                for j in (i + 1)..n {
+                    if rng.gen_range(0, 2) == 0 {
+                        edges.push((i, j, rng.gen_range(1, 20) as i64));
+                    }
+                }
+            }
+            let mate = max_weight_matching(&edges);
+            assert!(is_matching(&mate));
+            let best = brute_force(&edges, &mut vec![false; n], 0);
+            assert_eq!(weight(&edges, &mate), best, "{:?}", edges);
+        }
+    }
+}
@@ -1,28 +1,138 @@
+use chrono::{Datelike, NaiveDate};
 use serde::{Deserialize, Serialize};
 use std::cmp::Ord;
+use std::collections::BTreeMap;
 use std::convert::TryFrom;
+use strum_macros::{Display, EnumIter, EnumString};
 
-#[derive(Debug, Serialize, Deserialize, Clone, Ord, PartialOrd, Eq, PartialEq)]
+// There is one per user and it is almost always Weekly, so boxing it would
+// save nothing
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Ord, PartialOrd, Eq, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum Availability {
     /// Disabled
+    #[default]
     Never,
     /// Schedule every week in the future
     Weekly(WeekSchedule),
 }
 
-impl Default for Availability {
-    fn default() -> Self {
-        Availability::Never
+/// Length of each slot of a DaySchedule
+#[derive(
+    Debug,
+    Default,
+    Serialize,
+    Deserialize,
+    Copy,
+    Clone,
+    EnumIter,
+    Display,
+    EnumString,
+    Ord,
+    PartialOrd,
+    Eq,
+    PartialEq,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Resolution {
+    #[default]
+    Hour,
+    HalfHour,
+    QuarterHour,
+}
+
+impl Resolution {
+    /// The finest resolution, able to represent any schedule
+    pub const FINEST: Self = Resolution::QuarterHour;
+
+    /// Length of a slot, in minutes
+    pub const fn minutes(self) -> usize {
+        match self {
+            Resolution::Hour => 60,
+            Resolution::HalfHour => 30,
+            Resolution::QuarterHour => 15,
+        }
+    }
+
+    pub const fn slots_per_hour(self) -> usize {
+        60 / self.minutes()
+    }
+
+    pub const fn slots_in_day(self) -> usize {
+        DaySchedule::HOURS_IN_DAY * self.slots_per_hour()
+    }
+
+    /// The coarsest resolution able to represent the given minute of the day
+    fn for_minute(minute: usize) -> Option<Self> {
+        match minute % 60 {
+            0 => Some(Resolution::Hour),
+            30 => Some(Resolution::HalfHour),
+            15 | 45 => Some(Resolution::QuarterHour),
+            _ => None,
+        }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Default, Ord, PartialOrd, Eq, PartialEq)]
-#[serde(rename_all = "snake_case")]
+/// Availability during a day. The day is divided in slots, whose length
+/// depends on the resolution.
+#[derive(Debug, Serialize, Deserialize, Clone, Ord, PartialOrd, Eq, PartialEq)]
+#[serde(rename_all = "snake_case", from = "DayScheduleRepr")]
 pub struct DaySchedule {
-    pub hours: [bool; 24],
+    pub resolution: Resolution,
+    pub slots: Vec<bool>,
 }
 
+/// Serialized DaySchedule. Before resolution was configurable, only the
+/// `hours` field was stored: it is still accepted, so existing settings are
+/// migrated transparently.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DayScheduleRepr {
+    Slots {
+        resolution: Resolution,
+        slots: Vec<bool>,
+    },
+    Hours {
+        hours: Vec<bool>,
+    },
+}
+
+impl From<DayScheduleRepr> for DaySchedule {
+    fn from(repr: DayScheduleRepr) -> Self {
+        let (resolution, mut slots) = match repr {
+            DayScheduleRepr::Slots { resolution, slots } => (resolution, slots),
+            DayScheduleRepr::Hours { hours } => (Resolution::Hour, hours),
+        };
+
+        slots.resize(resolution.slots_in_day(), false);
+
+        DaySchedule { resolution, slots }
+    }
+}
+
+impl Default for DaySchedule {
+    fn default() -> Self {
+        Self::never()
+    }
+}
+
+/// Parse a time of the day (`17` or `17:30`) as minutes since midnight
+fn parse_minute_of_day(s: &str) -> Option<usize> {
+    let mut parts = s.trim().splitn(2, ':');
+    let h: usize = parts.next()?.parse().ok()?;
+    let m: usize = parts.next().map(|m| m.parse().ok()).unwrap_or(Some(0))?;
+    let minute = h * 60 + m;
+
+    if m < 60 && minute <= DaySchedule::HOURS_IN_DAY * 60 {
+        Some(minute)
+    } else {
+        None
+    }
+}
+
+/// Parse a comma separated list of hours (`5,7,21`) or ranges
+/// (`17:30-19:00`). Ranges end is excluded. `*` means always.
 impl TryFrom<&str> for DaySchedule {
     type Error = ();
 
@@ -31,21 +141,45 @@ impl TryFrom<&str> for DaySchedule {
             return Ok(Self::always());
         }
 
-        let numbers: Vec<usize> = s.split(',').filter_map(|x| x.parse().ok()).collect();
+        // Each item is converted to a range of minutes
+        let ranges: Vec<(usize, usize)> = s
+            .split(',')
+            .filter_map(|x| {
+                if let Some(sep) = x.find('-') {
+                    let begin = parse_minute_of_day(&x[..sep])?;
+                    let end = parse_minute_of_day(&x[sep + 1..])?;
+                    Some((begin, end))
+                } else {
+                    let h: usize = x.trim().parse().ok()?;
+                    Some((h * 60, (h + 1) * 60))
+                }
+            })
+            .filter(|(begin, end)| begin < end && *end <= Self::HOURS_IN_DAY * 60)
+            .collect();
 
-        if numbers.is_empty() {
+        if ranges.is_empty() {
             return Ok(Self::never());
         }
 
-        let mut hours: [bool; Self::HOURS_IN_DAY] = [false; Self::HOURS_IN_DAY];
+        let resolution = ranges
+            .iter()
+            .flat_map(|(begin, end)| vec![*begin, *end])
+            .map(Resolution::for_minute)
+            .collect::<Option<Vec<_>>>()
+            .ok_or(())?
+            .into_iter()
+            .max()
+            .unwrap_or_default();
+
+        let mut day = Self::empty(resolution);
 
-        for n in numbers {
-            if n < hours.len() {
-                hours[n] = true;
+        for (begin, end) in ranges {
+            for slot in (begin / resolution.minutes())..(end / resolution.minutes()) {
+                day.slots[slot] = true;
             }
         }
 
-        Ok(DaySchedule { hours })
+        Ok(day)
     }
 }
 
@@ -53,19 +187,76 @@ impl DaySchedule {
     pub const HOURS_IN_DAY: usize = 24;
 
     pub fn never() -> Self {
-        DaySchedule {
-            hours: [false; Self::HOURS_IN_DAY],
-        }
+        Self::empty(Resolution::Hour)
     }
 
     pub fn always() -> Self {
         DaySchedule {
-            hours: [true; Self::HOURS_IN_DAY],
+            resolution: Resolution::Hour,
+            slots: vec![true; Self::HOURS_IN_DAY],
         }
     }
 
     pub fn new(hours: [bool; Self::HOURS_IN_DAY]) -> Self {
-        DaySchedule { hours }
+        DaySchedule {
+            resolution: Resolution::Hour,
+            slots: hours.to_vec(),
+        }
+    }
+
+    /// Create a schedule with no available slots
+    pub fn empty(resolution: Resolution) -> Self {
+        DaySchedule {
+            resolution,
+            slots: vec![false; resolution.slots_in_day()],
+        }
+    }
+
+    /// Return true if available at the given minute of the day
+    pub fn is_available_at(&self, minute_of_day: usize) -> bool {
+        let slot = minute_of_day / self.resolution.minutes();
+        self.slots.get(slot).copied().unwrap_or(false)
+    }
+
+    /// Convert to a different resolution. When converting to a coarser
+    /// resolution, a slot is available only if it was entirely available.
+    pub fn to_resolution(&self, resolution: Resolution) -> Self {
+        if resolution == self.resolution {
+            return self.clone();
+        }
+
+        let minutes = resolution.minutes();
+        let step = minutes.min(self.resolution.minutes());
+
+        let slots = (0..resolution.slots_in_day())
+            .map(|slot| {
+                let begin = slot * minutes;
+                (begin..(begin + minutes))
+                    .step_by(step)
+                    .all(|m| self.is_available_at(m))
+            })
+            .collect();
+
+        DaySchedule { resolution, slots }
+    }
+}
+
+/// Change to the weekly schedule on a specific date. Slots in `added` are
+/// available even if they are not in the weekly schedule, slots in `removed`
+/// are not available even if they are.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, Ord, PartialOrd, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct DayException {
+    pub added: DaySchedule,
+    pub removed: DaySchedule,
+}
+
+impl DayException {
+    fn to_resolution(&self, resolution: Resolution) -> Self {
+        DayException {
+            added: self.added.to_resolution(resolution),
+            removed: self.removed.to_resolution(resolution),
+        }
     }
 }
 
@@ -80,8 +271,172 @@ pub struct WeekSchedule {
     pub fri: DaySchedule,
     pub sat: DaySchedule,
     pub sun: DaySchedule,
+    /// Exceptions on specific dates, in the same time zone of the schedule
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub exceptions: BTreeMap<NaiveDate, DayException>,
+}
+
+impl WeekSchedule {
+    /// The finest resolution among all days. Exceptions do not change it,
+    /// they are converted to it when they are edited.
+    pub fn resolution(&self) -> Resolution {
+        self.iter()
+            .map(|(_, day)| day.resolution)
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Convert all days and exceptions to a different resolution
+    pub fn to_resolution(&self, resolution: Resolution) -> Self {
+        let mut week = self.clone();
+        for day in WEEKDAYS.iter() {
+            week[*day] = self[*day].to_resolution(resolution);
+        }
+        for exception in week.exceptions.values_mut() {
+            *exception = exception.to_resolution(resolution);
+        }
+        week
+    }
+
+    /// Minutes of the week when both schedules are available. Exceptions on
+    /// specific dates are not considered.
+    pub fn overlap_minutes(&self, other: &WeekSchedule) -> usize {
+        let resolution = Resolution::FINEST;
+        let a = self.to_resolution(resolution);
+        let b = other.to_resolution(resolution);
+
+        let slots = WEEKDAYS
+            .iter()
+            .flat_map(|&day| a[day].slots.iter().zip(b[day].slots.iter()))
+            .filter(|(a, b)| **a && **b)
+            .count();
+
+        slots * resolution.minutes()
+    }
+
+    /// Return true if available at the given minute of a specific date,
+    /// taking exceptions into account
+    pub fn is_available_on(&self, date: NaiveDate, minute_of_day: usize) -> bool {
+        let weekly = self[date.weekday()].is_available_at(minute_of_day);
+
+        match self.exceptions.get(&date) {
+            Some(e) => {
+                (weekly || e.added.is_available_at(minute_of_day))
+                    && !e.removed.is_available_at(minute_of_day)
+            }
+            None => weekly,
+        }
+    }
+
+    /// Schedule of a specific date, taking exceptions into account
+    pub fn on(&self, date: NaiveDate) -> DaySchedule {
+        let resolution = self.resolution();
+        let minutes = resolution.minutes();
+
+        let slots = (0..resolution.slots_in_day())
+            .map(|slot| {
+                let begin = slot * minutes;
+                (begin..(begin + minutes))
+                    .step_by(Resolution::FINEST.minutes())
+                    .all(|m| self.is_available_on(date, m))
+            })
+            .collect();
+
+        DaySchedule { resolution, slots }
+    }
+
+    /// Remove exceptions on dates before `date`, which are not needed anymore
+    pub fn remove_exceptions_before(&mut self, date: NaiveDate) {
+        self.exceptions = self.exceptions.split_off(&date);
+    }
+
+    /// Remove all availability on a specific date
+    pub fn set_unavailable_on(&mut self, date: NaiveDate) {
+        let exception = DayException {
+            added: DaySchedule::never(),
+            removed: DaySchedule::always(),
+        };
+        self.exceptions.insert(date, exception);
+    }
+
+    /// Change availability of a slot on a specific date, adding an exception
+    /// to the weekly schedule. The slot index is relative to the resolution
+    /// of the whole schedule. Returns false, changing nothing, if there is
+    /// no such slot.
+    pub fn set_available_on(&mut self, date: NaiveDate, slot: usize, available: bool) -> bool {
+        let resolution = self.resolution();
+
+        if slot >= resolution.slots_in_day() {
+            return false;
+        }
+
+        let weekly = self[date.weekday()].to_resolution(resolution).slots[slot];
+
+        let exception = self.exceptions.entry(date).or_default();
+        *exception = exception.to_resolution(resolution);
+        exception.added.slots[slot] = available && !weekly;
+        exception.removed.slots[slot] = !available && weekly;
+        true
+    }
+
+    /// Compare with a new schedule, at the finest resolution of the two.
+    /// Exceptions on specific dates are not compared.
+    pub fn diff(&self, new: &WeekSchedule) -> WeekScheduleDiff {
+        let resolution = self.resolution().max(new.resolution());
+        let old = self.to_resolution(resolution);
+        let new = new.to_resolution(resolution);
+
+        let mut diff = WeekScheduleDiff::default();
+
+        for &day in WEEKDAYS.iter() {
+            let changed = |f: fn(bool, bool) -> bool| DaySchedule {
+                resolution,
+                slots: old[day]
+                    .slots
+                    .iter()
+                    .zip(new[day].slots.iter())
+                    .map(|(&o, &n)| f(o, n))
+                    .collect(),
+            };
+            diff.added[day] = changed(|o, n| n && !o);
+            diff.removed[day] = changed(|o, n| o && !n);
+        }
+
+        diff
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (chrono::Weekday, &DaySchedule)> {
+        WEEKDAYS.iter().map(move |&day| (day, &self[day]))
+    }
+}
+
+/// Slots which change when a weekly schedule is replaced by another
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct WeekScheduleDiff {
+    /// Available only in the new schedule
+    pub added: WeekSchedule,
+    /// Available only in the old schedule
+    pub removed: WeekSchedule,
 }
 
+impl WeekScheduleDiff {
+    pub fn is_empty(&self) -> bool {
+        let no_slots = |week: &WeekSchedule| week.iter().all(|(_, d)| d.slots.iter().all(|s| !s));
+        no_slots(&self.added) && no_slots(&self.removed)
+    }
+}
+
+const WEEKDAYS: [chrono::Weekday; 7] = [
+    chrono::Weekday::Mon,
+    chrono::Weekday::Tue,
+    chrono::Weekday::Wed,
+    chrono::Weekday::Thu,
+    chrono::Weekday::Fri,
+    chrono::Weekday::Sat,
+    chrono::Weekday::Sun,
+];
+
 impl std::ops::Index<chrono::Weekday> for WeekSchedule {
     type Output = DaySchedule;
     fn index(&self, index: chrono::Weekday) -> &Self::Output {
@@ -119,11 +474,206 @@ mod tests {
     fn hourly_schedule_from_comma_separated_list() {
         let s = "5,7,21";
         let schedule = DaySchedule::try_from(s).unwrap();
-        assert!(!schedule.hours[4]);
-        assert!(schedule.hours[5]);
-        assert!(!schedule.hours[6]);
-        assert!(schedule.hours[7]);
-        assert!(schedule.hours[21]);
-        assert!(!schedule.hours[22]);
+        assert_eq!(schedule.resolution, Resolution::Hour);
+        assert!(!schedule.slots[4]);
+        assert!(schedule.slots[5]);
+        assert!(!schedule.slots[6]);
+        assert!(schedule.slots[7]);
+        assert!(schedule.slots[21]);
+        assert!(!schedule.slots[22]);
+    }
+
+    #[test]
+    fn half_hour_schedule_from_range() {
+        let schedule = DaySchedule::try_from("17:30-19:00").unwrap();
+        assert_eq!(schedule.resolution, Resolution::HalfHour);
+        assert_eq!(schedule.slots.len(), 48);
+        assert!(!schedule.slots[34]);
+        assert!(schedule.slots[35]);
+        assert!(schedule.slots[36]);
+        assert!(schedule.slots[37]);
+        assert!(!schedule.slots[38]);
+    }
+
+    #[test]
+    fn quarter_hour_schedule_from_mixed_list() {
+        let schedule = DaySchedule::try_from("8,17:45-18:15").unwrap();
+        assert_eq!(schedule.resolution, Resolution::QuarterHour);
+        assert!(schedule.is_available_at(8 * 60));
+        assert!(schedule.is_available_at(8 * 60 + 59));
+        assert!(!schedule.is_available_at(9 * 60));
+        assert!(!schedule.is_available_at(17 * 60 + 30));
+        assert!(schedule.is_available_at(17 * 60 + 45));
+        assert!(schedule.is_available_at(18 * 60));
+        assert!(!schedule.is_available_at(18 * 60 + 15));
+    }
+
+    #[test]
+    fn schedule_with_unsupported_minutes() {
+        assert!(DaySchedule::try_from("17:10-18:00").is_err());
+    }
+
+    #[test]
+    fn convert_to_finer_resolution() {
+        let schedule = DaySchedule::try_from("5").unwrap();
+        let schedule = schedule.to_resolution(Resolution::QuarterHour);
+        assert_eq!(
+            schedule,
+            DaySchedule::try_from("5:00-6:00")
+                .unwrap()
+                .to_resolution(Resolution::QuarterHour)
+        );
+        assert_eq!(schedule.slots.iter().filter(|x| **x).count(), 4);
+    }
+
+    #[test]
+    fn convert_to_coarser_resolution() {
+        let schedule = DaySchedule::try_from("17:30-19:00").unwrap();
+        let schedule = schedule.to_resolution(Resolution::Hour);
+        assert_eq!(schedule, DaySchedule::try_from("18").unwrap());
+    }
+
+    #[test]
+    fn deserialize_legacy_hourly_schedule() {
+        let mut hours = vec![false; 24];
+        hours[5] = true;
+        let json = serde_json::json!({ "hours": hours });
+        let schedule: DaySchedule = serde_json::from_value(json).unwrap();
+        assert_eq!(schedule, DaySchedule::try_from("5").unwrap());
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let schedule = DaySchedule::try_from("17:30-19:00").unwrap();
+        let json = serde_json::to_string(&schedule).unwrap();
+        let deserialized: DaySchedule = serde_json::from_str(&json).unwrap();
+        assert_eq!(schedule, deserialized);
+    }
+
+    #[test]
+    fn weekly_overlap() {
+        let a = WeekSchedule {
+            mon: DaySchedule::try_from("18:00-20:00").unwrap(),
+            sat: DaySchedule::try_from("10").unwrap(),
+            ..WeekSchedule::default()
+        };
+        let b = WeekSchedule {
+            mon: DaySchedule::try_from("19:30-21:00").unwrap(),
+            sat: DaySchedule::try_from("10").unwrap(),
+            ..WeekSchedule::default()
+        };
+
+        assert_eq!(a.overlap_minutes(&b), 90);
+        assert_eq!(b.overlap_minutes(&a), 90);
+    }
+
+    #[test]
+    fn exceptions_add_and_remove_slots() {
+        let mut week = WeekSchedule {
+            mon: DaySchedule::try_from("21,22").unwrap(),
+            ..WeekSchedule::default()
+        };
+
+        let date = NaiveDate::from_ymd(2020, 1, 6); // Monday
+        week.set_available_on(date, 21, false);
+        week.set_available_on(date, 10, true);
+
+        assert_eq!(week.on(date), DaySchedule::try_from("10,22").unwrap());
+        assert!(!week.is_available_on(date, 21 * 60 + 30));
+
+        // Other mondays are not affected
+        let next_week = NaiveDate::from_ymd(2020, 1, 13);
+        assert_eq!(week.on(next_week), DaySchedule::try_from("21,22").unwrap());
+    }
+
+    #[test]
+    fn exceptions_out_of_the_day_are_ignored() {
+        let mut week = WeekSchedule {
+            mon: DaySchedule::try_from("21,22").unwrap(),
+            ..WeekSchedule::default()
+        };
+
+        let date = NaiveDate::from_ymd(2020, 1, 6); // Monday
+        assert!(week.set_available_on(date, 23, true));
+        assert!(!week.set_available_on(date, 24, true));
+        assert!(!week.set_available_on(date, usize::MAX, false));
+
+        assert_eq!(week.on(date), DaySchedule::try_from("21,22,23").unwrap());
+        assert_eq!(week.exceptions.len(), 1);
+    }
+
+    #[test]
+    fn exceptions_with_finer_resolution() {
+        let mut week = WeekSchedule::default();
+        let date = NaiveDate::from_ymd(2020, 1, 11); // Saturday
+        week.exceptions.insert(
+            date,
+            DayException {
+                added: DaySchedule::try_from("10:30-11:00").unwrap(),
+                removed: DaySchedule::never(),
+            },
+        );
+
+        assert_eq!(week.resolution(), Resolution::Hour);
+        assert!(week.is_available_on(date, 10 * 60 + 30));
+        assert!(!week.is_available_on(date, 10 * 60));
+    }
+
+    #[test]
+    fn remove_past_exceptions() {
+        let mut week = WeekSchedule::default();
+        let date = NaiveDate::from_ymd(2020, 1, 11);
+        week.set_unavailable_on(date.pred());
+        week.set_unavailable_on(date);
+        week.set_unavailable_on(date.succ());
+
+        week.remove_exceptions_before(date);
+        let dates: Vec<_> = week.exceptions.keys().copied().collect();
+        assert_eq!(dates, vec![date, date.succ()]);
+    }
+
+    #[test]
+    fn diff_with_different_resolutions() {
+        let old = WeekSchedule {
+            mon: DaySchedule::try_from("9,10").unwrap(),
+            tue: DaySchedule::try_from("9").unwrap(),
+            ..WeekSchedule::default()
+        };
+
+        let new = WeekSchedule {
+            mon: DaySchedule::try_from("10:00-11:30").unwrap(),
+            tue: DaySchedule::try_from("9").unwrap(),
+            ..WeekSchedule::default()
+        };
+
+        let diff = old.diff(&new);
+        assert_eq!(
+            diff.added.mon,
+            DaySchedule::try_from("11:00-11:30").unwrap()
+        );
+        assert_eq!(
+            diff.removed.mon,
+            DaySchedule::try_from("9:00-10:00")
+                .unwrap()
+                .to_resolution(Resolution::HalfHour)
+        );
+        assert!(diff.added.tue.slots.iter().all(|s| !s));
+        assert!(!diff.is_empty());
+        assert!(new.diff(&new).is_empty());
+    }
+
+    #[test]
+    fn deserialize_week_without_exceptions() {
+        let week = WeekSchedule::default();
+        let mut json = serde_json::to_value(&week).unwrap();
+        assert!(json.get("exceptions").is_none());
+
+        let date = NaiveDate::from_ymd(2026, 12, 24);
+        let mut week_with_exception = week.clone();
+        week_with_exception.set_available_on(date, 0, true);
+        json["exceptions"] = serde_json::to_value(&week_with_exception.exceptions).unwrap();
+
+        let deserialized: WeekSchedule = serde_json::from_value(json).unwrap();
+        assert_eq!(deserialized, week_with_exception);
     }
 }
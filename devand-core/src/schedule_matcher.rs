@@ -1,162 +1,177 @@
-use crate::{Affinity, AffinityParams, Availability, DaySchedule, UserId, WeekSchedule};
+use crate::{
+    are_spoken_language_compatible, Affinity, AffinityConfig, AffinityParams, Availability,
+    DaySchedule, Language, Resolution, UserId, Vacation, WeekSchedule,
+};
 use chrono::prelude::*;
 use chrono::Duration;
+use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
 
-struct AffinityMatrix(usize, Vec<Option<Affinity>>);
+mod bitset;
+mod group;
 
-impl std::ops::Index<(UserId, UserId)> for AffinityMatrix {
-    type Output = Option<Affinity>;
+use bitset::BitSet;
+pub use group::{GroupCandidates, GroupFilter, GroupSlot, MAX_GROUP_SLOTS, MIN_GROUP_SIZE};
 
-    fn index(&self, pair: (UserId, UserId)) -> &Self::Output {
-        let (UserId(i), UserId(j)) = pair;
-        let p = (j as usize) * self.0 + (i as usize);
-        &self.1[p]
+/// Resolution of schedules in WeekScheduleMatrix. Schedules with a coarser
+/// resolution are converted to this one.
+pub const RESOLUTION: Resolution = Resolution::FINEST;
+
+const SLOTS_IN_DAY: usize = RESOLUTION.slots_in_day();
+
+/// Max number of users kept for each slot, the ones with best affinity
+pub const MAX_USERS_IN_SLOT: usize = 10;
+
+/// Max number of days users can be matched in advance
+pub const MAX_HORIZON_DAYS: usize = 28;
+
+/// Index of a slot in the day, with RESOLUTION
+#[derive(Serialize, Deserialize, Copy, Clone)]
+struct Slot(usize);
+
+/// A DayScheduleMatrix contains info about all users state (for example:
+/// availability) in a given slot of the day.
+/// Users are mapped to dense indexes, so memory depends on the number of
+/// users and not on their ids. For each slot, available users are stored in
+/// a bitset: finding users available in a range of slots is a word-level
+/// intersection.
+pub struct DayScheduleMatrix {
+    /// User of each dense index
+    users: Vec<UserId>,
+    /// Dense index of each user
+    indexes: BTreeMap<UserId, usize>,
+    /// Users available in each slot of the day
+    slots: Vec<BitSet>,
+}
+
+impl Default for DayScheduleMatrix {
+    fn default() -> Self {
+        Self {
+            users: Vec::default(),
+            indexes: BTreeMap::default(),
+            slots: vec![BitSet::default(); SLOTS_IN_DAY],
+        }
     }
 }
 
-/// Creates an AffinityMatrix from a Vec of (UserId,AffinityParams), where the
-/// UserId can be unsorted and with holes.
-/// The resulting matrix size is (n * n), where n is the max
-/// UserId.
-impl From<Vec<(UserId, AffinityParams)>> for AffinityMatrix {
-    fn from(ua: Vec<(UserId, AffinityParams)>) -> Self {
-        if let Some((UserId(max_user_id), _)) = ua.iter().max_by_key(|x| x.0) {
-            let width = (*max_user_id + 1) as usize;
-            let size = width * width;
-
-            let mut data = Vec::with_capacity(size);
-            data.resize_with(size, Default::default);
-
-            for (s, (UserId(j), y)) in ua.iter().enumerate().skip(1) {
-                for (UserId(i), x) in ua.iter().take(s) {
-                    let affinity = Affinity::from_params(x, y);
-                    let p = (*j as usize) * width + (*i as usize);
-                    data.get_mut(p).map(|x| *x = Some(affinity));
-                }
-            }
-            Self(width, data)
+impl std::ops::Index<&(UserId, Slot)> for DayScheduleMatrix {
+    type Output = bool;
+
+    fn index(&self, pair: &(UserId, Slot)) -> &Self::Output {
+        let (user, Slot(s)) = pair;
+        let available = self
+            .indexes
+            .get(user)
+            .map_or(false, |&i| self.slots[*s].contains(i));
+        if available {
+            &true
         } else {
-            Self(0, Vec::default())
+            &false
         }
     }
 }
 
-impl AffinityMatrix {
-    // TODO Cleanup?
-    #[allow(dead_code)]
-    fn find_best_match<'a, I>(&self, u: &UserId, o: I) -> Option<UserId>
-    where
-        I: IntoIterator<Item = &'a UserId>,
-    {
-        o.into_iter()
-            .map(|x| (*x, self[(*u, *x)]))
-            .max_by_key(|x| x.1)
-            .map(|x| x.0)
+impl From<Vec<(UserId, DaySchedule)>> for DayScheduleMatrix {
+    fn from(mut us: Vec<(UserId, DaySchedule)>) -> Self {
+        // Dense indexes follow ids order, so results are sorted by id
+        us.sort_by_key(|x| x.0);
+
+        let mut matrix = Self::default();
+        for (user, day) in us.iter() {
+            matrix.set_day(*user, day);
+        }
+        matrix
     }
 }
 
-#[derive(Serialize, Deserialize)]
-struct Hour(i32);
+impl DayScheduleMatrix {
+    /// Return a Vec of all users available in every slot of a given range
+    pub fn get_available_in_slots(&self, slots: std::ops::Range<usize>) -> Vec<UserId> {
+        self.users_in(&self.available_in_slots(slots))
+    }
 
-impl ToString for Hour {
-    fn to_string(&self) -> String {
-        format!("{:02}", self.0)
+    /// Count users available in every slot of a given range
+    pub fn count_available_in_slots(&self, slots: std::ops::Range<usize>) -> usize {
+        self.available_in_slots(slots).count()
     }
-}
 
-/// A DayScheduleMatrix contains info about all users state (for example:
-/// availability) in a given hour of the day.
-/// It's useful because, given an hour of the day and an user id, the access
-/// time is constant.
-pub struct DayScheduleMatrix {
-    data: Vec<bool>,
-}
+    fn available_in_slots(&self, slots: std::ops::Range<usize>) -> BitSet {
+        let mut slots = self.slots[slots].iter();
 
-impl std::ops::Index<&(UserId, Hour)> for DayScheduleMatrix {
-    type Output = bool;
+        let mut available = if let Some(first) = slots.next() {
+            first.clone()
+        } else {
+            return BitSet::default();
+        };
+
+        for slot in slots {
+            available.intersect_with(slot);
+        }
 
-    fn index(&self, pair: &(UserId, Hour)) -> &Self::Output {
-        let (UserId(i), Hour(h)) = pair;
-        let p = (*i as usize) * DaySchedule::HOURS_IN_DAY + (*h as usize);
-        &self.data[p]
+        available
     }
-}
 
-impl std::ops::IndexMut<&(UserId, Hour)> for DayScheduleMatrix {
-    fn index_mut(&mut self, pair: &(UserId, Hour)) -> &mut Self::Output {
-        let (UserId(i), Hour(h)) = pair;
-        let p = (*i as usize) * DaySchedule::HOURS_IN_DAY + (*h as usize);
-        &mut self.data[p]
+    /// Map dense indexes to users, sorted by id
+    fn users_in(&self, set: &BitSet) -> Vec<UserId> {
+        let mut users: Vec<_> = set.iter().map(|i| self.users[i]).collect();
+        // Already sorted, unless users were added later with a lower id
+        users.sort();
+        users
     }
-}
 
-impl From<Vec<(UserId, DaySchedule)>> for DayScheduleMatrix {
-    fn from(us: Vec<(UserId, DaySchedule)>) -> Self {
-        if let Some((UserId(max_user_id), _)) = us.iter().max_by_key(|x| x.0) {
-            let size = (1 + (*max_user_id as usize)) * DaySchedule::HOURS_IN_DAY;
-
-            let mut data = Vec::with_capacity(size);
-            data.resize_with(size, Default::default);
-
-            for h in 0..DaySchedule::HOURS_IN_DAY {
-                for (UserId(i), day) in us.iter() {
-                    let in_schedule = day.hours[h];
-                    let p = (*i as usize) * DaySchedule::HOURS_IN_DAY + h;
-                    let cell = unsafe { data.get_unchecked_mut(p) };
-                    *cell = in_schedule;
-                }
-            }
-            Self { data }
-        } else {
-            Self {
-                data: Vec::default(),
-            }
-        }
+    pub fn users_len(&self) -> usize {
+        self.users.len()
     }
-}
 
-impl DayScheduleMatrix {
-    /// Return a Vec of all users available in a given hour
-    fn get_available_at_hour(&self, h: Hour) -> Vec<UserId> {
-        self.data
-            .iter()
-            .skip(h.0 as usize)
-            .step_by(DaySchedule::HOURS_IN_DAY)
-            .enumerate()
-            .filter(|(_, available)| **available) // Remove non available users
-            .map(|(id, _)| UserId(id as i32)) // Index is UserId
-            .collect()
+    fn has_user(&self, user: UserId) -> bool {
+        self.indexes.contains_key(&user)
     }
 
-    /// Return a Vec of all users available in a given daily schedule
-    // TODO Cleanup?
-    #[allow(dead_code)]
-    fn get_available_at_day(&self, day: &DaySchedule) -> Vec<UserId> {
-        use std::collections::BTreeSet;
+    /// Dense index of the user, added if not already in the matrix. Added
+    /// users are not available in any slot.
+    fn insert_user(&mut self, user: UserId) -> usize {
+        if let Some(&i) = self.indexes.get(&user) {
+            return i;
+        }
 
-        let mut set: BTreeSet<UserId> = BTreeSet::new();
+        let i = self.users.len();
+        self.users.push(user);
+        self.indexes.insert(user, i);
 
-        day.hours.iter().enumerate().for_each(|(h, is_available)| {
-            if *is_available {
-                self.get_available_at_hour(Hour(h as i32))
-                    .iter()
-                    .for_each(|&u| {
-                        set.insert(u);
-                    });
-            }
-        });
+        for slot in self.slots.iter_mut() {
+            slot.grow(i + 1);
+        }
 
-        set.into_iter().collect()
+        i
     }
 
-    fn users_len(&self) -> usize {
-        self.data.len() / DaySchedule::HOURS_IN_DAY
+    /// User's availability for the whole day, with RESOLUTION
+    fn day(&self, user: UserId) -> DaySchedule {
+        let mut day = DaySchedule::empty(RESOLUTION);
+        if let Some(&i) = self.indexes.get(&user) {
+            for (available, slot) in day.slots.iter_mut().zip(self.slots.iter()) {
+                *available = slot.contains(i);
+            }
+        }
+        day
+    }
+
+    /// Set user's availability for the whole day
+    fn set_day(&mut self, user: UserId, day: &DaySchedule) {
+        let i = self.insert_user(user);
+        let day = day.to_resolution(RESOLUTION);
+        for (slot, &available) in self.slots.iter_mut().zip(day.slots.iter()) {
+            slot.set(i, available);
+        }
     }
 }
 
 /// WeekScheduleMatrix contains a DayScheduleMatrix for each day of the
-/// week.
+/// week. Users' schedules are stored in their local time, but the matrix
+/// is in UTC: local schedules are projected on a specific week, so daylight
+/// saving time transitions are taken into account.
 pub struct WeekScheduleMatrix {
     mon: DayScheduleMatrix,
     tue: DayScheduleMatrix,
@@ -165,6 +180,103 @@ pub struct WeekScheduleMatrix {
     fri: DayScheduleMatrix,
     sat: DayScheduleMatrix,
     sun: DayScheduleMatrix,
+    /// Schedules in users' local time, needed to project them again when
+    /// the week changes
+    local_schedules: BTreeMap<UserId, (WeekSchedule, Tz)>,
+    /// Users on vacation are not available in the vacation period
+    vacations: BTreeMap<UserId, Vacation>,
+    /// UTC days the local schedules are currently projected on
+    days: Vec<Date<Utc>>,
+    /// Local schedules projected on the following weeks, by their first
+    /// day (see `set_period`). Kept up to date when schedules change.
+    projections: BTreeMap<Date<Utc>, WeekScheduleMatrix>,
+    /// Used to rank matching users. Users without a profile have no
+    /// affinity, so they never match.
+    profiles: BTreeMap<UserId, MatchingProfile>,
+}
+
+/// What is needed to rank users available in the same slot
+#[derive(Default, Clone, Debug)]
+pub struct MatchingProfile {
+    /// Schedule is not needed: users in the same slot share it
+    pub params: AffinityParams,
+    /// User pairs only with people who speak a common spoken language
+    pub require_common_spoken_language: bool,
+}
+
+impl MatchingProfile {
+    /// Affinity with another user, or None if they cannot pair
+    fn affinity(&self, other: &MatchingProfile, config: &AffinityConfig) -> Option<Affinity> {
+        let required = self.require_common_spoken_language || other.require_common_spoken_language;
+        let compatible = are_spoken_language_compatible(
+            self.params.spoken_languages(),
+            other.params.spoken_languages(),
+        );
+
+        if required && !compatible {
+            return None;
+        }
+
+        let affinity = Affinity::from_params(&self.params, &other.params, config);
+        Some(affinity).filter(|affinity| !affinity.is_zero())
+    }
+}
+
+/// Restrict the period and the users to find matching availability
+#[derive(Clone, Debug)]
+pub struct MatchFilter {
+    /// Slots starting before are skipped
+    pub start: DateTime<Utc>,
+    /// Number of days from `start`, up to MAX_HORIZON_DAYS
+    pub days: usize,
+    /// Only users who both know this language
+    pub language: Option<Language>,
+    pub min_affinity: Affinity,
+    /// Only users available for a session at least this long
+    pub min_session: Duration,
+}
+
+impl MatchFilter {
+    /// Users available in the 7 days from `start`, without any other filter
+    pub fn new(start: DateTime<Utc>) -> Self {
+        Self {
+            start,
+            days: 7,
+            language: None,
+            min_affinity: Affinity::NONE,
+            min_session: Duration::zero(),
+        }
+    }
+
+    pub fn with_days(mut self, days: usize) -> Self {
+        self.days = days;
+        self
+    }
+
+    pub fn with_language(mut self, language: Option<Language>) -> Self {
+        self.language = language;
+        self
+    }
+
+    pub fn with_min_affinity(mut self, min_affinity: Affinity) -> Self {
+        self.min_affinity = min_affinity;
+        self
+    }
+
+    pub fn with_min_session(mut self, min_session: Duration) -> Self {
+        self.min_session = min_session;
+        self
+    }
+
+    fn accepts(&self, profile: &MatchingProfile, other: &MatchingProfile) -> bool {
+        match self.language {
+            Some(language) => {
+                profile.params.languages().contains_key(&language)
+                    && other.params.languages().contains_key(&language)
+            }
+            None => true,
+        }
+    }
 }
 
 impl std::ops::Index<chrono::Weekday> for WeekScheduleMatrix {
@@ -182,12 +294,34 @@ impl std::ops::Index<chrono::Weekday> for WeekScheduleMatrix {
     }
 }
 
+/// Creates a WeekScheduleMatrix from schedules already expressed in UTC
 impl From<Vec<(UserId, WeekSchedule)>> for WeekScheduleMatrix {
     fn from(v: Vec<(UserId, WeekSchedule)>) -> Self {
-        type UsersDaySchedules = Vec<(UserId, DaySchedule)>;
+        let v = v.into_iter().map(|(u, s)| (u, s, Tz::UTC)).collect();
+        Self::from_local(v, Utc::now())
+    }
+}
+
+impl WeekScheduleMatrix {
+    /// Creates a WeekScheduleMatrix from schedules expressed in users' local
+    /// time, projecting them on the 7 days starting from `date`
+    pub fn from_local(v: Vec<(UserId, WeekSchedule, Tz)>, date: DateTime<Utc>) -> Self {
+        let days = days_from(7, date);
+
+        let utc_schedules = v
+            .iter()
+            .map(|(user, week_schedule, tz)| (*user, utc_week_schedule(week_schedule, *tz, &days)))
+            .collect();
+
+        let mut wsm = Self::from_utc(utc_schedules);
 
-        let max_user_id = v.iter().map(|x| x.0).max().unwrap_or(UserId(0));
-        let size = max_user_id.0 as usize + 1;
+        wsm.local_schedules = v.into_iter().map(|(u, s, tz)| (u, (s, tz))).collect();
+        wsm.days = days;
+        wsm
+    }
+
+    fn from_utc(v: Vec<(UserId, WeekSchedule)>) -> Self {
+        type UsersDaySchedules = Vec<(UserId, DaySchedule)>;
 
         let mut mon = UsersDaySchedules::default();
         let mut tue = UsersDaySchedules::default();
@@ -197,23 +331,14 @@ impl From<Vec<(UserId, WeekSchedule)>> for WeekScheduleMatrix {
         let mut sat = UsersDaySchedules::default();
         let mut sun = UsersDaySchedules::default();
 
-        mon.resize_with(size, Default::default);
-        tue.resize_with(size, Default::default);
-        wed.resize_with(size, Default::default);
-        thu.resize_with(size, Default::default);
-        fri.resize_with(size, Default::default);
-        sat.resize_with(size, Default::default);
-        sun.resize_with(size, Default::default);
-
         for (user, week_schedule) in v {
-            let i = user.0 as usize;
-            mon[i] = (user, week_schedule[chrono::Weekday::Mon].clone());
-            tue[i] = (user, week_schedule[chrono::Weekday::Tue].clone());
-            wed[i] = (user, week_schedule[chrono::Weekday::Wed].clone());
-            thu[i] = (user, week_schedule[chrono::Weekday::Thu].clone());
-            fri[i] = (user, week_schedule[chrono::Weekday::Fri].clone());
-            sat[i] = (user, week_schedule[chrono::Weekday::Sat].clone());
-            sun[i] = (user, week_schedule[chrono::Weekday::Sun].clone());
+            mon.push((user, week_schedule[chrono::Weekday::Mon].clone()));
+            tue.push((user, week_schedule[chrono::Weekday::Tue].clone()));
+            wed.push((user, week_schedule[chrono::Weekday::Wed].clone()));
+            thu.push((user, week_schedule[chrono::Weekday::Thu].clone()));
+            fri.push((user, week_schedule[chrono::Weekday::Fri].clone()));
+            sat.push((user, week_schedule[chrono::Weekday::Sat].clone()));
+            sun.push((user, week_schedule[chrono::Weekday::Sun].clone()));
         }
 
         Self {
@@ -224,13 +349,19 @@ impl From<Vec<(UserId, WeekSchedule)>> for WeekScheduleMatrix {
             fri: DayScheduleMatrix::from(fri),
             sat: DayScheduleMatrix::from(sat),
             sun: DayScheduleMatrix::from(sun),
+            local_schedules: BTreeMap::default(),
+            vacations: BTreeMap::default(),
+            days: Vec::default(),
+            projections: BTreeMap::default(),
+            profiles: BTreeMap::default(),
         }
     }
 }
 
 impl WeekScheduleMatrix {
-    /// Return a vector with an item for each hour of the week, each one
-    /// containing all available users at that moment.
+    /// Return a vector with an item for each slot of the week, each one
+    /// containing all users available for the whole slot. Slots have the
+    /// same resolution of the target schedule.
     fn match_all_week(
         &self,
         user: UserId,
@@ -241,18 +372,20 @@ impl WeekScheduleMatrix {
             .flat_map(|(date, day_sched)| {
                 let weekday = date.weekday();
                 let day_sched_mat = &self[weekday];
+                let resolution = day_sched.resolution;
+                let k = SLOTS_IN_DAY / resolution.slots_in_day();
                 let z: Vec<_> = day_sched
-                    .hours
+                    .slots
                     .iter()
                     .enumerate()
                     .filter(|(_, is_available)| **is_available)
-                    .map(|(h, _)| {
-                        let t = date.and_hms(h as u32, 0, 0);
-                        let h = Hour(h as i32);
+                    .map(|(s, _)| {
+                        let minutes = (s * resolution.minutes()) as i64;
+                        let t = date.and_hms(0, 0, 0) + Duration::minutes(minutes);
 
-                        // All user available at this hour in the day
+                        // All user available for the whole slot
                         let other_users = day_sched_mat
-                            .get_available_at_hour(h)
+                            .get_available_in_slots((s * k)..((s + 1) * k))
                             .into_iter()
                             .filter(|&user_id| user_id != user) // Remove same user
                             .collect();
@@ -265,83 +398,406 @@ impl WeekScheduleMatrix {
             .collect()
     }
 
-    /// Given `date`, for any hour in the next 7 days, find all users with
-    /// matching availability.
+    /// Given `date`, for any slot in the next 7 days, find users with
+    /// matching availability. See `find_all_users_matching`.
     pub fn find_all_users_matching_in_week(
         &self,
         user: UserId,
         date: DateTime<Utc>,
         availability: Availability,
+        tz: Tz,
+        profile: &MatchingProfile,
+        config: &AffinityConfig,
     ) -> AvailabilityMatch {
-        let days = days_from(7, date);
-        let future_availability = attach_schedule(days, availability);
+        let filter = MatchFilter::new(date);
+        self.find_all_users_matching(user, availability, tz, profile, config, &filter)
+    }
+
+    /// For any slot in the period given by `filter`, find users with
+    /// matching availability. `availability` is in `tz` local time and the
+    /// resulting slots have the same resolution.
+    /// Users in each slot are sorted by affinity with `profile`: users who
+    /// cannot pair or are excluded by `filter` are removed and only the best
+    /// MAX_USERS_IN_SLOT are kept.
+    /// The matrix should be projected on the period (see `set_period`).
+    /// Other weeks are projected on the fly, which is slower.
+    pub fn find_all_users_matching(
+        &self,
+        user: UserId,
+        availability: Availability,
+        tz: Tz,
+        profile: &MatchingProfile,
+        config: &AffinityConfig,
+        filter: &MatchFilter,
+    ) -> AvailabilityMatch {
+        let days = days_from(filter.days.min(MAX_HORIZON_DAYS), filter.start);
+
+        let resolution = match &availability {
+            Availability::Weekly(week_schedule) => week_schedule.resolution(),
+            Availability::Never => Resolution::default(),
+        };
+
+        // Same users are usually available in many slots
+        let mut affinities = BTreeMap::new();
+        let mut slots = Vec::new();
+
+        for week in days.chunks(7) {
+            let projected;
+            let matrix = match self.projection(week) {
+                Some(matrix) => matrix,
+                None => {
+                    projected = self.projected_on(week);
+                    &projected
+                }
+            };
+
+            let availability = match (&availability, self.vacations.get(&user)) {
+                (Availability::Weekly(week_schedule), Some(vacation)) => {
+                    Availability::Weekly(without_vacation(week_schedule, vacation, week))
+                }
+                (availability, _) => availability.clone(),
+            };
+
+            let future_availability = attach_schedule(week.to_vec(), availability, tz);
+
+            for (t, users) in matrix.match_all_week(user, &future_availability) {
+                if t < filter.start {
+                    continue;
+                }
+
+                let mut users: Vec<_> = users
+                    .into_iter()
+                    .filter_map(|u| {
+                        let affinity = *affinities.entry(u).or_insert_with(|| {
+                            self.profiles
+                                .get(&u)
+                                .filter(|other| filter.accepts(profile, other))
+                                .and_then(|other| profile.affinity(other, config))
+                                .filter(|affinity| *affinity >= filter.min_affinity)
+                        });
+                        affinity.map(|affinity| (u, affinity))
+                    })
+                    .collect();
+
+                users.sort_by_key(|&(u, affinity)| (Reverse(affinity), u));
+
+                slots.push((t, users));
+            }
+        }
+
+        let mut res = AvailabilityMatch { slots, resolution };
+
+        if filter.min_session > Duration::zero() {
+            res.retain_sessions(filter.min_session);
+        }
+
+        for (_, users) in res.slots.iter_mut() {
+            users.truncate(MAX_USERS_IN_SLOT);
+        }
+
+        res.slots.retain(|(_, users)| !users.is_empty());
+        res
+    }
 
-        let slots = self
-            .match_all_week(user, &future_availability)
-            .into_iter()
-            .filter(|(_, users)| !users.is_empty())
+    /// Project all local schedules on other days, leaving this matrix
+    /// untouched. Only the matrices of each weekday and the days are set.
+    fn projected_on(&self, days: &[Date<Utc>]) -> WeekScheduleMatrix {
+        let utc_schedules = self
+            .local_schedules
+            .keys()
+            .map(|&user| (user, self.project(user, days)))
             .collect();
 
-        AvailabilityMatch { slots }
+        let mut projected = Self::from_utc(utc_schedules);
+        projected.days = days.to_vec();
+        projected
+    }
+
+    /// The matrix with schedules projected on `week`, if any: either this
+    /// one or a cached projection
+    fn projection(&self, week: &[Date<Utc>]) -> Option<&WeekScheduleMatrix> {
+        if self.days.starts_with(week) {
+            return Some(self);
+        }
+
+        let first = week.first()?;
+        self.projections
+            .get(first)
+            .filter(|projected| projected.days.starts_with(week))
+    }
+
+    /// True if schedules are already projected on all the `days` days from
+    /// `date` (see `set_period`)
+    pub fn is_projected_on(&self, date: DateTime<Utc>, days: usize) -> bool {
+        days_from(days.min(MAX_HORIZON_DAYS), date)
+            .chunks(7)
+            .all(|week| self.projection(week).is_some())
+    }
+
+    /// Project all local schedules on the `days` days from `date`, up to
+    /// MAX_HORIZON_DAYS. The first week is projected on this matrix (see
+    /// `set_week`), the following ones are cached.
+    pub fn set_period(&mut self, date: DateTime<Utc>, days: usize) {
+        self.set_week(date);
+
+        for week in days_from(days.min(MAX_HORIZON_DAYS), date).chunks(7) {
+            if self.projection(week).is_none() {
+                let week = days_from(7, week[0].and_hms(0, 0, 0));
+                let projected = self.projected_on(&week);
+                self.projections.insert(week[0], projected);
+            }
+        }
+    }
+
+    /// User's availability on the week the matrix is projected on, in UTC
+    /// with RESOLUTION and without the vacation period (see `set_week`)
+    pub fn utc_schedule(&self, user: UserId) -> WeekSchedule {
+        WeekSchedule {
+            mon: self.mon.day(user),
+            tue: self.tue.day(user),
+            wed: self.wed.day(user),
+            thu: self.thu.day(user),
+            fri: self.fri.day(user),
+            sat: self.sat.day(user),
+            sun: self.sun.day(user),
+            ..WeekSchedule::default()
+        }
+    }
+
+    /// Set the profile used to rank the user among matching users
+    pub fn set_profile(&mut self, user: UserId, profile: MatchingProfile) {
+        self.profiles.insert(user, profile);
     }
 
     fn has_user(&self, user: UserId) -> bool {
-        self.mon.users_len() > user.0 as usize
+        self.mon.has_user(user)
     }
 
     fn update_week_schedule(&mut self, user: UserId, week_sched: &WeekSchedule) {
         if !self.has_user(user) {
-            // Not in the matrix, cannot update
-            log::error!("Cannot update wsm");
-            return;
+            let is_never = week_sched
+                .iter()
+                .all(|(_, day)| day.slots.iter().all(|available| !available));
+
+            // An user not in the matrix is never available, nothing to do
+            if is_never {
+                return;
+            }
         }
 
-        for h in 0..DaySchedule::HOURS_IN_DAY {
-            let i = (user, Hour(h as i32));
-            self.mon[&i] = week_sched.mon.hours[h];
-            self.tue[&i] = week_sched.tue.hours[h];
-            self.wed[&i] = week_sched.wed.hours[h];
-            self.thu[&i] = week_sched.thu.hours[h];
-            self.fri[&i] = week_sched.fri.hours[h];
-            self.sat[&i] = week_sched.sat.hours[h];
-            self.sun[&i] = week_sched.sun.hours[h];
+        // Users joined after the matrix was created are added here
+        self.mon.set_day(user, &week_sched.mon);
+        self.tue.set_day(user, &week_sched.tue);
+        self.wed.set_day(user, &week_sched.wed);
+        self.thu.set_day(user, &week_sched.thu);
+        self.fri.set_day(user, &week_sched.fri);
+        self.sat.set_day(user, &week_sched.sat);
+        self.sun.set_day(user, &week_sched.sun);
+    }
+
+    /// Project the local schedule of an user on `days`, excluding the
+    /// vacation period
+    fn project(&self, user: UserId, days: &[Date<Utc>]) -> WeekSchedule {
+        match (self.local_schedules.get(&user), self.vacations.get(&user)) {
+            (Some((week_schedule, tz)), Some(vacation)) => {
+                let week_schedule = without_vacation(week_schedule, vacation, days);
+                utc_week_schedule(&week_schedule, *tz, days)
+            }
+            (Some((week_schedule, tz)), None) => utc_week_schedule(week_schedule, *tz, days),
+            (None, _) => WeekSchedule::default(),
         }
     }
 
-    pub fn update(&mut self, user: UserId, availability: &Availability) {
+    /// Update user's availability, expressed in `tz` local time
+    pub fn update(&mut self, user: UserId, availability: &Availability, tz: Tz) {
         match availability {
             Availability::Weekly(week_schedule) => {
-                self.update_week_schedule(user, week_schedule);
+                self.local_schedules
+                    .insert(user, (week_schedule.clone(), tz));
             }
             Availability::Never => {
-                self.update_week_schedule(user, &WeekSchedule::default());
+                self.local_schedules.remove(&user);
             }
         }
+
+        self.update_projections(user);
+    }
+
+    /// Set or clear user's vacation. Dates are in the time zone of user's
+    /// schedule.
+    pub fn set_vacation(&mut self, user: UserId, vacation: Option<Vacation>) {
+        match vacation {
+            Some(vacation) => self.vacations.insert(user, vacation),
+            None => self.vacations.remove(&user),
+        };
+
+        self.update_projections(user);
+    }
+
+    /// Project again the local schedule of an user, on this matrix and on
+    /// cached projections
+    fn update_projections(&mut self, user: UserId) {
+        let utc = self.project(user, &self.days);
+        self.update_week_schedule(user, &utc);
+
+        let projected: Vec<_> = self
+            .projections
+            .iter()
+            .map(|(&first, matrix)| (first, self.project(user, &matrix.days)))
+            .collect();
+
+        for (first, utc) in projected {
+            if let Some(matrix) = self.projections.get_mut(&first) {
+                matrix.update_week_schedule(user, &utc);
+            }
+        }
+    }
+
+    /// Project all local schedules on the 7 days starting from `date`. This
+    /// is needed when the week changes, because the UTC offset of users'
+    /// time zones may have changed.
+    pub fn set_week(&mut self, date: DateTime<Utc>) {
+        let days = days_from(7, date);
+
+        if days == self.days {
+            return;
+        }
+
+        let utc_schedules: Vec<_> = self
+            .local_schedules
+            .keys()
+            .map(|&user| (user, self.project(user, &days)))
+            .collect();
+
+        for (user, week_schedule) in utc_schedules {
+            self.update_week_schedule(user, &week_schedule);
+        }
+
+        // Only projections of the following weeks of the period are kept
+        let first = days[0];
+        self.projections.retain(|day, _| {
+            let offset = (*day - first).num_days();
+            offset > 0 && offset % 7 == 0 && offset < MAX_HORIZON_DAYS as i64
+        });
+
+        self.days = days;
+    }
+}
+
+/// Return true if the local time `t` is in the weekly schedule, taking
+/// exceptions on specific dates into account
+pub(crate) fn is_available_at(week_schedule: &WeekSchedule, t: DateTime<Tz>) -> bool {
+    let minute_of_day = t.hour() * 60 + t.minute();
+    week_schedule.is_available_on(t.naive_local().date(), minute_of_day as usize)
+}
+
+/// Project a weekly schedule, expressed in `tz` local time, on an UTC day,
+/// with the given resolution. An UTC slot is available only if the whole
+/// slot is available in local time: this matters for time zones with an
+/// offset which is not a multiple of the slot length (e.g. IST, UTC+05:30)
+/// where an UTC slot overlaps two local slots.
+fn utc_day_schedule(
+    date: Date<Utc>,
+    week_schedule: &WeekSchedule,
+    tz: Tz,
+    resolution: Resolution,
+) -> DaySchedule {
+    let mut day = DaySchedule::empty(resolution);
+    let length = Duration::minutes(resolution.minutes() as i64);
+
+    for (s, available) in day.slots.iter_mut().enumerate() {
+        let begin = date.and_hms(0, 0, 0) + length * (s as i32);
+        let end = begin + length - Duration::seconds(1);
+        *available = is_available_at(week_schedule, begin.with_timezone(&tz))
+            && is_available_at(week_schedule, end.with_timezone(&tz));
+    }
+
+    day
+}
+
+/// Project a weekly schedule, expressed in `tz` local time, on the given
+/// UTC days. The resulting schedule is in UTC, with RESOLUTION, so no
+/// information is lost with any time zone offset. Days should be
+/// consecutive and no more than 7, so each weekday is projected at most once.
+pub fn utc_week_schedule(week_schedule: &WeekSchedule, tz: Tz, days: &[Date<Utc>]) -> WeekSchedule {
+    let mut utc = WeekSchedule::default().to_resolution(RESOLUTION);
+
+    for day in days {
+        utc[day.weekday()] = utc_day_schedule(*day, week_schedule, tz, RESOLUTION);
+    }
+
+    utc
+}
+
+/// Project an availability, expressed in `tz` local time, on the 7 days
+/// starting from `date`. The resulting schedule is in UTC, with RESOLUTION.
+pub fn utc_availability(availability: &Availability, tz: Tz, date: DateTime<Utc>) -> WeekSchedule {
+    match availability {
+        Availability::Weekly(week_schedule) => {
+            utc_week_schedule(week_schedule, tz, &days_from(7, date))
+        }
+        Availability::Never => WeekSchedule::default(),
     }
 }
 
-#[derive(Serialize, Deserialize)]
-struct DayMatching {
-    pub hours: [Vec<UserId>; 24],
+/// Same as `utc_availability`, but all dates in the vacation period are
+/// removed
+pub fn utc_availability_without_vacation(
+    availability: &Availability,
+    tz: Tz,
+    vacation: &Vacation,
+    date: DateTime<Utc>,
+) -> WeekSchedule {
+    match availability {
+        Availability::Weekly(week_schedule) => {
+            let days = days_from(7, date);
+            let week_schedule = without_vacation(week_schedule, vacation, &days);
+            utc_week_schedule(&week_schedule, tz, &days)
+        }
+        Availability::Never => WeekSchedule::default(),
+    }
 }
 
-fn get_day_schedule(date: Date<Utc>, week_schedule: &WeekSchedule) -> (Date<Utc>, DaySchedule) {
-    (date, week_schedule[date.weekday()].clone())
+/// Remove from a weekly schedule, expressed in local time, all local dates
+/// in the vacation period which overlap the given UTC days
+fn without_vacation(
+    week_schedule: &WeekSchedule,
+    vacation: &Vacation,
+    days: &[Date<Utc>],
+) -> WeekSchedule {
+    let mut week_schedule = week_schedule.clone();
+
+    // Depending on time zone, an UTC day overlaps the previous or next local day
+    let dates = days
+        .iter()
+        .map(|day| day.naive_utc())
+        .flat_map(|date| vec![date.pred(), date, date.succ()]);
+
+    for date in dates {
+        if vacation.contains(date) {
+            week_schedule.set_unavailable_on(date);
+        }
+    }
+
+    week_schedule
 }
 
 fn attach_schedule(
     days: Vec<Date<Utc>>,
     availability: Availability,
+    tz: Tz,
 ) -> Vec<(Date<Utc>, DaySchedule)> {
     match availability {
         Availability::Never => days
             .iter()
             .map(|day| (*day, DaySchedule::default()))
             .collect(),
-        Availability::Weekly(week_schedule) => days
-            .iter()
-            .map(|day| get_day_schedule(*day, &week_schedule))
-            .collect(),
+        Availability::Weekly(week_schedule) => {
+            let resolution = week_schedule.resolution();
+            days.iter()
+                .map(|day| (*day, utc_day_schedule(*day, &week_schedule, tz, resolution)))
+                .collect()
+        }
     }
 }
 
@@ -352,20 +808,100 @@ fn days_from(n: usize, from: DateTime<Utc>) -> Vec<Date<Utc>> {
         .collect()
 }
 
+/// Users available in a slot, with their affinity
+pub type SlotUsers = Vec<(UserId, Affinity)>;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AvailabilityMatch {
-    pub slots: Vec<(DateTime<Utc>, Vec<UserId>)>,
+    /// Users available in each slot, sorted by affinity
+    pub slots: Vec<(DateTime<Utc>, SlotUsers)>,
+    /// Length of each slot
+    #[serde(default)]
+    pub resolution: Resolution,
+}
+
+/// Consecutive slots where an user is available, merged in a single range
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct MatchingSession {
+    pub user: UserId,
+    pub affinity: Affinity,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl MatchingSession {
+    pub fn duration(&self) -> Duration {
+        self.end - self.start
+    }
+}
+
+impl AvailabilityMatch {
+    /// Merge consecutive slots into a session for each user. Sessions
+    /// shorter than `min_duration` are removed. Sessions are sorted by start
+    /// time, then by affinity.
+    pub fn sessions(&self, min_duration: Duration) -> Vec<MatchingSession> {
+        let length = Duration::minutes(self.resolution.minutes() as i64);
+
+        let mut slots: Vec<_> = self.slots.iter().collect();
+        slots.sort_by_key(|(t, _)| *t);
+
+        // Last session of each user, which may still be extended
+        let mut open: BTreeMap<UserId, MatchingSession> = BTreeMap::new();
+        let mut sessions = Vec::new();
+
+        for (t, users) in slots {
+            for &(user, affinity) in users {
+                match open.get_mut(&user) {
+                    Some(session) if session.end == *t => session.end = *t + length,
+                    _ => {
+                        let session = MatchingSession {
+                            user,
+                            affinity,
+                            start: *t,
+                            end: *t + length,
+                        };
+                        sessions.extend(open.insert(user, session));
+                    }
+                }
+            }
+        }
+
+        sessions.extend(open.into_values());
+        sessions.retain(|session| session.duration() >= min_duration);
+        sessions.sort_by_key(|session| (session.start, Reverse(session.affinity), session.user));
+        sessions
+    }
+
+    /// Keep only users in sessions at least `min_duration` long
+    fn retain_sessions(&mut self, min_duration: Duration) {
+        let mut ranges: BTreeMap<UserId, Vec<_>> = BTreeMap::new();
+        for session in self.sessions(min_duration) {
+            ranges
+                .entry(session.user)
+                .or_default()
+                .push(session.start..session.end);
+        }
+
+        for (t, users) in self.slots.iter_mut() {
+            users.retain(|(user, _)| {
+                ranges
+                    .get(user)
+                    .map_or(false, |ranges| ranges.iter().any(|range| range.contains(t)))
+            });
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{Language, LanguagePreference, Level, Priority, User};
+    use crate::{Intent, Language, LanguagePreference, Level, Priority, User};
     use std::convert::TryFrom;
 
     #[test]
-    fn calculate_affinity_matrix() {
-        let params = vec![
+    fn calculate_affinities() {
+        let params = [
             (
                 UserId(0),
                 AffinityParams::from(vec![(
@@ -373,6 +909,7 @@ mod tests {
                     LanguagePreference {
                         level: Level::Novice,
                         priority: Priority::High,
+                        intent: Intent::Peer,
                     },
                 )]),
             ),
@@ -384,6 +921,7 @@ mod tests {
                         LanguagePreference {
                             level: Level::Novice,
                             priority: Priority::High,
+                            intent: Intent::Peer,
                         },
                     ),
                     (
@@ -391,6 +929,7 @@ mod tests {
                         LanguagePreference {
                             level: Level::Expert,
                             priority: Priority::Low,
+                            intent: Intent::Peer,
                         },
                     ),
                 ]),
@@ -402,6 +941,7 @@ mod tests {
                     LanguagePreference {
                         level: Level::Novice,
                         priority: Priority::No,
+                        intent: Intent::Peer,
                     },
                 )]),
             ),
@@ -412,6 +952,7 @@ mod tests {
                     LanguagePreference {
                         level: Level::Novice,
                         priority: Priority::High,
+                        intent: Intent::Peer,
                     },
                 )]),
             ),
@@ -422,24 +963,42 @@ mod tests {
                     LanguagePreference {
                         level: Level::Novice,
                         priority: Priority::High,
+                        intent: Intent::Peer,
                     },
                 )]),
             ),
         ];
 
-        let expected_len = params.len() * params.len();
-        let matrix = AffinityMatrix::from(params);
+        let config = AffinityConfig::default();
+        let affinity = |a: i32, b: i32| {
+            let params_of = |user| &params.iter().find(|(u, _)| *u == UserId(user)).unwrap().1;
+            Affinity::from_params(params_of(a), params_of(b), &config)
+        };
+
+        assert!(affinity(0, 2) == Affinity::NONE);
+        assert!(affinity(0, 3) == Affinity::NONE);
+        assert!(affinity(1, 2) != Affinity::FULL);
+        assert!(affinity(2, 4) == Affinity::FULL);
+        assert!(affinity(4, 2) == Affinity::FULL);
 
-        assert!(matrix.1.len() == expected_len);
-        assert!(matrix[(UserId(0), UserId(2))] == Some(Affinity::NONE));
-        assert!(matrix[(UserId(0), UserId(3))] == Some(Affinity::NONE));
-        assert!(matrix[(UserId(1), UserId(2))] != Some(Affinity::FULL));
-        assert!(matrix[(UserId(2), UserId(4))] == Some(Affinity::FULL));
+        // Best match of user 1
+        assert!(affinity(1, 2) > affinity(1, 3));
+    }
 
-        let u = UserId(1);
-        let o = vec![UserId(2), UserId(3)];
-        let best_match = matrix.find_best_match(&u, &o);
-        assert!(best_match == Some(UserId(2)));
+    /// First slot of hour `h`
+    fn hour(h: usize) -> Slot {
+        Slot(h * RESOLUTION.slots_per_hour())
+    }
+
+    /// Slots of an hour
+    fn slot_range(h: usize) -> std::ops::Range<usize> {
+        let Slot(s) = hour(h);
+        s..(s + RESOLUTION.slots_per_hour())
+    }
+
+    /// Parse a schedule and convert it to RESOLUTION
+    fn day(s: &str) -> DaySchedule {
+        DaySchedule::try_from(s).unwrap().to_resolution(RESOLUTION)
     }
 
     #[test]
@@ -451,28 +1010,20 @@ mod tests {
             (UserId(3), DaySchedule::try_from("3,4,5").unwrap()),
         ];
 
-        let expected_len = params.len() * 24;
+        let expected_len = params.len();
         let matrix = DayScheduleMatrix::from(params);
 
-        assert!(matrix.data.len() == expected_len);
-        assert!(matrix[&(UserId(0), Hour(1))]);
-        assert!(matrix[&(UserId(2), Hour(3))]);
-        assert!(matrix[&(UserId(2), Hour(5))]);
-        assert!(!matrix[&(UserId(2), Hour(1))]);
-        assert!(!matrix[&(UserId(1), Hour(5))]);
-        assert!(!matrix[&(UserId(3), Hour(6))]);
-
-        assert!(matrix.get_available_at_hour(Hour(1)) == vec![UserId(0), UserId(1)]);
-        assert!(matrix.get_available_at_hour(Hour(3)) == vec![UserId(1), UserId(2), UserId(3)]);
+        assert!(matrix.users_len() == expected_len);
+        assert!(matrix[&(UserId(0), hour(1))]);
+        assert!(matrix[&(UserId(2), hour(3))]);
+        assert!(matrix[&(UserId(2), hour(5))]);
+        assert!(!matrix[&(UserId(2), hour(1))]);
+        assert!(!matrix[&(UserId(1), hour(5))]);
+        assert!(!matrix[&(UserId(3), hour(6))]);
 
+        assert!(matrix.get_available_in_slots(slot_range(1)) == vec![UserId(0), UserId(1)]);
         assert!(
-            matrix.get_available_at_day(&DaySchedule::try_from("2,3,5,6").unwrap())
-                == vec![UserId(0), UserId(1), UserId(2), UserId(3)]
-        );
-
-        assert!(
-            matrix.get_available_at_day(&DaySchedule::try_from("4,5,6").unwrap())
-                == vec![UserId(2), UserId(3)]
+            matrix.get_available_in_slots(slot_range(3)) == vec![UserId(1), UserId(2), UserId(3)]
         );
     }
 
@@ -489,10 +1040,70 @@ mod tests {
         let days = days_from(7, next_week);
         let User { settings, .. } = crate::mock::user();
         let availability = settings.schedule;
-        let _uture_availability = attach_schedule(days, availability);
+        let _uture_availability = attach_schedule(days, availability, Tz::UTC);
         // dbg!(future_availability);
     }
 
+    #[test]
+    fn schedule_matrix_size_does_not_depend_on_user_ids() {
+        let params = vec![
+            (UserId(1_000_000), DaySchedule::try_from("1,2").unwrap()),
+            (UserId(7), DaySchedule::try_from("2").unwrap()),
+        ];
+
+        let matrix = DayScheduleMatrix::from(params);
+
+        assert_eq!(matrix.users_len(), 2);
+        assert!(matrix.slots.iter().all(|slot| slot.count() <= 2));
+        assert_eq!(
+            matrix.get_available_in_slots(slot_range(2)),
+            vec![UserId(7), UserId(1_000_000)]
+        );
+        assert!(!matrix[&(UserId(8), hour(2))]);
+    }
+
+    #[test]
+    fn available_in_slots_matches_schedules() {
+        // Small linear congruential generator, to have reproducible schedules
+        let mut seed: u64 = 42;
+        let mut random = move || {
+            seed = seed
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            seed >> 33
+        };
+
+        let mut schedules = Vec::new();
+        for user in 0..300 {
+            let mut day = DaySchedule::empty(RESOLUTION);
+            for slot in day.slots.iter_mut() {
+                *slot = random() % 3 != 0;
+            }
+            // Sparse ids, unsorted
+            schedules.push((UserId((random() % 10_000) as i32 * 300 + user), day));
+        }
+
+        let mut matrix = DayScheduleMatrix::from(schedules.clone());
+
+        // Users added later, with lower ids
+        let late = (UserId(-1), DaySchedule::always().to_resolution(RESOLUTION));
+        matrix.set_day(late.0, &late.1);
+        schedules.push(late);
+
+        for start in (0..SLOTS_IN_DAY).step_by(7) {
+            let slots = start..(start + 4).min(SLOTS_IN_DAY);
+            let mut expected: Vec<_> = schedules
+                .iter()
+                .filter(|(_, day)| day.slots[slots.clone()].iter().all(|x| *x))
+                .map(|(user, _)| *user)
+                .collect();
+            expected.sort();
+
+            assert_eq!(matrix.get_available_in_slots(slots.clone()), expected);
+            assert_eq!(matrix.count_available_in_slots(slots), expected.len());
+        }
+    }
+
     #[test]
     fn week_sched_mat_from_sorted_vec() {
         let v: Vec<(UserId, WeekSchedule)> = vec![
@@ -512,7 +1123,7 @@ mod tests {
             (UserId(3), WeekSchedule::default()),
         ];
         let wsm = WeekScheduleMatrix::from(v);
-        assert!(wsm.mon.users_len() == 4);
+        assert!(wsm.mon.users_len() == 3);
     }
 
     #[test]
@@ -534,7 +1145,7 @@ mod tests {
             (UserId(0), WeekSchedule::default()),
         ];
         let wsm = WeekScheduleMatrix::from(v);
-        assert!(wsm.mon.users_len() == 4);
+        assert!(wsm.mon.users_len() == 3);
     }
 
     #[test]
@@ -546,14 +1157,675 @@ mod tests {
         ];
 
         let mut wsm = WeekScheduleMatrix::from(v);
-        assert_eq!(wsm.mon[&(UserId(3), Hour(5))], false);
+        assert!(!wsm.mon[&(UserId(3), hour(5))]);
 
         let mut new_schedule = WeekSchedule::default();
-        new_schedule.mon.hours[5] = true;
+        new_schedule.mon.slots[5] = true;
 
         let availability = Availability::Weekly(new_schedule);
 
-        wsm.update(UserId(3), &availability);
-        assert_eq!(wsm.mon[&(UserId(3), Hour(5))], true);
+        wsm.update(UserId(3), &availability, Tz::UTC);
+        assert!(wsm.mon[&(UserId(3), hour(5))]);
+    }
+
+    #[test]
+    fn week_sched_mat_update_new_user() {
+        let date = at(2020, 1, 6, 0);
+
+        let week = WeekSchedule {
+            mon: DaySchedule::try_from("10").unwrap(),
+            ..WeekSchedule::default()
+        };
+        let availability = Availability::Weekly(week.clone());
+
+        let mut wsm = WeekScheduleMatrix::from_local(vec![(UserId(1), week, Tz::UTC)], date);
+        set_rust_profiles(&mut wsm, &[UserId(1), UserId(5)]);
+        assert_eq!(wsm.mon.users_len(), 1);
+
+        let find = |wsm: &WeekScheduleMatrix, user| {
+            wsm.find_all_users_matching_in_week(
+                user,
+                date,
+                availability.clone(),
+                Tz::UTC,
+                &rust_profile(),
+                &AffinityConfig::default(),
+            )
+        };
+
+        // New user without a schedule is not added
+        wsm.update(UserId(5), &Availability::Never, Tz::UTC);
+        assert_eq!(wsm.mon.users_len(), 1);
+
+        wsm.update(UserId(5), &availability, Tz::UTC);
+        assert_eq!(wsm.sun.users_len(), 2);
+        assert_eq!(slot_users(&find(&wsm, UserId(1)), 0), vec![UserId(5)]);
+
+        // Users switching to Never are not available anymore
+        wsm.update(UserId(5), &Availability::Never, Tz::UTC);
+        assert!(find(&wsm, UserId(1)).slots.is_empty());
+
+        // Projecting on a new week keeps the new user
+        wsm.update(UserId(5), &availability, Tz::UTC);
+        wsm.set_week(at(2020, 1, 13, 0));
+        let res = wsm.find_all_users_matching_in_week(
+            UserId(1),
+            at(2020, 1, 13, 0),
+            availability.clone(),
+            Tz::UTC,
+            &rust_profile(),
+            &AffinityConfig::default(),
+        );
+        assert_eq!(slot_users(&res, 0), vec![UserId(5)]);
+    }
+
+    fn rust_profile() -> MatchingProfile {
+        MatchingProfile {
+            params: AffinityParams::from(vec![(
+                Language::Rust,
+                LanguagePreference {
+                    level: Level::Proficient,
+                    priority: Priority::High,
+                    intent: Intent::Peer,
+                },
+            )]),
+            require_common_spoken_language: false,
+        }
+    }
+
+    fn set_rust_profiles(wsm: &mut WeekScheduleMatrix, users: &[UserId]) {
+        for &user in users {
+            wsm.set_profile(user, rust_profile());
+        }
+    }
+
+    fn slot_users(res: &AvailabilityMatch, i: usize) -> Vec<UserId> {
+        res.slots[i].1.iter().map(|(user, _)| *user).collect()
+    }
+
+    fn at(y: i32, m: u32, d: u32, h: u32) -> DateTime<Utc> {
+        Utc.ymd(y, m, d).and_hms(h, 0, 0)
+    }
+
+    #[test]
+    fn utc_week_schedule_with_fixed_offset() {
+        // Monday 2020-01-06, CET is UTC+01:00
+        let days = days_from(7, at(2020, 1, 6, 0));
+        let local = WeekSchedule {
+            mon: DaySchedule::try_from("21,22,23").unwrap(),
+            ..WeekSchedule::default()
+        };
+
+        let utc = utc_week_schedule(&local, Tz::Europe__Rome, &days);
+
+        assert_eq!(utc.mon, day("20,21,22"));
+    }
+
+    #[test]
+    fn utc_week_schedule_crossing_midnight() {
+        // Monday 2020-01-06, EST is UTC-05:00
+        let days = days_from(7, at(2020, 1, 6, 0));
+        let local = WeekSchedule {
+            mon: DaySchedule::try_from("18,22,23").unwrap(),
+            ..WeekSchedule::default()
+        };
+
+        let utc = utc_week_schedule(&local, Tz::America__New_York, &days);
+
+        assert_eq!(utc.mon, day("23"));
+        assert_eq!(utc.tue, day("3,4"));
+    }
+
+    #[test]
+    fn utc_week_schedule_across_dst_transition() {
+        // Daylight saving time in Italy starts on Sunday 2020-03-29
+        let days = days_from(7, at(2020, 3, 23, 0));
+        let local = WeekSchedule {
+            sat: DaySchedule::try_from("10").unwrap(),
+            sun: DaySchedule::try_from("10").unwrap(),
+            ..WeekSchedule::default()
+        };
+
+        let utc = utc_week_schedule(&local, Tz::Europe__Rome, &days);
+
+        assert_eq!(utc.sat, day("9"));
+        assert_eq!(utc.sun, day("8"));
+    }
+
+    #[test]
+    fn utc_week_schedule_with_half_hour_offset() {
+        // IST is UTC+05:30
+        let days = days_from(7, at(2020, 1, 6, 0));
+        let local = WeekSchedule {
+            mon: DaySchedule::try_from("17,18").unwrap(),
+            ..WeekSchedule::default()
+        };
+
+        let utc = utc_week_schedule(&local, Tz::Asia__Kolkata, &days);
+
+        assert_eq!(utc.mon, day("11:30-13:30"));
+    }
+
+    #[test]
+    fn utc_week_schedule_with_exceptions() {
+        // Monday 2020-01-06, CET is UTC+01:00
+        let days = days_from(7, at(2020, 1, 6, 0));
+        let mut local = WeekSchedule {
+            mon: DaySchedule::try_from("21,22,23").unwrap(),
+            ..WeekSchedule::default()
+        };
+        local.set_available_on(NaiveDate::from_ymd(2020, 1, 6), 22, false);
+        local.set_available_on(NaiveDate::from_ymd(2020, 1, 11), 10, true);
+        // Out of the projected week
+        local.set_available_on(NaiveDate::from_ymd(2020, 1, 13), 10, true);
+
+        let utc = utc_week_schedule(&local, Tz::Europe__Rome, &days);
+
+        assert_eq!(utc.mon, day("20,22"));
+        assert_eq!(utc.sat, day("9"));
+    }
+
+    #[test]
+    fn match_users_in_different_time_zones() {
+        let date = at(2020, 1, 6, 0);
+
+        // 21:00 in Rome is 15:00 in New York
+        let rome = WeekSchedule {
+            mon: DaySchedule::try_from("21").unwrap(),
+            ..WeekSchedule::default()
+        };
+        let new_york = WeekSchedule {
+            mon: DaySchedule::try_from("15").unwrap(),
+            ..WeekSchedule::default()
+        };
+
+        let mut wsm = WeekScheduleMatrix::from_local(
+            vec![(UserId(1), new_york, Tz::America__New_York)],
+            date,
+        );
+        set_rust_profiles(&mut wsm, &[UserId(1)]);
+
+        let res = wsm.find_all_users_matching_in_week(
+            UserId(2),
+            date,
+            Availability::Weekly(rome),
+            Tz::Europe__Rome,
+            &rust_profile(),
+            &AffinityConfig::default(),
+        );
+
+        assert_eq!(res.slots.len(), 1);
+        assert_eq!(res.slots[0].0, at(2020, 1, 6, 20));
+        assert_eq!(slot_users(&res, 0), vec![UserId(1)]);
+        assert_eq!(res.resolution, Resolution::Hour);
+    }
+
+    #[test]
+    fn match_users_with_different_resolutions() {
+        let date = at(2020, 1, 6, 0);
+
+        let hourly = WeekSchedule {
+            mon: DaySchedule::try_from("20").unwrap(),
+            ..WeekSchedule::default()
+        };
+        let half_hour = WeekSchedule {
+            mon: DaySchedule::try_from("20:30-21:30").unwrap(),
+            ..WeekSchedule::default()
+        };
+
+        let mut wsm = WeekScheduleMatrix::from_local(
+            vec![
+                (UserId(1), hourly.clone(), Tz::UTC),
+                (UserId(2), half_hour.clone(), Tz::UTC),
+            ],
+            date,
+        );
+        set_rust_profiles(&mut wsm, &[UserId(1), UserId(2)]);
+
+        // Only 20:30-21:00 is shared
+        let res = wsm.find_all_users_matching_in_week(
+            UserId(2),
+            date,
+            Availability::Weekly(half_hour),
+            Tz::UTC,
+            &rust_profile(),
+            &AffinityConfig::default(),
+        );
+        assert_eq!(res.resolution, Resolution::HalfHour);
+        assert_eq!(res.slots.len(), 1);
+        assert_eq!(res.slots[0].0, date + Duration::minutes(20 * 60 + 30));
+        assert_eq!(slot_users(&res, 0), vec![UserId(1)]);
+
+        // No user is available for the whole hour
+        let res = wsm.find_all_users_matching_in_week(
+            UserId(1),
+            date,
+            Availability::Weekly(hourly),
+            Tz::UTC,
+            &rust_profile(),
+            &AffinityConfig::default(),
+        );
+        assert!(res.slots.is_empty());
+    }
+
+    #[test]
+    fn users_on_vacation_do_not_match() {
+        let date = at(2020, 1, 6, 0);
+
+        let week = WeekSchedule {
+            mon: DaySchedule::try_from("10").unwrap(),
+            tue: DaySchedule::try_from("10").unwrap(),
+            ..WeekSchedule::default()
+        };
+
+        let mut wsm = WeekScheduleMatrix::from_local(
+            vec![
+                (UserId(1), week.clone(), Tz::UTC),
+                (UserId(2), week.clone(), Tz::UTC),
+            ],
+            date,
+        );
+        set_rust_profiles(&mut wsm, &[UserId(1), UserId(2)]);
+
+        let find = |wsm: &WeekScheduleMatrix, user| {
+            wsm.find_all_users_matching_in_week(
+                user,
+                date,
+                Availability::Weekly(week.clone()),
+                Tz::UTC,
+                &rust_profile(),
+                &AffinityConfig::default(),
+            )
+        };
+
+        assert_eq!(find(&wsm, UserId(2)).slots.len(), 2);
+
+        // Only on monday
+        let vacation = Vacation {
+            start: None,
+            end: Some(NaiveDate::from_ymd(2020, 1, 6)),
+        };
+        wsm.set_vacation(UserId(1), Some(vacation));
+        let res = find(&wsm, UserId(2));
+        assert_eq!(res.slots.len(), 1);
+        assert_eq!(res.slots[0].0, at(2020, 1, 7, 10));
+
+        // User on vacation does not find matches
+        assert_eq!(find(&wsm, UserId(1)).slots.len(), 1);
+
+        wsm.set_vacation(UserId(1), Some(Vacation::default()));
+        assert!(find(&wsm, UserId(2)).slots.is_empty());
+        assert!(find(&wsm, UserId(1)).slots.is_empty());
+
+        wsm.set_vacation(UserId(1), None);
+        assert_eq!(find(&wsm, UserId(2)).slots.len(), 2);
+    }
+
+    #[test]
+    fn week_sched_mat_set_week_across_dst_transition() {
+        let local = WeekSchedule {
+            sun: DaySchedule::try_from("10").unwrap(),
+            ..WeekSchedule::default()
+        };
+
+        let mut wsm = WeekScheduleMatrix::from_local(
+            vec![(UserId(1), local, Tz::Europe__Rome)],
+            at(2020, 3, 16, 0),
+        );
+        assert!(wsm.sun[&(UserId(1), hour(9))]);
+
+        wsm.set_week(at(2020, 3, 23, 0));
+        assert!(!wsm.sun[&(UserId(1), hour(9))]);
+        assert!(wsm.sun[&(UserId(1), hour(8))]);
+    }
+
+    fn profile(language: Language, level: Level, priority: Priority) -> MatchingProfile {
+        MatchingProfile {
+            params: AffinityParams::from(vec![(
+                language,
+                LanguagePreference {
+                    level,
+                    priority,
+                    intent: Intent::Peer,
+                },
+            )]),
+            require_common_spoken_language: false,
+        }
+    }
+
+    #[test]
+    fn matching_users_are_ranked_by_affinity() {
+        use crate::{SpokenLanguage, SpokenLanguages};
+
+        let date = at(2020, 1, 6, 0);
+        let week = WeekSchedule {
+            mon: DaySchedule::try_from("10").unwrap(),
+            ..WeekSchedule::default()
+        };
+
+        let users: Vec<_> = (1..=5).map(UserId).collect();
+        let schedules = users.iter().map(|&u| (u, week.clone(), Tz::UTC)).collect();
+        let mut wsm = WeekScheduleMatrix::from_local(schedules, date);
+
+        let low = profile(Language::Rust, Level::Proficient, Priority::Low);
+        let high = profile(Language::Rust, Level::Proficient, Priority::High);
+        let other_language = profile(Language::Python, Level::Proficient, Priority::High);
+
+        let english = SpokenLanguages::from(
+            vec![SpokenLanguage::English]
+                .into_iter()
+                .collect::<std::collections::BTreeSet<_>>(),
+        );
+        let italian = SpokenLanguages::from(
+            vec![SpokenLanguage::Italian]
+                .into_iter()
+                .collect::<std::collections::BTreeSet<_>>(),
+        );
+        let mut high_italian = high.clone();
+        high_italian.params = high_italian.params.with_spoken_languages(italian);
+        high_italian.require_common_spoken_language = true;
+
+        wsm.set_profile(UserId(1), low);
+        wsm.set_profile(UserId(2), high.clone());
+        wsm.set_profile(UserId(3), other_language);
+        wsm.set_profile(UserId(4), high_italian);
+        // UserId(5) has no profile
+
+        let mut me = high;
+        me.params = me.params.with_spoken_languages(english);
+
+        let res = wsm.find_all_users_matching_in_week(
+            UserId(6),
+            date,
+            Availability::Weekly(week),
+            Tz::UTC,
+            &me,
+            &AffinityConfig::default(),
+        );
+
+        assert_eq!(res.slots.len(), 1);
+        assert_eq!(slot_users(&res, 0), vec![UserId(2), UserId(1)]);
+        let affinities: Vec<_> = res.slots[0].1.iter().map(|(_, a)| *a).collect();
+        assert!(affinities[0] > affinities[1]);
+    }
+
+    #[test]
+    fn matching_users_are_truncated() {
+        let date = at(2020, 1, 6, 0);
+        let week = WeekSchedule {
+            mon: DaySchedule::try_from("10").unwrap(),
+            ..WeekSchedule::default()
+        };
+
+        let n = MAX_USERS_IN_SLOT as i32 + 5;
+        let schedules = (1..=n)
+            .map(|u| (UserId(u), week.clone(), Tz::UTC))
+            .collect();
+        let mut wsm = WeekScheduleMatrix::from_local(schedules, date);
+
+        // Only users with an even id have the best affinity
+        for u in 1..=n {
+            let priority = if u % 2 == 0 {
+                Priority::High
+            } else {
+                Priority::Low
+            };
+            wsm.set_profile(
+                UserId(u),
+                profile(Language::Rust, Level::Proficient, priority),
+            );
+        }
+
+        let res = wsm.find_all_users_matching_in_week(
+            UserId(0),
+            date,
+            Availability::Weekly(week),
+            Tz::UTC,
+            &rust_profile(),
+            &AffinityConfig::default(),
+        );
+
+        let users = slot_users(&res, 0);
+        assert_eq!(users.len(), MAX_USERS_IN_SLOT);
+        // Best ones first, same affinity sorted by id
+        let best: Vec<_> = (1..=n).filter(|u| u % 2 == 0).map(UserId).collect();
+        assert_eq!(users[..best.len()], best[..]);
+    }
+
+    #[test]
+    fn consecutive_slots_are_merged_in_sessions() {
+        let a = Affinity::from_number(0.5);
+        let b = Affinity::from_number(0.8);
+
+        let res = AvailabilityMatch {
+            slots: vec![
+                (at(2020, 1, 6, 10), vec![(UserId(1), a)]),
+                (at(2020, 1, 6, 11), vec![(UserId(2), b), (UserId(1), a)]),
+                (at(2020, 1, 6, 12), vec![(UserId(2), b)]),
+                (at(2020, 1, 6, 14), vec![(UserId(1), a)]),
+            ],
+            resolution: Resolution::Hour,
+        };
+
+        let session = |user, affinity, start, end| MatchingSession {
+            user: UserId(user),
+            affinity,
+            start: at(2020, 1, 6, start),
+            end: at(2020, 1, 6, end),
+        };
+
+        assert_eq!(
+            res.sessions(Duration::zero()),
+            vec![
+                session(1, a, 10, 12),
+                session(2, b, 11, 13),
+                session(1, a, 14, 15),
+            ]
+        );
+
+        let long = res.sessions(Duration::hours(2));
+        assert_eq!(long, vec![session(1, a, 10, 12), session(2, b, 11, 13)]);
+        assert_eq!(long[0].duration(), Duration::hours(2));
+    }
+
+    #[test]
+    fn sessions_with_finer_resolution() {
+        let a = Affinity::from_number(0.5);
+        let t = at(2020, 1, 6, 10);
+
+        let res = AvailabilityMatch {
+            slots: (0..3)
+                .map(|i| (t + Duration::minutes(15 * i), vec![(UserId(1), a)]))
+                .collect(),
+            resolution: Resolution::QuarterHour,
+        };
+
+        let sessions = res.sessions(Duration::minutes(30));
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].duration(), Duration::minutes(45));
+    }
+
+    #[test]
+    fn match_users_in_following_weeks() {
+        // Daylight saving time in Italy starts on Sunday 2020-03-29
+        let date = at(2020, 3, 23, 0);
+
+        let week = WeekSchedule {
+            mon: DaySchedule::try_from("10").unwrap(),
+            ..WeekSchedule::default()
+        };
+
+        let mut wsm = WeekScheduleMatrix::from_local(
+            vec![
+                (UserId(1), week.clone(), Tz::Europe__Rome),
+                (UserId(2), week.clone(), Tz::Europe__Rome),
+            ],
+            date,
+        );
+        set_rust_profiles(&mut wsm, &[UserId(1), UserId(2)]);
+
+        // Only on the third monday
+        let vacation = Vacation {
+            start: Some(NaiveDate::from_ymd(2020, 4, 6)),
+            end: Some(NaiveDate::from_ymd(2020, 4, 6)),
+        };
+        wsm.set_vacation(UserId(1), Some(vacation));
+
+        let filter = MatchFilter::new(date).with_days(21);
+        let res = wsm.find_all_users_matching(
+            UserId(2),
+            Availability::Weekly(week),
+            Tz::Europe__Rome,
+            &rust_profile(),
+            &AffinityConfig::default(),
+            &filter,
+        );
+
+        let times: Vec<_> = res.slots.iter().map(|(t, _)| *t).collect();
+        assert_eq!(times, vec![at(2020, 3, 23, 9), at(2020, 3, 30, 8)]);
+        assert_eq!(slot_users(&res, 1), vec![UserId(1)]);
+    }
+
+    #[test]
+    fn projections_are_cached_and_updated() {
+        let date = at(2020, 3, 23, 0);
+
+        let week = WeekSchedule {
+            mon: DaySchedule::try_from("10").unwrap(),
+            ..WeekSchedule::default()
+        };
+
+        let mut wsm = WeekScheduleMatrix::from_local(
+            vec![
+                (UserId(1), week.clone(), Tz::Europe__Rome),
+                (UserId(2), week.clone(), Tz::Europe__Rome),
+            ],
+            date,
+        );
+        set_rust_profiles(&mut wsm, &[UserId(1), UserId(2)]);
+
+        assert!(wsm.is_projected_on(date, 7));
+        assert!(!wsm.is_projected_on(date, 21));
+        wsm.set_period(date, 21);
+        assert!(wsm.is_projected_on(date, 21));
+        assert!(!wsm.is_projected_on(date, 28));
+
+        // Cached projections are updated too
+        let vacation = Vacation {
+            start: Some(NaiveDate::from_ymd(2020, 4, 6)),
+            end: Some(NaiveDate::from_ymd(2020, 4, 6)),
+        };
+        wsm.set_vacation(UserId(1), Some(vacation));
+
+        let find = |wsm: &WeekScheduleMatrix, date| {
+            let filter = MatchFilter::new(date).with_days(21);
+            wsm.find_all_users_matching(
+                UserId(2),
+                Availability::Weekly(week.clone()),
+                Tz::Europe__Rome,
+                &rust_profile(),
+                &AffinityConfig::default(),
+                &filter,
+            )
+        };
+
+        let times: Vec<_> = find(&wsm, date).slots.iter().map(|(t, _)| *t).collect();
+        assert_eq!(times, vec![at(2020, 3, 23, 9), at(2020, 3, 30, 8)]);
+
+        // Moving to the next week keeps the projections still needed
+        let date = at(2020, 3, 30, 0);
+        wsm.set_week(date);
+        assert!(wsm.is_projected_on(date, 14));
+        assert!(!wsm.is_projected_on(date, 21));
+        wsm.set_period(date, 21);
+        let times: Vec<_> = find(&wsm, date).slots.iter().map(|(t, _)| *t).collect();
+        assert_eq!(times, vec![at(2020, 3, 30, 8), at(2020, 4, 13, 8)]);
+    }
+
+    #[test]
+    fn match_filter_restricts_users() {
+        let date = at(2020, 1, 6, 0);
+
+        let week = WeekSchedule {
+            mon: DaySchedule::try_from("8,10,11,12").unwrap(),
+            tue: DaySchedule::try_from("10").unwrap(),
+            ..WeekSchedule::default()
+        };
+
+        let short = WeekSchedule {
+            mon: DaySchedule::try_from("10").unwrap(),
+            ..WeekSchedule::default()
+        };
+
+        let mut wsm = WeekScheduleMatrix::from_local(
+            vec![
+                (UserId(1), week.clone(), Tz::UTC),
+                (UserId(2), short, Tz::UTC),
+                (UserId(3), week.clone(), Tz::UTC),
+            ],
+            date,
+        );
+
+        let mut me = rust_profile();
+        me.params = AffinityParams::from(vec![
+            (
+                Language::Rust,
+                LanguagePreference {
+                    level: Level::Proficient,
+                    priority: Priority::High,
+                    intent: Intent::Peer,
+                },
+            ),
+            (
+                Language::Python,
+                LanguagePreference {
+                    level: Level::Proficient,
+                    priority: Priority::Low,
+                    intent: Intent::Peer,
+                },
+            ),
+        ]);
+
+        wsm.set_profile(UserId(1), rust_profile());
+        wsm.set_profile(UserId(2), rust_profile());
+        wsm.set_profile(
+            UserId(3),
+            profile(Language::Python, Level::Proficient, Priority::Low),
+        );
+
+        let find = |filter: MatchFilter| {
+            wsm.find_all_users_matching(
+                UserId(0),
+                Availability::Weekly(week.clone()),
+                Tz::UTC,
+                &me,
+                &AffinityConfig::default(),
+                &filter,
+            )
+        };
+
+        // Slots before start are skipped
+        let res = find(MatchFilter::new(at(2020, 1, 6, 9)));
+        assert_eq!(res.slots[0].0, at(2020, 1, 6, 10));
+        assert_eq!(res.slots.len(), 4);
+
+        let res = find(MatchFilter::new(date).with_language(Some(Language::Python)));
+        assert_eq!(slot_users(&res, 0), vec![UserId(3)]);
+
+        let high = me.affinity(&rust_profile(), &AffinityConfig::default());
+        let res = find(MatchFilter::new(date).with_min_affinity(high.unwrap()));
+        assert!(res
+            .slots
+            .iter()
+            .all(|(_, users)| users.iter().all(|(u, _)| *u != UserId(3))));
+
+        // User 2 is available only for an hour
+        let res = find(MatchFilter::new(date).with_min_session(Duration::hours(3)));
+        let times: Vec<_> = res.slots.iter().map(|(t, _)| *t).collect();
+        assert_eq!(
+            times,
+            vec![at(2020, 1, 6, 10), at(2020, 1, 6, 11), at(2020, 1, 6, 12)]
+        );
+        assert_eq!(slot_users(&res, 0), vec![UserId(1), UserId(3)]);
     }
 }
@@ -0,0 +1,109 @@
+const WORD_BITS: usize = 64;
+
+/// A growable set of small integers, stored as one bit each. Set operations
+/// work on whole words, so they are fast even with lots of items.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct BitSet {
+    words: Vec<u64>,
+}
+
+impl BitSet {
+    /// Make room for items in `0..len`
+    pub(crate) fn grow(&mut self, len: usize) {
+        let words_len = (len + WORD_BITS - 1) / WORD_BITS;
+        if self.words.len() < words_len {
+            self.words.resize(words_len, 0);
+        }
+    }
+
+    pub(crate) fn contains(&self, i: usize) -> bool {
+        self.words
+            .get(i / WORD_BITS)
+            .map_or(false, |word| word & (1 << (i % WORD_BITS)) != 0)
+    }
+
+    /// Set or unset the item `i`. The set must have room for it.
+    pub(crate) fn set(&mut self, i: usize, value: bool) {
+        let word = &mut self.words[i / WORD_BITS];
+        let mask = 1 << (i % WORD_BITS);
+        if value {
+            *word |= mask;
+        } else {
+            *word &= !mask;
+        }
+    }
+
+    /// Keep only items which are in `other` too
+    pub(crate) fn intersect_with(&mut self, other: &BitSet) {
+        for (i, word) in self.words.iter_mut().enumerate() {
+            *word &= other.words.get(i).copied().unwrap_or(0);
+        }
+    }
+
+    pub(crate) fn count(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    /// Iterate over all items in the set, in increasing order
+    pub(crate) fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(i, &word)| {
+            let mut word = word;
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    None
+                } else {
+                    let bit = word.trailing_zeros() as usize;
+                    // Clear the lowest set bit
+                    word &= word - 1;
+                    Some(i * WORD_BITS + bit)
+                }
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bitset(len: usize, items: &[usize]) -> BitSet {
+        let mut set = BitSet::default();
+        set.grow(len);
+        for &i in items {
+            set.set(i, true);
+        }
+        set
+    }
+
+    #[test]
+    fn set_and_unset() {
+        let mut set = bitset(130, &[0, 63, 64, 129]);
+        assert!(set.contains(63));
+        assert!(set.contains(64));
+        assert!(!set.contains(65));
+        assert!(!set.contains(1000));
+
+        set.set(64, false);
+        assert!(!set.contains(64));
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![0, 63, 129]);
+        assert_eq!(set.count(), 3);
+    }
+
+    #[test]
+    fn intersection() {
+        let mut set = bitset(200, &[1, 70, 150, 199]);
+        set.intersect_with(&bitset(100, &[1, 2, 70]));
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![1, 70]);
+    }
+
+    #[test]
+    fn grow_keeps_items() {
+        let mut set = bitset(10, &[3]);
+        set.grow(1000);
+        set.set(999, true);
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![3, 999]);
+    }
+}
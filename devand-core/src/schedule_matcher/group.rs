@@ -0,0 +1,371 @@
+use super::{days_from, WeekScheduleMatrix, MAX_HORIZON_DAYS, RESOLUTION, SLOTS_IN_DAY};
+use crate::{Language, UserId};
+use chrono::prelude::*;
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Smaller groups are pairs, see `find_all_users_matching`
+pub const MIN_GROUP_SIZE: usize = 3;
+
+/// Only the best group slots are kept
+pub const MAX_GROUP_SLOTS: usize = 50;
+
+/// Users who may be part of a group
+#[derive(Clone, Debug, PartialEq)]
+pub enum GroupCandidates {
+    /// Only these users, e.g. a team
+    Users(BTreeSet<UserId>),
+    /// Any user who knows this language
+    Language(Language),
+}
+
+/// Restrict the period and the users to find group slots
+#[derive(Clone, Debug)]
+pub struct GroupFilter {
+    pub candidates: GroupCandidates,
+    /// Minimum number of available users, at least MIN_GROUP_SIZE
+    pub min_size: usize,
+    /// Only groups including this user
+    pub member: Option<UserId>,
+    /// Slots starting before are skipped
+    pub start: DateTime<Utc>,
+    /// Number of days from `start`, up to MAX_HORIZON_DAYS
+    pub days: usize,
+    /// Only group slots at least this long
+    pub min_session: Duration,
+}
+
+impl GroupFilter {
+    /// Slots in the 7 days from `start` where all `users` are available
+    pub fn new(users: BTreeSet<UserId>, start: DateTime<Utc>) -> Self {
+        Self {
+            min_size: users.len(),
+            candidates: GroupCandidates::Users(users),
+            member: None,
+            start,
+            days: 7,
+            min_session: Duration::zero(),
+        }
+    }
+
+    /// Slots in the 7 days from `start` where at least MIN_GROUP_SIZE users
+    /// who know `language` are available
+    pub fn with_language(language: Language, start: DateTime<Utc>) -> Self {
+        Self {
+            candidates: GroupCandidates::Language(language),
+            min_size: MIN_GROUP_SIZE,
+            member: None,
+            start,
+            days: 7,
+            min_session: Duration::zero(),
+        }
+    }
+
+    pub fn with_min_size(mut self, min_size: usize) -> Self {
+        self.min_size = min_size;
+        self
+    }
+
+    pub fn with_member(mut self, member: UserId) -> Self {
+        self.member = Some(member);
+        self
+    }
+
+    pub fn with_days(mut self, days: usize) -> Self {
+        self.days = days;
+        self
+    }
+
+    pub fn with_min_session(mut self, min_session: Duration) -> Self {
+        self.min_session = min_session;
+        self
+    }
+}
+
+/// A range where all users of a group are available
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct GroupSlot {
+    /// Sorted by id
+    pub users: Vec<UserId>,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl GroupSlot {
+    pub fn duration(&self) -> Duration {
+        self.end - self.start
+    }
+}
+
+impl WeekScheduleMatrix {
+    /// Find ranges where groups of candidates are available together. A
+    /// group slot lasts as long as all its users are available: when some
+    /// of them leave, the remaining ones may form another group slot, which
+    /// starts when all of them were available.
+    /// Bigger groups come first, then longer slots, then earlier ones. Only
+    /// the best MAX_GROUP_SLOTS are kept.
+    pub fn find_group_slots(&self, filter: &GroupFilter) -> Vec<GroupSlot> {
+        let candidates = self.group_candidates(&filter.candidates);
+        let min_size = filter.min_size.max(MIN_GROUP_SIZE);
+        let length = Duration::minutes(RESOLUTION.minutes() as i64);
+
+        // Candidates available in each slot of the period, sorted by time
+        let mut slots: Vec<(DateTime<Utc>, BTreeSet<UserId>)> = Vec::new();
+
+        let days = days_from(filter.days.min(MAX_HORIZON_DAYS), filter.start);
+
+        for week in days.chunks(7) {
+            let projected;
+            let matrix = match self.projection(week) {
+                Some(matrix) => matrix,
+                None => {
+                    projected = self.projected_on(week);
+                    &projected
+                }
+            };
+
+            for date in week {
+                let day_matrix = &matrix[date.weekday()];
+
+                for s in 0..SLOTS_IN_DAY {
+                    let minutes = (s * RESOLUTION.minutes()) as i64;
+                    let t = date.and_hms(0, 0, 0) + Duration::minutes(minutes);
+
+                    if t < filter.start {
+                        continue;
+                    }
+
+                    let users = day_matrix
+                        .get_available_in_slots(s..(s + 1))
+                        .into_iter()
+                        .filter(|user| candidates.contains(user))
+                        .collect();
+
+                    slots.push((t, users));
+                }
+            }
+        }
+
+        let mut open: BTreeMap<Vec<UserId>, GroupSlot> = BTreeMap::new();
+        let mut groups = Vec::new();
+
+        for (i, (t, users)) in slots.iter().enumerate() {
+            // Groups with someone who left are closed
+            let (still_open, closed): (BTreeMap<_, _>, BTreeMap<_, _>) = std::mem::take(&mut open)
+                .into_iter()
+                .partition(|(group, slot)| {
+                    slot.end == *t && group.iter().all(|user| users.contains(user))
+                });
+
+            open = still_open;
+            groups.extend(closed.into_values());
+
+            let group: Vec<UserId> = users.iter().copied().collect();
+
+            if group.len() >= min_size && !open.contains_key(&group) {
+                // The group was already available, as part of bigger groups
+                let start = slots[..i]
+                    .iter()
+                    .rev()
+                    .scan(*t, |next, (prev, prev_users)| {
+                        let contiguous = *prev + length == *next;
+                        *next = *prev;
+                        Some((*prev, contiguous && users.is_subset(prev_users)))
+                    })
+                    .take_while(|&(_, available)| available)
+                    .last()
+                    .map_or(*t, |(prev, _)| prev);
+
+                let slot = GroupSlot {
+                    users: group.clone(),
+                    start,
+                    end: *t,
+                };
+                open.insert(group, slot);
+            }
+
+            for slot in open.values_mut() {
+                slot.end = *t + length;
+            }
+        }
+
+        groups.extend(open.into_values());
+
+        groups.retain(|slot| {
+            slot.duration() >= filter.min_session
+                && filter.member.map_or(true, |m| slot.users.contains(&m))
+        });
+
+        groups.sort_by_key(|slot| {
+            (
+                Reverse(slot.users.len()),
+                Reverse(slot.duration()),
+                slot.start,
+            )
+        });
+        groups.truncate(MAX_GROUP_SLOTS);
+        groups
+    }
+
+    fn group_candidates(&self, candidates: &GroupCandidates) -> BTreeSet<UserId> {
+        match candidates {
+            GroupCandidates::Users(users) => users.clone(),
+            GroupCandidates::Language(language) => self
+                .profiles
+                .iter()
+                .filter(|(_, profile)| profile.params.languages().contains_key(language))
+                .map(|(&user, _)| user)
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schedule_matcher::MatchingProfile;
+    use crate::{AffinityParams, DaySchedule, Intent, LanguagePreference, Level, Priority};
+    use crate::{Vacation, WeekSchedule};
+    use chrono_tz::Tz;
+    use std::convert::TryFrom;
+
+    fn at(y: i32, m: u32, d: u32, h: u32) -> DateTime<Utc> {
+        Utc.ymd(y, m, d).and_hms(h, 0, 0)
+    }
+
+    fn monday(hours: &str) -> WeekSchedule {
+        WeekSchedule {
+            mon: DaySchedule::try_from(hours).unwrap(),
+            ..WeekSchedule::default()
+        }
+    }
+
+    fn users(ids: &[i32]) -> Vec<UserId> {
+        ids.iter().copied().map(UserId).collect()
+    }
+
+    fn profile(language: Language) -> MatchingProfile {
+        MatchingProfile {
+            params: AffinityParams::from(vec![(
+                language,
+                LanguagePreference {
+                    level: Level::Proficient,
+                    priority: Priority::High,
+                    intent: Intent::Peer,
+                },
+            )]),
+            require_common_spoken_language: false,
+        }
+    }
+
+    // Monday 2020-01-06
+    fn team() -> WeekScheduleMatrix {
+        WeekScheduleMatrix::from_local(
+            vec![
+                (UserId(1), monday("17-21"), Tz::UTC),
+                (UserId(2), monday("17-20"), Tz::UTC),
+                (UserId(3), monday("18-21"), Tz::UTC),
+                (UserId(4), monday("18-19"), Tz::UTC),
+            ],
+            at(2020, 1, 6, 0),
+        )
+    }
+
+    #[test]
+    fn all_users_available() {
+        let wsm = team();
+        let filter = GroupFilter::new(users(&[1, 2, 3]).into_iter().collect(), at(2020, 1, 6, 0));
+
+        let res = wsm.find_group_slots(&filter);
+
+        assert_eq!(
+            res,
+            vec![GroupSlot {
+                users: users(&[1, 2, 3]),
+                start: at(2020, 1, 6, 18),
+                end: at(2020, 1, 6, 20),
+            }]
+        );
+    }
+
+    #[test]
+    fn groups_are_ranked_by_size_and_length() {
+        let wsm = team();
+        let filter = GroupFilter::new(
+            users(&[1, 2, 3, 4]).into_iter().collect(),
+            at(2020, 1, 6, 0),
+        )
+        .with_min_size(3);
+
+        let res = wsm.find_group_slots(&filter);
+        let groups: Vec<_> = res
+            .iter()
+            .map(|slot| (slot.users.clone(), slot.start, slot.end))
+            .collect();
+
+        assert_eq!(
+            groups,
+            vec![
+                (users(&[1, 2, 3, 4]), at(2020, 1, 6, 18), at(2020, 1, 6, 19)),
+                (users(&[1, 2, 3]), at(2020, 1, 6, 18), at(2020, 1, 6, 20)),
+            ]
+        );
+
+        // Too short
+        let res = wsm.find_group_slots(&filter.with_min_session(Duration::hours(3)));
+        assert!(res.is_empty());
+    }
+
+    #[test]
+    fn groups_by_language() {
+        let mut wsm = team();
+        wsm.set_profile(UserId(1), profile(Language::Rust));
+        wsm.set_profile(UserId(2), profile(Language::Rust));
+        wsm.set_profile(UserId(3), profile(Language::Rust));
+        wsm.set_profile(UserId(4), profile(Language::Python));
+
+        let filter = GroupFilter::with_language(Language::Rust, at(2020, 1, 6, 0));
+        let res = wsm.find_group_slots(&filter);
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].users, users(&[1, 2, 3]));
+
+        let res = wsm.find_group_slots(&filter.clone().with_member(UserId(4)));
+        assert!(res.is_empty());
+    }
+
+    #[test]
+    fn groups_without_users_on_vacation() {
+        let mut wsm = team();
+
+        let vacation = Vacation {
+            start: Some(NaiveDate::from_ymd(2020, 1, 6)),
+            end: Some(NaiveDate::from_ymd(2020, 1, 6)),
+        };
+        wsm.set_vacation(UserId(4), Some(vacation));
+
+        let filter = GroupFilter::new(
+            users(&[1, 2, 3, 4]).into_iter().collect(),
+            at(2020, 1, 6, 0),
+        )
+        .with_min_size(3)
+        .with_days(14);
+
+        let res = wsm.find_group_slots(&filter);
+        let groups: Vec<_> = res
+            .iter()
+            .map(|slot| (slot.users.clone(), slot.start))
+            .collect();
+
+        assert_eq!(
+            groups,
+            vec![
+                (users(&[1, 2, 3, 4]), at(2020, 1, 13, 18)),
+                (users(&[1, 2, 3]), at(2020, 1, 6, 18)),
+                (users(&[1, 2, 3]), at(2020, 1, 13, 18)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,406 @@
+//! Pairing sessions are proposed by an user to one or more invitees, for a
+//! given time. Sessions must fit in the schedule of all members and must not
+//! overlap other active sessions of the same members.
+
+use crate::schedule_matcher::is_available_at;
+use crate::{Availability, Language, Resolution, User, UserId, UserSettings};
+use chrono::prelude::*;
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumIter, EnumString};
+
+/// Longer sessions are not valid
+pub const MAX_SESSION_HOURS: i64 = 8;
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, Eq, PartialEq, Ord, PartialOrd)]
+pub struct SessionId(pub uuid::Uuid);
+
+impl std::fmt::Display for SessionId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::str::FromStr for SessionId {
+    type Err = uuid::ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        uuid::Uuid::parse_str(s).map(SessionId)
+    }
+}
+
+#[derive(
+    Debug, Serialize, Deserialize, Copy, Clone, EnumIter, Display, EnumString, Eq, PartialEq,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum SessionStatus {
+    /// Waiting for an answer of the invitees
+    Proposed,
+    Accepted,
+    Declined,
+    Cancelled,
+    Done,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct PairingSession {
+    pub id: SessionId,
+    pub proposer: UserId,
+    pub invitees: Vec<UserId>,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// Language the session is about, if any
+    pub language: Option<Language>,
+    pub status: SessionStatus,
+}
+
+impl PairingSession {
+    /// All members of the session, proposer first
+    pub fn members(&self) -> impl Iterator<Item = UserId> + '_ {
+        std::iter::once(self.proposer).chain(self.invitees.iter().copied())
+    }
+
+    pub fn is_member(&self, user: UserId) -> bool {
+        self.members().any(|member| member == user)
+    }
+
+    /// Proposed and accepted sessions take the time of their members
+    pub fn is_active(&self) -> bool {
+        matches!(
+            self.status,
+            SessionStatus::Proposed | SessionStatus::Accepted
+        )
+    }
+
+    fn overlaps(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> bool {
+        self.start < end && start < self.end
+    }
+
+    /// Return true if `user` can change the status of the session to
+    /// `status`. Invitees accept or decline a proposal before the session
+    /// starts. Any member can cancel an active session before it ends, or
+    /// mark an accepted session as done once it has started. With many
+    /// invitees, the first answer is the answer for the whole session.
+    pub fn can_set_status(&self, user: UserId, status: SessionStatus, now: DateTime<Utc>) -> bool {
+        use SessionStatus::*;
+
+        let is_invitee = self.invitees.contains(&user);
+
+        match (self.status, status) {
+            (Proposed, Accepted) | (Proposed, Declined) => is_invitee && now < self.start,
+            (Proposed, Cancelled) | (Accepted, Cancelled) => self.is_member(user) && now < self.end,
+            (Accepted, Done) => self.is_member(user) && now >= self.start,
+            _ => false,
+        }
+    }
+
+    /// The first active session of `user`, other than this one, which
+    /// overlaps this one
+    pub fn find_overlapping(&self, user: UserId, sessions: &[PairingSession]) -> Option<SessionId> {
+        find_overlapping(user, self.start, self.end, sessions, Some(self.id))
+    }
+
+    /// Check the session again before it is accepted: since it was
+    /// proposed, members may have changed their schedule, gone on vacation
+    /// or booked other sessions. Members missing from `users` are
+    /// unavailable.
+    pub fn conflicts(&self, users: &[User], sessions: &[PairingSession]) -> Vec<SessionConflict> {
+        let members: Vec<UserId> = self.members().collect();
+        members_conflicts(
+            &members,
+            self.start,
+            self.end,
+            users,
+            sessions,
+            Some(self.id),
+        )
+    }
+}
+
+/// A new session, proposed by the logged user
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct SessionProposal {
+    pub invitees: Vec<UserId>,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    #[serde(default)]
+    pub language: Option<Language>,
+}
+
+/// Reasons why a session cannot be proposed
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionConflict {
+    /// The session is in the past, it is empty or too long, there are no
+    /// invitees or the proposer is invited
+    Invalid,
+    /// The user is not available for the whole session, according to their
+    /// schedule and vacation
+    Unavailable(UserId),
+    /// The user has another active session at the same time
+    Overlapping(UserId, SessionId),
+}
+
+impl SessionProposal {
+    /// Check the proposal of `proposer` against the schedules of all
+    /// members, given in `users`, and against their `sessions`. Members
+    /// missing from `users` are unavailable.
+    pub fn conflicts(
+        &self,
+        proposer: UserId,
+        users: &[User],
+        sessions: &[PairingSession],
+        now: DateTime<Utc>,
+    ) -> Vec<SessionConflict> {
+        let too_long = self.end - self.start > Duration::hours(MAX_SESSION_HOURS);
+
+        if self.start < now
+            || self.end <= self.start
+            || too_long
+            || self.invitees.is_empty()
+            || self.invitees.contains(&proposer)
+        {
+            return vec![SessionConflict::Invalid];
+        }
+
+        let mut members = vec![proposer];
+        for &invitee in self.invitees.iter() {
+            if !members.contains(&invitee) {
+                members.push(invitee);
+            }
+        }
+
+        members_conflicts(&members, self.start, self.end, users, sessions, None)
+    }
+}
+
+fn members_conflicts(
+    members: &[UserId],
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    users: &[User],
+    sessions: &[PairingSession],
+    except: Option<SessionId>,
+) -> Vec<SessionConflict> {
+    let mut conflicts = Vec::new();
+
+    for &member in members {
+        let available = users
+            .iter()
+            .filter(|u| u.id == member)
+            .any(|u| is_available_between(&u.settings, start, end));
+
+        if !available {
+            conflicts.push(SessionConflict::Unavailable(member));
+        }
+
+        if let Some(id) = find_overlapping(member, start, end, sessions, except) {
+            conflicts.push(SessionConflict::Overlapping(member, id));
+        }
+    }
+
+    conflicts
+}
+
+fn find_overlapping(
+    user: UserId,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    sessions: &[PairingSession],
+    except: Option<SessionId>,
+) -> Option<SessionId> {
+    sessions
+        .iter()
+        .filter(|s| Some(s.id) != except)
+        .find(|s| s.is_active() && s.is_member(user) && s.overlaps(start, end))
+        .map(|s| s.id)
+}
+
+/// Return true if all slots between `start` and `end`, even partially
+/// included, are in the schedule and not in vacation
+fn is_available_between(settings: &UserSettings, start: DateTime<Utc>, end: DateTime<Utc>) -> bool {
+    let week_schedule = match &settings.schedule {
+        Availability::Weekly(week_schedule) => week_schedule,
+        Availability::Never => return false,
+    };
+
+    let tz = *settings.time_zone;
+    let seconds = Resolution::FINEST.minutes() as i64 * 60;
+    let step = Duration::seconds(seconds);
+
+    // Start from the beginning of the first slot
+    let offset = (start.minute() as i64 * 60 + start.second() as i64) % seconds;
+    let first = start - Duration::seconds(offset);
+    let slots = ((end - first).num_seconds() + seconds - 1) / seconds;
+
+    (0..slots as i32).map(|i| first + step * i).all(|t| {
+        !settings.is_on_vacation(t) && is_available_at(week_schedule, t.with_timezone(&tz))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::user_on_monday;
+    use crate::{UserTimeZone, Vacation};
+
+    fn session_id(n: u8) -> SessionId {
+        SessionId(uuid::Uuid::from_bytes(&[n; 16]).unwrap())
+    }
+
+    fn session(id: u8, proposer: i32, invitee: i32, start: DateTime<Utc>) -> PairingSession {
+        PairingSession {
+            id: session_id(id),
+            proposer: UserId(proposer),
+            invitees: vec![UserId(invitee)],
+            start,
+            end: start + Duration::hours(1),
+            language: None,
+            status: SessionStatus::Proposed,
+        }
+    }
+
+    // Monday
+    fn start() -> DateTime<Utc> {
+        Utc.ymd(2020, 1, 6).and_hms(18, 0, 0)
+    }
+
+    fn proposal(invitee: i32, hours: i64) -> SessionProposal {
+        SessionProposal {
+            invitees: vec![UserId(invitee)],
+            start: start(),
+            end: start() + Duration::hours(hours),
+            language: None,
+        }
+    }
+
+    #[test]
+    fn proposal_in_both_schedules() {
+        let users = vec![user_on_monday(1, "17-21"), user_on_monday(2, "18-20")];
+        let now = start() - Duration::days(1);
+
+        assert!(proposal(2, 2)
+            .conflicts(UserId(1), &users, &[], now)
+            .is_empty());
+
+        assert_eq!(
+            proposal(2, 3).conflicts(UserId(1), &users, &[], now),
+            vec![SessionConflict::Unavailable(UserId(2))]
+        );
+    }
+
+    #[test]
+    fn proposal_in_user_time_zone() {
+        let mut rome = user_on_monday(2, "19-20");
+        rome.settings.time_zone = UserTimeZone(chrono_tz::Tz::Europe__Rome);
+        let users = vec![user_on_monday(1, "18"), rome];
+        let now = start() - Duration::days(1);
+
+        assert!(proposal(2, 1)
+            .conflicts(UserId(1), &users, &[], now)
+            .is_empty());
+    }
+
+    #[test]
+    fn invalid_proposals() {
+        let users = vec![user_on_monday(1, "*"), user_on_monday(2, "*")];
+        let now = start() - Duration::days(1);
+        let invalid = vec![SessionConflict::Invalid];
+
+        assert_eq!(
+            proposal(1, 1).conflicts(UserId(1), &users, &[], now),
+            invalid
+        );
+        assert_eq!(
+            proposal(2, 0).conflicts(UserId(1), &users, &[], now),
+            invalid
+        );
+        assert_eq!(
+            proposal(2, 9).conflicts(UserId(1), &users, &[], now),
+            invalid
+        );
+
+        let late = start() + Duration::minutes(1);
+        assert_eq!(
+            proposal(2, 1).conflicts(UserId(1), &users, &[], late),
+            invalid
+        );
+    }
+
+    #[test]
+    fn proposal_during_vacation_or_other_sessions() {
+        let mut on_vacation = user_on_monday(3, "*");
+        on_vacation.settings.vacation_mode = true;
+        on_vacation.settings.vacation = Vacation::default();
+
+        let users = vec![user_on_monday(1, "*"), user_on_monday(2, "*"), on_vacation];
+        let now = start() - Duration::days(1);
+
+        let mut sessions = vec![session(1, 2, 4, start() + Duration::minutes(30))];
+
+        assert_eq!(
+            proposal(2, 1).conflicts(UserId(1), &users, &sessions, now),
+            vec![SessionConflict::Overlapping(UserId(2), session_id(1))]
+        );
+
+        // Declined sessions do not take time
+        sessions[0].status = SessionStatus::Declined;
+        assert!(proposal(2, 1)
+            .conflicts(UserId(1), &users, &sessions, now)
+            .is_empty());
+
+        assert_eq!(
+            proposal(3, 1).conflicts(UserId(1), &users, &sessions, now),
+            vec![SessionConflict::Unavailable(UserId(3))]
+        );
+    }
+
+    #[test]
+    fn accepted_session_is_checked_again() {
+        let s = session(1, 1, 2, start());
+        let mut users = vec![user_on_monday(1, "*"), user_on_monday(2, "*")];
+        let mut sessions = vec![s.clone()];
+
+        assert!(s.conflicts(&users, &sessions).is_empty());
+
+        sessions.push(session(2, 2, 3, start() + Duration::minutes(30)));
+        assert_eq!(
+            s.conflicts(&users, &sessions),
+            vec![SessionConflict::Overlapping(UserId(2), session_id(2))]
+        );
+
+        users[1].settings.vacation_mode = true;
+        users[1].settings.vacation = Vacation::default();
+        assert_eq!(
+            s.conflicts(&users, &sessions[..1]),
+            vec![SessionConflict::Unavailable(UserId(2))]
+        );
+    }
+
+    #[test]
+    fn status_changes() {
+        use SessionStatus::*;
+
+        let s = session(1, 1, 2, start());
+        let before = start() - Duration::hours(1);
+        let during = start() + Duration::minutes(30);
+
+        assert!(s.can_set_status(UserId(2), Accepted, before));
+        assert!(!s.can_set_status(UserId(1), Accepted, before));
+        assert!(!s.can_set_status(UserId(2), Accepted, during));
+        assert!(s.can_set_status(UserId(1), Cancelled, during));
+        assert!(!s.can_set_status(UserId(3), Cancelled, before));
+        assert!(!s.can_set_status(UserId(1), Done, during));
+
+        let accepted = PairingSession {
+            status: Accepted,
+            ..s
+        };
+        assert!(accepted.can_set_status(UserId(2), Done, during));
+        assert!(!accepted.can_set_status(UserId(2), Done, before));
+        assert!(!accepted.can_set_status(UserId(2), Declined, before));
+    }
+}
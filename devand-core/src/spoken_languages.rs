@@ -1,7 +1,8 @@
 use isolang::Language as IsoLang;
 use serde::{Deserialize, Serialize};
 use std::cmp::Ord;
-use strum_macros::{EnumIter, EnumString};
+use std::collections::{BTreeMap, BTreeSet};
+use strum_macros::{Display, EnumIter, EnumString};
 
 #[derive(
     Debug, Serialize, Deserialize, Ord, PartialOrd, Eq, PartialEq, Copy, Clone, EnumIter, EnumString,
@@ -51,12 +52,91 @@ impl std::fmt::Display for SpokenLanguage {
     }
 }
 
-#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+/// How well an user speaks a language
+#[derive(
+    Debug,
+    Serialize,
+    Deserialize,
+    Copy,
+    Clone,
+    EnumIter,
+    Display,
+    EnumString,
+    Ord,
+    PartialOrd,
+    Eq,
+    PartialEq,
+)]
 #[serde(rename_all = "snake_case")]
-pub struct SpokenLanguages(pub std::collections::BTreeSet<SpokenLanguage>);
+pub enum Proficiency {
+    Basic,
+    Conversational,
+    Fluent,
+    Native,
+}
+
+impl Proficiency {
+    pub fn as_number(&self) -> usize {
+        match self {
+            Proficiency::Basic => 1,
+            Proficiency::Conversational => 2,
+            Proficiency::Fluent => 3,
+            Proficiency::Native => 4,
+        }
+    }
+}
+
+/// Languages set before proficiency was introduced are considered fluent
+impl Default for Proficiency {
+    fn default() -> Self {
+        Proficiency::Fluent
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Copy, Clone, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct SpokenLanguagePreference {
+    #[serde(default)]
+    pub proficiency: Proficiency,
+    /// User prefers to speak this language when pairing
+    #[serde(default)]
+    pub preferred: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case", from = "SpokenLanguagesRepr")]
+pub struct SpokenLanguages(pub BTreeMap<SpokenLanguage, SpokenLanguagePreference>);
+
+/// Spoken languages used to be a plain list, without preferences
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SpokenLanguagesRepr {
+    Preferences(BTreeMap<SpokenLanguage, SpokenLanguagePreference>),
+    List(BTreeSet<SpokenLanguage>),
+}
+
+impl From<SpokenLanguagesRepr> for SpokenLanguages {
+    fn from(repr: SpokenLanguagesRepr) -> Self {
+        match repr {
+            SpokenLanguagesRepr::Preferences(preferences) => SpokenLanguages(preferences),
+            SpokenLanguagesRepr::List(list) => list.into(),
+        }
+    }
+}
+
+/// All languages with default preferences
+impl From<BTreeSet<SpokenLanguage>> for SpokenLanguages {
+    fn from(list: BTreeSet<SpokenLanguage>) -> Self {
+        SpokenLanguages(
+            list.into_iter()
+                .map(|lang| (lang, SpokenLanguagePreference::default()))
+                .collect(),
+        )
+    }
+}
 
 impl std::ops::Deref for SpokenLanguages {
-    type Target = std::collections::BTreeSet<SpokenLanguage>;
+    type Target = BTreeMap<SpokenLanguage, SpokenLanguagePreference>;
     fn deref(&self) -> &Self::Target {
         &self.0
     }
@@ -71,13 +151,43 @@ impl std::ops::DerefMut for SpokenLanguages {
 /// Check if users have compatible languages. Users without any language set
 /// are compatible with everybody by default.
 pub fn are_spoken_language_compatible(l: &SpokenLanguages, r: &SpokenLanguages) -> bool {
-    l.0.is_empty() || r.0.is_empty() || l.0.iter().any(|x| r.0.iter().any(|y| x == y))
+    l.0.is_empty() || r.0.is_empty() || l.0.keys().any(|x| r.0.contains_key(x))
+}
+
+/// How well two users can talk to each other, in [0..1]. The best common
+/// language is chosen, limited by the lower proficiency of the two users.
+/// Preferred languages score higher. Return None if any of the users has not
+/// set spoken languages.
+pub fn spoken_languages_score(l: &SpokenLanguages, r: &SpokenLanguages) -> Option<f64> {
+    /// Score of a common language when no user prefers it
+    const NOT_PREFERRED_SCORE: f64 = 0.8;
+
+    if l.0.is_empty() || r.0.is_empty() {
+        return None;
+    }
+
+    let max_proficiency = Proficiency::Native.as_number() as f64;
+
+    let score =
+        l.0.iter()
+            .filter_map(|(lang, l_pref)| r.0.get(lang).map(|r_pref| (l_pref, r_pref)))
+            .map(|(l_pref, r_pref)| {
+                let proficiency = l_pref.proficiency.min(r_pref.proficiency);
+                let proficiency = proficiency.as_number() as f64 / max_proficiency;
+                let preferred_count = (l_pref.preferred as u8 + r_pref.preferred as u8) as f64;
+                let preferred =
+                    NOT_PREFERRED_SCORE + (1.0 - NOT_PREFERRED_SCORE) * preferred_count / 2.0;
+                proficiency * preferred
+            })
+            .fold(0.0, f64::max);
+
+    Some(score)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use maplit::btreeset;
+    use maplit::{btreemap, btreeset};
 
     #[test]
     fn spoken_lang_serde() {
@@ -103,28 +213,75 @@ mod tests {
     #[test]
     fn are_spoken_language_compatible_empty_is_yes() {
         assert!(are_spoken_language_compatible(
-            &SpokenLanguages(btreeset![]),
-            &SpokenLanguages(btreeset![SpokenLanguage::English]),
+            &SpokenLanguages::from(btreeset![]),
+            &SpokenLanguages::from(btreeset![SpokenLanguage::English]),
         ));
         assert!(are_spoken_language_compatible(
-            &SpokenLanguages(btreeset![SpokenLanguage::English]),
-            &SpokenLanguages(btreeset![]),
+            &SpokenLanguages::from(btreeset![SpokenLanguage::English]),
+            &SpokenLanguages::from(btreeset![]),
         ));
     }
 
     #[test]
     fn are_spoken_language_compatible_yes() {
         assert!(are_spoken_language_compatible(
-            &SpokenLanguages(btreeset![SpokenLanguage::English]),
-            &SpokenLanguages(btreeset![SpokenLanguage::English])
+            &SpokenLanguages::from(btreeset![SpokenLanguage::English]),
+            &SpokenLanguages::from(btreeset![SpokenLanguage::English])
         ));
     }
 
+    #[test]
+    fn spoken_languages_deserialize_legacy_list() {
+        let legacy: SpokenLanguages = serde_json::from_str("[\"english\"]").unwrap();
+        assert_eq!(
+            legacy,
+            SpokenLanguages::from(btreeset![SpokenLanguage::English])
+        );
+
+        let json = serde_json::to_string(&legacy).unwrap();
+        let deserialized: SpokenLanguages = serde_json::from_str(&json).unwrap();
+        assert_eq!(legacy, deserialized);
+    }
+
+    fn speaks(lang: SpokenLanguage, proficiency: Proficiency, preferred: bool) -> SpokenLanguages {
+        SpokenLanguages(btreemap! {
+            lang => SpokenLanguagePreference { proficiency, preferred },
+        })
+    }
+
+    #[test]
+    fn spoken_languages_score_is_limited_by_lower_proficiency() {
+        let native = speaks(SpokenLanguage::English, Proficiency::Native, true);
+        let basic = speaks(SpokenLanguage::English, Proficiency::Basic, true);
+        let italian = speaks(SpokenLanguage::Italian, Proficiency::Native, true);
+
+        assert_eq!(spoken_languages_score(&native, &native), Some(1.0));
+        assert_eq!(spoken_languages_score(&native, &basic), Some(0.25));
+        assert_eq!(spoken_languages_score(&native, &italian), Some(0.0));
+        assert_eq!(
+            spoken_languages_score(&native, &SpokenLanguages::default()),
+            None
+        );
+    }
+
+    #[test]
+    fn preferred_spoken_languages_score_higher() {
+        let preferred = speaks(SpokenLanguage::English, Proficiency::Native, true);
+        let not_preferred = speaks(SpokenLanguage::English, Proficiency::Native, false);
+
+        let both = spoken_languages_score(&preferred, &preferred).unwrap();
+        let one = spoken_languages_score(&preferred, &not_preferred).unwrap();
+        let none = spoken_languages_score(&not_preferred, &not_preferred).unwrap();
+
+        assert!(both > one);
+        assert!(one > none);
+    }
+
     #[test]
     fn are_spoken_language_compatible_no() {
         assert!(!are_spoken_language_compatible(
-            &SpokenLanguages(btreeset![SpokenLanguage::Italian]),
-            &SpokenLanguages(btreeset![SpokenLanguage::English])
+            &SpokenLanguages::from(btreeset![SpokenLanguage::Italian]),
+            &SpokenLanguages::from(btreeset![SpokenLanguage::English])
         ));
     }
 }
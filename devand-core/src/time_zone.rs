@@ -0,0 +1,67 @@
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// The IANA time zone of an user. Schedules are stored in user's local time
+/// and converted to UTC only when matching.
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, Eq, PartialEq)]
+pub struct UserTimeZone(pub Tz);
+
+impl Default for UserTimeZone {
+    fn default() -> Self {
+        UserTimeZone(Tz::UTC)
+    }
+}
+
+impl std::ops::Deref for UserTimeZone {
+    type Target = Tz;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for UserTimeZone {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.name())
+    }
+}
+
+impl FromStr for UserTimeZone {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Tz::from_str(s).map(UserTimeZone)
+    }
+}
+
+impl UserTimeZone {
+    /// Iterate over all known time zones
+    pub fn iter() -> impl Iterator<Item = Self> {
+        chrono_tz::TZ_VARIANTS.iter().copied().map(UserTimeZone)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_zone_serde() {
+        let tz = UserTimeZone(Tz::Europe__Rome);
+        let j = serde_json::to_string(&tz).unwrap();
+        assert_eq!("\"Europe/Rome\"", j);
+        let tz: UserTimeZone = serde_json::from_str(&j).unwrap();
+        assert_eq!(UserTimeZone(Tz::Europe__Rome), tz);
+    }
+
+    #[test]
+    fn time_zone_default_is_utc() {
+        assert_eq!("UTC", UserTimeZone::default().to_string());
+    }
+
+    #[test]
+    fn time_zone_from_str() {
+        assert!(UserTimeZone::from_str("America/New_York").is_ok());
+        assert!(UserTimeZone::from_str("Mars/Olympus_Mons").is_err());
+    }
+}
@@ -0,0 +1,269 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+/// A topic (framework, library, domain...) users want to pair on, beyond the
+/// programming language: "embedded", "webassembly", "tokio", "react"...
+/// Topics are normalized, so "Machine Learning" and "machine_learning" are
+/// the same topic.
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[serde(try_from = "String", into = "String")]
+pub struct Topic(String);
+
+impl Topic {
+    /// Max length of a normalized topic
+    pub const MAX_LEN: usize = 32;
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Lowercase, words separated by a single dash, only alphanumeric characters
+/// and a few symbols used in technology names (c++, node.js). Normalized
+/// topics can be used as they are in urls.
+fn normalize(s: &str) -> String {
+    let words = s
+        .to_lowercase()
+        .split(|c: char| c.is_whitespace() || c == '_' || c == '-')
+        .map(|word| {
+            word.chars()
+                .filter(|&c| c.is_alphanumeric() || c == '+' || c == '.')
+                .collect::<String>()
+        })
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>();
+
+    words.join("-")
+}
+
+impl FromStr for Topic {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let topic = normalize(s);
+
+        if topic.is_empty() {
+            Err(format!("Invalid topic: {}", s))
+        } else if topic.chars().count() > Topic::MAX_LEN {
+            Err(format!("Topic too long: {}", s))
+        } else {
+            Ok(Topic(topic))
+        }
+    }
+}
+
+impl TryFrom<String> for Topic {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Topic> for String {
+    fn from(topic: Topic) -> Self {
+        topic.0
+    }
+}
+
+impl std::fmt::Display for Topic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Topics of an user. Stored topics which are not valid anymore are dropped
+/// when deserialized, instead of making all the settings unreadable: use
+/// `Topics::parse` to reject them.
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct Topics(pub BTreeSet<Topic>);
+
+impl<'de> Deserialize<'de> for Topics {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut topics = Topics::default();
+
+        for s in Vec::<String>::deserialize(deserializer)? {
+            if topics.len() == Topics::MAX_LEN {
+                break;
+            }
+            if let Ok(topic) = s.parse() {
+                topics.insert(topic);
+            }
+        }
+
+        Ok(topics)
+    }
+}
+
+impl std::ops::Deref for Topics {
+    type Target = BTreeSet<Topic>;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl std::ops::DerefMut for Topics {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl Topics {
+    /// Max number of topics of an user
+    pub const MAX_LEN: usize = 20;
+
+    /// Parse topics, failing if any is invalid or if they are too many
+    pub fn parse<S: AsRef<str>>(v: &[S]) -> Result<Self, String> {
+        let topics = v
+            .iter()
+            .map(|s| s.as_ref().parse())
+            .collect::<Result<BTreeSet<Topic>, _>>()?;
+
+        if topics.len() > Topics::MAX_LEN {
+            Err(format!("Too many topics: {}", topics.len()))
+        } else {
+            Ok(Topics(topics))
+        }
+    }
+
+    pub fn common(&self, other: &Self) -> Vec<Topic> {
+        self.0.intersection(&other.0).cloned().collect()
+    }
+}
+
+/// Score of topics overlap, in [0..1]. It is 1.0 when all the topics of the
+/// user with less topics are shared. None if any user has no topics.
+pub fn topics_score(l: &Topics, r: &Topics) -> Option<f64> {
+    let min_len = l.len().min(r.len());
+
+    if min_len == 0 {
+        return None;
+    }
+
+    Some(l.common(r).len() as f64 / min_len as f64)
+}
+
+/// How many users are interested in a topic
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct TopicCount {
+    pub topic: Topic,
+    pub users: usize,
+}
+
+/// Count users for each topic, most popular topics first
+pub fn count_topics<'a>(topics: impl IntoIterator<Item = &'a Topics>) -> Vec<TopicCount> {
+    let mut counts: BTreeMap<&Topic, usize> = BTreeMap::new();
+
+    for topic in topics.into_iter().flat_map(|x| x.iter()) {
+        *counts.entry(topic).or_default() += 1;
+    }
+
+    let mut counts: Vec<_> = counts
+        .into_iter()
+        .map(|(topic, users)| TopicCount {
+            topic: topic.clone(),
+            users,
+        })
+        .collect();
+
+    // Sort is stable, so topics with same count are in alphabetical order
+    counts.sort_by_key(|count| Reverse(count.users));
+
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn topics(v: &[&str]) -> Topics {
+        Topics(v.iter().map(|x| x.parse().unwrap()).collect())
+    }
+
+    #[test]
+    fn topic_is_normalized() {
+        let topic = |s: &str| s.parse::<Topic>().unwrap().to_string();
+        assert_eq!(topic("WebAssembly"), "webassembly");
+        assert_eq!(topic("  Machine  Learning "), "machine-learning");
+        assert_eq!(topic("machine_learning"), "machine-learning");
+        assert_eq!(topic("--embedded--"), "embedded");
+        assert_eq!(topic("C++"), "c++");
+        assert_eq!(topic("Node.js"), "node.js");
+        assert_eq!(topic("tokio!"), "tokio");
+        assert_eq!(topic("F#"), "f");
+    }
+
+    #[test]
+    fn invalid_topic() {
+        assert!("".parse::<Topic>().is_err());
+        assert!(" - !".parse::<Topic>().is_err());
+        assert!("a".repeat(Topic::MAX_LEN + 1).parse::<Topic>().is_err());
+    }
+
+    #[test]
+    fn topic_serde() {
+        let topic: Topic = "Embedded".parse().unwrap();
+        let j = serde_json::to_string(&topic).unwrap();
+        assert_eq!("\"embedded\"", j);
+        let topic: Topic = serde_json::from_str("\"React Native\"").unwrap();
+        assert_eq!("react-native", topic.as_str());
+        assert!(serde_json::from_str::<Topic>("\"\"").is_err());
+    }
+
+    #[test]
+    fn invalid_stored_topics_are_dropped() {
+        let t: Topics = serde_json::from_str(r#"["Tokio", "", "tokio", "embedded"]"#).unwrap();
+        assert_eq!(t, topics(&["tokio", "embedded"]));
+
+        let many: Vec<_> = (0..Topics::MAX_LEN + 5)
+            .map(|i| format!("t{}", i))
+            .collect();
+        let t: Topics = serde_json::from_str(&serde_json::to_string(&many).unwrap()).unwrap();
+        assert_eq!(t.len(), Topics::MAX_LEN);
+    }
+
+    #[test]
+    fn topics_parse_is_strict() {
+        assert_eq!(
+            Topics::parse(&["Tokio", "react"]),
+            Ok(topics(&["tokio", "react"]))
+        );
+        assert!(Topics::parse(&["tokio", ""]).is_err());
+
+        let many: Vec<_> = (0..=Topics::MAX_LEN).map(|i| format!("t{}", i)).collect();
+        assert!(Topics::parse(&many).is_err());
+    }
+
+    #[test]
+    fn topics_score_empty_is_none() {
+        assert_eq!(topics_score(&topics(&[]), &topics(&["tokio"])), None);
+    }
+
+    #[test]
+    fn topics_score_is_overlap_of_smaller_set() {
+        let a = topics(&["tokio", "embedded", "databases", "webassembly"]);
+        let b = topics(&["tokio", "react"]);
+        let c = topics(&["tokio"]);
+        assert_eq!(topics_score(&a, &b), Some(0.5));
+        assert_eq!(topics_score(&a, &c), Some(1.0));
+        assert_eq!(topics_score(&b, &topics(&["embedded"])), Some(0.0));
+    }
+
+    #[test]
+    fn count_topics_most_popular_first() {
+        let users = vec![
+            topics(&["tokio", "embedded"]),
+            topics(&["react", "tokio"]),
+            topics(&["embedded", "tokio"]),
+        ];
+
+        let counts = count_topics(&users);
+
+        let counts: Vec<_> = counts.iter().map(|x| (x.topic.as_str(), x.users)).collect();
+        assert_eq!(counts, vec![("tokio", 3), ("embedded", 2), ("react", 1)]);
+    }
+}
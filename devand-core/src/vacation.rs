@@ -0,0 +1,81 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+/// Vacation period, with dates in user's time zone. Both start and end dates
+/// are included. A missing date means the period is unbounded on that side,
+/// so a vacation without dates lasts until vacation mode is disabled.
+#[derive(Debug, Serialize, Deserialize, Default, Copy, Clone, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct Vacation {
+    pub start: Option<NaiveDate>,
+    pub end: Option<NaiveDate>,
+}
+
+impl Vacation {
+    /// Return false if the vacation ends before it starts
+    pub fn is_valid(&self) -> bool {
+        match (self.start, self.end) {
+            (Some(start), Some(end)) => start <= end,
+            _ => true,
+        }
+    }
+
+    /// Return true if `date` is in the vacation period
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        let started = self.start.map_or(true, |start| date >= start);
+        let not_ended = self.end.map_or(true, |end| date <= end);
+        started && not_ended
+    }
+
+    /// Return true if `t` is in the vacation period, whose dates are in `tz`
+    pub fn contains_time(&self, t: DateTime<Utc>, tz: Tz) -> bool {
+        self.contains(t.with_timezone(&tz).date().naive_local())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unbounded_vacation() {
+        let vacation = Vacation::default();
+        assert!(vacation.contains(NaiveDate::from_ymd(2020, 1, 1)));
+    }
+
+    #[test]
+    fn bounded_vacation() {
+        let vacation = Vacation {
+            start: Some(NaiveDate::from_ymd(2020, 8, 1)),
+            end: Some(NaiveDate::from_ymd(2020, 8, 15)),
+        };
+        assert!(!vacation.contains(NaiveDate::from_ymd(2020, 7, 31)));
+        assert!(vacation.contains(NaiveDate::from_ymd(2020, 8, 1)));
+        assert!(vacation.contains(NaiveDate::from_ymd(2020, 8, 15)));
+        assert!(!vacation.contains(NaiveDate::from_ymd(2020, 8, 16)));
+    }
+
+    #[test]
+    fn vacation_ending_before_start_is_invalid() {
+        let mut vacation = Vacation {
+            start: Some(NaiveDate::from_ymd(2020, 8, 15)),
+            end: Some(NaiveDate::from_ymd(2020, 8, 1)),
+        };
+        assert!(!vacation.is_valid());
+        vacation.end = vacation.start;
+        assert!(vacation.is_valid());
+        vacation.start = None;
+        assert!(vacation.is_valid());
+    }
+
+    #[test]
+    fn vacation_without_end() {
+        let vacation = Vacation {
+            start: Some(NaiveDate::from_ymd(2020, 8, 1)),
+            end: None,
+        };
+        assert!(!vacation.contains(NaiveDate::from_ymd(2020, 7, 31)));
+        assert!(vacation.contains(NaiveDate::from_ymd(2030, 1, 1)));
+    }
+}
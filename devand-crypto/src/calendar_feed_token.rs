@@ -0,0 +1,16 @@
+use crate::signed_token;
+use serde::{Deserialize, Serialize};
+
+/// Authenticates the calendar feed of a user. Calendar clients keep polling
+/// the same url, so the token lasts long: it is invalidated by changing the
+/// feed key instead.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CalendarFeed {
+    pub user_id: i32,
+    pub key: u32,
+}
+
+impl signed_token::Signable for CalendarFeed {
+    const KIND: &'static str = "calendar_feed";
+    const EXP_SECONDS: i64 = 10 * 365 * 24 * 60 * 60;
+}
@@ -7,5 +7,6 @@ pub struct EmailVerification {
 }
 
 impl signed_token::Signable for EmailVerification {
+    const KIND: &'static str = "email_verification";
     const EXP_SECONDS: i64 = 3 * 60 * 60;
 }
@@ -1,7 +1,50 @@
+mod calendar_feed_token;
 mod email_verification_token;
 mod password_reset_token;
+mod push_channel_token;
 mod signed_token;
 
+pub use calendar_feed_token::CalendarFeed;
 pub use email_verification_token::EmailVerification;
 pub use password_reset_token::PasswordReset;
+pub use push_channel_token::PushChannel;
 pub use signed_token::{Decoder, Encoder, Signable, SignedToken};
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tokens_of_other_kinds_are_rejected() {
+        let encoder = Encoder::new_from_secret(b"secret");
+        let decoder = Decoder::new_from_secret(b"secret");
+
+        let tokens = [
+            PasswordReset { user_id: 42 }.sign(&encoder),
+            EmailVerification {
+                address: "user@example.com".into(),
+            }
+            .sign(&encoder),
+            CalendarFeed {
+                user_id: 42,
+                key: 7,
+            }
+            .sign(&encoder),
+            PushChannel { user_id: 42 }.sign(&encoder),
+        ];
+
+        let accepted = |token| {
+            [
+                PasswordReset::try_from_token(token, &decoder).is_some(),
+                EmailVerification::try_from_token(token, &decoder).is_some(),
+                CalendarFeed::try_from_token(token, &decoder).is_some(),
+                PushChannel::try_from_token(token, &decoder).is_some(),
+            ]
+        };
+
+        for (i, token) in tokens.iter().enumerate() {
+            let expected: Vec<bool> = (0..tokens.len()).map(|j| i == j).collect();
+            assert_eq!(accepted(token).to_vec(), expected);
+        }
+    }
+}
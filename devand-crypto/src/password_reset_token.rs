@@ -7,6 +7,7 @@ pub struct PasswordReset {
 }
 
 impl signed_token::Signable for PasswordReset {
+    const KIND: &'static str = "password_reset";
     const EXP_SECONDS: i64 = 3 * 60 * 60;
 }
 
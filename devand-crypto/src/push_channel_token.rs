@@ -0,0 +1,15 @@
+use crate::signed_token;
+use serde::{Deserialize, Serialize};
+
+/// Authenticates the push channel of a user. The channel is served outside
+/// Rocket, where the login cookie cannot be read, so the UI asks for this
+/// token first. Once expired, the UI asks for a new one.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PushChannel {
+    pub user_id: i32,
+}
+
+impl signed_token::Signable for PushChannel {
+    const KIND: &'static str = "push_channel";
+    const EXP_SECONDS: i64 = 60 * 60;
+}
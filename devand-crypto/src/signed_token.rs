@@ -1,8 +1,14 @@
+use bincode::Options;
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
 
 /// Implement this trait for all types that needs to be converted to a SignedToken
 pub trait Signable {
+    /// Identifies the type of the token. All tokens are signed with the same
+    /// secret, so the kind is signed with the data and checked on decoding:
+    /// a token of a type must not be accepted as a token of another type.
+    const KIND: &'static str;
+
     /// Identifies the expiration (in seconds since encoding) after which the
     /// JWT must not be accepted for processing.
     const EXP_SECONDS: i64;
@@ -31,9 +37,9 @@ struct Claims {
 #[derive(Clone)]
 pub struct SignedToken(String);
 
-impl Into<String> for SignedToken {
-    fn into(self) -> String {
-        self.0
+impl From<SignedToken> for String {
+    fn from(token: SignedToken) -> Self {
+        token.0
     }
 }
 
@@ -71,7 +77,7 @@ impl Encoder {
     where
         T: serde::ser::Serialize + Signable,
     {
-        let encoded_data = bincode::serialize(data).ok()?;
+        let encoded_data = bincode_options().serialize(&(T::KIND, data)).ok()?;
         let base64_encoded_data = base64::encode(&encoded_data);
         let now = chrono::Utc::now();
         let exp = now.checked_add_signed(chrono::Duration::seconds(T::EXP_SECONDS))?;
@@ -107,18 +113,30 @@ impl Decoder {
             .ok()
     }
 
-    /// Decode any deserializable type from a SignedToken
+    /// Decode any Signable type from a SignedToken, if the token is of the
+    /// same kind
     pub(crate) fn decode<T>(&self, token: &SignedToken) -> Option<T>
     where
-        T: serde::de::DeserializeOwned,
+        T: serde::de::DeserializeOwned + Signable,
     {
         let decoded: Claims = self.decode_claims(token)?;
         let base64_encoded_data = decoded.sub;
         let encoded_data = base64::decode(base64_encoded_data).ok()?;
-        bincode::deserialize(&encoded_data).ok()
+        let (kind, data): (String, T) = bincode_options().deserialize(&encoded_data).ok()?;
+        if kind == T::KIND {
+            Some(data)
+        } else {
+            None
+        }
     }
 }
 
+/// Data with extra bytes could be decoded as a shorter type, so they are
+/// rejected
+fn bincode_options() -> impl Options {
+    bincode::options().reject_trailing_bytes()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -139,7 +157,7 @@ mod test {
         let token = encoder.encode_claims(claims).unwrap();
         let data = decoder.decode_claims(&token).unwrap();
 
-        assert!(sub == &data.sub);
+        assert!(sub == data.sub);
     }
 
     #[test]
@@ -151,9 +169,10 @@ mod test {
         #[derive(Serialize, Deserialize, Debug)]
         struct Data {
             x: u32,
-        };
+        }
 
         impl Signable for Data {
+            const KIND: &'static str = "data";
             const EXP_SECONDS: i64 = 3600;
         }
 
@@ -164,4 +183,30 @@ mod test {
 
         assert_eq!(decoded.x, data.x);
     }
+
+    #[test]
+    fn trailing_bytes_are_rejected() {
+        let key = b"secret";
+        let encoder = Encoder::new_from_secret(key);
+        let decoder = Decoder::new_from_secret(key);
+
+        #[derive(Serialize, Deserialize, Debug)]
+        struct Data {
+            x: u32,
+        }
+
+        impl Signable for Data {
+            const KIND: &'static str = "data";
+            const EXP_SECONDS: i64 = 3600;
+        }
+
+        let encoded_data = bincode_options().serialize(&("data", 42u32, 7u32)).unwrap();
+        let claims = Claims {
+            sub: base64::encode(&encoded_data),
+            exp: 9999999999,
+        };
+
+        let token = encoder.encode_claims(claims).unwrap();
+        assert!(Data::try_from_token(&token, &decoder).is_none());
+    }
 }
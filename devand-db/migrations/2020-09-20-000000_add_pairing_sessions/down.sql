@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS pairing_sessions_invitees_index;
+DROP INDEX IF EXISTS pairing_sessions_proposer_index;
+DROP TABLE pairing_sessions;
@@ -0,0 +1,13 @@
+CREATE TABLE pairing_sessions (
+  id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+  proposer INTEGER NOT NULL,
+  invitees INTEGER[] NOT NULL,
+  start_at TIMESTAMP NOT NULL,
+  end_at TIMESTAMP NOT NULL,
+  language JSONB,
+  status VARCHAR(16) NOT NULL,
+  created_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS pairing_sessions_proposer_index ON pairing_sessions (proposer);
+CREATE INDEX IF NOT EXISTS pairing_sessions_invitees_index ON pairing_sessions USING GIN (invitees);
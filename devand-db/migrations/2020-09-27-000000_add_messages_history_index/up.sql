@@ -0,0 +1,2 @@
+-- Chat history is loaded in pages, sorted by creation time
+CREATE INDEX IF NOT EXISTS messages_chat_id_created_at_index ON messages (chat_id, created_at, id);
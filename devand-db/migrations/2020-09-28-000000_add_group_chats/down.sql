@@ -0,0 +1,13 @@
+DROP INDEX IF EXISTS chats_direct_members_index;
+
+DELETE FROM unread_messages WHERE message_id IN (
+  SELECT messages.id FROM messages
+  INNER JOIN chats ON chats.id = messages.chat_id
+  WHERE chats.name IS NOT NULL
+);
+
+DELETE FROM messages WHERE chat_id IN (SELECT id FROM chats WHERE name IS NOT NULL);
+
+DELETE FROM chats WHERE name IS NOT NULL;
+
+ALTER TABLE chats DROP COLUMN name;
@@ -0,0 +1,21 @@
+ALTER TABLE chats ADD COLUMN name VARCHAR;
+
+-- Members were compared in the given order, so the same members may have
+-- more chats. Members are made canonical and their chats are merged.
+UPDATE chats SET members = ARRAY(SELECT DISTINCT m FROM unnest(members) AS m ORDER BY m);
+
+UPDATE messages SET chat_id = kept.id
+FROM chats AS merged, chats AS kept
+WHERE messages.chat_id = merged.id
+  AND merged.members = kept.members
+  AND kept.id < merged.id
+  AND NOT EXISTS (
+    SELECT 1 FROM chats AS first
+    WHERE first.members = kept.members AND first.id < kept.id
+  );
+
+DELETE FROM chats AS merged USING chats AS kept
+WHERE merged.members = kept.members AND kept.id < merged.id;
+
+-- Group chats are found by id, so only direct chats must be unique
+CREATE UNIQUE INDEX chats_direct_members_index ON chats (members) WHERE name IS NULL;
@@ -0,0 +1,10 @@
+ALTER TABLE messages ADD COLUMN edited_at TIMESTAMP;
+ALTER TABLE messages ADD COLUMN deleted_at TIMESTAMP;
+
+-- Previous texts of edited messages, removed when the message is deleted
+CREATE TABLE message_edits (
+  message_id UUID NOT NULL,
+  edited_at TIMESTAMP NOT NULL,
+  txt VARCHAR(512) NOT NULL,
+  PRIMARY KEY(message_id, edited_at)
+);
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS weekly_pairs_user_b_index;
+DROP TABLE weekly_pairs;
+DROP TABLE weekly_pairings;
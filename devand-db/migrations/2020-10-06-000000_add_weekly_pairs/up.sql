@@ -0,0 +1,15 @@
+-- Weeks whose pairs have been computed, even if there are no pairs
+CREATE TABLE weekly_pairings (
+  week TIMESTAMP PRIMARY KEY,
+  created_at TIMESTAMP NOT NULL
+);
+
+CREATE TABLE weekly_pairs (
+  week TIMESTAMP NOT NULL,
+  user_a INTEGER NOT NULL,
+  user_b INTEGER NOT NULL,
+  pair JSONB NOT NULL,
+  PRIMARY KEY(week, user_a)
+);
+
+CREATE INDEX IF NOT EXISTS weekly_pairs_user_b_index ON weekly_pairs (week, user_b);
@@ -0,0 +1,16 @@
+DROP VIEW chat_members;
+
+CREATE VIEW chat_members AS
+SELECT chats.id as chat_id,
+       chats.members as members,
+       users.id as user_id,
+       users.username as username,
+       users.visible_name as visible_name,
+       users.bio as bio,
+       users.projects as projects,
+       users.settings->'languages' as languages,
+       users.settings->'spoken_languages' as spoken_languages,
+       users.settings->'vacation_mode' as vacation_mode,
+       users.settings->'vacation' as vacation,
+       users.settings->'time_zone' as time_zone
+FROM chats INNER JOIN users ON members @> ARRAY[users.id];
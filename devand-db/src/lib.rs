@@ -22,6 +22,10 @@ extern crate diesel_migrations;
 
 embed_migrations!();
 
+/// Rows inserted by a single query. Postgres limits the number of
+/// parameters of a query.
+const MAX_INSERTED_ROWS: usize = 1000;
+
 fn database_url() -> String {
     dotenv().ok();
     env::var("DATABASE_URL").expect("DATABASE_URL must be set")
@@ -38,6 +42,12 @@ pub enum Error {
     Generic(String),
 }
 
+impl From<diesel::result::Error> for Error {
+    fn from(err: diesel::result::Error) -> Self {
+        Error::Generic(format!("{:?}", err))
+    }
+}
+
 // TODO This is very expensive. Should return an iterator and should be cached
 // somewhere. Or we could use a custom database when this is needed, like
 // when searching for user affinity
@@ -85,6 +95,29 @@ pub fn load_user_by_id(id: devand_core::UserId, conn: &PgConnection) -> Option<d
     user.try_into().map_err(|e| dbg!(e)).ok()
 }
 
+/// Load users by id and lock them until the end of the current transaction,
+/// so changes which depend on many users, like sessions, are done one at a
+/// time for the same users. Users are locked in order of id, to avoid
+/// deadlocks.
+pub fn lock_users_by_id(
+    ids: &[devand_core::UserId],
+    conn: &PgConnection,
+) -> Result<Vec<devand_core::User>, Error> {
+    let ids: Vec<i32> = ids.iter().map(|x| x.0).collect();
+
+    let users: Vec<models::User> = schema::users::table
+        .filter(schema::users::dsl::id.eq_any(ids))
+        .order(schema::users::dsl::id)
+        .for_update()
+        .load(conn)
+        .map_err(|err| Error::Generic(format!("Cannot lock users: {:?}", err)))?;
+
+    Ok(users
+        .into_iter()
+        .filter_map(|u| u.try_into().ok())
+        .collect())
+}
+
 /// Save the given user
 /// If the email has changed, it set as not verified
 /// If the visible_name is set to empty, it is set to username instead
@@ -177,15 +210,24 @@ fn remove_empty(ss: Vec<String>) -> Vec<String> {
     ss.into_iter().filter(|x| !x.is_empty()).collect()
 }
 
+/// Members are stored in canonical order, see
+/// `devand_core::chat::canonical_members`
+fn canonical_members(members: &[devand_core::UserId]) -> Vec<i32> {
+    devand_core::chat::canonical_members(members)
+        .into_iter()
+        .map(|x| x.0)
+        .collect()
+}
+
+/// Find the direct chat with the given members, in any order. Group chats
+/// are found by id only.
 fn find_chat_id_by_members(
     members: &[devand_core::UserId],
     conn: &PgConnection,
 ) -> Option<devand_core::chat::ChatId> {
-    let members: Vec<_> = members.iter().map(|x| x.0).collect();
-
-    // TODO eq may not be good, what if members are in different order?
     schema::chats::table
-        .filter(schema::chats::dsl::members.eq(members))
+        .filter(schema::chats::dsl::members.eq(canonical_members(members)))
+        .filter(schema::chats::dsl::name.is_null())
         .select(schema::chats::id)
         .first(conn)
         .ok()
@@ -199,26 +241,151 @@ fn find_or_create_chat_by_members(
     if let Some(chat_id) = find_chat_id_by_members(members, conn) {
         Ok(chat_id)
     } else {
-        let members: Vec<_> = members.iter().map(|x| x.0).collect();
-        let new_chat = models::NewChat { members };
+        let new_chat = models::NewChat {
+            members: canonical_members(members),
+            name: None,
+        };
 
         diesel::insert_into(schema::chats::table)
             .values(new_chat)
             .get_result(conn)
-            .map_err(|err| {
-                dbg!(err);
-                Error::Unknown
-            })
             .map(|x: models::Chat| devand_core::chat::ChatId(x.id))
+            .or_else(|err| {
+                // Direct chats are unique, so it may have been created in
+                // the meantime
+                find_chat_id_by_members(members, conn).ok_or_else(|| {
+                    dbg!(err);
+                    Error::Unknown
+                })
+            })
     }
 }
 
+/// Find the direct chat with the given members, in any order. The chat is
+/// created if it does not exist yet.
+pub fn find_or_create_direct_chat(
+    members: &[devand_core::UserId],
+    conn: &PgConnection,
+) -> Result<devand_core::chat::Chat, Error> {
+    let id = find_or_create_chat_by_members(members, conn)?;
+
+    Ok(devand_core::chat::Chat {
+        id,
+        members: devand_core::chat::canonical_members(members),
+        name: None,
+    })
+}
+
+/// Load a chat, direct or group, by id
+pub fn load_chat_by_id(
+    chat_id: devand_core::chat::ChatId,
+    conn: &PgConnection,
+) -> Option<devand_core::chat::Chat> {
+    let chat: models::Chat = schema::chats::table
+        .filter(schema::chats::dsl::id.eq(chat_id.0))
+        .first(conn)
+        .ok()?;
+
+    chat.try_into().map_err(|e| dbg!(e)).ok()
+}
+
+/// Create a group chat. Name and members must be validated before, see
+/// `devand_core::chat::NewGroupChat`.
+pub fn add_group_chat(
+    name: String,
+    members: &[devand_core::UserId],
+    conn: &PgConnection,
+) -> Result<devand_core::chat::Chat, Error> {
+    let new_chat = models::NewChat {
+        members: canonical_members(members),
+        name: Some(name),
+    };
+
+    let chat: models::Chat = diesel::insert_into(schema::chats::table)
+        .values(new_chat)
+        .get_result(conn)
+        .map_err(|err| Error::Generic(format!("Cannot add group chat: {:?}", err)))?;
+
+    chat.try_into()
+        .map_err(|err| Error::Generic(format!("Invalid chat: {:?}", err)))
+}
+
+/// Add a member to a group chat. Permissions must be checked before.
+pub fn add_group_chat_member(
+    chat_id: devand_core::chat::ChatId,
+    member: devand_core::UserId,
+    conn: &PgConnection,
+) -> Result<devand_core::chat::Chat, Error> {
+    update_group_chat_members(chat_id, conn, |members| members.push(member))
+}
+
+/// Remove a member from a group chat. The member does not have unread
+/// messages in the chat anymore.
+pub fn remove_group_chat_member(
+    chat_id: devand_core::chat::ChatId,
+    member: devand_core::UserId,
+    conn: &PgConnection,
+) -> Result<devand_core::chat::Chat, Error> {
+    conn.transaction(|| {
+        let chat =
+            update_group_chat_members(chat_id, conn, |members| members.retain(|&m| m != member))?;
+
+        let chat_messages = schema::messages::table
+            .filter(schema::messages::dsl::chat_id.eq(chat_id.0))
+            .select(schema::messages::dsl::id);
+
+        diesel::delete(
+            schema::unread_messages::table
+                .filter(schema::unread_messages::user_id.eq(member.0))
+                .filter(schema::unread_messages::message_id.eq_any(chat_messages)),
+        )
+        .execute(conn)
+        .map_err(|err| Error::Generic(format!("Cannot remove unread messages: {:?}", err)))?;
+
+        Ok(chat)
+    })
+}
+
+/// Change members of a group chat, atomically. Direct chats are keyed by
+/// their members, so they cannot be changed.
+fn update_group_chat_members<F>(
+    chat_id: devand_core::chat::ChatId,
+    conn: &PgConnection,
+    f: F,
+) -> Result<devand_core::chat::Chat, Error>
+where
+    F: FnOnce(&mut Vec<devand_core::UserId>),
+{
+    conn.transaction(|| {
+        let chat: models::Chat = schema::chats::table
+            .filter(schema::chats::dsl::id.eq(chat_id.0))
+            .filter(schema::chats::dsl::name.is_not_null())
+            .for_update()
+            .first(conn)
+            .map_err(|err| Error::Generic(format!("Cannot load group chat: {:?}", err)))?;
+
+        let mut members: Vec<_> = chat.members.into_iter().map(devand_core::UserId).collect();
+        f(&mut members);
+
+        let chat: models::Chat =
+            diesel::update(schema::chats::table.filter(schema::chats::dsl::id.eq(chat_id.0)))
+                .set(schema::chats::dsl::members.eq(canonical_members(&members)))
+                .get_result(conn)
+                .map_err(|err| Error::Generic(format!("Cannot update group chat: {:?}", err)))?;
+
+        chat.try_into()
+            .map_err(|err| Error::Generic(format!("Invalid chat: {:?}", err)))
+    })
+}
+
 // TODO This function is huge, needs refactoring
 fn load_user_chat_by_id(
     user: devand_core::UserId,
     id: uuid::Uuid,
     conn: &PgConnection,
 ) -> Result<devand_core::UserChat, Error> {
+    let now = Utc::now();
+
     let members: Vec<devand_core::PublicUserProfile> = schema_view::chat_members::table
         .filter(schema_view::chat_members::chat_id.eq(id))
         .load(conn)
@@ -233,6 +400,9 @@ fn load_user_chat_by_id(
                 languages,
                 spoken_languages,
                 projects,
+                vacation_mode,
+                vacation,
+                time_zone,
                 ..
             } = chat_member;
 
@@ -247,6 +417,17 @@ fn load_user_chat_by_id(
                 .and_then(|x| serde_json::from_value(x).ok())
                 .unwrap_or_default();
 
+            // Only current vacations are public, as in PublicUserProfile
+            let vacation_mode = vacation_mode
+                .and_then(|x| serde_json::from_value(x).ok())
+                .unwrap_or(false);
+            let time_zone: devand_core::UserTimeZone = time_zone
+                .and_then(|x| serde_json::from_value(x).ok())
+                .unwrap_or_default();
+            let vacation = vacation
+                .and_then(|x| serde_json::from_value::<devand_core::Vacation>(x).ok())
+                .filter(|vacation| vacation_mode && vacation.contains_time(now, *time_zone));
+
             let profile = devand_core::PublicUserProfile {
                 id: devand_core::UserId(user_id),
                 username,
@@ -254,7 +435,9 @@ fn load_user_chat_by_id(
                 languages,
                 bio,
                 spoken_languages,
+                topics: devand_core::Topics::default(),
                 projects,
+                vacation,
             };
 
             Some(profile)
@@ -274,10 +457,17 @@ fn load_user_chat_by_id(
 
     let unread_messages = unread_messages as usize;
 
+    let name: Option<String> = schema::chats::table
+        .filter(schema::chats::dsl::id.eq(id))
+        .select(schema::chats::dsl::name)
+        .first(conn)
+        .map_err(|e| Error::Generic(format!("Error loading chat from database: {:?}", e)))?;
+
     let chat = devand_core::UserChat {
         chat: devand_core::chat::Chat {
             id: devand_core::chat::ChatId(id),
             members: members_ids,
+            name,
         },
         unread_messages,
         members,
@@ -319,6 +509,92 @@ pub fn load_chat_history_by_members(
         .unwrap_or_default()
 }
 
+/// Load a page of the chat history, in chronological order. At most `limit`
+/// messages are loaded, see `devand_core::chat::history_page_size`. When the
+/// cursor refers to a message not in the chat, the page is empty.
+pub fn load_chat_history_page_by_id(
+    chat_id: devand_core::chat::ChatId,
+    cursor: devand_core::chat::HistoryCursor,
+    limit: usize,
+    conn: &PgConnection,
+) -> Vec<devand_core::chat::ChatMessage> {
+    use devand_core::chat::HistoryCursor;
+    use schema::messages::dsl;
+
+    let limit = limit.min(devand_core::chat::MAX_HISTORY_PAGE_SIZE) as i64;
+    let chat = dsl::messages
+        .filter(dsl::chat_id.eq(chat_id.0))
+        .into_boxed();
+
+    // Messages created at the same time are sorted by id
+    let position = |message_id: uuid::Uuid| -> Option<(NaiveDateTime, uuid::Uuid)> {
+        dsl::messages
+            .filter(dsl::chat_id.eq(chat_id.0))
+            .filter(dsl::id.eq(message_id))
+            .select((dsl::created_at, dsl::id))
+            .first(conn)
+            .ok()
+    };
+
+    let messages: QueryResult<Vec<models::ChatMessage>> = match cursor {
+        HistoryCursor::Latest => chat
+            .order((dsl::created_at.desc(), dsl::id.desc()))
+            .limit(limit)
+            .load(conn)
+            .map(reversed),
+        HistoryCursor::Before(message_id) => match position(message_id) {
+            Some((created_at, id)) => chat
+                .filter(
+                    dsl::created_at
+                        .lt(created_at)
+                        .or(dsl::created_at.eq(created_at).and(dsl::id.lt(id))),
+                )
+                .order((dsl::created_at.desc(), dsl::id.desc()))
+                .limit(limit)
+                .load(conn)
+                .map(reversed),
+            None => Ok(Vec::new()),
+        },
+        HistoryCursor::After(message_id) => match position(message_id) {
+            Some((created_at, id)) => chat
+                .filter(
+                    dsl::created_at
+                        .gt(created_at)
+                        .or(dsl::created_at.eq(created_at).and(dsl::id.gt(id))),
+                )
+                .order((dsl::created_at.asc(), dsl::id.asc()))
+                .limit(limit)
+                .load(conn),
+            None => Ok(Vec::new()),
+        },
+    };
+
+    messages
+        .map(|v| v.into_iter().map(|x| x.into()).collect())
+        .unwrap_or_else(|err| {
+            log::error!("Cannot load chat history: {:?}", err);
+            Vec::new()
+        })
+}
+
+/// Load a page of the chat history, given chat members. See
+/// `load_chat_history_page_by_id`.
+pub fn load_chat_history_page_by_members(
+    members: &[devand_core::UserId],
+    cursor: devand_core::chat::HistoryCursor,
+    limit: usize,
+    conn: &PgConnection,
+) -> Vec<devand_core::chat::ChatMessage> {
+    find_chat_id_by_members(members, conn)
+        .map(|chat_id| load_chat_history_page_by_id(chat_id, cursor, limit, conn))
+        .unwrap_or_default()
+}
+
+fn reversed<T>(mut v: Vec<T>) -> Vec<T> {
+    v.reverse();
+    v
+}
+
 pub fn mark_messages_as_read_by(
     user_id: devand_core::UserId,
     messages: &[devand_core::chat::ChatMessage],
@@ -413,6 +689,329 @@ pub fn add_chat_message_by_members(
     }
 }
 
+/// Load a message and the chat it belongs to
+pub fn load_chat_message_by_id(
+    id: uuid::Uuid,
+    conn: &PgConnection,
+) -> Option<(devand_core::chat::Chat, devand_core::chat::ChatMessage)> {
+    let message: models::ChatMessage = schema::messages::table
+        .filter(schema::messages::dsl::id.eq(id))
+        .first(conn)
+        .ok()?;
+
+    let chat = load_chat_by_id(devand_core::chat::ChatId(message.chat_id), conn)?;
+
+    Some((chat, message.into()))
+}
+
+/// Change the text of a message, keeping the previous one in its edit
+/// history. Permissions must be checked before.
+pub fn edit_chat_message(
+    id: uuid::Uuid,
+    txt: String,
+    conn: &PgConnection,
+) -> Result<devand_core::chat::ChatMessage, Error> {
+    use schema::messages::dsl;
+
+    conn.transaction(|| {
+        let message: models::ChatMessage = dsl::messages
+            .filter(dsl::id.eq(id))
+            .filter(dsl::deleted_at.is_null())
+            .for_update()
+            .first(conn)
+            .map_err(|err| Error::Generic(format!("Cannot load message: {:?}", err)))?;
+
+        let now = Utc::now().naive_utc();
+
+        let previous = models::MessageEdit {
+            message_id: id,
+            edited_at: now,
+            txt: message.txt,
+        };
+
+        diesel::insert_into(schema::message_edits::table)
+            .values(previous)
+            .execute(conn)
+            .map_err(|err| Error::Generic(format!("Cannot save message edit: {:?}", err)))?;
+
+        let message: models::ChatMessage = diesel::update(dsl::messages.filter(dsl::id.eq(id)))
+            .set((dsl::txt.eq(txt), dsl::edited_at.eq(now)))
+            .get_result(conn)
+            .map_err(|err| Error::Generic(format!("Cannot edit message: {:?}", err)))?;
+
+        Ok(message.into())
+    })
+}
+
+/// Delete a message, leaving it in the history without text. Its edit
+/// history is removed and nobody has it unread anymore. Permissions must be
+/// checked before.
+pub fn delete_chat_message(
+    id: uuid::Uuid,
+    conn: &PgConnection,
+) -> Result<devand_core::chat::ChatMessage, Error> {
+    use schema::messages::dsl;
+
+    conn.transaction(|| {
+        diesel::delete(
+            schema::message_edits::table.filter(schema::message_edits::message_id.eq(id)),
+        )
+        .execute(conn)
+        .map_err(|err| Error::Generic(format!("Cannot remove message edits: {:?}", err)))?;
+
+        diesel::delete(
+            schema::unread_messages::table.filter(schema::unread_messages::message_id.eq(id)),
+        )
+        .execute(conn)
+        .map_err(|err| Error::Generic(format!("Cannot remove unread messages: {:?}", err)))?;
+
+        let message: models::ChatMessage = diesel::update(
+            dsl::messages
+                .filter(dsl::id.eq(id))
+                .filter(dsl::deleted_at.is_null()),
+        )
+        .set((dsl::txt.eq(""), dsl::deleted_at.eq(Utc::now().naive_utc())))
+        .get_result(conn)
+        .map_err(|err| Error::Generic(format!("Cannot delete message: {:?}", err)))?;
+
+        Ok(message.into())
+    })
+}
+
+/// Previous texts of a message, oldest first
+pub fn load_chat_message_edits(
+    id: uuid::Uuid,
+    conn: &PgConnection,
+) -> Vec<devand_core::chat::ChatMessageEdit> {
+    schema::message_edits::table
+        .filter(schema::message_edits::message_id.eq(id))
+        .order(schema::message_edits::edited_at.asc())
+        .load(conn)
+        .map(|v: Vec<models::MessageEdit>| v.into_iter().map(|x| x.into()).collect())
+        .unwrap_or_default()
+}
+
+/// Load messages of the chat edited or deleted after the given time, in the
+/// order they were changed. Polling clients use it to update messages they
+/// have already loaded, and continue from the last change they got (see
+/// `next_changes_cursor`), so no change is skipped when there are more than
+/// a page.
+pub fn load_chat_changes_by_id(
+    chat_id: devand_core::chat::ChatId,
+    after: DateTime<Utc>,
+    conn: &PgConnection,
+) -> Vec<devand_core::chat::ChatMessage> {
+    use schema::messages::dsl;
+
+    let after = after.naive_utc();
+
+    dsl::messages
+        .filter(dsl::chat_id.eq(chat_id.0))
+        .filter(schema_view::greatest(dsl::edited_at, dsl::deleted_at).gt(after))
+        .order((
+            schema_view::greatest(dsl::edited_at, dsl::deleted_at).asc(),
+            dsl::id.asc(),
+        ))
+        .limit(devand_core::chat::MAX_HISTORY_PAGE_SIZE as i64)
+        .load(conn)
+        .map(|v: Vec<models::ChatMessage>| v.into_iter().map(|x| x.into()).collect())
+        .unwrap_or_else(|err| {
+            log::error!("Cannot load chat changes: {:?}", err);
+            Vec::new()
+        })
+}
+
+/// Load changed messages, given chat members. See `load_chat_changes_by_id`.
+pub fn load_chat_changes_by_members(
+    members: &[devand_core::UserId],
+    after: DateTime<Utc>,
+    conn: &PgConnection,
+) -> Vec<devand_core::chat::ChatMessage> {
+    find_chat_id_by_members(members, conn)
+        .map(|chat_id| load_chat_changes_by_id(chat_id, after, conn))
+        .unwrap_or_default()
+}
+
+fn into_sessions(
+    sessions: Vec<models::PairingSession>,
+) -> Vec<devand_core::session::PairingSession> {
+    sessions
+        .into_iter()
+        .filter_map(|x| x.try_into().map_err(|e| log::error!("Error: {:?}", e)).ok())
+        .collect()
+}
+
+/// Load all sessions the given users are members of, in any status
+pub fn load_sessions_by_members(
+    members: &[devand_core::UserId],
+    conn: &PgConnection,
+) -> Vec<devand_core::session::PairingSession> {
+    let members: Vec<_> = members.iter().map(|x| x.0).collect();
+
+    schema::pairing_sessions::table
+        .filter(
+            schema::pairing_sessions::proposer
+                .eq_any(members.clone())
+                .or(schema::pairing_sessions::invitees.overlaps_with(members)),
+        )
+        .order(schema::pairing_sessions::start_at)
+        .load(conn)
+        .map(into_sessions)
+        .unwrap_or_default()
+}
+
+/// Load all sessions the given user is member of, in any status
+pub fn load_sessions_by_member(
+    member: devand_core::UserId,
+    conn: &PgConnection,
+) -> Vec<devand_core::session::PairingSession> {
+    load_sessions_by_members(&[member], conn)
+}
+
+/// Load single session by id (unique)
+pub fn load_session_by_id(
+    id: devand_core::session::SessionId,
+    conn: &PgConnection,
+) -> Option<devand_core::session::PairingSession> {
+    let session: models::PairingSession = schema::pairing_sessions::table
+        .filter(schema::pairing_sessions::dsl::id.eq(id.0))
+        .first(conn)
+        .ok()?;
+
+    session.try_into().map_err(|e| dbg!(e)).ok()
+}
+
+/// Add a new session, proposed by `proposer`. Conflicts must be checked
+/// before.
+pub fn add_session(
+    proposer: devand_core::UserId,
+    proposal: devand_core::session::SessionProposal,
+    conn: &PgConnection,
+) -> Result<devand_core::session::PairingSession, Error> {
+    let devand_core::session::SessionProposal {
+        invitees,
+        start,
+        end,
+        language,
+    } = proposal;
+
+    let new_session = models::NewPairingSession {
+        proposer: proposer.0,
+        invitees: invitees.into_iter().map(|x| x.0).collect(),
+        start_at: start.naive_utc(),
+        end_at: end.naive_utc(),
+        language: language.and_then(|x| serde_json::to_value(x).ok()),
+        status: devand_core::session::SessionStatus::Proposed.to_string(),
+        created_at: Utc::now().naive_utc(),
+    };
+
+    let session: models::PairingSession = diesel::insert_into(schema::pairing_sessions::table)
+        .values(new_session)
+        .get_result(conn)
+        .map_err(|err| Error::Generic(format!("Cannot add session: {:?}", err)))?;
+
+    session
+        .try_into()
+        .map_err(|err| Error::Generic(format!("Invalid session: {:?}", err)))
+}
+
+/// Change the status of a session. Permissions must be checked before.
+pub fn set_session_status(
+    id: devand_core::session::SessionId,
+    status: devand_core::session::SessionStatus,
+    conn: &PgConnection,
+) -> Result<devand_core::session::PairingSession, Error> {
+    let session: models::PairingSession = diesel::update(
+        schema::pairing_sessions::table.filter(schema::pairing_sessions::dsl::id.eq(id.0)),
+    )
+    .set(schema::pairing_sessions::dsl::status.eq(status.to_string()))
+    .get_result(conn)
+    .map_err(|err| Error::Generic(format!("Cannot update session: {:?}", err)))?;
+
+    session
+        .try_into()
+        .map_err(|err| Error::Generic(format!("Invalid session: {:?}", err)))
+}
+
+/// Return true if pairs of the week have already been saved, even if there
+/// are no pairs
+pub fn has_weekly_pairs(week: DateTime<Utc>, conn: &PgConnection) -> bool {
+    use schema::weekly_pairings::dsl;
+
+    dsl::weekly_pairings
+        .filter(dsl::week.eq(week.naive_utc()))
+        .count()
+        .get_result::<i64>(conn)
+        .map(|count| count > 0)
+        .unwrap_or(false)
+}
+
+/// Save pairs of the week. Pairs are saved once: if they have already been
+/// saved, e.g. by another server, they are kept.
+pub fn save_weekly_pairs(
+    week: DateTime<Utc>,
+    pairs: &devand_core::pairing::WeeklyPairs,
+    conn: &PgConnection,
+) -> Result<(), Error> {
+    let week = week.naive_utc();
+
+    let rows = pairs
+        .0
+        .iter()
+        .map(|pair| {
+            let (a, b) = pair.users;
+            Ok(models::WeeklyPair {
+                week,
+                user_a: a.0,
+                user_b: b.0,
+                pair: serde_json::to_value(pair)?,
+            })
+        })
+        .collect::<Result<Vec<_>, serde_json::Error>>()
+        .map_err(|err| Error::Generic(format!("Cannot serialize weekly pair: {:?}", err)))?;
+
+    conn.transaction(|| {
+        let pairing = models::WeeklyPairing {
+            week,
+            created_at: Utc::now().naive_utc(),
+        };
+
+        let inserted = diesel::insert_into(schema::weekly_pairings::table)
+            .values(pairing)
+            .on_conflict_do_nothing()
+            .execute(conn)?;
+
+        if inserted == 0 {
+            return Ok(());
+        }
+
+        for rows in rows.chunks(MAX_INSERTED_ROWS) {
+            diesel::insert_into(schema::weekly_pairs::table)
+                .values(rows)
+                .execute(conn)?;
+        }
+
+        Ok(())
+    })
+}
+
+/// The pair of the week of the user, if any
+pub fn load_weekly_pair(
+    week: DateTime<Utc>,
+    user: devand_core::UserId,
+    conn: &PgConnection,
+) -> Option<devand_core::pairing::WeeklyPair> {
+    use schema::weekly_pairs::dsl;
+
+    let pair: models::WeeklyPair = dsl::weekly_pairs
+        .filter(dsl::week.eq(week.naive_utc()))
+        .filter(dsl::user_a.eq(user.0).or(dsl::user_b.eq(user.0)))
+        .first(conn)
+        .ok()?;
+
+    serde_json::from_value(pair.pair).ok()
+}
+
 /// Return true if the given user has a verified email
 pub fn has_verified_email(
     user_id: devand_core::UserId,
@@ -465,7 +1064,17 @@ fn clear_table(table: &str, conn: &PgConnection) -> Result<(), diesel::result::E
 
 /// Clear all tables and their auto-increment counters
 fn clear_all(conn: &PgConnection) -> Result<(), diesel::result::Error> {
-    let tables = vec!["auth", "chats", "messages", "unread_messages", "users"];
+    let tables = vec![
+        "auth",
+        "chats",
+        "message_edits",
+        "messages",
+        "pairing_sessions",
+        "unread_messages",
+        "users",
+        "weekly_pairings",
+        "weekly_pairs",
+    ];
 
     for table in tables {
         clear_table(table, conn)?;
@@ -657,4 +1266,219 @@ mod tests {
 
         assert_eq!(user.visible_name, user.username);
     }
+
+    #[test]
+    #[ignore]
+    #[serial]
+    fn load_chat_history_pages() {
+        use devand_core::chat::HistoryCursor;
+
+        let (conn, user) = fresh_db_with_fake_user();
+        let members = [user.id, devand_core::UserId(user.id.0 + 1)];
+
+        let messages: Vec<_> = (0..5)
+            .map(|i| add_chat_message_by_members(&members, user.id, i.to_string(), &conn))
+            .map(Option::unwrap)
+            .collect();
+
+        let txts = |page: Vec<devand_core::chat::ChatMessage>| -> Vec<String> {
+            page.into_iter().map(|m| m.txt).collect()
+        };
+
+        let latest = load_chat_history_page_by_members(&members, HistoryCursor::Latest, 2, &conn);
+        assert_eq!(txts(latest), vec!["3", "4"]);
+
+        let older = HistoryCursor::Before(messages[3].id);
+        let older = load_chat_history_page_by_members(&members, older, 2, &conn);
+        assert_eq!(txts(older), vec!["1", "2"]);
+
+        let newer = HistoryCursor::After(messages[1].id);
+        let newer = load_chat_history_page_by_members(&members, newer, 10, &conn);
+        assert_eq!(txts(newer), vec!["2", "3", "4"]);
+
+        let unknown = HistoryCursor::After(uuid::Uuid::nil());
+        assert!(load_chat_history_page_by_members(&members, unknown, 10, &conn).is_empty());
+    }
+
+    #[test]
+    #[ignore]
+    #[serial]
+    fn direct_chats_ignore_members_order() {
+        let (conn, user) = fresh_db_with_fake_user();
+        let other = devand_core::UserId(user.id.0 + 1);
+
+        add_chat_message_by_members(&[user.id, other], user.id, "a".into(), &conn).unwrap();
+        add_chat_message_by_members(&[other, user.id], other, "b".into(), &conn).unwrap();
+
+        let history = load_chat_history_by_members(&[other, user.id], &conn);
+        assert_eq!(history.len(), 2);
+        assert_eq!(load_chats_by_member(user.id, &conn).0.len(), 1);
+    }
+
+    #[test]
+    #[ignore]
+    #[serial]
+    fn chat_members_on_vacation() {
+        let (conn, user) = fresh_db_with_fake_user();
+        let join_data = auth::JoinData {
+            username: "bar".to_string(),
+            email: "bar@example.com".to_string(),
+            password: "ZXokdUB6dWplaW5nYXU3am".to_string(),
+        };
+        auth::join(join_data, &conn).unwrap();
+        let mut other = load_user_by_username("bar", &conn).unwrap();
+
+        add_chat_message_by_members(&[user.id, other.id], user.id, "a".into(), &conn).unwrap();
+        let chats = load_chats_by_member(user.id, &conn);
+        assert_eq!(chats.0[0].members[0].vacation, None);
+
+        // Vacation mode without dates lasts until it is disabled
+        other.settings.vacation_mode = true;
+        save_user(other, &conn).unwrap();
+        let chats = load_chats_by_member(user.id, &conn);
+        assert_eq!(
+            chats.0[0].members[0].vacation,
+            Some(devand_core::Vacation::default())
+        );
+    }
+
+    #[test]
+    #[ignore]
+    #[serial]
+    fn add_and_leave_group_chat() {
+        let (conn, user) = fresh_db_with_fake_user();
+        let a = devand_core::UserId(user.id.0 + 1);
+        let b = devand_core::UserId(user.id.0 + 2);
+        let c = devand_core::UserId(user.id.0 + 3);
+
+        let chat = add_group_chat("Group".into(), &[b, user.id, a], &conn).unwrap();
+        assert_eq!(chat.members, vec![user.id, a, b]);
+        assert!(chat.is_group());
+
+        // Group chats are not found as direct chats
+        assert!(find_chat_id_by_members(&[user.id, a, b], &conn).is_none());
+
+        add_chat_message_by_id(chat.id, user.id, "hi".into(), &conn).unwrap();
+
+        let chat = add_group_chat_member(chat.id, c, &conn).unwrap();
+        assert_eq!(chat.members, vec![user.id, a, b, c]);
+
+        let chat = remove_group_chat_member(chat.id, a, &conn).unwrap();
+        assert_eq!(chat.members, vec![user.id, b, c]);
+        assert_eq!(load_chats_by_member(a, &conn).total_unread_messages(), 0);
+        assert_eq!(load_chats_by_member(b, &conn).total_unread_messages(), 1);
+    }
+
+    #[test]
+    #[ignore]
+    #[serial]
+    fn edit_and_delete_chat_message() {
+        let (conn, user) = fresh_db_with_fake_user();
+        let other = devand_core::UserId(user.id.0 + 1);
+        let members = [user.id, other];
+        let before = Utc::now();
+
+        let message = add_chat_message_by_members(&members, user.id, "a".into(), &conn).unwrap();
+
+        let edited = edit_chat_message(message.id, "b".into(), &conn).unwrap();
+        assert_eq!(edited.txt, "b");
+        assert!(edited.edited_at.is_some());
+
+        let edits = load_chat_message_edits(message.id, &conn);
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].txt, "a");
+
+        let changes = load_chat_changes_by_members(&members, before, &conn);
+        assert_eq!(changes.len(), 1);
+        let cursor = devand_core::chat::next_changes_cursor(before, &changes);
+        assert!(load_chat_changes_by_members(&members, cursor, &conn).is_empty());
+
+        assert_eq!(
+            load_chats_by_member(other, &conn).total_unread_messages(),
+            1
+        );
+
+        let deleted = delete_chat_message(message.id, &conn).unwrap();
+        assert!(deleted.is_deleted());
+        assert!(deleted.txt.is_empty());
+        assert!(load_chat_message_edits(message.id, &conn).is_empty());
+        assert_eq!(
+            load_chats_by_member(other, &conn).total_unread_messages(),
+            0
+        );
+
+        let changes = load_chat_changes_by_members(&members, cursor, &conn);
+        assert_eq!(changes.len(), 1);
+        assert!(changes[0].is_deleted());
+
+        // Deleted messages cannot be changed anymore
+        assert!(edit_chat_message(message.id, "c".into(), &conn).is_err());
+        assert!(delete_chat_message(message.id, &conn).is_err());
+
+        let (chat, message) = load_chat_message_by_id(message.id, &conn).unwrap();
+        assert_eq!(chat.members, members.to_vec());
+        assert!(message.is_deleted());
+    }
+
+    #[test]
+    #[ignore]
+    #[serial]
+    fn add_and_accept_session() {
+        use devand_core::session::{SessionProposal, SessionStatus};
+
+        let (conn, user) = fresh_db_with_fake_user();
+        let invitee = devand_core::UserId(user.id.0 + 1);
+        let start = Utc::now() + chrono::Duration::days(1);
+
+        let proposal = SessionProposal {
+            invitees: vec![invitee],
+            start,
+            end: start + chrono::Duration::hours(1),
+            language: Some(devand_core::Language::Rust),
+        };
+
+        let session = add_session(user.id, proposal, &conn).unwrap();
+        assert_eq!(session.status, SessionStatus::Proposed);
+        assert_eq!(session.language, Some(devand_core::Language::Rust));
+
+        let sessions = load_sessions_by_member(invitee, &conn);
+        assert_eq!(sessions.len(), 1);
+        assert!(load_sessions_by_member(devand_core::UserId(0), &conn).is_empty());
+
+        set_session_status(session.id, SessionStatus::Accepted, &conn).unwrap();
+        let session = load_session_by_id(session.id, &conn).unwrap();
+        assert_eq!(session.status, SessionStatus::Accepted);
+    }
+
+    #[test]
+    #[ignore]
+    #[serial]
+    fn save_weekly_pairs_once() {
+        use devand_core::pairing::{WeeklyPair, WeeklyPairs};
+        use devand_core::{Affinity, UserId};
+
+        let conn = fresh_db();
+        let week = Utc.ymd(2020, 10, 5).and_hms(0, 0, 0);
+        let pair = |a, b| WeeklyPair {
+            users: (UserId(a), UserId(b)),
+            affinity: Affinity::FULL,
+            overlap_minutes: 60,
+        };
+
+        assert!(!has_weekly_pairs(week, &conn));
+        save_weekly_pairs(week, &WeeklyPairs(vec![pair(1, 2)]), &conn).unwrap();
+        assert!(has_weekly_pairs(week, &conn));
+
+        assert_eq!(load_weekly_pair(week, UserId(2), &conn), Some(pair(1, 2)));
+        assert_eq!(load_weekly_pair(week, UserId(3), &conn), None);
+
+        // Pairs already saved are kept
+        save_weekly_pairs(week, &WeeklyPairs(vec![pair(2, 3)]), &conn).unwrap();
+        assert_eq!(load_weekly_pair(week, UserId(1), &conn), Some(pair(1, 2)));
+        assert_eq!(load_weekly_pair(week, UserId(3), &conn), None);
+
+        let next_week = week + chrono::Duration::days(7);
+        assert!(!has_weekly_pairs(next_week, &conn));
+        assert_eq!(load_weekly_pair(next_week, UserId(1), &conn), None);
+    }
 }
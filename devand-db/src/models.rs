@@ -1,4 +1,7 @@
-use super::schema::{auth, chats, messages, unread_messages, users};
+use super::schema::{
+    auth, chats, message_edits, messages, pairing_sessions, unread_messages, users,
+    weekly_pairings, weekly_pairs,
+};
 use chrono::{DateTime, Utc};
 use std::convert::TryInto;
 
@@ -67,6 +70,8 @@ pub struct ChatMessage {
     pub created_at: chrono::NaiveDateTime,
     pub txt: String,
     pub author: i32,
+    pub edited_at: Option<chrono::NaiveDateTime>,
+    pub deleted_at: Option<chrono::NaiveDateTime>,
 }
 
 #[derive(Insertable)]
@@ -82,6 +87,7 @@ pub struct NewChatMessage {
 pub struct Chat {
     pub id: uuid::Uuid,
     pub members: Vec<i32>,
+    pub name: Option<String>,
 }
 
 impl TryInto<devand_core::chat::Chat> for Chat {
@@ -93,6 +99,7 @@ impl TryInto<devand_core::chat::Chat> for Chat {
         let chat = devand_core::chat::Chat {
             id: devand_core::chat::ChatId(self.id),
             members,
+            name: self.name,
         };
 
         Ok(chat)
@@ -103,15 +110,35 @@ impl TryInto<devand_core::chat::Chat> for Chat {
 #[table_name = "chats"]
 pub struct NewChat {
     pub members: Vec<i32>,
+    pub name: Option<String>,
 }
 
-impl Into<devand_core::chat::ChatMessage> for ChatMessage {
-    fn into(self) -> devand_core::chat::ChatMessage {
-        devand_core::chat::ChatMessage {
-            id: self.id,
-            created_at: DateTime::from_utc(self.created_at, Utc),
-            txt: self.txt,
-            author: devand_core::UserId(self.author),
+impl From<ChatMessage> for devand_core::chat::ChatMessage {
+    fn from(message: ChatMessage) -> Self {
+        Self {
+            id: message.id,
+            created_at: DateTime::from_utc(message.created_at, Utc),
+            txt: message.txt,
+            author: devand_core::UserId(message.author),
+            edited_at: message.edited_at.map(|t| DateTime::from_utc(t, Utc)),
+            deleted_at: message.deleted_at.map(|t| DateTime::from_utc(t, Utc)),
+        }
+    }
+}
+
+#[derive(Insertable, Queryable)]
+#[table_name = "message_edits"]
+pub struct MessageEdit {
+    pub message_id: uuid::Uuid,
+    pub edited_at: chrono::NaiveDateTime,
+    pub txt: String,
+}
+
+impl From<MessageEdit> for devand_core::chat::ChatMessageEdit {
+    fn from(edit: MessageEdit) -> Self {
+        Self {
+            edited_at: DateTime::from_utc(edit.edited_at, Utc),
+            txt: edit.txt,
         }
     }
 }
@@ -133,9 +160,79 @@ pub struct ChatMember {
     pub languages: serde_json::Value,
     pub spoken_languages: Option<serde_json::Value>,
     pub projects: Vec<String>,
+    pub vacation_mode: Option<serde_json::Value>,
+    pub vacation: Option<serde_json::Value>,
+    pub time_zone: Option<serde_json::Value>,
+}
+
+#[derive(Queryable)]
+pub struct PairingSession {
+    pub id: uuid::Uuid,
+    pub proposer: i32,
+    pub invitees: Vec<i32>,
+    pub start_at: chrono::NaiveDateTime,
+    pub end_at: chrono::NaiveDateTime,
+    pub language: Option<serde_json::Value>,
+    pub status: String,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "pairing_sessions"]
+pub struct NewPairingSession {
+    pub proposer: i32,
+    pub invitees: Vec<i32>,
+    pub start_at: chrono::NaiveDateTime,
+    pub end_at: chrono::NaiveDateTime,
+    pub language: Option<serde_json::Value>,
+    pub status: String,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl TryInto<devand_core::session::PairingSession> for PairingSession {
+    type Error = Error;
+
+    fn try_into(self) -> Result<devand_core::session::PairingSession, Error> {
+        let status = self
+            .status
+            .parse()
+            .map_err(|_| Error::CannotDeserializeSessionStatus(self.status.clone()))?;
+
+        // An unknown language is not an error, the session is still valid
+        let language = self.language.and_then(|x| serde_json::from_value(x).ok());
+
+        let session = devand_core::session::PairingSession {
+            id: devand_core::session::SessionId(self.id),
+            proposer: devand_core::UserId(self.proposer),
+            invitees: self.invitees.into_iter().map(devand_core::UserId).collect(),
+            start: DateTime::from_utc(self.start_at, Utc),
+            end: DateTime::from_utc(self.end_at, Utc),
+            language,
+            status,
+        };
+
+        Ok(session)
+    }
+}
+
+#[derive(Insertable)]
+#[table_name = "weekly_pairings"]
+pub struct WeeklyPairing {
+    pub week: chrono::NaiveDateTime,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, Queryable)]
+#[table_name = "weekly_pairs"]
+pub struct WeeklyPair {
+    pub week: chrono::NaiveDateTime,
+    pub user_a: i32,
+    pub user_b: i32,
+    pub pair: serde_json::Value,
 }
 
 #[derive(Debug)]
 pub enum Error {
     CannotDeserializeUserSettings(String),
+    CannotDeserializeSessionStatus(String),
 }
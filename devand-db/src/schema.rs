@@ -9,6 +9,15 @@ table! {
     chats (id) {
         id -> Uuid,
         members -> Array<Int4>,
+        name -> Nullable<Varchar>,
+    }
+}
+
+table! {
+    message_edits (message_id, edited_at) {
+        message_id -> Uuid,
+        edited_at -> Timestamp,
+        txt -> Varchar,
     }
 }
 
@@ -19,6 +28,21 @@ table! {
         created_at -> Timestamp,
         txt -> Varchar,
         author -> Int4,
+        edited_at -> Nullable<Timestamp>,
+        deleted_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    pairing_sessions (id) {
+        id -> Uuid,
+        proposer -> Int4,
+        invitees -> Array<Int4>,
+        start_at -> Timestamp,
+        end_at -> Timestamp,
+        language -> Nullable<Jsonb>,
+        status -> Varchar,
+        created_at -> Timestamp,
     }
 }
 
@@ -43,4 +67,30 @@ table! {
     }
 }
 
-allow_tables_to_appear_in_same_query!(auth, chats, messages, unread_messages, users,);
+table! {
+    weekly_pairings (week) {
+        week -> Timestamp,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    weekly_pairs (week, user_a) {
+        week -> Timestamp,
+        user_a -> Int4,
+        user_b -> Int4,
+        pair -> Jsonb,
+    }
+}
+
+allow_tables_to_appear_in_same_query!(
+    auth,
+    chats,
+    message_edits,
+    messages,
+    pairing_sessions,
+    unread_messages,
+    users,
+    weekly_pairings,
+    weekly_pairs,
+);
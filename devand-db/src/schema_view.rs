@@ -18,6 +18,9 @@ table! {
         languages -> Jsonb,
         spoken_languages -> Nullable<Jsonb>,
         projects -> Array<Text>,
+        vacation_mode -> Nullable<Jsonb>,
+        vacation -> Nullable<Jsonb>,
+        time_zone -> Nullable<Jsonb>,
     }
 }
 
@@ -28,3 +31,11 @@ table! {
         user_id -> Int4,
     }
 }
+
+sql_function! {
+    /// Largest non null argument
+    fn greatest(
+        a: diesel::sql_types::Nullable<diesel::sql_types::Timestamp>,
+        b: diesel::sql_types::Nullable<diesel::sql_types::Timestamp>
+    ) -> diesel::sql_types::Nullable<diesel::sql_types::Timestamp>;
+}
@@ -6,13 +6,38 @@ pub enum Text<'a> {
     Schedule,
     Security,
     Messages,
+    Topics,
+    Sessions,
     AffinitiesTableDescription,
     NoMatchingUsersFound,
     ExtendYourLanguageSelection,
     YourCurrentWeeklySchedule,
     SetYourAvailability,
     YouHaventScheduled,
+    DateExceptions,
+    ImportAvailability,
+    ImportAvailabilityDescription,
+    InvalidCalendarFile,
+    ImportPreview,
+    NoScheduleChanges,
+    ApplyImport,
+    Cancel,
     ChatWith(&'a str),
+    LoadOlderMessages,
+    EditMessage,
+    DeleteMessage,
+    SaveMessage,
+    MessageEdited,
+    MessageDeleted,
+    ConfirmDeleteMessage,
+    Chats,
+    NewGroupChat,
+    GroupChatName,
+    CreateGroupChat,
+    GroupChatMembers,
+    AddGroupChatMember,
+    LeaveGroupChat,
+    YouLeftGroupChat,
     UnverifiedEmailAlert,
     UserWithUnverifiedEmail(&'a str),
     NoChatsYet,
@@ -37,17 +62,77 @@ pub enum Text<'a> {
     OldPasswordWrong,
     OldPasswordOk,
     VacationModeEnabled,
+    VacationStart,
+    VacationEnd,
+    VacationEndsBeforeStart,
+    UserOnVacation(&'a str),
+    UserOnVacationUntil(&'a str, &'a str),
     AddressUnverified,
     CheckEmailForLink,
     Verify,
     UsernameCannotBeChanged,
     RemainingCharacters(usize, usize),
     VacationMode,
+    WeeklyPairing,
+    CalendarFeed,
+    CalendarFeedDescription,
+    CalendarFeedDisabled,
+    EnableCalendarFeed,
+    RegenerateCalendarFeed,
+    RevokeCalendarFeed,
+    CalendarName,
+    AvailableForPairing,
+    PairingWith(&'a str),
+    SessionsDescription,
+    NoSessions,
+    SessionWith(&'a str),
+    ProposeSession,
+    SessionProposed,
+    SessionConflict,
+    SessionNotChanged,
+    SessionStatusProposed,
+    SessionStatusAccepted,
+    SessionStatusDeclined,
+    SessionStatusCancelled,
+    SessionStatusDone,
+    AcceptSession,
+    DeclineSession,
+    CancelSession,
+    MarkSessionDone,
+    GroupSchedule,
+    GroupScheduleDescription,
+    FindGroup,
+    PickGroupUsers,
+    GroupSize,
+    AtLeastUsers(usize),
+    NoGroupSlots,
+    TimeZone,
+    SlotLength,
+    Minutes(usize),
+    MinSessionLength,
+    AnyLength,
+    Starting,
+    InHours(usize),
+    InDays(usize),
+    LookAhead,
+    Weeks(usize),
+    Language,
+    AnyLanguage,
+    MinAffinity,
+    AnyAffinity,
     Languages,
     SpokenLanguages,
     SelectOneLanguage,
     SelectOneHighPrioLanguage,
     SelectOneSpokenLanguage,
+    RequireCommonSpokenLanguage,
+    PreferredSpokenLanguage,
+    Proficiency,
+    TopicsDescription,
+    AddTopic,
+    NoTopics,
+    UsersInterestedIn(&'a str),
+    NoUsersInterested,
     VisibleName,
     Bio,
     Projects,
@@ -56,6 +141,15 @@ pub enum Text<'a> {
     MediumAffinity,
     HighAffinity,
     UserSpeaks(&'a str),
+    CanMentorYou(&'a str),
+    WantsToLearnFromYou(&'a str),
+    WhyThisAffinity,
+    BestLanguage(&'a str),
+    MatchingLanguagesRatio,
+    ScheduleOverlap,
+    SpokenLanguagesFit,
+    TopicsInCommon,
+    Weight(&'a str),
     Loading,
     EmailVerifySubject,
     EmailVerifyBodyMarkdown(&'a str),
@@ -86,13 +180,38 @@ impl<'a> ToString for Text<'a> {
             Text::Schedule => "Schedule".into(),
             Text::Security => "Security".into(),
             Text::Messages => "Messages".into(),
+            Text::Topics => "Topics".into(),
+            Text::Sessions => "Sessions".into(),
             Text::AffinitiesTableDescription => "In the table below, you can see a list of developers who love the same languages as you. Just click the chat icon to start chatting and organize your next pair-programming session.".into(),
             Text::NoMatchingUsersFound => "Sorry, no matching users found. You can try to ".into(),
             Text::ExtendYourLanguageSelection => "extend your language selection.".into(),
-            Text::YourCurrentWeeklySchedule => "Your current weekly schedule. Check your available hours. All hours are in UTC".into(),
+            Text::YourCurrentWeeklySchedule => "Your current weekly schedule. Check your available hours. All hours are in your time zone".into(),
             Text::SetYourAvailability => "Set your availability".into(),
             Text::YouHaventScheduled => "You haven't scheduled anything yet".into(),
+            Text::DateExceptions => "Changes to your weekly schedule on specific dates".into(),
+            Text::ImportAvailability => "Import from your calendar".into(),
+            Text::ImportAvailabilityDescription => "An .ics or free/busy file. Recurring events shown as free become your availability, otherwise you are available when you are not busy.".into(),
+            Text::InvalidCalendarFile => "This file has no free or busy time".into(),
+            Text::ImportPreview => "Changes to your weekly schedule".into(),
+            Text::NoScheduleChanges => "Your weekly schedule would not change".into(),
+            Text::ApplyImport => "Apply".into(),
+            Text::Cancel => "Cancel".into(),
             Text::ChatWith(name) => format!("Chat with {}", name),
+            Text::LoadOlderMessages => "Load older messages".into(),
+            Text::EditMessage => "Edit".into(),
+            Text::DeleteMessage => "Delete".into(),
+            Text::SaveMessage => "Save".into(),
+            Text::MessageEdited => "edited".into(),
+            Text::MessageDeleted => "This message was deleted".into(),
+            Text::ConfirmDeleteMessage => "Delete this message?".into(),
+            Text::Chats => "Chats".into(),
+            Text::NewGroupChat => "New group chat".into(),
+            Text::GroupChatName => "Name".into(),
+            Text::CreateGroupChat => "Create".into(),
+            Text::GroupChatMembers => "Members".into(),
+            Text::AddGroupChatMember => "Add a member".into(),
+            Text::LeaveGroupChat => "Leave".into(),
+            Text::YouLeftGroupChat => "You left the group chat. Back to ".into(),
             Text::UnverifiedEmailAlert => "Your email address is not verified. You will not receive any email notification. Please, verify your email address.".into(),
             Text::UserWithUnverifiedEmail(name) => format!("{0}'s email address is not verified. {0} does not receive email notifications.", name),
             Text::NoChatsYet => "You don't have any chat yet".into(),
@@ -117,17 +236,79 @@ impl<'a> ToString for Text<'a> {
             Text::OldPasswordWrong => "Old password is wrong".into(),
             Text::OldPasswordOk => "Old password ok".into(),
             Text::VacationModeEnabled => "You are currently in vacation mode".into(),
+            Text::VacationStart => "First day of vacation (optional)".into(),
+            Text::VacationEnd => "Last day of vacation (optional)".into(),
+            Text::VacationEndsBeforeStart => "Last day of vacation is before the first one, changes are not saved".into(),
+            Text::UserOnVacation(name) => format!("{} is on vacation", name),
+            Text::UserOnVacationUntil(name, date) => format!("{} is on vacation until {}", name, date),
             Text::AddressUnverified => "This address is not verified.".into(),
             Text::CheckEmailForLink => "Check your email for a link to verify your email address. If it doesn’t appear within a few minutes, check your spam folder.".into(),
             Text::Verify => "Verify".into(),
             Text::UsernameCannotBeChanged => "Username cannot be changed".into(),
             Text::RemainingCharacters(total, remaining) => format!("{}/{} remaining characters", remaining, total),
             Text::VacationMode => " Vacation mode".into(),
+            Text::WeeklyPairing => " Suggest me a pair every week".into(),
+            Text::CalendarFeed => "Calendar feed".into(),
+            Text::CalendarFeedDescription => "Subscribe to this secret address with your calendar app to see your availability and matching users. Do not share it: anyone with this address can see your calendar.".into(),
+            Text::CalendarFeedDisabled => "Your calendar feed is disabled".into(),
+            Text::EnableCalendarFeed => "Enable".into(),
+            Text::RegenerateCalendarFeed => "Regenerate".into(),
+            Text::RevokeCalendarFeed => "Revoke".into(),
+            Text::CalendarName => "DevAndDev".into(),
+            Text::AvailableForPairing => "Available for pairing".into(),
+            Text::PairingWith(name) => format!("Pair programming with {}", name),
+            Text::SessionsDescription => "Pair programming sessions you proposed or you are invited to".into(),
+            Text::NoSessions => "No sessions yet. Find someone available in your schedule and propose a session.".into(),
+            Text::SessionWith(names) => format!("With {}", names),
+            Text::ProposeSession => "Propose a session".into(),
+            Text::SessionProposed => "Session proposed! You can follow it in".into(),
+            Text::SessionConflict => "This session does not fit in everyone's schedule anymore".into(),
+            Text::SessionNotChanged => "This session cannot be changed anymore, or it overlaps another session".into(),
+            Text::SessionStatusProposed => "Proposed".into(),
+            Text::SessionStatusAccepted => "Accepted".into(),
+            Text::SessionStatusDeclined => "Declined".into(),
+            Text::SessionStatusCancelled => "Cancelled".into(),
+            Text::SessionStatusDone => "Done".into(),
+            Text::AcceptSession => "Accept".into(),
+            Text::DeclineSession => "Decline".into(),
+            Text::CancelSession => "Cancel session".into(),
+            Text::MarkSessionDone => "Mark as done".into(),
+            Text::GroupSchedule => "Group schedule".into(),
+            Text::GroupScheduleDescription => "Find when a group of users is available at the same time as you, for a mob programming session. Pick the users, or look for anyone who knows a language.".into(),
+            Text::FindGroup => "Looking for a group? Find a slot for mob programming".into(),
+            Text::PickGroupUsers => "Users".into(),
+            Text::GroupSize => "Group size".into(),
+            Text::AtLeastUsers(n) => format!("At least {} users", n),
+            Text::NoGroupSlots => "Sorry, there are no slots where the group is available. You can try to ".into(),
+            Text::TimeZone => "Time zone".into(),
+            Text::SlotLength => "Slot length".into(),
+            Text::Minutes(n) => format!("{} minutes", n),
+            Text::MinSessionLength => "Minimum session length".into(),
+            Text::AnyLength => "Any length".into(),
+            Text::Starting => "Starting".into(),
+            Text::InHours(n) => format!("In {} hours", n),
+            Text::InDays(1) => "Tomorrow".into(),
+            Text::InDays(n) => format!("In {} days", n),
+            Text::LookAhead => "Look ahead".into(),
+            Text::Weeks(1) => "1 week".into(),
+            Text::Weeks(n) => format!("{} weeks", n),
+            Text::Language => "Language".into(),
+            Text::AnyLanguage => "Any language".into(),
+            Text::MinAffinity => "Minimum affinity".into(),
+            Text::AnyAffinity => "Any affinity".into(),
             Text::Languages => "Languages".into(),
             Text::SpokenLanguages => "Spoken Languages".into(),
             Text::SelectOneLanguage => "Please, select at least one language with Low or High priority".into(),
             Text::SelectOneHighPrioLanguage => "It seems that you don't have any high priority language. Please, set high priority to at least one language to get better affinity results".into(),
             Text::SelectOneSpokenLanguage => "Please, select at least one spoken language".into(),
+            Text::RequireCommonSpokenLanguage => " Pair me only with people who speak one of my languages".into(),
+            Text::PreferredSpokenLanguage => " Preferred".into(),
+            Text::Proficiency => "Proficiency".into(),
+            Text::TopicsDescription => "Frameworks, libraries or fields you want to pair on, like tokio, embedded or webassembly".into(),
+            Text::AddTopic => "Add".into(),
+            Text::NoTopics => "Nobody added a topic yet".into(),
+            Text::UsersInterestedIn(topic) => format!("Developers interested in {}", topic),
+            Text::NoUsersInterested => "Nobody is interested in this topic yet".into(),
             Text::VisibleName => "Visible name".into(),
             Text::Bio => "Bio".into(),
             Text::Projects => "Projects".into(),
@@ -136,6 +317,15 @@ impl<'a> ToString for Text<'a> {
             Text::MediumAffinity => "Medium affinity".into(),
             Text::HighAffinity => "High affinity".into(),
             Text::UserSpeaks(user) => format!("{} speaks", user),
+            Text::CanMentorYou(lang) => format!("Can mentor you in {}", lang),
+            Text::WantsToLearnFromYou(lang) => format!("Wants to learn {} from you", lang),
+            Text::WhyThisAffinity => "Why this affinity?".into(),
+            Text::BestLanguage(lang) => format!("Best matching language: {}", lang),
+            Text::MatchingLanguagesRatio => "Languages in common".into(),
+            Text::ScheduleOverlap => "Weekly availability overlap".into(),
+            Text::SpokenLanguagesFit => "Spoken languages fit".into(),
+            Text::TopicsInCommon => "Topics in common".into(),
+            Text::Weight(weight) => format!("(weight {})", weight),
             Text::Loading => "Loading...".into(),
             Text::EmailVerifySubject => "Verify Email Address for DevAndDev".into(),
             Text::EmailVerifyBodyMarkdown(url) => format!("Thanks for registering for an account on *DevAndDev*!\n\n\
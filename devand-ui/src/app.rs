@@ -3,7 +3,8 @@ mod workers;
 
 use self::components::*;
 use self::workers::{main_worker, main_worker::MainWorker};
-use devand_core::{PublicUserProfile, User};
+use devand_core::chat::ChatEvent;
+use devand_core::{AffinityConfig, PublicUserProfile, User};
 use devand_text::Text;
 use yew::prelude::*;
 use yew_router::switch::Permissive;
@@ -18,8 +19,12 @@ pub enum AppRoute {
     Affinities,
     #[to = "/code-now"]
     CodeNow,
+    #[to = "/schedule/groups"]
+    GroupSchedule,
     #[to = "/schedule"]
     Schedule,
+    #[to = "/sessions"]
+    Sessions,
     #[to = "/page-not-found"]
     NotFound(Permissive<String>),
     #[to = "/dashboard"]
@@ -28,10 +33,16 @@ pub enum AppRoute {
     SecuritySettings,
     #[to = "/chat/{username}"]
     Chat(String),
+    #[to = "/group-chat/{id}"]
+    GroupChat(String),
     #[to = "/chat"]
     Chats,
     #[to = "/u/{username}"]
     UserProfile(String),
+    #[to = "/topics/{topic}"]
+    Topic(String),
+    #[to = "/topics"]
+    Topics,
 }
 
 pub struct App {
@@ -43,6 +54,7 @@ pub struct App {
 #[derive(Default)]
 pub struct State {
     user: Option<User>,
+    affinity_config: Option<AffinityConfig>,
     pending_save: bool,
     verifying_email: bool,
     online_users: usize,
@@ -63,6 +75,7 @@ impl Component for App {
     fn create(_: Self::Properties, link: ComponentLink<Self>) -> Self {
         let mut main_worker = MainWorker::bridge(link.callback(Msg::MainWorkerRes));
         main_worker.send(main_worker::Request::Init);
+        main_worker.send(main_worker::Request::LoadAffinityConfig);
 
         App {
             main_worker,
@@ -95,10 +108,9 @@ impl Component for App {
     }
 
     fn view(&self) -> Html {
-        if let Some(user) = &self.state.user {
-            self.view_ok(user)
-        } else {
-            html! { <BusyIndicator /> }
+        match (&self.state.user, self.state.affinity_config) {
+            (Some(user), Some(affinity_config)) => self.view_ok(user, affinity_config),
+            _ => html! { <BusyIndicator /> },
         }
     }
 }
@@ -114,6 +126,11 @@ impl App {
                 true
             }
 
+            Response::AffinityConfigFetched(affinity_config) => {
+                self.state.affinity_config = Some(*affinity_config);
+                true
+            }
+
             Response::CodeNowFetched(code_now) => {
                 let my_id = code_now.current_user.id;
                 let online_users_now = code_now.all_users.iter().filter(|u| u.id != my_id).count();
@@ -129,6 +146,12 @@ impl App {
                 changed
             }
 
+            Response::ChatEventPushed(ChatEvent::UnreadMessages(unread_messages)) => {
+                let changed = self.state.unread_messages != unread_messages;
+                self.state.unread_messages = unread_messages;
+                changed
+            }
+
             Response::Error(err) => {
                 log::error!("Error: {}", err);
                 // TODO Show error alert
@@ -139,16 +162,16 @@ impl App {
         }
     }
 
-    fn view_ok(&self, user: &User) -> Html {
+    fn view_ok(&self, user: &User, affinity_config: AffinityConfig) -> Html {
         html! {
             <>
             { view_menu(&self.state) }
-            { self.view_routes(user) }
+            { self.view_routes(user, affinity_config) }
             </>
         }
     }
 
-    fn view_routes(&self, user: &User) -> Html {
+    fn view_routes(&self, user: &User, affinity_config: AffinityConfig) -> Html {
         let on_settings_change = self.link.callback(Msg::UserStore);
         let on_verify_email = self.link.callback(|_| Msg::VerifyEmail);
         let pub_user_profile: PublicUserProfile = user.clone().into();
@@ -160,14 +183,22 @@ impl App {
                 render = Router::render(move |switch: AppRoute| {
                     match switch {
                         AppRoute::Settings=> html!{ <SettingsPage on_change=on_settings_change.clone() user=user.clone() on_verify_email=on_verify_email.clone() verifying_email=verifying_email /> },
-                        AppRoute::Affinities=> html!{ <AffinitiesPage/> },
-                        AppRoute::CodeNow=> html!{ <CodeNowPage/> },
-                        AppRoute::Schedule=> html!{ <SchedulePage me=pub_user_profile.clone()/> },
-                        AppRoute::Chat(username) => html!{ <ChatPage chat_with=username me=pub_user_profile.clone() />},
+                        AppRoute::Affinities=> html!{ <AffinitiesPage affinity_config=affinity_config /> },
+                        AppRoute::CodeNow=> html!{ <CodeNowPage affinity_config=affinity_config /> },
+                        AppRoute::Schedule=> html!{ <SchedulePage me=pub_user_profile.clone() time_zone=user.settings.time_zone affinity_config=affinity_config /> },
+                        AppRoute::GroupSchedule=> html!{ <GroupSchedulePage me=pub_user_profile.clone() time_zone=user.settings.time_zone /> },
+                        AppRoute::Sessions=> html!{ <SessionsPage me=pub_user_profile.clone() time_zone=user.settings.time_zone /> },
+                        AppRoute::Chat(username) => html!{ <ChatPage chat_with=ChatWith::User(username) me=pub_user_profile.clone() />},
+                        AppRoute::GroupChat(id) => match id.parse() {
+                            Ok(id) => html!{ <ChatPage chat_with=ChatWith::Group(id) me=pub_user_profile.clone() />},
+                            Err(_) => html!{ <NotFoundPage missed_route=Some(format!("/group-chat/{}", id)) />},
+                        },
                         AppRoute::Chats => html!{ <ChatsPage />},
                         AppRoute::NotFound(Permissive(missed_route)) => html!{ <NotFoundPage missed_route=missed_route/>},
                         AppRoute::SecuritySettings => html!{ <SecuritySettingsPage /> },
                         AppRoute::UserProfile(username) => html!{ <UserProfilePage username=username /> },
+                        AppRoute::Topic(topic) => html!{ <TopicPage topic=topic /> },
+                        AppRoute::Topics => html!{ <TopicsPage /> },
                     }
                 })
                 redirect = Router::redirect(|route: Route| { AppRoute::NotFound(Permissive(Some(route.route))) })
@@ -188,6 +219,8 @@ fn view_menu(state: &State) -> Html {
         <li class="devand-menu-item"><RouterAnchor route=AppRoute::Affinities classes="pure-menu-link" >{ Text::Affinities }</RouterAnchor></li>
         <li class="devand-menu-item"><RouterAnchor route=AppRoute::CodeNow classes="pure-menu-link" >{ view_code_now(*online_users) }</RouterAnchor></li>
         <li class="devand-menu-item"><RouterAnchor route=AppRoute::Schedule classes="pure-menu-link" >{ Text::Schedule }</RouterAnchor></li>
+        <li class="devand-menu-item"><RouterAnchor route=AppRoute::Sessions classes="pure-menu-link" >{ Text::Sessions }</RouterAnchor></li>
+        <li class="devand-menu-item"><RouterAnchor route=AppRoute::Topics classes="pure-menu-link" >{ Text::Topics }</RouterAnchor></li>
         <li class="devand-menu-item"><RouterAnchor route=AppRoute::SecuritySettings classes="pure-menu-link" >{ Text::Security }</RouterAnchor></li>
         <li class="devand-menu-item"><RouterAnchor route=AppRoute::Chats classes="pure-menu-link" >{ view_messages(*unread_messages) }</RouterAnchor></li>
     </ul>
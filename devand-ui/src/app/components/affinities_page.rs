@@ -2,7 +2,7 @@ use crate::app::components::AffinitiesTable;
 use crate::app::components::{Alert, BusyIndicator};
 use crate::app::workers::{main_worker, main_worker::MainWorker};
 use crate::app::{AppRoute, RouterAnchor};
-use devand_core::UserAffinity;
+use devand_core::{AffinityConfig, UserAffinity};
 use devand_text::Text;
 use yew::prelude::*;
 use yewtil::NeqAssign;
@@ -23,7 +23,9 @@ pub struct AffinitiesPage {
 }
 
 #[derive(Clone, PartialEq, Properties)]
-pub struct Props {}
+pub struct Props {
+    pub affinity_config: AffinityConfig,
+}
 
 impl Component for AffinitiesPage {
     type Message = Msg;
@@ -70,7 +72,7 @@ impl Component for AffinitiesPage {
         <h1>{ Text::Affinities }</h1>
         {
             if let Some(affinities) = &self.state.affinities {
-                view_affinities(affinities)
+                view_affinities(affinities, self.props.affinity_config)
             } else {
             html! { <BusyIndicator /> }
             }
@@ -80,7 +82,7 @@ impl Component for AffinitiesPage {
     }
 }
 
-fn view_affinities(affinities: &[UserAffinity]) -> Html {
+fn view_affinities(affinities: &[UserAffinity], affinity_config: AffinityConfig) -> Html {
     if affinities.is_empty() {
         view_no_affinities()
     } else {
@@ -89,7 +91,7 @@ fn view_affinities(affinities: &[UserAffinity]) -> Html {
         html! {
         <>
             <p>{ Text::AffinitiesTableDescription }</p>
-            <AffinitiesTable affinities=affinities />
+            <AffinitiesTable affinities=affinities affinity_config=affinity_config />
         </>
         }
     }
@@ -1,6 +1,7 @@
 use crate::app::components::user_affinity_bubble;
 use crate::app::components::LanguageTag;
-use devand_core::{Language, UserAffinity};
+use devand_core::{AffinityBreakdown, AffinityConfig, Language, UserAffinity};
+use devand_text::Text;
 use std::str::FromStr;
 use strum::IntoEnumIterator;
 use yew::prelude::*;
@@ -15,6 +16,7 @@ pub struct AffinitiesTable {
 #[derive(Clone, PartialEq, Properties)]
 pub struct Props {
     pub affinities: Vec<UserAffinity>,
+    pub affinity_config: AffinityConfig,
 }
 
 pub enum Msg {
@@ -98,6 +100,7 @@ impl AffinitiesTable {
     }
 
     fn view_affinities_table(&self) -> Html {
+        let affinity_config = &self.props.affinity_config;
         let affinities = self
             .props
             .affinities
@@ -111,7 +114,7 @@ impl AffinitiesTable {
                     true
                 }
             })
-            .map(view_affinity);
+            .map(|x| view_affinity(x, affinity_config));
         html! {
         <ul class="devand-user-affinities">
         { for affinities}
@@ -120,7 +123,7 @@ impl AffinitiesTable {
     }
 }
 
-fn view_affinity(user_affinity: &UserAffinity) -> Html {
+fn view_affinity(user_affinity: &UserAffinity, affinity_config: &AffinityConfig) -> Html {
     let languages = user_affinity.user.languages.clone().into_sorted_vec();
 
     let languages_tags = languages.iter().map(|(lang, pref)| {
@@ -128,6 +131,87 @@ fn view_affinity(user_affinity: &UserAffinity) -> Html {
     });
 
     html! {
-    <li>{ user_affinity_bubble(user_affinity) } { for languages_tags }</li>
+    <li>
+        { user_affinity_bubble(user_affinity, affinity_config) } { for languages_tags }
+        { view_breakdown(&user_affinity.breakdown) }
+    </li>
+    }
+}
+
+fn percent(x: f64) -> String {
+    format!("{:.0}%", x * 100.0)
+}
+
+fn view_breakdown(breakdown: &Option<AffinityBreakdown>) -> Html {
+    let breakdown = if let Some(breakdown) = breakdown {
+        breakdown
+    } else {
+        return html! {};
+    };
+
+    let best_language = breakdown.best_language.map(|lang| lang.to_string());
+
+    let languages = breakdown.languages.iter().map(|(lang, aff)| {
+        let class = if Some(*lang) == breakdown.best_language {
+            "devand-best-language"
+        } else {
+            ""
+        };
+        html! { <li class=class>{ lang }{ ": " }{ aff.to_string() }</li> }
+    });
+
+    let schedule_overlap = if let Some(overlap) = breakdown.schedule_overlap {
+        html! {
+        <li>
+            { Text::ScheduleOverlap }{ ": " }{ percent(overlap) }
+            { " " }{ Text::Weight(&percent(breakdown.weights.schedule)) }
+        </li>
+        }
+    } else {
+        html! {}
+    };
+
+    let spoken_languages = if let Some(fit) = breakdown.spoken_languages {
+        html! {
+        <li>
+            { Text::SpokenLanguagesFit }{ ": " }{ percent(fit) }
+            { " " }{ Text::Weight(&percent(breakdown.weights.spoken_languages)) }
+        </li>
+        }
+    } else {
+        html! {}
+    };
+
+    let topics = if let Some(overlap) = breakdown.topics {
+        let common_topics: Vec<_> = breakdown.common_topics.iter().map(|x| x.as_str()).collect();
+        html! {
+        <li>
+            { Text::TopicsInCommon }{ ": " }{ percent(overlap) }
+            { " " }{ Text::Weight(&percent(breakdown.weights.topics)) }
+            <span class="devand-common-topics">{ common_topics.join(", ") }</span>
+        </li>
+        }
+    } else {
+        html! {}
+    };
+
+    html! {
+    <details class="devand-affinity-breakdown">
+        <summary>{ Text::WhyThisAffinity }</summary>
+        <ul>
+            <li>
+                { Text::BestLanguage(best_language.as_deref().unwrap_or_default()) }
+                { " " }{ Text::Weight(&percent(breakdown.weights.best_language)) }
+                <ul>{ for languages }</ul>
+            </li>
+            <li>
+                { Text::MatchingLanguagesRatio }{ ": " }{ percent(breakdown.matching_ratio) }
+                { " " }{ Text::Weight(&percent(breakdown.weights.matching_ratio)) }
+            </li>
+            { schedule_overlap }
+            { spoken_languages }
+            { topics }
+        </ul>
+    </details>
     }
 }
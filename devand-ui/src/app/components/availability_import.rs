@@ -0,0 +1,235 @@
+use super::availability_table::slot_label;
+use crate::app::components::{Alert, BusyIndicator};
+use crate::app::workers::{main_worker, main_worker::MainWorker};
+use chrono::Weekday;
+use devand_core::{Availability, DaySchedule, WeekSchedule};
+use devand_text::Text;
+use yew::prelude::*;
+use yew::services::reader::{File, FileData, ReaderService, ReaderTask};
+use yewtil::NeqAssign;
+
+const WEEKDAYS: [Weekday; 7] = [
+    Weekday::Mon,
+    Weekday::Tue,
+    Weekday::Wed,
+    Weekday::Thu,
+    Weekday::Fri,
+    Weekday::Sat,
+    Weekday::Sun,
+];
+
+/// Import the weekly schedule from an iCalendar file. Changes are shown
+/// before they are applied to the schedule.
+pub struct AvailabilityImport {
+    props: Props,
+    link: ComponentLink<Self>,
+    state: State,
+    main_worker: Box<dyn Bridge<MainWorker>>,
+    _reader_task: Option<ReaderTask>,
+}
+
+#[derive(Clone, PartialEq, Properties)]
+pub struct Props {
+    pub schedule: Availability,
+    pub on_apply: Callback<Availability>,
+}
+
+pub enum Msg {
+    MainWorkerRes(main_worker::Response),
+    LoadFile(File),
+    FileLoaded(FileData),
+    Apply,
+    Cancel,
+    Nope,
+}
+
+#[derive(Default)]
+struct State {
+    /// Waiting for the file or the server
+    pending: bool,
+    invalid_file: bool,
+    /// Current schedule with imported days, exceptions are kept
+    preview: Option<WeekSchedule>,
+}
+
+impl Component for AvailabilityImport {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let main_worker = MainWorker::bridge(link.callback(Msg::MainWorkerRes));
+
+        Self {
+            props,
+            link,
+            state: State::default(),
+            main_worker,
+            _reader_task: None,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Msg::LoadFile(file) => {
+                let callback = self.link.callback(Msg::FileLoaded);
+                self._reader_task = ReaderService::read_file(file, callback).ok();
+                self.state = State {
+                    pending: true,
+                    ..State::default()
+                };
+                true
+            }
+            Msg::FileLoaded(data) => {
+                let ics = String::from_utf8_lossy(&data.content).into_owned();
+                self.main_worker
+                    .send(main_worker::Request::ImportAvailability(ics));
+                false
+            }
+            Msg::MainWorkerRes(res) => {
+                use main_worker::Response;
+
+                if !self.state.pending {
+                    return false;
+                }
+
+                match res {
+                    Response::AvailabilityImported(imported) => {
+                        let mut preview = current_week(&self.props.schedule);
+                        for &day in WEEKDAYS.iter() {
+                            preview[day] = imported[day].clone();
+                        }
+                        self.state.pending = false;
+                        self.state.preview = Some(preview);
+                        true
+                    }
+                    Response::Error(_) => {
+                        self.state.pending = false;
+                        self.state.invalid_file = true;
+                        true
+                    }
+                    _ => false,
+                }
+            }
+            Msg::Apply => {
+                if let Some(preview) = self.state.preview.take() {
+                    self.props.on_apply.emit(Availability::Weekly(preview));
+                }
+                true
+            }
+            Msg::Cancel => {
+                self.state = State::default();
+                true
+            }
+            Msg::Nope => false,
+        }
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props.neq_assign(props)
+    }
+
+    fn view(&self) -> Html {
+        let on_change = self.link.callback(|cd: ChangeData| match cd {
+            ChangeData::Files(files) => files.get(0).map_or(Msg::Nope, Msg::LoadFile),
+            _ => Msg::Nope,
+        });
+
+        html! {
+            <fieldset class="pure-u-1">
+                <legend>{ Text::ImportAvailability }</legend>
+                <div class="pure-control-group">
+                    <input type="file" id="availability_import" accept=".ics,text/calendar" onchange=on_change />
+                    <span class="pure-form-message-inline">{ Text::ImportAvailabilityDescription }</span>
+                </div>
+                { self.view_result() }
+            </fieldset>
+        }
+    }
+}
+
+impl AvailabilityImport {
+    fn view_result(&self) -> Html {
+        if self.state.pending {
+            html! { <BusyIndicator /> }
+        } else if self.state.invalid_file {
+            html! { <Alert>{ Text::InvalidCalendarFile }</Alert> }
+        } else if let Some(preview) = &self.state.preview {
+            self.view_preview(preview)
+        } else {
+            html! {}
+        }
+    }
+
+    fn view_preview(&self, preview: &WeekSchedule) -> Html {
+        let current = current_week(&self.props.schedule);
+        let diff = current.diff(preview);
+
+        if diff.is_empty() {
+            return html! {
+                <>
+                <Alert>{ Text::NoScheduleChanges }</Alert>
+                <button class="pure-button" onclick=self.link.callback(|_| Msg::Cancel)>{ Text::Cancel }</button>
+                </>
+            };
+        }
+
+        let days = WEEKDAYS.iter().map(|&day| {
+            let resolution = diff.added[day].resolution;
+            let available = preview[day].to_resolution(resolution);
+            view_day_diff(day, &available, &diff.added[day], &diff.removed[day])
+        });
+
+        html! {
+            <>
+            <h3>{ Text::ImportPreview }</h3>
+            <ul class="devand-availability-week devand-availability-preview pure-g">
+                { for days }
+            </ul>
+            <button class="pure-button pure-button-primary" onclick=self.link.callback(|_| Msg::Apply)>{ Text::ApplyImport }</button>
+            <button class="pure-button" onclick=self.link.callback(|_| Msg::Cancel)>{ Text::Cancel }</button>
+            </>
+        }
+    }
+}
+
+fn current_week(schedule: &Availability) -> WeekSchedule {
+    match schedule {
+        Availability::Weekly(week) => week.clone(),
+        Availability::Never => WeekSchedule::default(),
+    }
+}
+
+/// Slots of the imported schedule, highlighting added and removed ones
+fn view_day_diff(
+    day: Weekday,
+    available: &DaySchedule,
+    added: &DaySchedule,
+    removed: &DaySchedule,
+) -> Html {
+    let resolution = available.resolution;
+
+    let slots = (0..available.slots.len()).map(|s| {
+        let class = if added.slots[s] {
+            "devand-slot-added"
+        } else if removed.slots[s] {
+            "devand-slot-removed"
+        } else if available.slots[s] {
+            "pure-button-primary"
+        } else {
+            ""
+        };
+
+        html! {
+            <button class=classes!("pure-button", class) disabled=true>{ slot_label(resolution, s) }</button>
+        }
+    });
+
+    html! {
+        <li class="devand-availability-day pure-u-1 pure-u-sm-1-3 pure-u-xl-1-4">
+            <h3>{ format!("{:?}", day) }</h3>
+            <div class="devand-availability-day-hours">
+                { for slots }
+            </div>
+        </li>
+    }
+}
@@ -1,12 +1,15 @@
-use chrono::Weekday;
-use devand_core::{Availability, DaySchedule, WeekSchedule};
+use chrono::{NaiveDate, Weekday};
+use devand_core::{Availability, DaySchedule, Resolution, WeekSchedule};
 use devand_text::Text;
+use std::str::FromStr;
+use strum::IntoEnumIterator;
 use yew::{prelude::*, Properties};
 use yewtil::NeqAssign;
 
 pub struct AvailabilityTable {
     props: Props,
     link: ComponentLink<Self>,
+    new_exception_date: String,
 }
 
 #[derive(Clone, PartialEq, Properties)]
@@ -17,7 +20,13 @@ pub struct Props {
 
 pub enum Msg {
     ResetSchedule,
-    ToggleDayHour(Weekday, usize),
+    ToggleDaySlot(Weekday, usize),
+    ChangeResolution(String),
+    SetNewExceptionDate(String),
+    AddException,
+    RemoveException(NaiveDate),
+    ToggleExceptionSlot(NaiveDate, usize),
+    Nope,
 }
 
 impl Component for AvailabilityTable {
@@ -25,7 +34,11 @@ impl Component for AvailabilityTable {
     type Properties = Props;
 
     fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
-        Self { props, link }
+        Self {
+            props,
+            link,
+            new_exception_date: String::default(),
+        }
     }
 
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
@@ -34,14 +47,54 @@ impl Component for AvailabilityTable {
                 self.props.schedule = Availability::Weekly(WeekSchedule::default());
                 true
             }
-            Msg::ToggleDayHour(d, h) => {
+            Msg::ToggleDaySlot(d, s) => {
                 if let Availability::Weekly(week) = &mut self.props.schedule {
                     let day = &mut week[d];
-                    day.hours[h] ^= true;
+                    day.slots[s] ^= true;
+                }
+                self.props.on_change.emit(self.props.schedule.clone());
+                true
+            }
+            Msg::ChangeResolution(s) => {
+                let resolution = Resolution::from_str(&s).unwrap();
+                if let Availability::Weekly(week) = &mut self.props.schedule {
+                    *week = week.to_resolution(resolution);
+                }
+                self.props.on_change.emit(self.props.schedule.clone());
+                true
+            }
+            Msg::SetNewExceptionDate(s) => {
+                self.new_exception_date = s;
+                false
+            }
+            Msg::AddException => {
+                let date = NaiveDate::parse_from_str(&self.new_exception_date, "%Y-%m-%d");
+                match (&mut self.props.schedule, date) {
+                    (Availability::Weekly(week), Ok(date)) => {
+                        week.exceptions.entry(date).or_default();
+                    }
+                    _ => return false,
+                }
+                self.props.on_change.emit(self.props.schedule.clone());
+                true
+            }
+            Msg::RemoveException(date) => {
+                if let Availability::Weekly(week) = &mut self.props.schedule {
+                    week.exceptions.remove(&date);
                 }
                 self.props.on_change.emit(self.props.schedule.clone());
                 true
             }
+            Msg::ToggleExceptionSlot(date, s) => {
+                if let Availability::Weekly(week) = &mut self.props.schedule {
+                    if let Some(&available) = week.on(date).slots.get(s) {
+                        week.set_available_on(date, s, !available);
+                    }
+                }
+                self.props.on_change.emit(self.props.schedule.clone());
+                true
+            }
+            Msg::Nope => false,
         }
     }
 
@@ -66,11 +119,37 @@ impl AvailabilityTable {
         html! {
             <fieldset class="pure-u-1">
                 <legend>{ Text::YourCurrentWeeklySchedule }</legend>
+                { self.view_resolution_select(schedule.resolution()) }
                 { self.view_days(schedule) }
+                { self.view_exceptions(schedule) }
             </fieldset>
         }
     }
 
+    fn view_resolution_select(&self, resolution: Resolution) -> Html {
+        let on_change = self.link.callback(move |cd: ChangeData| {
+            if let ChangeData::Select(se) = cd {
+                Msg::ChangeResolution(se.value())
+            } else {
+                Msg::Nope
+            }
+        });
+
+        let options = Resolution::iter().map(|r| {
+            let selected = r == resolution;
+            html! { <option value=r.to_string() selected=selected>{ Text::Minutes(r.minutes()) }</option> }
+        });
+
+        html! {
+            <div class="pure-control-group">
+                <label for="resolution">{ Text::SlotLength }</label>
+                <select name="resolution" id="resolution" onchange=on_change>
+                    { for options }
+                </select>
+            </div>
+        }
+    }
+
     fn view_days(&self, schedule: &WeekSchedule) -> Html {
         html! {
             <ul class="devand-availability-week pure-g">
@@ -86,7 +165,59 @@ impl AvailabilityTable {
     }
 
     fn view_day(&self, schedule: &DaySchedule, day: Weekday) -> Html {
-        let hours = schedule.hours.iter().enumerate().map(|(h, &on)| {
+        html! {
+            <li class="devand-availability-day pure-u-1 pure-u-sm-1-3 pure-u-xl-1-4">
+                <h3>{ format!("{:?}", day) }</h3>
+                { self.view_slots(schedule, move |s| Msg::ToggleDaySlot(day, s)) }
+            </li>
+        }
+    }
+
+    fn view_exceptions(&self, schedule: &WeekSchedule) -> Html {
+        let exceptions = schedule
+            .exceptions
+            .keys()
+            .map(|&date| self.view_exception(schedule, date));
+
+        html! {
+            <div class="devand-availability-exceptions">
+                <h3>{ Text::DateExceptions }</h3>
+                <ul class="devand-availability-week pure-g">
+                    { for exceptions }
+                </ul>
+                <div class="pure-control-group">
+                    <input
+                        type="date"
+                        value=self.new_exception_date.clone()
+                        oninput=self.link.callback(|e: InputData| Msg::SetNewExceptionDate(e.value)) />
+                    <button
+                        class="pure-button"
+                        onclick=self.link.callback(|_| Msg::AddException)>{ Text::Add }</button>
+                </div>
+            </div>
+        }
+    }
+
+    fn view_exception(&self, schedule: &WeekSchedule, date: NaiveDate) -> Html {
+        html! {
+            <li class="devand-availability-day pure-u-1 pure-u-sm-1-3 pure-u-xl-1-4">
+                <h3>
+                    { date.format("%a, %B %d %Y") }
+                    <button
+                        class="pure-button"
+                        onclick=self.link.callback(move |_| Msg::RemoveException(date))>{ "✖" }</button>
+                </h3>
+                { self.view_slots(&schedule.on(date), move |s| Msg::ToggleExceptionSlot(date, s)) }
+            </li>
+        }
+    }
+
+    fn view_slots<F>(&self, schedule: &DaySchedule, on_toggle: F) -> Html
+    where
+        F: Fn(usize) -> Msg + Copy + 'static,
+    {
+        let resolution = schedule.resolution;
+        let slots = schedule.slots.iter().enumerate().map(|(s, &on)| {
             let active = if on {
                 vec!["pure-button-active", "pure-button-primary"]
             } else {
@@ -96,17 +227,14 @@ impl AvailabilityTable {
             html! {
                 <button
                     class=classes!("pure-button", active)
-                    onclick=self.link.callback(move |_| Msg::ToggleDayHour(day, h))>{ h }</button>
+                    onclick=self.link.callback(move |_| on_toggle(s))>{ slot_label(resolution, s) }</button>
             }
         });
 
         html! {
-            <li class="devand-availability-day pure-u-1 pure-u-sm-1-3 pure-u-xl-1-4">
-                <h3>{ format!("{:?}", day) }</h3>
-                <div class="devand-availability-day-hours">
-                    { for hours }
-                </div>
-            </li>
+            <div class="devand-availability-day-hours">
+                { for slots }
+            </div>
         }
     }
 
@@ -125,3 +253,14 @@ impl AvailabilityTable {
         }
     }
 }
+
+/// Hourly slots are labeled with just the hour, finer slots with hour and
+/// minutes
+pub(crate) fn slot_label(resolution: Resolution, slot: usize) -> String {
+    let minute_of_day = slot * resolution.minutes();
+    let (h, m) = (minute_of_day / 60, minute_of_day % 60);
+    match resolution {
+        Resolution::Hour => h.to_string(),
+        _ => format!("{:02}:{:02}", h, m),
+    }
+}
@@ -1,14 +1,35 @@
-use crate::app::components::{Alert, BusyIndicator, ChatInput};
+use crate::app::components::{view_markdown, Alert, AlertLevel, BusyIndicator, ChatInput};
 use crate::app::workers::main_worker::Request::{
-    ChatLoadHistory, ChatPoll, ChatSendMessage, LoadPublicUserProfileByUsername,
+    AddGroupChatMember, ChatLoadHistory, ChatLoadOlderMessages, ChatPoll, ChatSendMessage,
+    DeleteChatMessage, EditChatMessage, LeaveGroupChat, LoadAllChats, LoadPublicUserProfile,
+    LoadPublicUserProfileByUsername,
+};
+use crate::app::workers::main_worker::{self, ChatAddress, MainWorker};
+use crate::app::{AppRoute, RouterAnchor};
+use chrono::{DateTime, TimeZone, Utc};
+use devand_core::chat::{
+    canonical_members, valid_edit, Chat, ChatConfig, ChatEvent, ChatId, ChatMessage, HistoryCursor,
+    HISTORY_PAGE_SIZE, MAX_MESSAGE_LEN,
 };
-use crate::app::workers::{main_worker, main_worker::MainWorker};
-use devand_core::chat::ChatMessage;
 use devand_core::{PublicUserProfile, UserId};
 use devand_text::Text;
+use std::collections::{BTreeMap, BTreeSet};
+use wasm_bindgen::prelude::*;
 use yew::services::interval::{IntervalService, IntervalTask};
+use yew::services::DialogService;
 use yew::{prelude::*, Properties};
 
+#[wasm_bindgen]
+extern "C" {
+    /// Chrono cannot read the clock in the browser
+    #[wasm_bindgen(js_namespace = Date, js_name = now)]
+    fn date_now() -> f64;
+}
+
+fn now() -> DateTime<Utc> {
+    Utc.timestamp_millis(date_now() as i64)
+}
+
 pub struct ChatPage {
     props: Props,
     state: State,
@@ -18,24 +39,68 @@ pub struct ChatPage {
     main_worker: Box<dyn Bridge<MainWorker>>,
 }
 
+#[derive(Clone, PartialEq)]
+pub enum ChatWith {
+    /// Direct chat with the user with this username
+    User(String),
+    Group(ChatId),
+}
+
 #[derive(Clone, PartialEq, Properties)]
 pub struct Props {
-    pub chat_with: String,
+    pub chat_with: ChatWith,
     pub me: PublicUserProfile,
 }
 
 pub enum Msg {
     SendMessage(String),
     Poll,
+    LoadOlderMessages,
+    AddMember(String),
+    LeaveGroup,
+    EditMessage(uuid::Uuid),
+    EditInput(String),
+    SaveEdit,
+    CancelEdit,
+    DeleteMessage(uuid::Uuid),
     MainWorkerRes(main_worker::Response),
 }
 
 #[derive(Default)]
 struct State {
+    /// Direct chats are addressed once the other user is loaded
+    address: Option<ChatAddress>,
     messages: Vec<ChatMessage>,
     other_user: Option<PublicUserProfile>,
     pending: bool,
+    /// New messages arrived while polling
+    poll_again: bool,
+    /// New messages are pushed, so polling is not needed
+    push_connected: bool,
+    /// Only the latest messages are loaded at first, older ones on request
+    has_older: bool,
+    loading_older: bool,
     verified_email: Option<bool>,
+    /// Loaded with the history, tells how long messages can be changed
+    config: ChatConfig,
+    /// Cursor of changes, given by the server
+    changed_after: Option<DateTime<Utc>>,
+    /// The message being edited, with its new text
+    editing: Option<(uuid::Uuid, String)>,
+    group: GroupState,
+}
+
+/// Only for group chats
+#[derive(Default)]
+struct GroupState {
+    name: Option<String>,
+    members: Vec<UserId>,
+    /// Users the logged user chats with, who can be added to the group
+    known_users: BTreeMap<UserId, PublicUserProfile>,
+    /// Other members, loaded to show their name
+    users: BTreeMap<UserId, PublicUserProfile>,
+    user_requests: BTreeSet<UserId>,
+    left: bool,
 }
 
 impl Component for ChatPage {
@@ -43,7 +108,7 @@ impl Component for ChatPage {
     type Properties = Props;
 
     fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
-        let state = State::default();
+        let mut state = State::default();
 
         let poll_task = IntervalService::spawn(
             std::time::Duration::from_secs(5),
@@ -51,7 +116,20 @@ impl Component for ChatPage {
         );
 
         let mut main_worker = MainWorker::bridge(link.callback(Msg::MainWorkerRes));
-        main_worker.send(LoadPublicUserProfileByUsername(props.chat_with.clone()));
+
+        match &props.chat_with {
+            ChatWith::User(username) => {
+                main_worker.send(LoadPublicUserProfileByUsername(username.clone()));
+            }
+            ChatWith::Group(id) => {
+                let address = ChatAddress::Group(*id);
+                // Polling waits for the history
+                state.pending = true;
+                main_worker.send(ChatLoadHistory(address.clone()));
+                main_worker.send(LoadAllChats);
+                state.address = Some(address);
+            }
+        }
 
         Self {
             props,
@@ -67,60 +145,207 @@ impl Component for ChatPage {
 
         match msg {
             Msg::SendMessage(txt) => {
-                // TODO Make it work with multiple members chat
-                if let Some(members) = self.two_members() {
-                    self.main_worker.send(ChatSendMessage(members, txt));
+                if let Some(address) = self.state.address.clone() {
+                    self.main_worker.send(ChatSendMessage(address, txt));
                 }
                 false
             }
 
             Msg::Poll => {
-                if !self.state.pending {
-                    // TODO Make it work with multiple members chat
-                    if let Some(members) = self.two_members() {
-                        self.state.pending = true;
-                        let from_created_at = self.state.messages.last().map(|x| x.created_at);
-                        self.main_worker.send(ChatPoll(members, from_created_at));
+                if !self.state.push_connected {
+                    self.poll();
+                }
+                false
+            }
+
+            Msg::LoadOlderMessages => {
+                let first = self.state.messages.first().map(|m| m.id);
+                if let (Some(address), Some(first)) = (self.state.address.clone(), first) {
+                    self.state.loading_older = true;
+                    let cursor = HistoryCursor::Before(first);
+                    self.main_worker
+                        .send(ChatLoadOlderMessages(address, cursor));
+                }
+                true
+            }
+
+            Msg::AddMember(user) => {
+                let user = user.parse().ok().map(UserId);
+                if let (ChatWith::Group(id), Some(user)) = (&self.props.chat_with, user) {
+                    self.main_worker.send(AddGroupChatMember(*id, user));
+                }
+                false
+            }
+
+            Msg::LeaveGroup => {
+                if let ChatWith::Group(id) = &self.props.chat_with {
+                    self.main_worker.send(LeaveGroupChat(*id, self.props.me.id));
+                }
+                false
+            }
+
+            Msg::EditMessage(id) => {
+                let message = self.state.messages.iter().find(|m| m.id == id);
+                self.state.editing = message.map(|m| (id, m.txt.clone()));
+                true
+            }
+
+            Msg::EditInput(txt) => {
+                if let Some((_, edited)) = &mut self.state.editing {
+                    *edited = txt;
+                }
+                true
+            }
+
+            Msg::SaveEdit => {
+                if let Some((id, txt)) = self.state.editing.take() {
+                    let message = self.state.messages.iter().find(|m| m.id == id);
+                    let changed = message.map_or(false, |m| m.txt != txt);
+                    match valid_edit(&txt) {
+                        Some(txt) if changed => self.main_worker.send(EditChatMessage(id, txt)),
+                        _ => {}
                     }
                 }
+                true
+            }
+
+            Msg::CancelEdit => {
+                self.state.editing = None;
+                true
+            }
+
+            Msg::DeleteMessage(id) => {
+                if DialogService::confirm(&Text::ConfirmDeleteMessage.to_string()) {
+                    self.main_worker.send(DeleteChatMessage(id));
+                }
                 false
             }
 
             Msg::MainWorkerRes(res) => match res {
-                Response::PublicUserProfileFetched(other_user) => {
-                    self.state.other_user = Some(*other_user);
-                    // TODO Make it work with multiple members chat
-                    if let Some(members) = self.two_members() {
-                        self.main_worker.send(ChatLoadHistory(members));
+                Response::PublicUserProfileFetched(user) => match &self.props.chat_with {
+                    ChatWith::User(username) if *username == user.username => {
+                        let members = canonical_members(&[self.props.me.id, user.id]);
+                        let address = ChatAddress::Direct(members);
+                        self.state.other_user = Some(*user);
+                        // Polling waits for the history
+                        self.state.pending = true;
+                        self.main_worker.send(ChatLoadHistory(address.clone()));
+                        self.state.address = Some(address);
+                        true
                     }
-                    true
-                }
+                    ChatWith::Group(_) if self.state.group.user_requests.contains(&user.id) => {
+                        self.state.group.users.insert(user.id, *user);
+                        true
+                    }
+                    _ => false,
+                },
 
                 Response::ChatHistoryLoaded(chat) => {
                     let devand_core::chat::ChatInfo {
+                        name,
                         mut messages,
                         members_info,
+                        config,
+                        changed_after,
                     } = chat;
 
                     self.state.pending = false;
+                    self.state.config = config;
+                    self.state.changed_after = changed_after;
+                    self.state.has_older = messages.len() >= HISTORY_PAGE_SIZE;
                     self.state.messages.append(&mut messages);
 
-                    for member in members_info {
-                        // TODO This works only for single member chats
-                        if member.user_id != self.props.me.id {
-                            self.state.verified_email = Some(member.verified_email);
+                    if self.is_group() {
+                        self.state.group.name = name;
+                        let members = members_info.iter().map(|m| m.user_id).collect();
+                        self.set_group_members(members);
+                    } else {
+                        for member in members_info {
+                            if member.user_id != self.props.me.id {
+                                self.state.verified_email = Some(member.verified_email);
+                            }
                         }
                     }
 
                     true
                 }
 
-                Response::ChatNewMessagesLoaded(mut messages) => {
+                Response::AllChatsLoaded(chats) if self.is_group() => {
+                    let me = self.props.me.id;
+                    self.state.group.known_users = chats
+                        .0
+                        .into_iter()
+                        .flat_map(|chat| chat.members)
+                        .filter(|user| user.id != me)
+                        .map(|user| (user.id, user))
+                        .collect();
+                    true
+                }
+
+                Response::GroupChatUpdated(chat) if self.is_this_chat(&chat) => {
+                    self.set_group_members(chat.members);
+                    true
+                }
+
+                Response::GroupChatLeft(chat) if self.is_this_chat(&chat) => {
+                    self.state.group.left = true;
+                    true
+                }
+
+                Response::ChatOlderMessagesLoaded(messages) => {
+                    self.state.loading_older = false;
+                    self.state.has_older = messages.len() >= HISTORY_PAGE_SIZE;
+                    self.prepend_messages(messages);
+                    true
+                }
+
+                Response::ChatNewMessagesLoaded(messages) => {
                     self.state.pending = false;
-                    self.state.messages.append(&mut messages);
+                    self.append_messages(messages);
+                    if std::mem::take(&mut self.state.poll_again) {
+                        self.poll();
+                    }
+                    true
+                }
+
+                Response::ChatUpdatesLoaded(updates) => {
+                    self.state.pending = false;
+                    self.state.changed_after = updates.changed_after.or(self.state.changed_after);
+                    self.append_messages(updates.messages);
+                    if std::mem::take(&mut self.state.poll_again) {
+                        self.poll();
+                    }
                     true
                 }
 
+                Response::PushConnected(connected) => {
+                    self.state.push_connected = connected;
+                    // Messages may have been missed while disconnected
+                    if connected {
+                        self.poll();
+                    }
+                    false
+                }
+
+                Response::ChatEventPushed(ChatEvent::NewMessage { chat, message }) => {
+                    if !self.is_this_chat(&chat) {
+                        false
+                    } else if message.author == self.props.me.id {
+                        self.append_messages(vec![message]);
+                        true
+                    } else {
+                        // Polling marks the message as read
+                        self.poll();
+                        false
+                    }
+                }
+
+                Response::ChatMessageChanged(message) => self.replace_message(message),
+
+                Response::ChatEventPushed(ChatEvent::MessageChanged { chat, message }) => {
+                    self.is_this_chat(&chat) && self.replace_message(message)
+                }
+
                 _ => false,
             },
         }
@@ -133,8 +358,18 @@ impl Component for ChatPage {
     }
 
     fn view(&self) -> Html {
+        if self.state.group.left {
+            return html! {
+                <Alert level=AlertLevel::Success>
+                    { Text::YouLeftGroupChat } <RouterAnchor route=AppRoute::Chats >{ Text::Chats }</RouterAnchor>
+                </Alert>
+            };
+        }
+
         if let Some(other_user) = &self.state.other_user {
-            self.view_messages(other_user)
+            self.view_direct_chat(other_user)
+        } else if let Some(name) = &self.state.group.name {
+            self.view_group_chat(name)
         } else {
             html! { <BusyIndicator /> }
         }
@@ -142,24 +377,109 @@ impl Component for ChatPage {
 }
 
 impl ChatPage {
-    // TODO ChatMembers should be a strong type, not a Vec
-    // TODO Sorting when encoded to string should be enforced
-    fn two_members(&self) -> Option<Vec<devand_core::UserId>> {
-        let other_user = self.state.other_user.as_ref()?;
+    /// Load messages after the last one, and the ones changed after the
+    /// last change seen
+    fn poll(&mut self) {
+        if self.state.pending {
+            // The pending request may not include the newest messages
+            self.state.poll_again = true;
+            return;
+        }
+
+        if let Some(address) = self.state.address.clone() {
+            self.state.pending = true;
+            let cursor = match self.state.messages.last() {
+                Some(last) => HistoryCursor::After(last.id),
+                None => HistoryCursor::Latest,
+            };
+            let changed_after = self.state.changed_after;
+            self.main_worker
+                .send(ChatPoll(address, cursor, changed_after));
+        }
+    }
+
+    /// Own messages are both received as response and pushed, and polling
+    /// also returns changed messages, so messages already shown are
+    /// replaced. Changed messages older than the loaded ones are skipped.
+    fn append_messages(&mut self, messages: Vec<ChatMessage>) {
+        for message in messages {
+            let newer = match self.state.messages.last() {
+                Some(last) => message.created_at >= last.created_at,
+                None => true,
+            };
+            let shown = self.state.messages.iter_mut().find(|m| m.id == message.id);
+            match shown {
+                Some(shown) => *shown = message,
+                None if newer => self.state.messages.push(message),
+                None => {}
+            }
+        }
+    }
+
+    /// Returns false if the message is not shown
+    fn replace_message(&mut self, message: ChatMessage) -> bool {
+        match self.state.messages.iter_mut().find(|m| m.id == message.id) {
+            Some(shown) => {
+                *shown = message;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Messages may have been loaded in the meantime, e.g. if the page
+    /// boundary was moved by new messages, so they are skipped too
+    fn prepend_messages(&mut self, messages: Vec<ChatMessage>) {
+        let loaded = &self.state.messages;
+        let older: Vec<_> = messages
+            .into_iter()
+            .filter(|message| !loaded.iter().any(|m| m.id == message.id))
+            .collect();
+        self.state.messages.splice(0..0, older);
+    }
+
+    fn is_group(&self) -> bool {
+        matches!(self.props.chat_with, ChatWith::Group(_))
+    }
+
+    /// Direct chats are matched by members, group chats by id, because a
+    /// group may have the same members of a direct chat
+    fn is_this_chat(&self, chat: &Chat) -> bool {
+        match &self.state.address {
+            Some(ChatAddress::Direct(members)) => {
+                !chat.is_group() && canonical_members(&chat.members) == *members
+            }
+            Some(ChatAddress::Group(id)) => chat.id == *id,
+            None => false,
+        }
+    }
+
+    /// Request public profiles of members not already known, but only if
+    /// loading has not already started
+    fn set_group_members(&mut self, members: Vec<UserId>) {
         let me = self.props.me.id;
-        let other_user_id = other_user.id;
-        let mut members = vec![other_user_id, me];
-        members.sort();
-        Some(members)
+        let group = &mut self.state.group;
+
+        for &user in &members {
+            let known = user == me || group.known_users.contains_key(&user);
+            if !known && group.user_requests.insert(user) {
+                self.main_worker.send(LoadPublicUserProfile(user));
+            }
+        }
+
+        group.members = members;
+    }
+
+    fn group_user(&self, user: UserId) -> Option<&PublicUserProfile> {
+        let group = &self.state.group;
+        group
+            .known_users
+            .get(&user)
+            .or_else(|| group.users.get(&user))
     }
 
-    fn view_messages(&self, other_user: &PublicUserProfile) -> Html {
+    fn view_direct_chat(&self, other_user: &PublicUserProfile) -> Html {
         let unverified_email = self.state.verified_email == Some(false);
-        let msg_bubbles = self
-            .state
-            .messages
-            .iter()
-            .map(|msg| view_bubble(self.props.me.id, msg));
 
         html! {
             <>
@@ -171,29 +491,151 @@ impl ChatPage {
                     html! { }
                 }
                 }
-                <div class="devand-chat-container">
-                    <div class="devand-chat-messages">
-                        { for msg_bubbles }
-                    </div>
-                    <ChatInput on_return=self.link.callback(Msg::SendMessage) />
-                </div>
+                { self.view_messages() }
             </>
         }
     }
-}
 
-fn view_bubble(me: UserId, msg: &ChatMessage) -> Html {
-    let from_me = msg.author == me;
-    let from_me_class = if from_me {
-        "devand-from-me"
-    } else {
-        "devand-from-other"
-    };
-    html! {
-        <div class=classes!("devand-chat-message-bubble", from_me_class)>
-            <span class="devand-chat-message-txt">{ &msg.txt }</span>
-            <span class="devand-timestamp">{ view_timestamp(&msg.created_at) }</span>
-        </div>
+    fn view_group_chat(&self, name: &str) -> Html {
+        let me = self.props.me.id;
+        let group = &self.state.group;
+
+        let members = group.members.iter().filter(|&&u| u != me).map(|&user| {
+            match self.group_user(user) {
+                Some(user) => html! {
+                    <span class="devand-visible-name devand-group-user"><RouterAnchor route=AppRoute::UserProfile(user.username.clone()) >{ &user.visible_name }</RouterAnchor></span>
+                },
+                None => html! {},
+            }
+        });
+
+        let candidates = group
+            .known_users
+            .values()
+            .filter(|user| !group.members.contains(&user.id))
+            .map(|user| html! { <option value=user.id.0.to_string()>{ &user.visible_name }</option> });
+
+        let on_add = self.link.callback(|cd: ChangeData| match cd {
+            ChangeData::Select(se) => Msg::AddMember(se.value()),
+            _ => Msg::AddMember(String::new()),
+        });
+
+        html! {
+            <>
+                <h1>{ name }</h1>
+                <p class="devand-group-chat-members">
+                    { Text::GroupChatMembers }{ ": " }
+                    { for members }
+                </p>
+                <form class="pure-form devand-group-chat-actions">
+                    <select onchange=on_add>
+                        <option value="" selected=true>{ Text::AddGroupChatMember }</option>
+                        { for candidates }
+                    </select>
+                    { " " }
+                    <button class="pure-button" type="button" onclick=self.link.callback(|_| Msg::LeaveGroup)>{ Text::LeaveGroupChat }</button>
+                </form>
+                { self.view_messages() }
+            </>
+        }
+    }
+
+    fn view_messages(&self) -> Html {
+        let now = now();
+        let is_group = self.is_group();
+        let msg_bubbles = self.state.messages.iter().map(|msg| {
+            let author = if is_group {
+                self.group_user(msg.author)
+                    .map(|user| user.visible_name.as_str())
+            } else {
+                None
+            };
+            self.view_bubble(msg, author, now)
+        });
+
+        html! {
+            <div class="devand-chat-container">
+                <div class="devand-chat-messages">
+                    { self.view_load_older() }
+                    { for msg_bubbles }
+                </div>
+                <ChatInput on_return=self.link.callback(Msg::SendMessage) />
+            </div>
+        }
+    }
+
+    fn view_load_older(&self) -> Html {
+        if !self.state.has_older {
+            return html! {};
+        }
+
+        html! {
+            <div class="devand-chat-load-older">
+                <button class="pure-button" disabled=self.state.loading_older onclick=self.link.callback(|_| Msg::LoadOlderMessages)>{ Text::LoadOlderMessages }</button>
+            </div>
+        }
+    }
+
+    /// In group chats, messages of other members show their author. Own
+    /// messages can be edited or deleted for a while after being sent.
+    fn view_bubble(&self, msg: &ChatMessage, author: Option<&str>, now: DateTime<Utc>) -> Html {
+        let me = self.props.me.id;
+        let from_me = msg.author == me;
+        let from_me_class = if from_me {
+            "devand-from-me"
+        } else {
+            "devand-from-other"
+        };
+        let author = match author {
+            Some(author) if !from_me => {
+                html! { <span class="devand-chat-message-author">{ author }</span> }
+            }
+            _ => html! {},
+        };
+        let txt = match &self.state.editing {
+            Some((id, txt)) if *id == msg.id => return self.view_edit(from_me_class, txt),
+            _ if msg.is_deleted() => html! {
+                <span class="devand-chat-message-deleted">{ Text::MessageDeleted }</span>
+            },
+            _ => view_markdown(&msg.txt),
+        };
+        let edited = match msg.edited_at {
+            Some(edited_at) if !msg.is_deleted() => html! {
+                <span class="devand-chat-message-edited" title=view_timestamp(&edited_at).to_string()>{ Text::MessageEdited }</span>
+            },
+            _ => html! {},
+        };
+        let actions = if msg.can_be_changed_by(me, now, self.state.config) {
+            let id = msg.id;
+            html! {
+                <div class="devand-chat-message-actions">
+                    <button class="pure-button" onclick=self.link.callback(move |_| Msg::EditMessage(id))>{ Text::EditMessage }</button>
+                    <button class="pure-button" onclick=self.link.callback(move |_| Msg::DeleteMessage(id))>{ Text::DeleteMessage }</button>
+                </div>
+            }
+        } else {
+            html! {}
+        };
+        html! {
+            <div class=classes!("devand-chat-message-bubble", from_me_class)>
+                { author }
+                <div class="devand-chat-message-txt">{ txt }</div>
+                { actions }
+                <span class="devand-timestamp">{ view_timestamp(&msg.created_at) }{ edited }</span>
+            </div>
+        }
+    }
+
+    fn view_edit(&self, from_me_class: &'static str, txt: &str) -> Html {
+        html! {
+            <div class=classes!("devand-chat-message-bubble", from_me_class)>
+                <div class="pure-form devand-chat-message-edit">
+                    <input type="text" maxlength=MAX_MESSAGE_LEN.to_string() value=txt.to_string() oninput=self.link.callback(|e: InputData| Msg::EditInput(e.value)) />
+                    <button class="pure-button pure-button-primary" onclick=self.link.callback(|_| Msg::SaveEdit)>{ Text::SaveMessage }</button>
+                    <button class="pure-button" onclick=self.link.callback(|_| Msg::CancelEdit)>{ Text::Cancel }</button>
+                </div>
+            </div>
+        }
     }
 }
 
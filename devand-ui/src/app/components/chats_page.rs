@@ -1,25 +1,33 @@
 use crate::app::components::common::{BusyIndicator, CountTag};
-use crate::app::workers::main_worker::Request::LoadAllChats;
+use crate::app::workers::main_worker::Request::{CreateGroupChat, LoadAllChats};
 use crate::app::workers::{main_worker, main_worker::MainWorker};
 use crate::app::{AppRoute, RouterAnchor};
-use devand_core::{UserChat, UserChats};
+use devand_core::chat::{ChatEvent, NewGroupChat, MIN_GROUP_CHAT_MEMBERS};
+use devand_core::{PublicUserProfile, UserChat, UserChats, UserId};
 use devand_text::Text;
+use std::collections::BTreeMap;
 use yew::{prelude::*, Properties};
 use yewtil::NeqAssign;
 
 #[derive(Default)]
 pub struct State {
     chats: Option<UserChats>,
+    new_group: NewGroupChat,
+    creating_group: bool,
 }
 
 pub enum Msg {
     MainWorkerRes(main_worker::Response),
+    ChangeGroupName(String),
+    ToggleGroupMember(UserId),
+    CreateGroup,
 }
 
 pub struct ChatsPage {
     props: Props,
     state: State,
-    _main_worker: Box<dyn Bridge<MainWorker>>,
+    link: ComponentLink<Self>,
+    main_worker: Box<dyn Bridge<MainWorker>>,
 }
 
 #[derive(Clone, PartialEq, Properties)]
@@ -38,7 +46,8 @@ impl Component for ChatsPage {
         Self {
             props,
             state,
-            _main_worker: main_worker,
+            link,
+            main_worker,
         }
     }
 
@@ -50,8 +59,45 @@ impl Component for ChatsPage {
                     true
                 }
 
+                main_worker::Response::GroupChatUpdated(_) if self.state.creating_group => {
+                    self.state.creating_group = false;
+                    self.state.new_group = NewGroupChat::default();
+                    self.main_worker.send(LoadAllChats);
+                    true
+                }
+
+                main_worker::Response::Error(_) if self.state.creating_group => {
+                    self.state.creating_group = false;
+                    true
+                }
+
+                // Unread messages of each chat are not pushed
+                main_worker::Response::ChatEventPushed(ChatEvent::UnreadMessages(_)) => {
+                    self.main_worker.send(LoadAllChats);
+                    false
+                }
+
                 _ => false,
             },
+            Msg::ChangeGroupName(name) => {
+                self.state.new_group.name = name;
+                true
+            }
+            Msg::ToggleGroupMember(user) => {
+                let members = &mut self.state.new_group.members;
+                if let Some(i) = members.iter().position(|&u| u == user) {
+                    members.remove(i);
+                } else {
+                    members.push(user);
+                }
+                true
+            }
+            Msg::CreateGroup => {
+                self.state.creating_group = true;
+                self.main_worker
+                    .send(CreateGroupChat(self.state.new_group.clone()));
+                true
+            }
         }
     }
 
@@ -62,10 +108,15 @@ impl Component for ChatsPage {
     fn view(&self) -> Html {
         html! {
             <>
-                <h1>{ "Chats" }</h1>
+                <h1>{ Text::Chats }</h1>
                 {
                 if let Some(chats) = &self.state.chats {
-                    view_chats(chats)
+                    html! {
+                        <>
+                        { view_chats(chats) }
+                        { self.view_new_group(chats) }
+                        </>
+                    }
                 } else {
                     html! { <BusyIndicator /> }
                 }
@@ -75,6 +126,48 @@ impl Component for ChatsPage {
     }
 }
 
+impl ChatsPage {
+    /// Group chats are created with users the logged user already chats with
+    fn view_new_group(&self, chats: &UserChats) -> Html {
+        let users: BTreeMap<UserId, &PublicUserProfile> = chats
+            .0
+            .iter()
+            .flat_map(|chat| chat.members.iter())
+            .map(|user| (user.id, user))
+            .collect();
+
+        // The logged user is not in members, but is added anyway
+        let new_group = &self.state.new_group;
+        let enough_members = new_group.members.len() + 1 >= MIN_GROUP_CHAT_MEMBERS;
+        let can_create =
+            enough_members && new_group.valid_name().is_some() && !self.state.creating_group;
+
+        let users = users.values().map(|user| {
+            let user_id = user.id;
+            let checked = new_group.members.contains(&user_id);
+            let id = format!("group_chat_user_{}", user_id.0);
+            html! {
+                <label for=id.clone() class="pure-checkbox devand-group-user">
+                    <input type="checkbox" id=id checked=checked onclick=self.link.callback(move |_| Msg::ToggleGroupMember(user_id)) />
+                    { " " }{ &user.visible_name }
+                </label>
+            }
+        });
+
+        html! {
+            <form class="pure-form pure-form-stacked devand-new-group-chat">
+                <fieldset>
+                    <legend>{ Text::NewGroupChat }</legend>
+                    <label for="group_chat_name">{ Text::GroupChatName }</label>
+                    <input type="text" id="group_chat_name" value=new_group.name.clone() oninput=self.link.callback(|e: InputData| Msg::ChangeGroupName(e.value)) />
+                    { for users }
+                    <button class="pure-button pure-button-primary" type="button" disabled=!can_create onclick=self.link.callback(|_| Msg::CreateGroup)>{ Text::CreateGroupChat }</button>
+                </fieldset>
+            </form>
+        }
+    }
+}
+
 fn view_chats(chats: &UserChats) -> Html {
     if chats.0.is_empty() {
         view_no_chats()
@@ -83,7 +176,7 @@ fn view_chats(chats: &UserChats) -> Html {
             .0
             .iter()
             .rev()
-            .filter(|c| !c.members.is_empty())
+            .filter(|c| c.chat.is_group() || !c.members.is_empty())
             .map(view_chat);
 
         html! {
@@ -95,10 +188,12 @@ fn view_chats(chats: &UserChats) -> Html {
 }
 
 fn view_chat(chat: &UserChat) -> Html {
-    match chat.members.len() {
-        0 => html! {},
-        1 => view_direct_chat(chat),
-        _ => view_group_chat(chat),
+    if chat.chat.is_group() {
+        view_group_chat(chat)
+    } else if chat.members.len() == 1 {
+        view_direct_chat(chat)
+    } else {
+        html! {}
     }
 }
 
@@ -116,8 +211,24 @@ fn view_direct_chat(chat: &UserChat) -> Html {
     }
 }
 
-fn view_group_chat(_chat: &UserChat) -> Html {
-    todo!()
+fn view_group_chat(chat: &UserChat) -> Html {
+    let id = chat.chat.id.to_string();
+    let name = chat.chat.name.clone().unwrap_or_default();
+    let members = chat
+        .members
+        .iter()
+        .map(|user| user.visible_name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let unread_messages = chat.unread_messages;
+
+    html! {
+    <li class="user-chat">
+        <span class="visible_name"><RouterAnchor route=AppRoute::GroupChat(id) >{ name }</RouterAnchor></span>
+        <span class="devand-group-chat-members">{ members }</span>
+        <CountTag count=unread_messages />
+    </li>
+    }
 }
 
 fn view_no_chats() -> Html {
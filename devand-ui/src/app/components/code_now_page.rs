@@ -2,7 +2,7 @@ use crate::app::components::common::{Alert, BusyIndicator};
 use crate::app::components::AffinitiesTable;
 use crate::app::workers::{main_worker, main_worker::MainWorker};
 use crate::app::{AppRoute, RouterAnchor};
-use devand_core::CodeNow;
+use devand_core::{AffinityConfig, CodeNow};
 use devand_text::Text;
 use yew::{prelude::*, Properties};
 
@@ -22,7 +22,9 @@ pub struct CodeNowPage {
 }
 
 #[derive(Clone, PartialEq, Properties)]
-pub struct Props {}
+pub struct Props {
+    pub affinity_config: AffinityConfig,
+}
 
 impl Component for CodeNowPage {
     type Message = Msg;
@@ -69,7 +71,7 @@ impl Component for CodeNowPage {
         <h1>{ Text::CodeNow }</h1>
         {
             if let Some(code_now) = &self.state.code_now {
-                view_code_now_users(code_now)
+                view_code_now_users(code_now, self.props.affinity_config)
             } else {
             html! { <BusyIndicator /> }
             }
@@ -79,7 +81,7 @@ impl Component for CodeNowPage {
     }
 }
 
-fn view_code_now_users(code_now: &CodeNow) -> Html {
+fn view_code_now_users(code_now: &CodeNow, affinity_config: AffinityConfig) -> Html {
     let CodeNow {
         all_users,
         current_user,
@@ -89,9 +91,9 @@ fn view_code_now_users(code_now: &CodeNow) -> Html {
         .into_iter()
         .filter(|u| u.username != code_now.current_user.username);
 
-    let user = current_user.into();
     let total_online_users_count = users.clone().count();
-    let mut affinities: Vec<_> = devand_core::calculate_affinities(&user, users).collect();
+    let mut affinities: Vec<_> =
+        devand_core::calculate_affinities(&current_user, users, &affinity_config).collect();
     affinities.sort_unstable_by_key(|x| x.affinity);
 
     if affinities.is_empty() {
@@ -104,7 +106,7 @@ fn view_code_now_users(code_now: &CodeNow) -> Html {
         html! {
         <>
             <p>{ Text::CodeNowTableDescription }</p>
-            <AffinitiesTable affinities=affinities />
+            <AffinitiesTable affinities=affinities affinity_config=affinity_config />
         </>
         }
     }
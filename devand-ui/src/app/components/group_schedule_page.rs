@@ -0,0 +1,358 @@
+use super::schedule_page::view_time_range;
+use crate::app::components::{Alert, AlertLevel, BusyIndicator};
+use crate::app::workers::main_worker::{self, GroupSlotsQuery, MainWorker};
+use crate::app::{AppRoute, RouterAnchor};
+use chrono::Duration;
+use devand_core::schedule_matcher::{GroupSlot, MIN_GROUP_SIZE};
+use devand_core::session::{SessionProposal, MAX_SESSION_HOURS};
+use devand_core::{PublicUserProfile, UserId, UserTimeZone};
+use devand_text::Text;
+use std::collections::{BTreeMap, BTreeSet};
+use yew::{prelude::*, Properties};
+use yewtil::NeqAssign;
+
+/// Choices for the minimum group size, the logged user included
+const MIN_SIZES: [usize; 4] = [MIN_GROUP_SIZE, 4, 5, 6];
+
+/// Find slots where the user is available with a group of other users,
+/// picked from the ones they chat with or by language, and propose a mob
+/// session to all of them
+pub struct GroupSchedulePage {
+    props: Props,
+    state: State,
+    link: ComponentLink<Self>,
+    main_worker: Box<dyn Bridge<MainWorker>>,
+}
+
+#[derive(Clone, PartialEq, Properties)]
+pub struct Props {
+    pub me: PublicUserProfile,
+    pub time_zone: UserTimeZone,
+}
+
+pub enum Msg {
+    MainWorkerRes(main_worker::Response),
+    ToggleUser(UserId),
+    ChangeLanguage(String),
+    ChangeMinSize(String),
+    ProposeSession(usize),
+    Nope,
+}
+
+enum ProposalState {
+    Pending,
+    Proposed,
+    Failed,
+}
+
+#[derive(Default)]
+struct State {
+    /// Users the logged user chats with, who can be picked for the group
+    known_users: BTreeMap<UserId, PublicUserProfile>,
+    /// Other users loaded to show their name
+    users: BTreeMap<UserId, PublicUserProfile>,
+    user_requests: BTreeSet<UserId>,
+    query: GroupSlotsQuery,
+    slots: Option<Vec<GroupSlot>>,
+    proposal: Option<ProposalState>,
+}
+
+impl Component for GroupSchedulePage {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let mut main_worker = MainWorker::bridge(link.callback(Msg::MainWorkerRes));
+        main_worker.send(main_worker::Request::LoadAllChats);
+
+        let state = State {
+            // Without users, the group is made of users who know the
+            // preferred language
+            query: GroupSlotsQuery {
+                language: props.me.languages.keys().next().copied(),
+                ..GroupSlotsQuery::default()
+            },
+            slots: Some(Vec::new()),
+            ..State::default()
+        };
+
+        let mut page = Self {
+            props,
+            state,
+            link,
+            main_worker,
+        };
+
+        page.load_group_slots();
+        page
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Msg::MainWorkerRes(res) => {
+                use main_worker::Response;
+
+                match res {
+                    Response::AllChatsLoaded(chats) => {
+                        let me = self.props.me.id;
+                        self.state.known_users = chats
+                            .0
+                            .into_iter()
+                            .flat_map(|chat| chat.members)
+                            .filter(|user| user.id != me)
+                            .map(|user| (user.id, user))
+                            .collect();
+                        true
+                    }
+                    Response::GroupSlotsFetched(slots) => {
+                        self.load_users(&slots);
+                        self.state.slots = Some(slots);
+                        true
+                    }
+                    Response::PublicUserProfileFetched(user) => {
+                        if self.state.user_requests.contains(&user.id) {
+                            self.state.users.insert(user.id, *user);
+                            true
+                        } else {
+                            false
+                        }
+                    }
+                    Response::SessionUpdated(_) if self.is_proposing() => {
+                        self.state.proposal = Some(ProposalState::Proposed);
+                        true
+                    }
+                    Response::Error(_) if self.is_proposing() => {
+                        self.state.proposal = Some(ProposalState::Failed);
+                        true
+                    }
+                    _ => false,
+                }
+            }
+            Msg::ToggleUser(user) => {
+                let users = &mut self.state.query.users;
+                if let Some(i) = users.iter().position(|&u| u == user) {
+                    users.remove(i);
+                } else {
+                    users.push(user);
+                }
+                self.load_group_slots()
+            }
+            Msg::ChangeLanguage(s) => {
+                self.state.query.language = s.parse().ok();
+                self.load_group_slots()
+            }
+            Msg::ChangeMinSize(s) => {
+                self.state.query.min_size = s.parse().ok();
+                self.load_group_slots()
+            }
+            Msg::ProposeSession(i) => {
+                let me = self.props.me.id;
+                let slot = self.state.slots.as_ref().and_then(|slots| slots.get(i));
+
+                if let Some(slot) = slot {
+                    // Long slots are proposed from their start
+                    let max_end = slot.start + Duration::hours(MAX_SESSION_HOURS);
+                    let proposal = SessionProposal {
+                        invitees: slot.users.iter().copied().filter(|&u| u != me).collect(),
+                        start: slot.start,
+                        end: std::cmp::min(slot.end, max_end),
+                        language: self.state.query.language,
+                    };
+                    self.state.proposal = Some(ProposalState::Pending);
+                    self.main_worker
+                        .send(main_worker::Request::ProposeSession(proposal));
+                }
+                true
+            }
+            Msg::Nope => false,
+        }
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props.neq_assign(props)
+    }
+
+    fn view(&self) -> Html {
+        html! {
+        <>
+        <h1>{ Text::GroupSchedule }</h1>
+        <p>{ Text::GroupScheduleDescription }</p>
+        { self.view_filters() }
+        { self.view_proposal() }
+        {
+        if let Some(slots) = &self.state.slots {
+            self.view_slots(slots)
+        } else {
+            html! { <BusyIndicator /> }
+        }
+        }
+        </>
+        }
+    }
+}
+
+impl GroupSchedulePage {
+    fn load_group_slots(&mut self) -> ShouldRender {
+        let query = &self.state.query;
+
+        // Search by language needs a language
+        if query.users.is_empty() && query.language.is_none() {
+            self.state.slots = Some(Vec::new());
+            return true;
+        }
+
+        self.state.slots = None;
+        self.main_worker
+            .send(main_worker::Request::LoadGroupSlots(query.clone()));
+        true
+    }
+
+    /// Request public profiles of users not already known, but only if
+    /// loading has not already started
+    fn load_users(&mut self, slots: &[GroupSlot]) {
+        let me = self.props.me.id;
+
+        for &user in slots.iter().flat_map(|slot| slot.users.iter()) {
+            let known = user == me || self.state.known_users.contains_key(&user);
+            if !known && self.state.user_requests.insert(user) {
+                self.main_worker
+                    .send(main_worker::Request::LoadPublicUserProfile(user));
+            }
+        }
+    }
+
+    fn is_proposing(&self) -> bool {
+        matches!(self.state.proposal, Some(ProposalState::Pending))
+    }
+
+    fn view_filters(&self) -> Html {
+        let query = &self.state.query;
+        let users_picked = !query.users.is_empty();
+
+        let known_users = self.state.known_users.values().map(|user| {
+            let user_id = user.id;
+            let checked = query.users.contains(&user_id);
+            let id = format!("group_user_{}", user_id.0);
+            html! {
+                <label for=id.clone() class="pure-checkbox devand-group-user">
+                    <input type="checkbox" id=id checked=checked onclick=self.link.callback(move |_| Msg::ToggleUser(user_id)) />
+                    { " " }{ &user.visible_name }
+                </label>
+            }
+        });
+
+        let languages = self.props.me.languages.keys().map(|&language| {
+            let selected = query.language == Some(language);
+            html! { <option value=language.to_string() selected=selected>{ language }</option> }
+        });
+
+        let min_sizes = MIN_SIZES.iter().map(|&min_size| {
+            let selected = query.min_size.unwrap_or(MIN_GROUP_SIZE) == min_size;
+            html! { <option value=min_size.to_string() selected=selected>{ Text::AtLeastUsers(min_size) }</option> }
+        });
+
+        html! {
+            <form class="pure-form pure-form-stacked devand-schedule-filters">
+                <fieldset>
+                <legend>{ Text::PickGroupUsers }</legend>
+                { for known_users }
+                </fieldset>
+                <fieldset>
+                <div class="pure-g">
+                    <div class="pure-u-1 pure-u-md-1-3">
+                        <label for="language">{ Text::Language }</label>
+                        <select id="language" disabled=users_picked onchange=self.on_select(Msg::ChangeLanguage)>
+                            { for languages }
+                        </select>
+                    </div>
+                    <div class="pure-u-1 pure-u-md-1-3">
+                        <label for="min_size">{ Text::GroupSize }</label>
+                        <select id="min_size" onchange=self.on_select(Msg::ChangeMinSize)>
+                            { for min_sizes }
+                        </select>
+                    </div>
+                </div>
+                </fieldset>
+            </form>
+        }
+    }
+
+    fn on_select(&self, msg: fn(String) -> Msg) -> Callback<ChangeData> {
+        self.link.callback(move |cd: ChangeData| {
+            if let ChangeData::Select(se) = cd {
+                msg(se.value())
+            } else {
+                Msg::Nope
+            }
+        })
+    }
+
+    fn view_proposal(&self) -> Html {
+        match self.state.proposal {
+            Some(ProposalState::Pending) => html! { <BusyIndicator /> },
+            Some(ProposalState::Proposed) => html! {
+            <Alert level=AlertLevel::Success>
+                { Text::SessionProposed } <RouterAnchor route=AppRoute::Sessions >{ Text::Sessions }</RouterAnchor>
+            </Alert>
+            },
+            Some(ProposalState::Failed) => html! {
+                <Alert level=AlertLevel::Danger>{ Text::SessionConflict }</Alert>
+            },
+            None => html! {},
+        }
+    }
+
+    fn view_slots(&self, slots: &[GroupSlot]) -> Html {
+        if slots.is_empty() {
+            return html! {
+            <Alert>
+                { Text::NoGroupSlots } <RouterAnchor route=AppRoute::Settings >{ Text::ExtendYourAvailability }</RouterAnchor>
+            </Alert>
+            };
+        }
+
+        let slots = slots
+            .iter()
+            .enumerate()
+            .map(|(i, slot)| html! { <li>{ self.view_slot(i, slot) }</li> });
+
+        html! {
+            <ul class="devand-schedule-slots">
+                { for slots }
+            </ul>
+        }
+    }
+
+    fn view_slot(&self, i: usize, slot: &GroupSlot) -> Html {
+        let me = self.props.me.id;
+        let minutes = slot.duration().num_minutes() as usize;
+
+        let users = slot.users.iter().filter(|&&u| u != me).map(|user_id| {
+            let user = self
+                .state
+                .known_users
+                .get(user_id)
+                .or_else(|| self.state.users.get(user_id));
+
+            match user {
+                Some(user) => html! {
+                    <span class="devand-visible-name devand-group-user"><RouterAnchor route=AppRoute::UserProfile(user.username.clone()) >{ &user.visible_name }</RouterAnchor></span>
+                },
+                None => html! {},
+            }
+        });
+
+        html! {
+            <>
+            <span class="devand-slot-time">
+                { view_time_range(&slot.start, &slot.end, self.props.time_zone) }
+                <span class="devand-slot-duration">{ Text::Minutes(minutes) }</span>
+            </span>
+            <span class="devand-slot-users">
+                { for users }
+                <button class="pure-button devand-propose-session" disabled=self.is_proposing() onclick=self.link.callback(move |_| Msg::ProposeSession(i))>{ Text::ProposeSession }</button>
+            </span>
+            </>
+        }
+    }
+}
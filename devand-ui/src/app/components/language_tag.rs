@@ -1,4 +1,4 @@
-use devand_core::{Language, LanguagePreference, Level, Priority};
+use devand_core::{Intent, Language, LanguagePreference, Level, Priority};
 use yew::{prelude::*, Properties};
 use yewtil::NeqAssign;
 
@@ -41,6 +41,7 @@ impl Component for LanguageTag {
             <span>{ self.props.lang }</span>
             { view_language_level(self.props.pref.level) }
             { view_language_priority(self.props.pref.priority) }
+            { view_language_intent(self.props.pref.intent) }
         </span>
         }
     }
@@ -59,6 +60,19 @@ pub fn view_language_priority(priority: Priority) -> Html {
     }
 }
 
+pub fn view_language_intent(intent: Intent) -> Html {
+    let icon = match intent {
+        Intent::Peer => return html! {},
+        Intent::Learn => "🎓",
+        Intent::Teach => "🧑‍🏫",
+    };
+    let title = format!("{}", intent);
+
+    html! {
+    <span class="devand-language-intent-tag" title=title>{ icon }</span>
+    }
+}
+
 pub fn view_language_level(level: Level) -> Html {
     let stars = (1..=3).map(|x| x <= level.as_number());
     let icon = |on| if on { "★" } else { "☆" };
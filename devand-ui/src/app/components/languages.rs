@@ -1,4 +1,4 @@
-use devand_core::{Language, LanguagePreference, Priority};
+use devand_core::{Intent, Language, LanguagePreference, Priority};
 use devand_text::Text;
 use std::str::FromStr;
 use strum::IntoEnumIterator;
@@ -15,6 +15,7 @@ pub enum Msg {
     ChangeLang(String),
     ChangeLevel(String),
     ChangePriority(String),
+    ChangeIntent(String),
     Add,
     Nope,
 }
@@ -57,6 +58,10 @@ impl Component for AddLanguageComponent {
                 let priority = Priority::from_str(&s).unwrap();
                 self.state.preferences.priority = priority;
             }
+            Msg::ChangeIntent(s) => {
+                let intent = Intent::from_str(&s).unwrap();
+                self.state.preferences.intent = intent;
+            }
             Msg::Add => {
                 if let Some(lang) = self.state.language {
                     let lang_pref = (lang, self.state.preferences.clone());
@@ -81,6 +86,7 @@ impl Component for AddLanguageComponent {
                 { self.view_lang_select() }
                 { self.view_level_select() }
                 { self.view_priority_select() }
+                { self.view_intent_select() }
                 { self.view_add_button() }
             </div>
         }
@@ -100,7 +106,7 @@ impl AddLanguageComponent {
     fn view_lang_select(&self) -> Html {
         let selected_language = self.state.language;
         html! {
-            <div class="pure-u-1 pure-u-sm-1-2">
+            <div class="pure-u-1 pure-u-sm-1-4">
             <label for="add_language">{ "Language: " }</label>
             <select name="add_language" id="add_language" class="pure-u-23-24" onchange=self.link.callback(move |cd: ChangeData| {
                 if let ChangeData::Select(se) = cd {
@@ -166,6 +172,28 @@ impl AddLanguageComponent {
         }
     }
 
+    fn view_intent_select(&self) -> Html {
+        html! {
+            <div class="pure-u-1 pure-u-sm-1-4">
+            <label for="add_intent">{ "Intent: " }</label>
+            <select name="add_intent" id="add_intent" class="pure-u-23-24" onchange=self.link.callback(move |cd: ChangeData| {
+                if let ChangeData::Select(se) = cd {
+                    Msg::ChangeIntent(se.value())
+                } else {
+                    Msg::Nope
+                }
+            } )>
+            { for Intent::iter().map(|x| {
+                html! {
+                    <option value=x.to_string()>{ x }</option>
+                }
+            })
+            }
+            </select>
+            </div>
+        }
+    }
+
     fn on_add(&self) -> Callback<yew::MouseEvent> {
         self.link.callback(|_| Msg::Add)
     }
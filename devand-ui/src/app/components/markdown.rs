@@ -0,0 +1,69 @@
+use devand_core::chat::highlight::{highlight, TokenKind};
+use devand_core::chat::markdown::{self, Block, Inline};
+use devand_core::Language;
+use yew::prelude::*;
+
+fn token_class(kind: TokenKind) -> Option<&'static str> {
+    match kind {
+        TokenKind::Plain => None,
+        TokenKind::Keyword => Some("devand-code-keyword"),
+        TokenKind::String => Some("devand-code-string"),
+        TokenKind::Number => Some("devand-code-number"),
+        TokenKind::Comment => Some("devand-code-comment"),
+    }
+}
+
+fn view_inline(inline: &Inline) -> Html {
+    match inline {
+        Inline::Text(s) => html! { <>{ s }</> },
+        Inline::Code(s) => html! { <code>{ s }</code> },
+        Inline::Strong(s) => html! { <strong>{ s }</strong> },
+        Inline::Emphasis(s) => html! { <em>{ s }</em> },
+        Inline::Link { text, url } => html! {
+            <a href=url.clone() target="_blank" rel="noopener noreferrer nofollow">{ text }</a>
+        },
+        Inline::LineBreak => html! { <br /> },
+    }
+}
+
+fn view_inlines(inlines: &[Inline]) -> Html {
+    html! { <>{ for inlines.iter().map(view_inline) }</> }
+}
+
+fn view_code(language: Option<Language>, code: &str) -> Html {
+    let code = match language {
+        Some(language) => {
+            let tokens =
+                highlight(code, language)
+                    .into_iter()
+                    .map(|token| match token_class(token.kind) {
+                        Some(class) => html! { <span class=class>{ token.text }</span> },
+                        None => html! { <>{ token.text }</> },
+                    });
+            html! { <>{ for tokens }</> }
+        }
+        None => html! { <>{ code }</> },
+    };
+
+    html! { <pre class="devand-code"><code>{ code }</code></pre> }
+}
+
+fn view_block(block: &Block) -> Html {
+    match block {
+        Block::Paragraph(inlines) => html! { <p>{ view_inlines(inlines) }</p> },
+        Block::List(items) => {
+            let items = items
+                .iter()
+                .map(|item| html! { <li>{ view_inlines(item) }</li> });
+            html! { <ul>{ for items }</ul> }
+        }
+        Block::Quote(inlines) => html! { <blockquote>{ view_inlines(inlines) }</blockquote> },
+        Block::Code { language, code } => view_code(*language, code),
+    }
+}
+
+/// Render a chat message written in Markdown. Only elements are created,
+/// so text is always escaped and a message cannot inject HTML.
+pub fn view_markdown(txt: &str) -> Html {
+    html! { <>{ for markdown::parse(txt).iter().map(view_block) }</> }
+}
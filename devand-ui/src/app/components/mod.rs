@@ -1,5 +1,6 @@
 mod affinities_page;
 mod affinities_table;
+mod availability_import;
 mod availability_table;
 mod chat_input;
 mod chat_page;
@@ -7,29 +8,40 @@ mod chats_page;
 mod code_now_page;
 mod common;
 mod editable_language_tag;
+mod group_schedule_page;
 mod language_tag;
 mod languages;
+mod markdown;
 mod not_found_page;
 mod schedule_page;
 mod security_settings_page;
+mod sessions_page;
 mod settings_page;
+mod topic_page;
+mod topics_page;
 mod user_bubble;
 mod user_profile_page;
 
 pub use affinities_page::AffinitiesPage;
 pub use affinities_table::AffinitiesTable;
+pub use availability_import::AvailabilityImport;
 pub use availability_table::AvailabilityTable;
 pub use chat_input::ChatInput;
-pub use chat_page::ChatPage;
+pub use chat_page::{ChatPage, ChatWith};
 pub use chats_page::ChatsPage;
 pub use code_now_page::CodeNowPage;
 pub use common::*;
 pub use editable_language_tag::EditableLanguageTag;
+pub use group_schedule_page::GroupSchedulePage;
 pub use language_tag::LanguageTag;
 pub use languages::AddLanguageComponent;
+pub use markdown::view_markdown;
 pub use not_found_page::NotFoundPage;
 pub use schedule_page::SchedulePage;
 pub use security_settings_page::SecuritySettingsPage;
+pub use sessions_page::SessionsPage;
 pub use settings_page::SettingsPage;
+pub use topic_page::TopicPage;
+pub use topics_page::TopicsPage;
 pub use user_bubble::user_affinity_bubble;
 pub use user_profile_page::UserProfilePage;
@@ -1,15 +1,30 @@
 use crate::app::components::user_affinity_bubble;
-use crate::app::components::{Alert, BusyIndicator};
-use crate::app::workers::{main_worker, main_worker::MainWorker};
+use crate::app::components::{Alert, AlertLevel, BusyIndicator};
+use crate::app::workers::main_worker::{self, AvailabilityMatchQuery, MainWorker};
 use crate::app::AppRoute;
 use crate::app::RouterAnchor;
-use chrono::{DateTime, Utc};
-use devand_core::schedule_matcher::AvailabilityMatch;
-use devand_core::{Affinity, AffinityParams, PublicUserProfile, UserAffinity, UserId};
+use chrono::{DateTime, Duration, Utc};
+use devand_core::schedule_matcher::{AvailabilityMatch, MatchingSession};
+use devand_core::session::{SessionProposal, MAX_SESSION_HOURS};
+use devand_core::{
+    Affinity, AffinityConfig, AffinityParams, PairRoles, PublicUserProfile, UserAffinity, UserId,
+    UserTimeZone,
+};
 use devand_text::Text;
+use std::collections::BTreeMap;
 use yew::{prelude::*, Properties};
 use yewtil::NeqAssign;
 
+/// Choices for the start of the search, in hours from now. The first one is
+/// the server default.
+const START_HOURS: [u32; 3] = [2, 24, 24 * 7];
+
+/// Choices for the search length, in weeks
+const WEEKS: [usize; 4] = [1, 2, 3, 4];
+
+/// Choices for the minimum session length, in minutes
+const MIN_SESSION_MINUTES: [usize; 4] = [0, 60, 120, 180];
+
 pub struct SchedulePage {
     props: Props,
     state: State,
@@ -21,11 +36,26 @@ pub struct SchedulePage {
 #[derive(Clone, PartialEq, Properties)]
 pub struct Props {
     pub me: PublicUserProfile,
+    pub time_zone: UserTimeZone,
+    pub affinity_config: AffinityConfig,
 }
 
 pub enum Msg {
     LoadUser(devand_core::UserId),
     MainWorkerRes(main_worker::Response),
+    ChangeStartHours(String),
+    ChangeWeeks(String),
+    ChangeLanguage(String),
+    ChangeMinAffinity(String),
+    ChangeMinSessionMinutes(String),
+    ProposeSession(UserId, DateTime<Utc>, DateTime<Utc>),
+    Nope,
+}
+
+enum ProposalState {
+    Pending,
+    Proposed,
+    Failed,
 }
 
 #[derive(Default)]
@@ -33,6 +63,8 @@ struct State {
     schedule: Option<AvailabilityMatch>,
     users: std::collections::BTreeMap<UserId, PublicUserProfile>,
     user_requests: std::collections::BTreeSet<UserId>,
+    query: AvailabilityMatchQuery,
+    proposal: Option<ProposalState>,
 }
 
 impl Component for SchedulePage {
@@ -43,7 +75,9 @@ impl Component for SchedulePage {
         let state = State::default();
 
         let mut main_worker = MainWorker::bridge(link.callback(Msg::MainWorkerRes));
-        main_worker.send(main_worker::Request::LoadAvailabilityMatch);
+        main_worker.send(main_worker::Request::LoadAvailabilityMatch(
+            state.query.clone(),
+        ));
 
         Self {
             props,
@@ -67,6 +101,14 @@ impl Component for SchedulePage {
                         self.state.schedule = Some(*schedule);
                         true
                     }
+                    Response::SessionUpdated(_) if self.is_proposing() => {
+                        self.state.proposal = Some(ProposalState::Proposed);
+                        true
+                    }
+                    Response::Error(_) if self.is_proposing() => {
+                        self.state.proposal = Some(ProposalState::Failed);
+                        true
+                    }
                     _ => false,
                 }
             }
@@ -78,6 +120,41 @@ impl Component for SchedulePage {
                 }
                 false
             }
+            Msg::ChangeStartHours(s) => {
+                self.state.query.start_hours = s.parse().ok();
+                self.load_availability_match()
+            }
+            Msg::ChangeWeeks(s) => {
+                self.state.query.days = s.parse::<usize>().ok().map(|weeks| weeks * 7);
+                self.load_availability_match()
+            }
+            Msg::ChangeLanguage(s) => {
+                self.state.query.language = s.parse().ok();
+                self.load_availability_match()
+            }
+            Msg::ChangeMinAffinity(s) => {
+                self.state.query.min_affinity = s.parse().ok();
+                self.load_availability_match()
+            }
+            Msg::ChangeMinSessionMinutes(s) => {
+                self.state.query.min_session_minutes = s.parse().ok();
+                self.load_availability_match()
+            }
+            Msg::ProposeSession(user, start, end) => {
+                // Long ranges are proposed from their start
+                let end = std::cmp::min(end, start + Duration::hours(MAX_SESSION_HOURS));
+                let proposal = SessionProposal {
+                    invitees: vec![user],
+                    start,
+                    end,
+                    language: self.state.query.language,
+                };
+                self.state.proposal = Some(ProposalState::Pending);
+                self.main_worker
+                    .send(main_worker::Request::ProposeSession(proposal));
+                true
+            }
+            Msg::Nope => false,
         }
     }
 
@@ -91,6 +168,9 @@ impl Component for SchedulePage {
         <h1>{ Text::Schedule }</h1>
         <p>{ "Here you find a list of users available at the same time as you." }</p>
         <p>{ "Just choose someone to pair-program with and start chatting" }</p>
+        <p><RouterAnchor route=AppRoute::GroupSchedule >{ Text::FindGroup }</RouterAnchor></p>
+        { self.view_filters() }
+        { self.view_proposal() }
         {
         if let Some(schedule) = &self.state.schedule {
             self.view_schedule(schedule)
@@ -104,14 +184,139 @@ impl Component for SchedulePage {
 }
 
 impl SchedulePage {
+    fn load_availability_match(&mut self) -> ShouldRender {
+        self.state.schedule = None;
+        self.main_worker
+            .send(main_worker::Request::LoadAvailabilityMatch(
+                self.state.query.clone(),
+            ));
+        true
+    }
+
     fn view_schedule(&self, schedule: &AvailabilityMatch) -> Html {
         if schedule.slots.is_empty() {
             self.view_no_slots()
         } else {
-            self.view_slots(&schedule.slots)
+            // Slots are already filtered by session length
+            self.view_sessions(&schedule.sessions(Duration::zero()))
+        }
+    }
+
+    fn is_proposing(&self) -> bool {
+        matches!(self.state.proposal, Some(ProposalState::Pending))
+    }
+
+    fn view_proposal(&self) -> Html {
+        match self.state.proposal {
+            Some(ProposalState::Pending) => html! { <BusyIndicator /> },
+            Some(ProposalState::Proposed) => html! {
+            <Alert level=AlertLevel::Success>
+                { Text::SessionProposed } <RouterAnchor route=AppRoute::Sessions >{ Text::Sessions }</RouterAnchor>
+            </Alert>
+            },
+            Some(ProposalState::Failed) => html! {
+                <Alert level=AlertLevel::Danger>{ Text::SessionConflict }</Alert>
+            },
+            None => html! {},
+        }
+    }
+
+    fn view_filters(&self) -> Html {
+        let query = &self.state.query;
+
+        let start_hours = START_HOURS.iter().map(|&hours| {
+            let selected = query.start_hours.unwrap_or(START_HOURS[0]) == hours;
+            let label = if hours < 24 {
+                Text::InHours(hours as usize)
+            } else {
+                Text::InDays(hours as usize / 24)
+            };
+            html! { <option value=hours.to_string() selected=selected>{ label }</option> }
+        });
+
+        let weeks = WEEKS.iter().map(|&weeks| {
+            let selected = query.days.unwrap_or(7) == weeks * 7;
+            html! { <option value=weeks.to_string() selected=selected>{ Text::Weeks(weeks) }</option> }
+        });
+
+        let any_language = query.language.is_none();
+        let languages = self.props.me.languages.keys().map(|&language| {
+            let selected = query.language == Some(language);
+            html! { <option value=language.to_string() selected=selected>{ language }</option> }
+        });
+
+        let config = &self.props.affinity_config;
+        let affinities = vec![
+            (0.0, Text::AnyAffinity),
+            (config.medium_affinity_threshold, Text::MediumAffinity),
+            (config.high_affinity_threshold, Text::HighAffinity),
+        ];
+        let affinities = affinities.into_iter().map(|(min_affinity, label)| {
+            let selected = query.min_affinity.unwrap_or(0.0) == min_affinity;
+            html! { <option value=min_affinity.to_string() selected=selected>{ label }</option> }
+        });
+
+        let min_session_minutes = MIN_SESSION_MINUTES.iter().map(|&minutes| {
+            let selected = query.min_session_minutes.unwrap_or(0) == minutes;
+            let label = if minutes == 0 {
+                Text::AnyLength
+            } else {
+                Text::Minutes(minutes)
+            };
+            html! { <option value=minutes.to_string() selected=selected>{ label }</option> }
+        });
+
+        html! {
+            <form class="pure-form pure-form-stacked devand-schedule-filters">
+                <fieldset>
+                <div class="pure-g">
+                    <div class="pure-u-1 pure-u-md-1-5">
+                        <label for="start_hours">{ Text::Starting }</label>
+                        <select id="start_hours" onchange=self.on_select(Msg::ChangeStartHours)>
+                            { for start_hours }
+                        </select>
+                    </div>
+                    <div class="pure-u-1 pure-u-md-1-5">
+                        <label for="weeks">{ Text::LookAhead }</label>
+                        <select id="weeks" onchange=self.on_select(Msg::ChangeWeeks)>
+                            { for weeks }
+                        </select>
+                    </div>
+                    <div class="pure-u-1 pure-u-md-1-5">
+                        <label for="language">{ Text::Language }</label>
+                        <select id="language" onchange=self.on_select(Msg::ChangeLanguage)>
+                            <option value="" selected=any_language>{ Text::AnyLanguage }</option>
+                            { for languages }
+                        </select>
+                    </div>
+                    <div class="pure-u-1 pure-u-md-1-5">
+                        <label for="min_affinity">{ Text::MinAffinity }</label>
+                        <select id="min_affinity" onchange=self.on_select(Msg::ChangeMinAffinity)>
+                            { for affinities }
+                        </select>
+                    </div>
+                    <div class="pure-u-1 pure-u-md-1-5">
+                        <label for="min_session_minutes">{ Text::MinSessionLength }</label>
+                        <select id="min_session_minutes" onchange=self.on_select(Msg::ChangeMinSessionMinutes)>
+                            { for min_session_minutes }
+                        </select>
+                    </div>
+                </div>
+                </fieldset>
+            </form>
         }
     }
 
+    fn on_select(&self, msg: fn(String) -> Msg) -> Callback<ChangeData> {
+        self.link.callback(move |cd: ChangeData| {
+            if let ChangeData::Select(se) = cd {
+                msg(se.value())
+            } else {
+                Msg::Nope
+            }
+        })
+    }
+
     fn view_no_slots(&self) -> Html {
         html! {
         <Alert>
@@ -128,35 +333,46 @@ impl SchedulePage {
         }
     }
 
-    fn view_slots(&self, slots: &[(DateTime<Utc>, Vec<UserId>)]) -> Html {
-        let mut at_least_one_non_empty_slot = false;
+    fn view_sessions(&self, sessions: &[MatchingSession]) -> Html {
+        if sessions.is_empty() {
+            return self.view_no_slots();
+        }
 
-        let slots: Vec<_> = slots
-            .iter()
-            .map(|(t, users)| {
-                let mut users: Vec<_> = users
-                    .iter()
-                    .filter_map(|&u| self.expand_user(u))
-                    .filter(|u| !u.affinity.is_zero())
-                    .collect();
+        // Users available in the same range are shown together, already
+        // sorted by affinity
+        let mut ranges: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        for session in sessions {
+            ranges
+                .entry((session.start, session.end))
+                .or_default()
+                .push((session.user, session.affinity));
+        }
 
-                at_least_one_non_empty_slot |= !users.is_empty();
+        let mut at_least_one_non_empty_range = false;
+
+        let ranges: Vec<_> = ranges
+            .into_iter()
+            .map(|(range, users)| {
+                let users: Vec<_> = users
+                    .into_iter()
+                    .filter_map(|(u, affinity)| self.expand_user(u, affinity))
+                    .collect();
 
-                users.sort_by_key(|u| std::cmp::Reverse(u.affinity));
+                at_least_one_non_empty_range |= !users.is_empty();
 
-                (t, users)
+                (range, users)
             })
             .filter(|(_, users)| !users.is_empty())
             .collect();
 
-        let slots_view = slots
+        let ranges_view = ranges
             .into_iter()
-            .map(|(t, users)| html! { <li> { self.view_slot(t, users) } </li> });
+            .map(|(range, users)| html! { <li> { self.view_range(range, users) } </li> });
 
-        if at_least_one_non_empty_slot {
+        if at_least_one_non_empty_range {
             html! {
                 <ul class="devand-schedule-slots">
-                    { for slots_view }
+                    { for ranges_view }
                 </ul>
             }
         } else {
@@ -164,30 +380,51 @@ impl SchedulePage {
         }
     }
 
-    fn view_slot(&self, t: &DateTime<Utc>, users: Vec<UserAffinity>) -> Html {
+    fn view_range(
+        &self,
+        (start, end): (DateTime<Utc>, DateTime<Utc>),
+        users: Vec<UserAffinity>,
+    ) -> Html {
+        let minutes = (end - start).num_minutes() as usize;
         html! {
             <>
-            <span class="devand-slot-time">{ view_timestamp(t) }</span>
+            <span class="devand-slot-time">
+                { view_time_range(&start, &end, self.props.time_zone) }
+                <span class="devand-slot-duration">{ Text::Minutes(minutes) }</span>
+            </span>
             <span class="devand-slot-users">
-            { for users.into_iter().map(|u| user_affinity_bubble(&u)) }
+            { for users.into_iter().map(|u| self.view_user(u, start, end)) }
             </span>
             </>
         }
     }
 
-    fn expand_user(&self, user_id: UserId) -> Option<UserAffinity> {
+    fn view_user(&self, user: UserAffinity, start: DateTime<Utc>, end: DateTime<Utc>) -> Html {
+        let user_id = user.user.id;
+        let onclick = self
+            .link
+            .callback(move |_| Msg::ProposeSession(user_id, start, end));
+        let pending = self.is_proposing();
+
+        html! {
+            <span class="devand-slot-user">
+                { user_affinity_bubble(&user, &self.props.affinity_config) }
+                <button class="pure-button devand-propose-session" disabled=pending onclick=onclick>{ Text::ProposeSession }</button>
+            </span>
+        }
+    }
+
+    fn expand_user(&self, user_id: UserId, affinity: Affinity) -> Option<UserAffinity> {
         if let Some(user) = self.state.users.get(&user_id) {
             let my_aff_params =
                 AffinityParams::new().with_languages(self.props.me.languages.clone());
 
             let u_aff_params = AffinityParams::new().with_languages(user.languages.clone());
-            let affinity = Affinity::from_params(&my_aff_params, &u_aff_params);
+            let roles =
+                PairRoles::from_params(&my_aff_params, &u_aff_params, &self.props.affinity_config);
 
             // TODO [optimization] Avoid clone
-            Some(UserAffinity {
-                user: user.clone(),
-                affinity,
-            })
+            Some(UserAffinity::new(user.clone(), affinity).with_roles(roles))
         } else {
             // Load user public profile, but only if loading has not already started
             self.link.send_message(Msg::LoadUser(user_id));
@@ -196,8 +433,14 @@ impl SchedulePage {
     }
 }
 
-fn view_timestamp(t: &chrono::DateTime<chrono::Utc>) -> impl ToString {
-    t.format("%A, %B %d - %R UTC")
+pub(crate) fn view_time_range(
+    start: &chrono::DateTime<chrono::Utc>,
+    end: &chrono::DateTime<chrono::Utc>,
+    time_zone: UserTimeZone,
+) -> String {
+    let start = start.with_timezone(&*time_zone).format("%A, %B %d - %R");
+    let end = end.with_timezone(&*time_zone).format("%R %Z");
+    format!("{}-{}", start, end)
 }
 
 #[cfg(test)]
@@ -208,7 +451,18 @@ mod test {
     fn timestamp_format() {
         use chrono::offset::TimeZone;
         let t = chrono::Utc.ymd(2020, 8, 2).and_hms_milli(20, 0, 1, 444);
-        let formatted_t = view_timestamp(&t);
-        assert_eq!("Sunday, August 02 - 20:00 UTC", formatted_t.to_string());
+        let end = t + Duration::hours(2);
+        let formatted_t = view_time_range(&t, &end, UserTimeZone::default());
+        assert_eq!("Sunday, August 02 - 20:00-22:00 UTC", formatted_t);
+    }
+
+    #[test]
+    fn timestamp_format_local() {
+        use chrono::offset::TimeZone;
+        let t = chrono::Utc.ymd(2020, 8, 2).and_hms_milli(20, 0, 1, 444);
+        let end = t + Duration::hours(2);
+        let time_zone = "Europe/Rome".parse().unwrap();
+        let formatted_t = view_time_range(&t, &end, time_zone);
+        assert_eq!("Sunday, August 02 - 22:00-00:00 CEST", formatted_t);
     }
 }
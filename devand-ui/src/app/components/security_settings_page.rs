@@ -1,4 +1,4 @@
-use crate::app::components::{Alert, AlertLevel};
+use crate::app::components::{Alert, AlertLevel, BusyIndicator};
 use crate::app::workers::{main_worker, main_worker::MainWorker};
 use devand_text::Text;
 use yew::prelude::*;
@@ -16,6 +16,8 @@ pub enum Msg {
     SetRepeatNewPassword(String),
     CheckOldPassword,
     ChangePassword,
+    RegenerateCalendarFeed,
+    RevokeCalendarFeed,
 }
 
 #[derive(Default)]
@@ -26,6 +28,8 @@ struct State {
     old_password_ok: Option<bool>,
     password_changed: Option<bool>,
     generic_alert: Option<String>,
+    /// Url of the calendar feed, if enabled. None while loading.
+    calendar_feed: Option<Option<String>>,
 }
 
 impl Component for SecuritySettingsPage {
@@ -34,7 +38,8 @@ impl Component for SecuritySettingsPage {
 
     fn create(_props: Self::Properties, link: ComponentLink<Self>) -> Self {
         let state = State::default();
-        let main_worker = MainWorker::bridge(link.callback(Msg::MainWorkerRes));
+        let mut main_worker = MainWorker::bridge(link.callback(Msg::MainWorkerRes));
+        main_worker.send(main_worker::Request::LoadCalendarFeed);
 
         SecuritySettingsPage {
             link,
@@ -76,6 +81,18 @@ impl Component for SecuritySettingsPage {
                 ));
                 false
             }
+            Msg::RegenerateCalendarFeed => {
+                self.state.calendar_feed = None;
+                self.main_worker
+                    .send(main_worker::Request::RegenerateCalendarFeed);
+                true
+            }
+            Msg::RevokeCalendarFeed => {
+                self.state.calendar_feed = None;
+                self.main_worker
+                    .send(main_worker::Request::RevokeCalendarFeed);
+                true
+            }
             Msg::MainWorkerRes(res) => {
                 use main_worker::Response;
                 match res {
@@ -87,6 +104,10 @@ impl Component for SecuritySettingsPage {
                         self.state.password_changed = Some(true);
                         true
                     }
+                    Response::CalendarFeedFetched(url) => {
+                        self.state.calendar_feed = Some(url);
+                        true
+                    }
                     Response::Error(e) => {
                         self.state.generic_alert = Some(e);
                         true
@@ -168,12 +189,42 @@ impl Component for SecuritySettingsPage {
                     }
                 }
             </fieldset>
+            { self.view_calendar_feed() }
         </div>
         </>
         }
     }
 }
 
+impl SecuritySettingsPage {
+    fn view_calendar_feed(&self) -> Html {
+        let feed = match &self.state.calendar_feed {
+            Some(Some(url)) => html! {
+                <>
+                <input type="text" class="pure-input-1" value=url.clone() readonly=true />
+                <span class="pure-form-message-inline">{ Text::CalendarFeedDescription }</span>
+                <button class="pure-button" onclick=self.link.callback(|_| Msg::RegenerateCalendarFeed)>{ Text::RegenerateCalendarFeed }</button>
+                <button class="pure-button button-warning" onclick=self.link.callback(|_| Msg::RevokeCalendarFeed)>{ Text::RevokeCalendarFeed }</button>
+                </>
+            },
+            Some(None) => html! {
+                <>
+                <Alert>{ Text::CalendarFeedDisabled }</Alert>
+                <button class="pure-button" onclick=self.link.callback(|_| Msg::RegenerateCalendarFeed)>{ Text::EnableCalendarFeed }</button>
+                </>
+            },
+            None => html! { <BusyIndicator /> },
+        };
+
+        html! {
+            <fieldset>
+                <legend>{ Text::CalendarFeed }</legend>
+                { feed }
+            </fieldset>
+        }
+    }
+}
+
 fn check_new_password(
     new_password: &str,
     repeat_new_password: &str,
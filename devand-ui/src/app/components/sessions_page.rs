@@ -0,0 +1,238 @@
+use super::schedule_page::view_time_range;
+use crate::app::components::{Alert, AlertLevel, BusyIndicator};
+use crate::app::workers::{main_worker, main_worker::MainWorker};
+use crate::app::{AppRoute, RouterAnchor};
+use devand_core::session::{PairingSession, SessionId, SessionStatus};
+use devand_core::{PublicUserProfile, UserId, UserTimeZone};
+use devand_text::Text;
+use std::collections::{BTreeMap, BTreeSet};
+use yew::prelude::*;
+use yewtil::NeqAssign;
+
+/// Sessions the user proposed or is invited to, with buttons to answer or
+/// cancel them
+pub struct SessionsPage {
+    props: Props,
+    state: State,
+    link: ComponentLink<Self>,
+    main_worker: Box<dyn Bridge<MainWorker>>,
+}
+
+#[derive(Clone, PartialEq, Properties)]
+pub struct Props {
+    pub me: PublicUserProfile,
+    pub time_zone: UserTimeZone,
+}
+
+pub enum Msg {
+    MainWorkerRes(main_worker::Response),
+    SetStatus(SessionId, SessionStatus),
+}
+
+#[derive(Default)]
+struct State {
+    sessions: Option<Vec<PairingSession>>,
+    users: BTreeMap<UserId, PublicUserProfile>,
+    user_requests: BTreeSet<UserId>,
+    /// Waiting for the server to change a session
+    pending: bool,
+    not_changed: bool,
+}
+
+impl Component for SessionsPage {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let mut main_worker = MainWorker::bridge(link.callback(Msg::MainWorkerRes));
+        main_worker.send(main_worker::Request::LoadSessions);
+
+        Self {
+            props,
+            state: State::default(),
+            link,
+            main_worker,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Msg::MainWorkerRes(res) => {
+                use main_worker::Response;
+
+                match res {
+                    Response::SessionsFetched(sessions) => {
+                        self.load_members(&sessions);
+                        self.state.sessions = Some(sessions);
+                        true
+                    }
+                    Response::SessionUpdated(updated) => {
+                        self.state.pending = false;
+                        if let Some(sessions) = &mut self.state.sessions {
+                            for session in sessions.iter_mut().filter(|s| s.id == updated.id) {
+                                *session = (*updated).clone();
+                            }
+                        }
+                        true
+                    }
+                    Response::PublicUserProfileFetched(user) => {
+                        if self.state.user_requests.contains(&user.id) {
+                            self.state.users.insert(user.id, *user);
+                            true
+                        } else {
+                            false
+                        }
+                    }
+                    Response::Error(_) if self.state.pending => {
+                        self.state.pending = false;
+                        self.state.not_changed = true;
+                        true
+                    }
+                    _ => false,
+                }
+            }
+            Msg::SetStatus(id, status) => {
+                self.state.pending = true;
+                self.state.not_changed = false;
+                self.main_worker
+                    .send(main_worker::Request::SetSessionStatus(id, status));
+                true
+            }
+        }
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props.neq_assign(props)
+    }
+
+    fn view(&self) -> Html {
+        html! {
+        <>
+        <h1>{ Text::Sessions }</h1>
+        <p>{ Text::SessionsDescription }</p>
+        {
+            if self.state.not_changed {
+                html! { <Alert level=AlertLevel::Danger>{ Text::SessionNotChanged }</Alert> }
+            } else {
+                html! {}
+            }
+        }
+        {
+            if let Some(sessions) = &self.state.sessions {
+                self.view_sessions(sessions)
+            } else {
+                html! { <BusyIndicator /> }
+            }
+        }
+        </>
+        }
+    }
+}
+
+impl SessionsPage {
+    /// Request public profiles of members, but only if loading has not
+    /// already started
+    fn load_members(&mut self, sessions: &[PairingSession]) {
+        let me = self.props.me.id;
+
+        for member in sessions.iter().flat_map(|s| s.members()) {
+            if member != me && self.state.user_requests.insert(member) {
+                self.main_worker
+                    .send(main_worker::Request::LoadPublicUserProfile(member));
+            }
+        }
+    }
+
+    fn view_sessions(&self, sessions: &[PairingSession]) -> Html {
+        if sessions.is_empty() {
+            return html! {
+            <Alert>
+                { Text::NoSessions } <RouterAnchor route=AppRoute::Schedule >{ Text::Schedule }</RouterAnchor>
+            </Alert>
+            };
+        }
+
+        html! {
+            <ul class="devand-sessions">
+                { for sessions.iter().map(|s| self.view_session(s)) }
+            </ul>
+        }
+    }
+
+    fn view_session(&self, session: &PairingSession) -> Html {
+        let me = self.props.me.id;
+
+        let others: Vec<_> = session
+            .members()
+            .filter(|&member| member != me)
+            .filter_map(|member| self.state.users.get(&member))
+            .map(|user| user.visible_name.as_str())
+            .collect();
+
+        let minutes = (session.end - session.start).num_minutes() as usize;
+
+        let actions = session_actions(session, me).into_iter().map(|status| {
+            let id = session.id;
+            let onclick = self.link.callback(move |_| Msg::SetStatus(id, status));
+            html! {
+                <button class="pure-button" disabled=self.state.pending onclick=onclick>{ action_text(status) }</button>
+            }
+        });
+
+        html! {
+            <li class=classes!("devand-session", status_class(session.status))>
+                <span class="devand-slot-time">
+                    { view_time_range(&session.start, &session.end, self.props.time_zone) }
+                    <span class="devand-slot-duration">{ Text::Minutes(minutes) }</span>
+                </span>
+                <span class="devand-session-status">{ status_text(session.status) }</span>
+                <span class="devand-session-members">{ Text::SessionWith(&others.join(", ")) }</span>
+                {
+                    if let Some(language) = session.language {
+                        html! { <span class="devand-session-language">{ language }</span> }
+                    } else {
+                        html! {}
+                    }
+                }
+                <span class="devand-session-actions">{ for actions }</span>
+            </li>
+        }
+    }
+}
+
+/// Status changes offered to `me`. The server checks them again, including
+/// the time of the session.
+fn session_actions(session: &PairingSession, me: UserId) -> Vec<SessionStatus> {
+    use SessionStatus::*;
+
+    match session.status {
+        Proposed if session.invitees.contains(&me) => vec![Accepted, Declined],
+        Proposed => vec![Cancelled],
+        Accepted => vec![Done, Cancelled],
+        Declined | Cancelled | Done => vec![],
+    }
+}
+
+fn status_text(status: SessionStatus) -> Text<'static> {
+    match status {
+        SessionStatus::Proposed => Text::SessionStatusProposed,
+        SessionStatus::Accepted => Text::SessionStatusAccepted,
+        SessionStatus::Declined => Text::SessionStatusDeclined,
+        SessionStatus::Cancelled => Text::SessionStatusCancelled,
+        SessionStatus::Done => Text::SessionStatusDone,
+    }
+}
+
+fn action_text(status: SessionStatus) -> Text<'static> {
+    match status {
+        SessionStatus::Accepted => Text::AcceptSession,
+        SessionStatus::Declined => Text::DeclineSession,
+        SessionStatus::Cancelled => Text::CancelSession,
+        SessionStatus::Done => Text::MarkSessionDone,
+        SessionStatus::Proposed => Text::EmptyString,
+    }
+}
+
+fn status_class(status: SessionStatus) -> String {
+    format!("devand-session-{}", status)
+}
@@ -5,24 +5,36 @@ use devand_core::*;
 use devand_text::Text;
 use yew::prelude::*;
 
-use crate::app::components::AvailabilityTable;
+use crate::app::components::{AvailabilityImport, AvailabilityTable};
 
 pub enum Msg {
     UpdateVisibleName(String),
     UpdateBio(String),
     UpdateProjects(String),
     UpdateEmail(String),
+    UpdateTimeZone(String),
     ToggleVacationMode,
+    ToggleWeeklyPairing,
+    UpdateVacationStart(String),
+    UpdateVacationEnd(String),
     ToggleSpokenLanguage(SpokenLanguage),
+    UpdateSpokenLanguageProficiency(SpokenLanguage, String),
+    ToggleSpokenLanguagePreferred(SpokenLanguage),
+    ToggleRequireCommonSpokenLanguage,
+    UpdateNewTopic(String),
+    AddTopic,
+    RemoveTopic(Topic),
     AddLanguage((Language, LanguagePreference)),
     RemoveLanguage(Language),
     UpdateSchedule(Availability),
     VerifyAddress,
+    Nope,
 }
 
 pub struct SettingsPage {
     props: Props,
     link: ComponentLink<Self>,
+    new_topic: String,
 }
 
 #[derive(Clone, Properties)]
@@ -38,7 +50,11 @@ impl Component for SettingsPage {
     type Properties = Props;
 
     fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
-        SettingsPage { props, link }
+        SettingsPage {
+            props,
+            link,
+            new_topic: String::default(),
+        }
     }
 
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
@@ -46,15 +62,55 @@ impl Component for SettingsPage {
             Msg::ToggleSpokenLanguage(lang) => {
                 self.update_user(move |user| {
                     let spoken_languages = &mut user.settings.spoken_languages;
-                    let is_set = spoken_languages.0.contains(&lang);
+                    let is_set = spoken_languages.contains_key(&lang);
 
                     if is_set {
-                        spoken_languages.0.remove(&lang);
+                        spoken_languages.remove(&lang);
                     } else {
-                        spoken_languages.0.insert(lang);
+                        spoken_languages.insert(lang, SpokenLanguagePreference::default());
                     }
                 });
             }
+            Msg::UpdateSpokenLanguageProficiency(lang, s) => {
+                if let Ok(proficiency) = s.parse() {
+                    self.update_user(move |user| {
+                        if let Some(pref) = user.settings.spoken_languages.get_mut(&lang) {
+                            pref.proficiency = proficiency;
+                        }
+                    });
+                }
+            }
+            Msg::ToggleSpokenLanguagePreferred(lang) => {
+                self.update_user(move |user| {
+                    if let Some(pref) = user.settings.spoken_languages.get_mut(&lang) {
+                        pref.preferred ^= true;
+                    }
+                });
+            }
+            Msg::ToggleRequireCommonSpokenLanguage => {
+                self.update_user(move |user| {
+                    user.settings.require_common_spoken_language ^= true;
+                });
+            }
+            Msg::UpdateNewTopic(s) => {
+                self.new_topic = s;
+            }
+            Msg::AddTopic => {
+                // Invalid topics are ignored, the input is left as is
+                if let Ok(topic) = self.new_topic.parse::<Topic>() {
+                    self.new_topic.clear();
+                    self.update_user(move |user| {
+                        if user.settings.topics.len() < Topics::MAX_LEN {
+                            user.settings.topics.insert(topic);
+                        }
+                    });
+                }
+            }
+            Msg::RemoveTopic(topic) => {
+                self.update_user(move |user| {
+                    user.settings.topics.remove(&topic);
+                });
+            }
             Msg::AddLanguage((lang, preferences)) => {
                 self.update_user(move |user| {
                     user.settings.languages.insert(lang, preferences);
@@ -86,11 +142,33 @@ impl Component for SettingsPage {
                     user.email = devand_core::string_utils::trimlow(s);
                 });
             }
+            Msg::UpdateTimeZone(s) => {
+                if let Ok(time_zone) = s.parse() {
+                    self.update_user(move |user| {
+                        user.settings.time_zone = time_zone;
+                    });
+                }
+            }
             Msg::ToggleVacationMode => {
                 self.update_user(move |user| {
                     user.settings.vacation_mode ^= true;
                 });
             }
+            Msg::ToggleWeeklyPairing => {
+                self.update_user(move |user| {
+                    user.settings.weekly_pairing ^= true;
+                });
+            }
+            Msg::UpdateVacationStart(s) => {
+                self.update_user(move |user| {
+                    user.settings.vacation.start = parse_date(&s);
+                });
+            }
+            Msg::UpdateVacationEnd(s) => {
+                self.update_user(move |user| {
+                    user.settings.vacation.end = parse_date(&s);
+                });
+            }
             Msg::UpdateSchedule(schedule) => {
                 self.update_user(move |user| {
                     user.settings.schedule = schedule;
@@ -99,6 +177,9 @@ impl Component for SettingsPage {
             Msg::VerifyAddress => {
                 self.props.on_verify_email.emit(());
             }
+            Msg::Nope => {
+                return false;
+            }
         }
 
         true
@@ -125,12 +206,14 @@ impl Component for SettingsPage {
     }
 }
 
-fn view_vacation_mode_panel() -> Html {
-    html! {
-        <fieldset class="pure-u-1">
-            <legend>{ Text::VacationModeEnabled }</legend>
-        </fieldset>
-    }
+/// An empty or invalid date is considered not set
+fn parse_date(s: &str) -> Option<chrono::NaiveDate> {
+    chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d").ok()
+}
+
+fn format_date(date: Option<chrono::NaiveDate>) -> String {
+    date.map(|d| d.format("%Y-%m-%d").to_string())
+        .unwrap_or_default()
 }
 
 impl SettingsPage {
@@ -145,18 +228,42 @@ impl SettingsPage {
                 { self.view_profile_panel(user) }
                 {
                     if user.settings.vacation_mode {
-                        view_vacation_mode_panel()
+                        self.view_vacation_mode_panel(&settings.vacation)
                     } else {
                         self.view_availability_panel(&settings.schedule)
                     }
                 }
                 { self.view_languages_panel(&settings.languages) }
-                { self.view_spoken_languages_panel(&settings.spoken_languages) }
+                { self.view_topics_panel(&settings.topics) }
+                { self.view_spoken_languages_panel(&settings.spoken_languages, settings.require_common_spoken_language) }
             </div>
             </>
         }
     }
 
+    fn view_vacation_mode_panel(&self, vacation: &Vacation) -> Html {
+        html! {
+            <fieldset class="pure-u-1">
+                <legend>{ Text::VacationModeEnabled }</legend>
+                <div class="pure-control-group">
+                    <label for="vacation_start">{ Text::VacationStart }</label>
+                    <input type="date" name="vacation_start" id="vacation_start" value=format_date(vacation.start) oninput=self.link.callback(move |e: InputData| Msg::UpdateVacationStart(e.value)) />
+                </div>
+                <div class="pure-control-group">
+                    <label for="vacation_end">{ Text::VacationEnd }</label>
+                    <input type="date" name="vacation_end" id="vacation_end" value=format_date(vacation.end) min=format_date(vacation.start) oninput=self.link.callback(move |e: InputData| Msg::UpdateVacationEnd(e.value)) />
+                </div>
+                {
+                    if vacation.is_valid() {
+                        html! {}
+                    } else {
+                        html! { <Alert level=AlertLevel::Warning>{ Text::VacationEndsBeforeStart }</Alert> }
+                    }
+                }
+            </fieldset>
+        }
+    }
+
     fn view_verify_email_button(&self, user: &User) -> Html {
         if !user.email_verified && !self.props.verifying_email {
             html! {
@@ -211,13 +318,41 @@ impl SettingsPage {
                     <textarea name="projects" class="pure-input-1" id="projects" value=user.projects.clone().join("\n") oninput=self.link.callback(move |e: InputData| Msg::UpdateProjects(e.value)) />
                     <span class="pure-form-message-inline">{ Text::ProjectsInputHint }</span>
                 </div>
+                { self.view_time_zone_select(user.settings.time_zone) }
                 <div class="pure-control-group">
                     <label for="vacation_mode" class="pure-checkbox"><input type="checkbox" id="vacation_mode" checked=user.settings.vacation_mode onclick=self.link.callback(move |_| Msg::ToggleVacationMode) />{ Text::VacationMode }</label>
                 </div>
+                <div class="pure-control-group">
+                    <label for="weekly_pairing" class="pure-checkbox"><input type="checkbox" id="weekly_pairing" checked=user.settings.weekly_pairing onclick=self.link.callback(move |_| Msg::ToggleWeeklyPairing) />{ Text::WeeklyPairing }</label>
+                </div>
             </fieldset>
         }
     }
 
+    fn view_time_zone_select(&self, time_zone: UserTimeZone) -> Html {
+        let on_change = self.link.callback(move |cd: ChangeData| {
+            if let ChangeData::Select(se) = cd {
+                Msg::UpdateTimeZone(se.value())
+            } else {
+                Msg::Nope
+            }
+        });
+
+        let options = UserTimeZone::iter().map(|tz| {
+            let selected = tz == time_zone;
+            html! { <option value=tz.to_string() selected=selected>{ tz }</option> }
+        });
+
+        html! {
+            <div class="pure-control-group">
+                <label for="time_zone">{ Text::TimeZone }</label>
+                <select name="time_zone" id="time_zone" class="pure-input-1" onchange=on_change>
+                    { for options }
+                </select>
+            </div>
+        }
+    }
+
     fn view_languages_panel(&self, languages: &Languages) -> Html {
         let languages_tags = languages.iter().map(|(&lang, pref)| {
             html! {
@@ -251,23 +386,57 @@ impl SettingsPage {
         }
     }
 
-    fn view_spoken_languages_panel(&self, spoken_languages: &SpokenLanguages) -> Html {
+    fn view_topics_panel(&self, topics: &Topics) -> Html {
+        let topics_tags = topics.iter().cloned().map(|topic| {
+            html! {
+            <span class="devand-topic-tag">
+                <button class="pure-button" onclick=self.link.callback(move |_| Msg::RemoveTopic(topic.clone()))>{ "✖" }</button>
+                <span>{ &topic }</span>
+            </span>
+            }
+        });
+
+        html! {
+            <fieldset class="pure-u-1">
+                <div class="pure-g">
+                    <legend class="pure-u-1">{ Text::Topics }</legend>
+                    <div class="pure-u-1">
+                        { for topics_tags }
+                    </div>
+                    <div class="pure-u-1">
+                        <input type="text" id="new_topic" value=self.new_topic.clone() oninput=self.link.callback(move |e: InputData| Msg::UpdateNewTopic(e.value)) />
+                        <button class="pure-button" onclick=self.link.callback(|_| Msg::AddTopic)>{ Text::AddTopic }</button>
+                        <span class="pure-form-message-inline">{ Text::TopicsDescription }</span>
+                    </div>
+                </div>
+            </fieldset>
+        }
+    }
+
+    fn view_spoken_languages_panel(
+        &self,
+        spoken_languages: &SpokenLanguages,
+        require_common: bool,
+    ) -> Html {
         use strum::IntoEnumIterator;
 
         let options = SpokenLanguage::iter().map(|spoken_lang| {
-            let checked = spoken_languages.contains(&spoken_lang);
+            let pref = spoken_languages.get(&spoken_lang);
             let input_id = format!("spoken-language-{}", spoken_lang);
             html! {
-            <label for=input_id.clone() class="pure-checkbox">
-                <input
-                    type="checkbox"
-                    id=input_id.clone()
-                    value=spoken_lang.to_string()
-                    checked=checked
-                    onclick=self.link.callback(move |_| Msg::ToggleSpokenLanguage(spoken_lang))
-                    />
-                { spoken_lang }
-            </label>
+            <div class="pure-control-group">
+                <label for=input_id.clone() class="pure-checkbox">
+                    <input
+                        type="checkbox"
+                        id=input_id.clone()
+                        value=spoken_lang.to_string()
+                        checked=pref.is_some()
+                        onclick=self.link.callback(move |_| Msg::ToggleSpokenLanguage(spoken_lang))
+                        />
+                    { spoken_lang }
+                </label>
+                { pref.map_or(html! {}, |pref| self.view_spoken_language_preference(spoken_lang, pref)) }
+            </div>
             }
         });
 
@@ -277,7 +446,7 @@ impl SettingsPage {
                     <legend class="pure-u-1">{ Text::SpokenLanguages }</legend>
                     <div class="pure-u-1">
                     {
-                        if spoken_languages.0.is_empty() {
+                        if spoken_languages.is_empty() {
                             view_no_spoken_language_warning()
                         } else {
                             html! {}
@@ -287,13 +456,63 @@ impl SettingsPage {
                     <div class="pure-u-1">
                         { for options }
                     </div>
+                    <div class="pure-u-1">
+                        <label for="require_common_spoken_language" class="pure-checkbox"><input type="checkbox" id="require_common_spoken_language" checked=require_common onclick=self.link.callback(move |_| Msg::ToggleRequireCommonSpokenLanguage) />{ Text::RequireCommonSpokenLanguage }</label>
+                    </div>
                 </div>
             </fieldset>
         }
     }
 
+    fn view_spoken_language_preference(
+        &self,
+        spoken_lang: SpokenLanguage,
+        pref: &SpokenLanguagePreference,
+    ) -> Html {
+        use strum::IntoEnumIterator;
+
+        let select_id = format!("spoken-language-proficiency-{}", spoken_lang);
+        let preferred_id = format!("spoken-language-preferred-{}", spoken_lang);
+
+        let on_change = self.link.callback(move |cd: ChangeData| {
+            if let ChangeData::Select(se) = cd {
+                Msg::UpdateSpokenLanguageProficiency(spoken_lang, se.value())
+            } else {
+                Msg::Nope
+            }
+        });
+
+        let options = Proficiency::iter().map(|x| {
+            let selected = x == pref.proficiency;
+            html! { <option value=x.to_string() selected=selected>{ x }</option> }
+        });
+
+        html! {
+            <>
+                <label for=select_id.clone()>{ Text::Proficiency }</label>
+                <select id=select_id onchange=on_change>
+                    { for options }
+                </select>
+                <label for=preferred_id.clone() class="pure-checkbox">
+                    <input
+                        type="checkbox"
+                        id=preferred_id
+                        checked=pref.preferred
+                        onclick=self.link.callback(move |_| Msg::ToggleSpokenLanguagePreferred(spoken_lang))
+                        />
+                    { Text::PreferredSpokenLanguage }
+                </label>
+            </>
+        }
+    }
+
     fn view_availability_panel(&self, schedule: &Availability) -> Html {
-        html! { <AvailabilityTable schedule=schedule.clone() on_change=self.link.callback(Msg::UpdateSchedule) /> }
+        html! {
+            <>
+            <AvailabilityTable schedule=schedule.clone() on_change=self.link.callback(Msg::UpdateSchedule) />
+            <AvailabilityImport schedule=schedule.clone() on_apply=self.link.callback(Msg::UpdateSchedule) />
+            </>
+        }
     }
 
     fn update_user<F>(&mut self, f: F)
@@ -302,7 +521,10 @@ impl SettingsPage {
     {
         if let Some(user) = &mut self.props.user {
             f(user);
-            self.props.on_change.emit(user.clone());
+            // Server rejects it, users are warned instead
+            if user.settings.vacation.is_valid() {
+                self.props.on_change.emit(user.clone());
+            }
         }
     }
 }
@@ -347,6 +569,7 @@ mod test {
             LanguagePreference {
                 level: Level::Novice,
                 priority: Priority::No,
+                intent: Intent::Peer,
             },
         );
         assert!(find_highest_priority(&languages) == Priority::No);
@@ -360,6 +583,7 @@ mod test {
             LanguagePreference {
                 level: Level::Novice,
                 priority: Priority::High,
+                intent: Intent::Peer,
             },
         );
         assert!(find_highest_priority(&languages) == Priority::High);
@@ -0,0 +1,124 @@
+use crate::app::components::common::{Alert, BusyIndicator};
+use crate::app::workers::{main_worker, main_worker::MainWorker};
+use crate::app::{AppRoute, RouterAnchor};
+use devand_core::{PublicUserProfile, Topic};
+use devand_text::Text;
+use yew::prelude::*;
+
+pub struct TopicPage {
+    props: Props,
+    state: State,
+    main_worker: Box<dyn Bridge<MainWorker>>,
+}
+
+#[derive(Clone, PartialEq, Properties)]
+pub struct Props {
+    pub topic: String,
+}
+
+pub enum Msg {
+    MainWorkerRes(main_worker::Response),
+}
+
+#[derive(Default)]
+struct State {
+    users: Option<Vec<PublicUserProfile>>,
+}
+
+impl Component for TopicPage {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let mut main_worker = MainWorker::bridge(link.callback(Msg::MainWorkerRes));
+
+        let users = load_topic_users(&mut main_worker, &props.topic);
+
+        let state = State { users };
+
+        Self {
+            props,
+            state,
+            main_worker,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Msg::MainWorkerRes(res) => {
+                use main_worker::Response;
+
+                match res {
+                    Response::TopicUsersFetched(users) => {
+                        self.state.users = Some(users);
+                        true
+                    }
+
+                    _ => false,
+                }
+            }
+        }
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        let changed = if self.props.topic != props.topic {
+            self.state.users = load_topic_users(&mut self.main_worker, &props.topic);
+            true
+        } else {
+            false
+        };
+
+        self.props = props;
+        changed
+    }
+
+    fn view(&self) -> Html {
+        html! {
+        <>
+        <h1>{ Text::UsersInterestedIn(&self.props.topic) }</h1>
+        {
+            if let Some(users) = &self.state.users {
+                view_users(users)
+            } else {
+            html! { <BusyIndicator /> }
+            }
+        }
+        </>
+        }
+    }
+}
+
+/// Request users interested in a topic. An invalid topic cannot have any
+/// user, so an empty list is returned immediately.
+fn load_topic_users(
+    main_worker: &mut Box<dyn Bridge<MainWorker>>,
+    topic: &str,
+) -> Option<Vec<PublicUserProfile>> {
+    if let Ok(topic) = topic.parse::<Topic>() {
+        main_worker.send(main_worker::Request::LoadTopicUsers(topic));
+        None
+    } else {
+        Some(Vec::new())
+    }
+}
+
+fn view_users(users: &[PublicUserProfile]) -> Html {
+    if users.is_empty() {
+        return html! { <Alert>{ Text::NoUsersInterested }</Alert> };
+    }
+
+    let users = users.iter().map(|user| {
+        html! {
+        <li>
+            <RouterAnchor route=AppRoute::UserProfile(user.username.clone()) >{ user.full_name() }</RouterAnchor>
+            <p class="devand-user-bio">{ &user.bio }</p>
+        </li>
+        }
+    });
+
+    html! {
+        <ul class="devand-topic-users">
+            { for users }
+        </ul>
+    }
+}
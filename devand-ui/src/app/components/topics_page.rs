@@ -0,0 +1,100 @@
+use crate::app::components::common::{Alert, BusyIndicator, CountTag};
+use crate::app::workers::{main_worker, main_worker::MainWorker};
+use crate::app::{AppRoute, RouterAnchor};
+use devand_core::TopicCount;
+use devand_text::Text;
+use yew::{prelude::*, Properties};
+
+#[derive(Default)]
+pub struct State {
+    topics: Option<Vec<TopicCount>>,
+}
+
+pub enum Msg {
+    MainWorkerRes(main_worker::Response),
+}
+
+pub struct TopicsPage {
+    props: Props,
+    state: State,
+    _main_worker: Box<dyn Bridge<MainWorker>>,
+}
+
+#[derive(Clone, PartialEq, Properties)]
+pub struct Props {}
+
+impl Component for TopicsPage {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let state = State::default();
+
+        let mut main_worker = MainWorker::bridge(link.callback(Msg::MainWorkerRes));
+        main_worker.send(main_worker::Request::LoadTopics);
+
+        Self {
+            props,
+            state,
+            _main_worker: main_worker,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Msg::MainWorkerRes(res) => {
+                use main_worker::Response;
+
+                match res {
+                    Response::TopicsFetched(topics) => {
+                        self.state.topics = Some(topics);
+                        true
+                    }
+
+                    _ => false,
+                }
+            }
+        }
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        html! {
+        <>
+        <h1>{ Text::Topics }</h1>
+        {
+            if let Some(topics) = &self.state.topics {
+                view_topics(topics)
+            } else {
+            html! { <BusyIndicator /> }
+            }
+        }
+        </>
+        }
+    }
+}
+
+fn view_topics(topics: &[TopicCount]) -> Html {
+    if topics.is_empty() {
+        return html! { <Alert>{ Text::NoTopics }</Alert> };
+    }
+
+    let topics = topics.iter().map(|TopicCount { topic, users }| {
+        html! {
+        <li class="devand-topic-tag">
+            <RouterAnchor route=AppRoute::Topic(topic.to_string()) >{ topic }</RouterAnchor>
+            <CountTag count=*users />
+        </li>
+        }
+    });
+
+    html! {
+        <ul class="devand-topics">
+            { for topics }
+        </ul>
+    }
+}
@@ -1,5 +1,5 @@
 use crate::app::{AppRoute, RouterAnchor, RouterButton};
-use devand_core::{AffinityLevel, UserAffinity};
+use devand_core::{AffinityConfig, AffinityLevel, PairRoles, Role, UserAffinity};
 use devand_text::Text;
 use yew::prelude::*;
 
@@ -19,11 +19,33 @@ fn affinity_class(affinity_level: AffinityLevel) -> &'static str {
     }
 }
 
-pub fn user_affinity_bubble(user: &UserAffinity) -> Html {
-    let UserAffinity { user, affinity } = user;
+fn view_other_role(roles: &Option<PairRoles>) -> Html {
+    if let Some(PairRoles {
+        language, other, ..
+    }) = roles
+    {
+        let language = language.to_string();
+        let text = match other {
+            Role::Mentor => Text::CanMentorYou(&language),
+            Role::Mentee => Text::WantsToLearnFromYou(&language),
+            Role::Peer => return html! {},
+        };
+        html! { <span class="devand-pair-role">{ text }</span> }
+    } else {
+        html! {}
+    }
+}
+
+pub fn user_affinity_bubble(user: &UserAffinity, affinity_config: &AffinityConfig) -> Html {
+    let UserAffinity {
+        user,
+        affinity,
+        roles,
+        ..
+    } = user;
     let username = &user.username;
     let affinity_str = affinity.to_string();
-    let affinity_level = AffinityLevel::from(*affinity);
+    let affinity_level = AffinityLevel::new(*affinity, affinity_config);
     let title = affinity_title(affinity_level).to_string();
     let class = affinity_class(affinity_level).to_string();
 
@@ -32,6 +54,7 @@ pub fn user_affinity_bubble(user: &UserAffinity) -> Html {
         <span class="devand-start-chat"><RouterButton route=AppRoute::Chat(username.clone())>{ "💬" }</RouterButton></span>
         <span class=classes!("devand-affinity-tag", class) title=title>{ affinity_str }</span>
         <span class="devand-visible-name"><RouterAnchor route=AppRoute::UserProfile(username.clone()) >{ &user.visible_name }</RouterAnchor></span>
+        { view_other_role(roles) }
     </span>
     }
 }
@@ -1,8 +1,8 @@
-use crate::app::components::common::BusyIndicator;
+use crate::app::components::common::{Alert, BusyIndicator};
 use crate::app::components::LanguageTag;
 use crate::app::workers::{main_worker, main_worker::MainWorker};
-use crate::app::{AppRoute, RouterButton};
-use devand_core::{PublicUserProfile, SpokenLanguages};
+use crate::app::{AppRoute, RouterAnchor, RouterButton};
+use devand_core::{PublicUserProfile, SpokenLanguages, Topics, Vacation};
 use devand_text::Text;
 use yew::prelude::*;
 
@@ -85,6 +85,8 @@ impl Component for UserProfilePage {
                 spoken_languages,
                 visible_name,
                 projects,
+                vacation,
+                topics,
                 ..
             } = other_user;
 
@@ -98,12 +100,14 @@ impl Component for UserProfilePage {
             <>
                 <h1><RouterButton route=AppRoute::Chat(other_user.username.clone())>{ "💬 " }</RouterButton>{ other_user.full_name() }</h1>
                 <p class="devand-user-bio">{ &other_user.bio }</p>
+                { view_vacation(visible_name, vacation) }
 
                 <h2>{ Text::Languages }</h2>
                 <div>
                     { for languages_tags }
                 </div>
 
+                { view_topics(topics) }
                 { view_projects(projects) }
                 { view_spoken_languages(visible_name, spoken_languages) }
             </>
@@ -114,11 +118,26 @@ impl Component for UserProfilePage {
     }
 }
 
+fn view_vacation(visible_name: &str, vacation: &Option<Vacation>) -> Html {
+    match vacation.and_then(|x| x.end) {
+        Some(end) => {
+            let end = end.format("%B %d, %Y").to_string();
+            html! { <Alert>{ Text::UserOnVacationUntil(visible_name, &end) }</Alert> }
+        }
+        None if vacation.is_some() => {
+            html! { <Alert>{ Text::UserOnVacation(visible_name) }</Alert> }
+        }
+        None => html! {},
+    }
+}
+
 fn view_spoken_languages(visible_name: &str, spoken_languages: &SpokenLanguages) -> Html {
     if spoken_languages.is_empty() {
         html! {}
     } else {
-        let spoken_languages = spoken_languages.iter().map(|x| html! { <li>{ x }</li> });
+        let spoken_languages = spoken_languages
+            .iter()
+            .map(|(lang, pref)| html! { <li>{ lang }{ " (" }{ pref.proficiency }{ ")" }</li> });
         html! {
         <>
             <h2>{ Text::UserSpeaks(visible_name) }</h2>
@@ -130,6 +149,28 @@ fn view_spoken_languages(visible_name: &str, spoken_languages: &SpokenLanguages)
     }
 }
 
+fn view_topics(topics: &Topics) -> Html {
+    if topics.is_empty() {
+        html! {}
+    } else {
+        let topics = topics.iter().map(|topic| {
+            html! {
+            <span class="devand-topic-tag">
+                <RouterAnchor route=AppRoute::Topic(topic.to_string()) >{ topic }</RouterAnchor>
+            </span>
+            }
+        });
+        html! {
+        <>
+            <h2>{ Text::Topics }</h2>
+            <div>
+                { for topics }
+            </div>
+        </>
+        }
+    }
+}
+
 fn view_projects(projects: &[String]) -> Html {
     if projects.is_empty() {
         html! {}
@@ -1,7 +1,13 @@
-use super::{MainWorker, Request, Response};
+use super::{ChatAddress, MainWorker, Msg, Request, Response};
+use chrono::{DateTime, SecondsFormat, Utc};
+use devand_core::chat::{ChatUpdates, HistoryCursor};
 use yew::format::{Json, Nothing, Text};
 use yew::services::fetch;
 
+fn api_url_push() -> &'static str {
+    "/api/push"
+}
+
 fn api_url_self_user() -> &'static str {
     "/api/user"
 }
@@ -26,8 +32,81 @@ fn api_url_affinities() -> &'static str {
     "/api/affinities"
 }
 
-fn api_url_availability_match() -> &'static str {
-    "/api/availability-match"
+fn api_url_affinity_config() -> &'static str {
+    "/api/affinity-config"
+}
+
+fn api_url_availability_match(query: &super::AvailabilityMatchQuery) -> String {
+    let mut params = Vec::new();
+
+    if let Some(start_hours) = query.start_hours {
+        params.push(format!("start_hours={}", start_hours));
+    }
+    if let Some(days) = query.days {
+        params.push(format!("days={}", days));
+    }
+    // Language is sent with its serialized name, which is safe in urls
+    if let Some(language) = query.language.and_then(|l| serde_json::to_value(l).ok()) {
+        if let Some(language) = language.as_str() {
+            params.push(format!("language={}", language));
+        }
+    }
+    if let Some(min_affinity) = query.min_affinity {
+        params.push(format!("min_affinity={}", min_affinity));
+    }
+    if let Some(min_session_minutes) = query.min_session_minutes {
+        params.push(format!("min_session_minutes={}", min_session_minutes));
+    }
+
+    if params.is_empty() {
+        "/api/availability-match".to_string()
+    } else {
+        format!("/api/availability-match?{}", params.join("&"))
+    }
+}
+
+fn api_url_group_slots(query: &super::GroupSlotsQuery) -> String {
+    let mut params = Vec::new();
+
+    if !query.users.is_empty() {
+        params.push(format!("users={}", encode_chat_members(&query.users)));
+    } else if let Some(language) = query.language.and_then(|l| serde_json::to_value(l).ok()) {
+        if let Some(language) = language.as_str() {
+            params.push(format!("language={}", language));
+        }
+    }
+    if let Some(min_size) = query.min_size {
+        params.push(format!("min_size={}", min_size));
+    }
+    if let Some(days) = query.days {
+        params.push(format!("days={}", days));
+    }
+
+    format!("/api/availability-match/groups?{}", params.join("&"))
+}
+
+fn api_url_calendar_feed() -> &'static str {
+    "/api/calendar-feed"
+}
+
+fn api_url_availability_import() -> &'static str {
+    "/api/availability-import"
+}
+
+fn api_url_sessions() -> &'static str {
+    "/api/sessions"
+}
+
+fn api_url_session_status(id: devand_core::session::SessionId) -> String {
+    format!("/api/sessions/{}/status", id)
+}
+
+fn api_url_topics() -> &'static str {
+    "/api/topics"
+}
+
+fn api_url_topic_users(topic: &devand_core::Topic) -> String {
+    format!("/api/topics/{}", topic)
 }
 
 fn api_url_password_check() -> &'static str {
@@ -37,31 +116,66 @@ fn api_url_password_edit() -> &'static str {
     "/api/password-edit"
 }
 
-fn api_url_chat(chat_members: &[devand_core::UserId]) -> String {
-    format!("/api/chat/{}", encode_chat_members(chat_members))
+fn api_url_chat(chat: &ChatAddress) -> String {
+    match chat {
+        ChatAddress::Direct(members) => format!("/api/chat/{}", encode_chat_members(members)),
+        ChatAddress::Group(id) => format!("/api/group-chat/{}", id),
+    }
 }
 
-fn api_url_chat_messages(members: &[devand_core::UserId]) -> String {
-    let members = encode_chat_members(members);
-    format!("/api/chat/{}/messages", members)
+fn api_url_chat_messages(chat: &ChatAddress) -> String {
+    format!("{}/messages", api_url_chat(chat))
 }
 
+fn api_url_chat_messages_page(chat: &ChatAddress, cursor: HistoryCursor) -> String {
+    let messages = api_url_chat_messages(chat);
+    match cursor {
+        HistoryCursor::Latest => messages,
+        HistoryCursor::Before(id) => format!("{}?before={}", messages, id),
+        HistoryCursor::After(id) => format!("{}?after={}", messages, id),
+    }
+}
+
+/// Polling after a message returns all new messages, not just a page, then
+/// the messages changed after the given time
 fn api_url_chat_messages_poll(
-    members: &[devand_core::UserId],
-    from_created_at: Option<chrono::DateTime<chrono::Utc>>,
+    chat: &ChatAddress,
+    cursor: HistoryCursor,
+    changed_after: Option<DateTime<Utc>>,
 ) -> String {
-    let members = encode_chat_members(members);
-    format!(
-        "/api/chat/{}/messages/poll/{}",
-        members,
-        from_created_at.map(|x| x.timestamp()).unwrap_or(0)
-    )
+    match (cursor, changed_after) {
+        (HistoryCursor::After(id), None) => {
+            format!("{}/poll/{}", api_url_chat_messages(chat), id)
+        }
+        (HistoryCursor::After(id), Some(t)) => format!(
+            "{}/poll/{}?changed_after={}",
+            api_url_chat_messages(chat),
+            id,
+            t.to_rfc3339_opts(SecondsFormat::Micros, true)
+        ),
+        (cursor, _) => api_url_chat_messages_page(chat, cursor),
+    }
+}
+
+fn api_url_chat_message(id: uuid::Uuid) -> String {
+    format!("/api/chat-message/{}", id)
 }
 
 fn api_url_chats() -> &'static str {
     "/api/chats"
 }
 
+fn api_url_group_chats() -> &'static str {
+    "/api/group-chats"
+}
+
+fn api_url_group_chat_member(
+    chat_id: devand_core::chat::ChatId,
+    member: devand_core::UserId,
+) -> String {
+    format!("/api/group-chat/{}/members/{}", chat_id, member.0)
+}
+
 fn encode_chat_members(chat_members: &[devand_core::UserId]) -> String {
     chat_members
         .iter()
@@ -107,11 +221,66 @@ pub fn request(worker: &mut MainWorker, msg: Request) {
             task(worker, req, Response::AffinitiesFetched)
         }
 
-        Request::LoadAvailabilityMatch => {
-            let req = get(api_url_availability_match());
+        Request::LoadAffinityConfig => {
+            let req = get(api_url_affinity_config());
+            task(worker, req, Response::AffinityConfigFetched)
+        }
+
+        Request::LoadAvailabilityMatch(query) => {
+            let req = get(&api_url_availability_match(&query));
             task(worker, req, Response::AvailabilityMatchFetched)
         }
 
+        Request::LoadGroupSlots(query) => {
+            let req = get(&api_url_group_slots(&query));
+            task(worker, req, Response::GroupSlotsFetched)
+        }
+
+        Request::LoadCalendarFeed => {
+            let req = get(api_url_calendar_feed());
+            task(worker, req, Response::CalendarFeedFetched)
+        }
+
+        Request::RegenerateCalendarFeed => {
+            let req = post(api_url_calendar_feed(), Nothing);
+            task(worker, req, Response::CalendarFeedFetched)
+        }
+
+        Request::RevokeCalendarFeed => {
+            let req = delete(api_url_calendar_feed());
+            task(worker, req, Response::CalendarFeedFetched)
+        }
+
+        Request::ImportAvailability(ics) => {
+            let req = post(api_url_availability_import(), json(ics));
+            task(worker, req, Response::AvailabilityImported)
+        }
+
+        Request::LoadSessions => {
+            let req = get(api_url_sessions());
+            task(worker, req, Response::SessionsFetched)
+        }
+
+        Request::ProposeSession(proposal) => {
+            let req = post(api_url_sessions(), json(proposal));
+            task(worker, req, Response::SessionUpdated)
+        }
+
+        Request::SetSessionStatus(id, status) => {
+            let req = put(&api_url_session_status(id), json(status));
+            task(worker, req, Response::SessionUpdated)
+        }
+
+        Request::LoadTopics => {
+            let req = get(api_url_topics());
+            task(worker, req, Response::TopicsFetched)
+        }
+
+        Request::LoadTopicUsers(topic) => {
+            let req = get(&api_url_topic_users(&topic));
+            task(worker, req, Response::TopicUsersFetched)
+        }
+
         Request::CheckOldPassword(old_password) => {
             let body = devand_core::PasswordEdit {
                 old_password,
@@ -132,26 +301,68 @@ pub fn request(worker: &mut MainWorker, msg: Request) {
             task(worker, req, Response::PasswordEdited)
         }
 
-        Request::ChatSendMessage(members, txt) => {
-            let req = post(&api_url_chat_messages(&members), json(txt));
+        Request::ChatSendMessage(chat, txt) => {
+            let req = post(&api_url_chat_messages(&chat), json(txt));
             task(worker, req, Response::ChatNewMessagesLoaded)
         }
 
-        Request::ChatPoll(members, from_created_at) => {
-            let req = get(&api_url_chat_messages_poll(&members, from_created_at));
-            task(worker, req, Response::ChatNewMessagesLoaded)
+        Request::ChatPoll(chat, cursor, changed_after) => {
+            let req = get(&api_url_chat_messages_poll(&chat, cursor, changed_after));
+            match cursor {
+                HistoryCursor::After(_) => task(worker, req, Response::ChatUpdatesLoaded),
+                // Without messages, there is nothing changed and the cursor
+                // is kept
+                _ => task(worker, req, move |messages| {
+                    Response::ChatUpdatesLoaded(ChatUpdates {
+                        messages,
+                        changed_after,
+                    })
+                }),
+            }
         }
 
-        Request::ChatLoadHistory(members) => {
-            let req = get(&api_url_chat(&members));
+        Request::ChatLoadHistory(chat) => {
+            let req = get(&api_url_chat(&chat));
             task(worker, req, Response::ChatHistoryLoaded)
         }
 
+        Request::ChatLoadOlderMessages(chat, cursor) => {
+            let req = get(&api_url_chat_messages_page(&chat, cursor));
+            task(worker, req, Response::ChatOlderMessagesLoaded)
+        }
+
+        Request::CreateGroupChat(new_chat) => {
+            let req = post(api_url_group_chats(), json(new_chat));
+            task(worker, req, Response::GroupChatUpdated)
+        }
+
+        Request::AddGroupChatMember(chat_id, member) => {
+            let req = put(&api_url_group_chat_member(chat_id, member), Nothing);
+            task(worker, req, Response::GroupChatUpdated)
+        }
+
+        Request::LeaveGroupChat(chat_id, member) => {
+            let req = delete(&api_url_group_chat_member(chat_id, member));
+            task(worker, req, Response::GroupChatLeft)
+        }
+
         Request::LoadAllChats => {
             let req = get(api_url_chats());
             task(worker, req, Response::AllChatsLoaded)
         }
 
+        Request::EditChatMessage(id, txt) => {
+            let req = put(&api_url_chat_message(id), json(txt));
+            task(worker, req, Response::ChatMessageChanged)
+        }
+
+        Request::DeleteChatMessage(id) => {
+            let req = delete(&api_url_chat_message(id));
+            task(worker, req, Response::ChatMessageChanged)
+        }
+
+        Request::ConnectPush => push_url_task(worker),
+
         // Program should never hit this
         Request::Lazy(_) => unimplemented!(),
     };
@@ -179,6 +390,10 @@ where
     fetch::Request::put(url).body(body).unwrap()
 }
 
+fn delete(url: &str) -> fetch::Request<Nothing> {
+    fetch::Request::delete(url).body(Nothing).unwrap()
+}
+
 fn json<T: serde::ser::Serialize>(data: T) -> Result<String, anyhow::Error> {
     serde_json::to_string(&data).map_err(|e| anyhow::anyhow!(e))
 }
@@ -209,3 +424,24 @@ where
 
     fetch::FetchService::fetch(req, handler.into()).ok()
 }
+
+/// Errors are not published: subscribers are only told whether the push
+/// channel is connected
+fn push_url_task(worker: &MainWorker) -> Option<fetch::FetchTask> {
+    let link = worker.link.clone();
+
+    let handler = move |response: fetch::Response<Json<Result<String, anyhow::Error>>>| {
+        let (meta, Json(data)) = response.into_parts();
+
+        match data {
+            Ok(url) => link.send_message(Msg::PushUrlFetched(url)),
+            Err(_) if meta.status == http::StatusCode::NOT_FOUND => {
+                link.send_message(Msg::PushUnavailable)
+            }
+            // Retried by the next auto update
+            Err(err) => log::error!("Cannot load push channel: {:?}", err),
+        }
+    };
+
+    fetch::FetchService::fetch(get(api_url_push()), handler.into()).ok()
+}
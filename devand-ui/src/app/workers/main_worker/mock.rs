@@ -1,4 +1,4 @@
-use super::{MainWorker, Request, Response};
+use super::{ChatAddress, MainWorker, Msg, Request, Response};
 use chrono::offset::TimeZone;
 use devand_core::*;
 use fake::faker::internet::raw::*;
@@ -11,6 +11,7 @@ use rand::rngs::StdRng;
 use rand::seq::IteratorRandom;
 use rand::Rng;
 use rand::SeedableRng;
+use std::convert::TryFrom;
 use strum::IntoEnumIterator;
 
 pub fn request(worker: &mut MainWorker, msg: Request) {
@@ -54,12 +55,82 @@ pub fn request(worker: &mut MainWorker, msg: Request) {
             link.send_message(Response::AffinitiesFetched(fake_affinities(&mut rng)));
         }
 
-        Request::LoadAvailabilityMatch => {
+        Request::LoadAffinityConfig => {
+            link.send_message(Response::AffinityConfigFetched(Box::default()));
+        }
+
+        Request::LoadAvailabilityMatch(_) => {
             link.send_message(Response::AvailabilityMatchFetched(Box::new(fake_matches(
                 &mut rng,
             ))));
         }
 
+        Request::LoadGroupSlots(query) => {
+            link.send_message(Response::GroupSlotsFetched(fake_group_slots(
+                &mut rng, query,
+            )));
+        }
+
+        Request::LoadCalendarFeed | Request::RegenerateCalendarFeed => {
+            let url = format!(
+                "http://localhost:8000/api/calendar/{}.ics",
+                fake_uuid(&mut rng)
+            );
+            link.send_message(Response::CalendarFeedFetched(Some(url)));
+        }
+
+        Request::RevokeCalendarFeed => {
+            link.send_message(Response::CalendarFeedFetched(None));
+        }
+
+        Request::ImportAvailability(_ics) => {
+            let week = WeekSchedule {
+                mon: DaySchedule::try_from("17-20").unwrap(),
+                wed: DaySchedule::try_from("17-20").unwrap(),
+                ..Default::default()
+            };
+            link.send_message(Response::AvailabilityImported(Box::new(week)));
+        }
+
+        Request::LoadSessions => {
+            link.send_message(Response::SessionsFetched(fake_sessions(&mut rng)));
+        }
+
+        Request::ProposeSession(proposal) => {
+            let session = session::PairingSession {
+                id: session::SessionId(fake_uuid(&mut rng)),
+                proposer: UserId(1),
+                invitees: proposal.invitees,
+                start: proposal.start,
+                end: proposal.end,
+                language: proposal.language,
+                status: session::SessionStatus::Proposed,
+            };
+            link.send_message(Response::SessionUpdated(Box::new(session)));
+        }
+
+        Request::SetSessionStatus(id, status) => {
+            let mut session = fake_sessions(&mut rng).remove(0);
+            session.id = id;
+            session.status = status;
+            link.send_message(Response::SessionUpdated(Box::new(session)));
+        }
+
+        Request::LoadTopics => {
+            link.send_message(Response::TopicsFetched(fake_topics(&mut rng)));
+        }
+
+        Request::LoadTopicUsers(topic) => {
+            let users = fake_public_profiles(&mut rng)
+                .into_iter()
+                .map(|mut u| {
+                    u.topics.insert(topic.clone());
+                    u
+                })
+                .collect();
+            link.send_message(Response::TopicUsersFetched(users));
+        }
+
         Request::CheckOldPassword(_old_password) => {
             link.send_message(Response::OldPasswordChecked(true));
         }
@@ -68,41 +139,98 @@ pub fn request(worker: &mut MainWorker, msg: Request) {
             link.send_message(Response::PasswordEdited(()));
         }
 
-        Request::ChatSendMessage(members, txt) => {
+        Request::ChatSendMessage(_chat, txt) => {
             let t: i64 = 1592475298;
             let new_message = chat::ChatMessage {
                 id: fake_uuid(&mut rng),
                 created_at: chrono::Utc.timestamp(t, 0),
-                author: members[0],
+                author: fake_me(),
                 txt,
+                edited_at: None,
+                deleted_at: None,
             };
             link.send_message(Response::ChatNewMessagesLoaded(vec![new_message]));
         }
 
-        Request::ChatPoll(members, from_created_at) => {
-            let t = 1 + from_created_at.map(|x| x.timestamp()).unwrap_or(1592475298);
-            let seed = t as u64;
-            let mut rng = StdRng::seed_from_u64(seed);
-            let author = members[1];
+        Request::ChatPoll(chat, cursor, changed_after) => {
+            let mut rng = StdRng::seed_from_u64(fake_cursor_seed(cursor));
+            let members = fake_chat_members(&chat);
+            let msg = fake_message(&mut rng, members[1]);
+            link.send_message(Response::ChatUpdatesLoaded(chat::ChatUpdates {
+                messages: vec![msg],
+                changed_after,
+            }));
+        }
 
-            let msg = chat::ChatMessage {
-                id: fake_uuid(&mut rng),
-                created_at: chrono::Utc.timestamp(t, 0),
-                author,
-                txt: Sentence(1..30).fake_with_rng(&mut rng),
+        Request::ChatLoadHistory(chat) => {
+            let info = fake_chat_info(&mut rng, &chat);
+            link.send_message(Response::ChatHistoryLoaded(info));
+        }
+
+        Request::ChatLoadOlderMessages(chat, cursor) => {
+            let mut rng = StdRng::seed_from_u64(fake_cursor_seed(cursor));
+            let n = rng.gen_range(0, chat::HISTORY_PAGE_SIZE + 1);
+            let members = fake_chat_members(&chat);
+            let messages = fake_messages(&mut rng, n, members[0], members[1]);
+            link.send_message(Response::ChatOlderMessagesLoaded(messages));
+        }
+
+        Request::CreateGroupChat(new_chat) => {
+            let chat = chat::Chat {
+                id: chat::ChatId(fake_uuid(&mut rng)),
+                members: new_chat.valid_members(fake_me()).unwrap_or_default(),
+                name: new_chat.valid_name(),
             };
+            link.send_message(Response::GroupChatUpdated(chat));
+        }
 
-            link.send_message(Response::ChatNewMessagesLoaded(vec![msg]));
+        Request::AddGroupChatMember(chat_id, member) => {
+            let mut members = fake_chat_members(&ChatAddress::Group(chat_id));
+            members.push(member);
+            let chat = chat::Chat {
+                id: chat_id,
+                members: chat::canonical_members(&members),
+                name: Some(Sentence(1..3).fake_with_rng(&mut rng)),
+            };
+            link.send_message(Response::GroupChatUpdated(chat));
         }
 
-        Request::ChatLoadHistory(_members) => {
-            link.send_message(Response::ChatHistoryLoaded(fake_chat_info(&mut rng)));
+        Request::LeaveGroupChat(chat_id, member) => {
+            let mut members = fake_chat_members(&ChatAddress::Group(chat_id));
+            members.retain(|&m| m != member);
+            let chat = chat::Chat {
+                id: chat_id,
+                members,
+                name: Some(Sentence(1..3).fake_with_rng(&mut rng)),
+            };
+            link.send_message(Response::GroupChatLeft(chat));
         }
 
         Request::LoadAllChats => {
             link.send_message(Response::AllChatsLoaded(fake_chats(&mut rng)));
         }
 
+        Request::EditChatMessage(id, txt) => {
+            let mut message = fake_message(&mut rng, fake_me());
+            message.id = id;
+            message.txt = txt;
+            message.edited_at = Some(message.created_at);
+            link.send_message(Response::ChatMessageChanged(message));
+        }
+
+        Request::DeleteChatMessage(id) => {
+            let mut message = fake_message(&mut rng, fake_me());
+            message.id = id;
+            message.txt.clear();
+            message.deleted_at = Some(message.created_at);
+            link.send_message(Response::ChatMessageChanged(message));
+        }
+
+        // Without a server, there is nothing to push
+        Request::ConnectPush => {
+            link.send_message(Msg::PushUnavailable);
+        }
+
         // Program should never hit this
         Request::Lazy(_) => unimplemented!(),
     }
@@ -118,7 +246,15 @@ fn fake_user(rng: &mut StdRng) -> User {
         if rng.gen_bool(0.2) {
             let level = Level::iter().choose(rng).unwrap();
             let priority = Priority::iter().choose(rng).unwrap();
-            languages.insert(lang, LanguagePreference { level, priority });
+            let intent = Intent::iter().choose(rng).unwrap();
+            languages.insert(
+                lang,
+                LanguagePreference {
+                    level,
+                    priority,
+                    intent,
+                },
+            );
         }
     }
 
@@ -139,7 +275,13 @@ fn fake_user(rng: &mut StdRng) -> User {
             languages: Languages(languages),
             schedule: Availability::default(),
             vacation_mode: false,
-            spoken_languages: SpokenLanguages(btreeset![SpokenLanguage::English]),
+            vacation: Vacation::default(),
+            spoken_languages: SpokenLanguages::from(btreeset![SpokenLanguage::English]),
+            require_common_spoken_language: false,
+            topics: fake_user_topics(rng),
+            time_zone: UserTimeZone::default(),
+            weekly_pairing: false,
+            calendar_feed: calendar::CalendarFeed::default(),
         },
         bio: "This is the bio".to_string(),
         projects: vec![],
@@ -162,6 +304,29 @@ fn fake_code_now(rng: &mut StdRng) -> CodeNow {
     }
 }
 
+const FAKE_TOPICS: &[&str] = &[
+    "embedded",
+    "webassembly",
+    "tokio",
+    "react",
+    "databases",
+    "game-development",
+];
+
+fn fake_user_topics(rng: &mut StdRng) -> Topics {
+    let topics = FAKE_TOPICS
+        .iter()
+        .filter(|_| rng.gen_bool(0.3))
+        .map(|x| x.parse().unwrap())
+        .collect();
+    Topics(topics)
+}
+
+fn fake_topics(rng: &mut StdRng) -> Vec<TopicCount> {
+    let users: Vec<_> = (0..20).map(|_| fake_user_topics(rng)).collect();
+    count_topics(&users)
+}
+
 fn fake_public_profiles(rng: &mut StdRng) -> Vec<PublicUserProfile> {
     let n = rng.gen_range(0, 20);
     let mut v = Vec::default();
@@ -178,12 +343,13 @@ fn fake_public_profile(rng: &mut StdRng) -> PublicUserProfile {
 
 fn fake_affinities(rng: &mut StdRng) -> Vec<UserAffinity> {
     let n = rng.gen_range(1, 10);
+    let me = AffinityParams::from(&fake_public_profile(rng));
+    let config = AffinityConfig::default();
     let mut v = Vec::default();
     for _ in 0..n {
-        v.push(UserAffinity {
-            user: fake_public_profile(rng),
-            affinity: Affinity::from_number(rng.gen_range(0.0, 1.0)),
-        })
+        let user = fake_public_profile(rng);
+        let breakdown = AffinityBreakdown::from_params(&me, &AffinityParams::from(&user), &config);
+        v.push(UserAffinity::new(user, breakdown.affinity).with_breakdown(breakdown))
     }
     v
 }
@@ -198,11 +364,77 @@ fn fake_matches(rng: &mut StdRng) -> schedule_matcher::AvailabilityMatch {
         let users_count = rng.gen_range(1, 10);
         let mut users = Vec::default();
         for _ in 0..users_count {
-            users.push(UserId(rng.gen_range(1, 1000)));
+            let affinity = Affinity::from_number(rng.gen_range(0.1, 1.0));
+            users.push((UserId(rng.gen_range(1, 1000)), affinity));
         }
+        users.sort_by_key(|&(_, affinity)| std::cmp::Reverse(affinity));
         slots.push((d, users))
     }
-    schedule_matcher::AvailabilityMatch { slots }
+    schedule_matcher::AvailabilityMatch {
+        slots,
+        resolution: Resolution::Hour,
+    }
+}
+
+fn fake_group_slots(
+    rng: &mut StdRng,
+    query: super::GroupSlotsQuery,
+) -> Vec<schedule_matcher::GroupSlot> {
+    let n = rng.gen_range(0, 10);
+    let t0: i64 = 1598810400;
+    let mut v = Vec::default();
+    for _ in 0..n {
+        let t = t0 + (3600 * rng.gen_range(1, 24 * 7));
+        let start = chrono::Utc.timestamp(t, 0);
+        let mut users = if query.users.is_empty() {
+            (0..rng.gen_range(2, 6))
+                .map(|_| UserId(rng.gen_range(2, 1000)))
+                .collect()
+        } else {
+            query.users.clone()
+        };
+        users.push(UserId(1));
+        users.sort();
+        users.dedup();
+        v.push(schedule_matcher::GroupSlot {
+            users,
+            start,
+            end: start + chrono::Duration::hours(rng.gen_range(1, 4)),
+        });
+    }
+    v.sort_by_key(|slot| {
+        (
+            std::cmp::Reverse(slot.users.len()),
+            std::cmp::Reverse(slot.duration()),
+        )
+    });
+    v
+}
+
+fn fake_sessions(rng: &mut StdRng) -> Vec<session::PairingSession> {
+    let n = rng.gen_range(1, 10);
+    let t0: i64 = 1598810400;
+    let mut v = Vec::default();
+    for _ in 0..n {
+        let t = t0 + (3600 * rng.gen_range(1, 24 * 7));
+        let start = chrono::Utc.timestamp(t, 0);
+        let (proposer, invitee) = if rng.gen_bool(0.5) {
+            (UserId(1), UserId(rng.gen_range(2, 1000)))
+        } else {
+            (UserId(rng.gen_range(2, 1000)), UserId(1))
+        };
+        v.push(session::PairingSession {
+            id: session::SessionId(fake_uuid(rng)),
+            proposer,
+            invitees: vec![invitee],
+            start,
+            end: start + chrono::Duration::hours(rng.gen_range(1, 3)),
+            language: Language::iter().choose(rng),
+            status: session::SessionStatus::iter().choose(rng).unwrap(),
+        });
+    }
+    v.sort_by_key(|s| s.start);
+    v
 }
 
 fn fake_message(rng: &mut StdRng, author: UserId) -> chat::ChatMessage {
@@ -213,6 +445,8 @@ fn fake_message(rng: &mut StdRng, author: UserId) -> chat::ChatMessage {
         created_at: chrono::Utc.timestamp(t, 0),
         author,
         txt: Sentence(1..30).fake_with_rng(rng),
+        edited_at: None,
+        deleted_at: None,
     }
 }
 
@@ -230,44 +464,81 @@ fn fake_messages(rng: &mut StdRng, n: usize, me: UserId, other: UserId) -> Vec<c
             created_at: chrono::Utc.timestamp(t, 0),
             author: if from_me { me } else { other },
             txt: Sentence(1..30).fake_with_rng(rng),
+            edited_at: None,
+            deleted_at: None,
         });
     }
 
     history
 }
 
-fn fake_chat_info(rng: &mut StdRng) -> chat::ChatInfo {
-    let me = fake_user(rng);
-    let other = fake_user(rng);
+/// The user sent on `Init`
+fn fake_me() -> UserId {
+    fake_user(&mut StdRng::seed_from_u64(100)).id
+}
 
-    let members = vec![me.clone(), other.clone()];
+/// Members of a fake chat, the logged user first
+fn fake_chat_members(chat: &ChatAddress) -> Vec<UserId> {
+    match chat {
+        ChatAddress::Direct(members) => {
+            let me = fake_me();
+            let others = members.iter().copied().filter(|&m| m != me);
+            std::iter::once(me).chain(others).collect()
+        }
+        ChatAddress::Group(_) => vec![fake_me(), UserId(2), UserId(3)],
+    }
+}
+
+fn fake_chat_info(rng: &mut StdRng, chat: &ChatAddress) -> chat::ChatInfo {
+    let members = fake_chat_members(chat);
 
     let members_info = members
         .iter()
-        .map(|user| chat::ChatMemberInfo {
-            user_id: user.id,
-            verified_email: user.email_verified,
+        .map(|&user_id| chat::ChatMemberInfo {
+            user_id,
+            verified_email: rng.gen(),
         })
         .collect();
 
-    let messages = fake_messages(rng, 10, me.id, other.id);
+    let messages = fake_messages(rng, 10, members[0], members[1]);
+
+    let name = match chat {
+        ChatAddress::Direct(_) => None,
+        ChatAddress::Group(_) => Some(Sentence(1..3).fake_with_rng(rng)),
+    };
+
+    // Fake messages are old, but they can be changed anyway
+    let config = chat::ChatConfig {
+        edit_window_minutes: 60 * 24 * 365 * 100,
+    };
 
     chat::ChatInfo {
+        name,
         members_info,
         messages,
+        config,
+        changed_after: None,
     }
 }
 
 fn fake_chats(rng: &mut StdRng) -> UserChats {
     let n = rng.gen_range(1, 10);
     let mut v = Vec::default();
-    for _ in 0..n {
+    for i in 0..n {
+        // The last one is a group chat
+        let group = i == n - 1;
+        let size = if group { 3 } else { 1 };
         let members: Vec<PublicUserProfile> =
-            fake_public_profiles(rng).into_iter().take(1).collect();
+            fake_public_profiles(rng).into_iter().take(size).collect();
         v.push(UserChat {
             chat: chat::Chat {
                 id: chat::ChatId(fake_uuid(rng)),
                 members: members.iter().map(|x| x.id).collect(),
+                name: if group {
+                    Some(Sentence(1..3).fake_with_rng(rng))
+                } else {
+                    None
+                },
             },
             unread_messages: rng.gen_range(0, 100),
             members,
@@ -276,6 +547,18 @@ fn fake_chats(rng: &mut StdRng) -> UserChats {
     UserChats(v)
 }
 
+/// Same cursor, same fake messages
+fn fake_cursor_seed(cursor: chat::HistoryCursor) -> u64 {
+    match cursor {
+        chat::HistoryCursor::Latest => 0,
+        chat::HistoryCursor::Before(id) | chat::HistoryCursor::After(id) => {
+            id.as_bytes().iter().fold(0, |seed: u64, &b| {
+                seed.wrapping_mul(31).wrapping_add(u64::from(b))
+            })
+        }
+    }
+}
+
 fn fake_uuid(rng: &mut StdRng) -> uuid::Uuid {
     let bytes: [u8; 16] = rng.gen();
     uuid::Uuid::from_bytes(&bytes).unwrap()
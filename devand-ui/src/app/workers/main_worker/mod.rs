@@ -4,6 +4,8 @@ mod http;
 #[cfg(feature = "mock_http")]
 mod mock;
 
+mod push;
+
 use serde_derive::{Deserialize, Serialize};
 use std::collections::HashSet;
 use std::sync::atomic::AtomicBool;
@@ -31,16 +33,77 @@ pub enum Request {
     LoadPublicUserProfileByUsername(String),
     LoadPublicUserProfile(devand_core::UserId),
     LoadAffinities,
-    LoadAvailabilityMatch,
+    LoadAffinityConfig,
+    LoadAvailabilityMatch(AvailabilityMatchQuery),
+    LoadGroupSlots(GroupSlotsQuery),
+    LoadCalendarFeed,
+    RegenerateCalendarFeed,
+    RevokeCalendarFeed,
+    ImportAvailability(String),
+    LoadSessions,
+    ProposeSession(devand_core::session::SessionProposal),
+    SetSessionStatus(
+        devand_core::session::SessionId,
+        devand_core::session::SessionStatus,
+    ),
+    LoadTopics,
+    LoadTopicUsers(devand_core::Topic),
     CheckOldPassword(String),
     EditPassword(String, String),
-    ChatSendMessage(Vec<devand_core::UserId>, String),
+    ChatSendMessage(ChatAddress, String),
+    /// New messages, after the cursor, then messages changed after the time
     ChatPoll(
-        Vec<devand_core::UserId>,
+        ChatAddress,
+        devand_core::chat::HistoryCursor,
         Option<chrono::DateTime<chrono::Utc>>,
     ),
-    ChatLoadHistory(Vec<devand_core::UserId>),
+    ChatLoadHistory(ChatAddress),
+    /// A page of messages, before the cursor
+    ChatLoadOlderMessages(ChatAddress, devand_core::chat::HistoryCursor),
+    CreateGroupChat(devand_core::chat::NewGroupChat),
+    AddGroupChatMember(devand_core::chat::ChatId, devand_core::UserId),
+    /// Only the logged user can leave a group chat
+    LeaveGroupChat(devand_core::chat::ChatId, devand_core::UserId),
     LoadAllChats,
+    /// Only the author can edit or delete a message
+    EditChatMessage(uuid::Uuid, String),
+    DeleteChatMessage(uuid::Uuid),
+    /// Open the push channel, used by the worker itself
+    ConnectPush,
+}
+
+/// Direct chats are addressed by their members, group chats by id
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ChatAddress {
+    Direct(Vec<devand_core::UserId>),
+    Group(devand_core::chat::ChatId),
+}
+
+/// Filters for the availability match. Missing ones have server defaults.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct AvailabilityMatchQuery {
+    /// Hours from now when the search starts
+    pub start_hours: Option<u32>,
+    /// Days to search, from start
+    pub days: Option<usize>,
+    /// Language both users know
+    pub language: Option<devand_core::Language>,
+    /// In [0..1]
+    pub min_affinity: Option<f64>,
+    pub min_session_minutes: Option<usize>,
+}
+
+/// Group of the logged user with other users, or with any user who knows
+/// a language. Missing filters have server defaults.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct GroupSlotsQuery {
+    /// Other users, the logged user is always part of the group
+    pub users: Vec<devand_core::UserId>,
+    /// Used only without users
+    pub language: Option<devand_core::Language>,
+    pub min_size: Option<usize>,
+    /// Days to search, from start
+    pub days: Option<usize>,
 }
 
 impl Request {
@@ -55,20 +118,47 @@ pub enum Response {
     CodeNowFetched(Box<devand_core::CodeNow>),
     PublicUserProfileFetched(Box<devand_core::PublicUserProfile>),
     AffinitiesFetched(Vec<devand_core::UserAffinity>),
+    AffinityConfigFetched(Box<devand_core::AffinityConfig>),
     AvailabilityMatchFetched(Box<devand_core::schedule_matcher::AvailabilityMatch>),
+    GroupSlotsFetched(Vec<devand_core::schedule_matcher::GroupSlot>),
+    CalendarFeedFetched(Option<String>),
+    AvailabilityImported(Box<devand_core::WeekSchedule>),
+    SessionsFetched(Vec<devand_core::session::PairingSession>),
+    SessionUpdated(Box<devand_core::session::PairingSession>),
+    TopicsFetched(Vec<devand_core::TopicCount>),
+    TopicUsersFetched(Vec<devand_core::PublicUserProfile>),
     OldPasswordChecked(bool),
     PasswordEdited(()),
     Done(()),
     Error(String),
     ChatNewMessagesLoaded(Vec<devand_core::chat::ChatMessage>),
+    /// New and changed messages, with the cursor of the next poll
+    ChatUpdatesLoaded(devand_core::chat::ChatUpdates),
     ChatHistoryLoaded(devand_core::chat::ChatInfo),
+    ChatOlderMessagesLoaded(Vec<devand_core::chat::ChatMessage>),
+    /// A message was edited or deleted by the logged user
+    ChatMessageChanged(devand_core::chat::ChatMessage),
+    GroupChatUpdated(devand_core::chat::Chat),
+    GroupChatLeft(devand_core::chat::Chat),
     AllChatsLoaded(devand_core::UserChats),
+    /// Sent on changes and to new subscribers. Without the push channel,
+    /// components poll.
+    PushConnected(bool),
+    ChatEventPushed(devand_core::chat::ChatEvent),
 }
 
 pub enum Msg {
     AutoUpdate,
     Request(Request),
     Response(Response),
+    PushUrlFetched(String),
+    /// The server has no push channel
+    PushUnavailable,
+    PushConnected,
+    PushDisconnected {
+        closed: bool,
+    },
+    PushEvent(devand_core::chat::ChatEvent),
 }
 
 impl From<Response> for Msg {
@@ -90,6 +180,10 @@ pub struct MainWorker {
     _on_unload: Closure<dyn FnMut(BeforeUnloadEvent)>,
 
     pending: Arc<AtomicBool>,
+
+    push: Option<push::PushChannel>,
+    push_connected: bool,
+    push_unavailable: bool,
 }
 
 impl Agent for MainWorker {
@@ -116,11 +210,16 @@ impl Agent for MainWorker {
             _timeout_task: None,
             _on_unload: make_on_unload_callback(pending.clone()),
             pending,
+            push: None,
+            push_connected: false,
+            push_unavailable: false,
         }
     }
 
     fn connected(&mut self, id: HandlerId) {
         self.subscribers.insert(id);
+        self.link
+            .respond(id, Response::PushConnected(self.push_connected));
     }
 
     fn disconnected(&mut self, id: HandlerId) {
@@ -131,7 +230,15 @@ impl Agent for MainWorker {
         match msg {
             Msg::AutoUpdate => {
                 self.link.send_input(Request::LoadCodeNow);
-                self.link.send_input(Request::LoadAllChats);
+
+                // Unread messages are pushed, when possible
+                if !self.push_connected {
+                    self.link.send_input(Request::LoadAllChats);
+                }
+
+                if self.push.is_none() && !self.push_unavailable {
+                    self.link.send_input(Request::ConnectPush);
+                }
             }
 
             Msg::Request(req) => {
@@ -141,6 +248,35 @@ impl Agent for MainWorker {
             Msg::Response(res) => {
                 self.publish(res);
             }
+
+            Msg::PushUrlFetched(url) => {
+                if self.push.is_none() {
+                    self.push = push::PushChannel::connect(&self.link, &url);
+                    self.push_unavailable = self.push.is_none();
+                }
+            }
+
+            Msg::PushUnavailable => {
+                log::info!("Push channel unavailable, polling");
+                self.push_unavailable = true;
+            }
+
+            Msg::PushConnected => {
+                self.set_push_connected(true);
+            }
+
+            Msg::PushDisconnected { closed } => {
+                self.set_push_connected(false);
+
+                // A new url is requested by the next auto update
+                if closed {
+                    self.push = None;
+                }
+            }
+
+            Msg::PushEvent(event) => {
+                self.publish(Response::ChatEventPushed(event));
+            }
         }
     }
 
@@ -168,6 +304,15 @@ impl MainWorker {
             self.link.respond(*sub, res.clone());
         }
     }
+
+    fn set_push_connected(&mut self, connected: bool) {
+        if self.push_connected != connected {
+            self.push_connected = connected;
+            self.publish(Response::PushConnected(connected));
+            // Events may have been missed while disconnected
+            self.link.send_input(Request::LoadAllChats);
+        }
+    }
 }
 
 fn make_auto_update_task(link: AgentLink<MainWorker>) -> Box<dyn Task> {
@@ -0,0 +1,72 @@
+use super::{MainWorker, Msg};
+use devand_core::chat::ChatEvent;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{Event, EventSource, MessageEvent};
+use yew::worker::AgentLink;
+
+/// Chat events pushed by the server with Server-Sent Events. While the
+/// connection is lost, the browser retries by itself. When it gives up
+/// (e.g. because the token expired), the channel is closed and the worker
+/// must connect to a new url. Dropping the channel closes the connection.
+pub struct PushChannel {
+    event_source: EventSource,
+    _on_open: Closure<dyn FnMut(Event)>,
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+    _on_error: Closure<dyn FnMut(Event)>,
+}
+
+impl PushChannel {
+    pub fn connect(link: &AgentLink<MainWorker>, url: &str) -> Option<Self> {
+        let event_source = EventSource::new(url).ok()?;
+
+        let on_open = {
+            let link = link.clone();
+            let on_open = move |_: Event| link.send_message(Msg::PushConnected);
+            Closure::wrap(Box::new(on_open) as Box<dyn FnMut(Event)>)
+        };
+
+        let on_message = {
+            let link = link.clone();
+            let on_message = move |e: MessageEvent| {
+                let event = e
+                    .data()
+                    .as_string()
+                    .and_then(|data| serde_json::from_str::<ChatEvent>(&data).ok());
+
+                match event {
+                    Some(event) => link.send_message(Msg::PushEvent(event)),
+                    None => log::error!("Cannot decode push event"),
+                }
+            };
+            Closure::wrap(Box::new(on_message) as Box<dyn FnMut(MessageEvent)>)
+        };
+
+        let on_error = {
+            let link = link.clone();
+            let event_source = event_source.clone();
+            let on_error = move |_: Event| {
+                let closed = event_source.ready_state() == EventSource::CLOSED;
+                link.send_message(Msg::PushDisconnected { closed });
+            };
+            Closure::wrap(Box::new(on_error) as Box<dyn FnMut(Event)>)
+        };
+
+        event_source.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+        event_source.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        event_source.set_onerror(Some(on_error.as_ref().unchecked_ref()));
+
+        Some(Self {
+            event_source,
+            _on_open: on_open,
+            _on_message: on_message,
+            _on_error: on_error,
+        })
+    }
+}
+
+impl Drop for PushChannel {
+    fn drop(&mut self) {
+        self.event_source.close();
+    }
+}
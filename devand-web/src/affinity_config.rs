@@ -0,0 +1,69 @@
+//! Affinity config, shared by the server and the batch jobs which need the
+//! same scoring.
+
+use devand_core::AffinityConfig;
+use rocket::config::ConfigError;
+use rocket::Config;
+use std::convert::TryFrom;
+
+/// Load affinity config. Every key is optional and can be set in Rocket.toml
+/// or by env, e.g. `ROCKET_AFFINITY_STRATEGY=best_language`. A key with a
+/// wrong type is an error, it is not replaced by the default.
+pub fn load(config: &Config) -> Result<AffinityConfig, String> {
+    let default = AffinityConfig::default();
+
+    let strategy = match optional(config.get_str("affinity_strategy"))? {
+        Some(strategy) => strategy
+            .parse()
+            .map_err(|_| format!("Unknown affinity strategy: {}", strategy))?,
+        None => default.strategy,
+    };
+
+    let float = |key: &str, default: f64| -> Result<f64, String> {
+        Ok(optional(config.get_float(key))?.unwrap_or(default))
+    };
+
+    let int = |key: &str, default: i32| -> Result<i32, String> {
+        match optional(config.get_int(key))? {
+            Some(x) => i32::try_from(x).map_err(|_| format!("{} is out of range: {}", key, x)),
+            None => Ok(default),
+        }
+    };
+
+    let affinity_config = AffinityConfig {
+        strategy,
+        best_language_weight: float(
+            "affinity_best_language_weight",
+            default.best_language_weight,
+        )?,
+        schedule_weight: float("affinity_schedule_weight", default.schedule_weight)?,
+        spoken_languages_weight: float(
+            "affinity_spoken_languages_weight",
+            default.spoken_languages_weight,
+        )?,
+        topics_weight: float("affinity_topics_weight", default.topics_weight)?,
+        low_priority_score: int("affinity_low_priority_score", default.low_priority_score)?,
+        high_priority_score: int("affinity_high_priority_score", default.high_priority_score)?,
+        max_level_score: int("affinity_max_level_score", default.max_level_score)?,
+        medium_affinity_threshold: float(
+            "affinity_medium_threshold",
+            default.medium_affinity_threshold,
+        )?,
+        high_affinity_threshold: float("affinity_high_threshold", default.high_affinity_threshold)?,
+    };
+
+    if !affinity_config.is_valid() {
+        return Err(format!("Invalid affinity config: {:?}", affinity_config));
+    }
+
+    Ok(affinity_config)
+}
+
+/// A missing key is `None`, any other error is kept
+fn optional<T>(value: Result<T, ConfigError>) -> Result<Option<T>, String> {
+    match value {
+        Ok(value) => Ok(Some(value)),
+        Err(ConfigError::Missing(_)) => Ok(None),
+        Err(err) => Err(err.to_string()),
+    }
+}
@@ -1,12 +1,34 @@
 use crate::auth::{AuthData, LoggedUser};
+use crate::push::Push;
 use crate::{CodeNowUsers, Mailer, PgDevandConn, WeekScheduleMatrix};
 use chrono::prelude::*;
 use chrono::Duration;
-use devand_core::schedule_matcher::AvailabilityMatch;
-use devand_core::{User, UserAffinity, UserId};
-use rocket::http::Status;
+use devand_core::calendar;
+use devand_core::chat::{
+    canonical_members, history_page_size, next_changes_cursor, valid_edit, Chat, ChatConfig,
+    ChatId, ChatInfo, ChatMessage, ChatMessageEdit, ChatUpdates, HistoryCursor, NewGroupChat,
+    HISTORY_PAGE_SIZE, MAX_HISTORY_PAGE_SIZE,
+};
+use devand_core::pairing::{self, WeeklyPair};
+use devand_core::schedule_matcher::{
+    self, AvailabilityMatch, GroupFilter, GroupSlot, MatchFilter, MatchingProfile, MatchingSession,
+};
+use devand_core::session::{
+    PairingSession, SessionConflict, SessionId, SessionProposal, SessionStatus,
+};
+use devand_core::{
+    Affinity, AffinityConfig, Availability, PublicUserProfile, Topic, TopicCount, User,
+    UserAffinity, UserId, WeekSchedule,
+};
+use devand_crypto::{Signable, SignedToken};
+use devand_text::Text;
+use rocket::http::{ContentType, Status};
+use rocket::request::Form;
+use rocket::response::{status, Content};
 use rocket::{Route, State};
+use rocket_contrib::databases::diesel::{self, Connection};
 use rocket_contrib::json::Json;
+use std::collections::{BTreeMap, BTreeSet};
 
 const BASE_URL: Option<&'static str> = option_env!("DEVAND_BASE_URL");
 const DEFAULT_BASE_URL: &str = "http://localhost:8000";
@@ -17,15 +39,42 @@ pub fn routes() -> Vec<Route> {
         user_put,
         verify_email,
         affinities,
+        affinity_config,
+        weekly_pair,
         code_now,
         availability_match,
+        availability_match_sessions,
+        availability_match_groups,
+        calendar_feed,
+        calendar_feed_regenerate,
+        calendar_feed_revoke,
+        calendar,
+        availability_import,
+        sessions,
+        session,
+        session_post,
+        session_status_put,
+        session_delete,
         chats,
         chat,
         chat_messages_get,
         chat_messages_post,
         chat_messages_poll,
+        group_chat_post,
+        group_chat,
+        group_chat_messages_get,
+        group_chat_messages_post,
+        group_chat_messages_poll,
+        group_chat_member_put,
+        group_chat_member_delete,
+        chat_message_put,
+        chat_message_delete,
+        chat_message_edits,
+        push,
         user_public_profile,
         user_public_profile_by_id,
+        topics,
+        topic_users,
         password_edit,
         password_check,
     ]
@@ -41,24 +90,47 @@ fn user(user: LoggedUser) -> Json<User> {
 #[put("/user", data = "<user>")]
 fn user_put(
     auth_data: AuthData,
-    user: Json<User>,
+    user: Json<serde_json::Value>,
     conn: PgDevandConn,
     wsmc: State<WeekScheduleMatrix>,
-) -> Option<Json<User>> {
+) -> Result<Json<User>, Status> {
+    // Topics are deserialized dropping invalid ones, so that stored settings
+    // are always readable: new ones are checked here instead
+    let topics = user
+        .pointer("/settings/topics")
+        .cloned()
+        .map(serde_json::from_value::<Vec<String>>)
+        .transpose()
+        .map_err(|_| Status::BadRequest)?
+        .unwrap_or_default();
+    devand_core::Topics::parse(&topics).map_err(|_| Status::BadRequest)?;
+
+    let mut user: User = serde_json::from_value(user.0).map_err(|_| Status::BadRequest)?;
+
+    if !user.settings.vacation.is_valid() {
+        return Err(Status::BadRequest);
+    }
+
     // Note: here we don't need LoggedUser (needs db access) but only
     // auth_data to check if we are modifiyng the right user.
     if !auth_data.matches_user(&user) {
-        return None;
+        return Err(Status::NotFound);
     }
 
+    // The calendar feed is changed only by its own endpoints, so an outdated
+    // copy of the settings cannot enable a revoked feed again
+    let stored_user = devand_db::load_user_by_id(user.id, &conn.0).ok_or(Status::NotFound)?;
+    user.settings.calendar_feed = stored_user.settings.calendar_feed;
+
+    user.settings.remove_past_exceptions(Utc::now());
+
     // Update immediately the week schedule matrix
-    wsmc.0
-        .write()
-        .unwrap()
-        .update(user.id, &user.settings.schedule);
+    wsmc.0.write().unwrap().update(&user);
 
     // Save new settings in db
-    devand_db::save_user(user.0, &conn.0).map(Json)
+    devand_db::save_user(user, &conn.0)
+        .map(Json)
+        .ok_or(Status::NotFound)
 }
 
 /// Send a verification email to the logged user
@@ -70,15 +142,40 @@ fn verify_email(user: LoggedUser, mailer: State<Mailer>) -> Json<()> {
     Json(())
 }
 
-/// Retrieve user's affinities
+/// Retrieve user's affinities. Users with overlapping schedules in the
+/// coming 7 days have higher affinity.
 #[get("/affinities")]
-fn affinities(user: LoggedUser, conn: PgDevandConn) -> Option<Json<Vec<UserAffinity>>> {
-    // TODO Optimize query for public profiles
+fn affinities(
+    user: LoggedUser,
+    conn: PgDevandConn,
+    wsm: State<WeekScheduleMatrix>,
+    affinity_config: State<AffinityConfig>,
+) -> Option<Json<Vec<UserAffinity>>> {
     let users = devand_db::load_users(&conn.0)?;
     let user: User = user.into();
-    let users = users.into_iter().map(|u| u.into());
-    let affinities = devand_core::calculate_affinities(&user.into(), users);
-    Some(Json(affinities.collect()))
+    let affinities = with_projected_matrix(&wsm, Utc::now(), 7, |wsm| {
+        devand_core::calculate_users_affinities(&user, users, wsm, &affinity_config).collect()
+    });
+    Some(Json(affinities))
+}
+
+/// Retrieve the affinity config, so affinities calculated by the client
+/// have the same levels and weights
+#[get("/affinity-config")]
+fn affinity_config(
+    _user: LoggedUser,
+    affinity_config: State<AffinityConfig>,
+) -> Json<AffinityConfig> {
+    Json(*affinity_config)
+}
+
+/// Retrieve the suggested pair of the coming week, if any. Pairs are chosen
+/// once a week among all users who opted in, maximizing the total affinity
+/// (see `devand-weekly-pairs`).
+#[get("/weekly-pair")]
+fn weekly_pair(user: LoggedUser, conn: PgDevandConn) -> Json<Option<WeeklyPair>> {
+    let week = pairing::next_week(Utc::now());
+    Json(devand_db::load_weekly_pair(week, user.id, &conn.0))
 }
 
 /// Retrieve user's affinities who are online. When an user access this
@@ -91,7 +188,7 @@ fn code_now(user: LoggedUser, code_now_users: State<CodeNowUsers>) -> Json<devan
         // We always need a write lock, because we are updating cache ttl
         let mut cache = code_now_users.0.write().unwrap();
         cache.touch(user.clone());
-        cache.clone().into()
+        cache.users_for(&user)
     };
 
     Json(devand_core::CodeNow {
@@ -100,21 +197,456 @@ fn code_now(user: LoggedUser, code_now_users: State<CodeNowUsers>) -> Json<devan
     })
 }
 
-/// Retrieve possible matching for the next week, considered user's schedule
-/// and affinities
-#[get("/availability-match")]
-fn availability_match(user: LoggedUser, wsm: State<WeekScheduleMatrix>) -> Json<AvailabilityMatch> {
-    let now = Utc::now();
-    let start = now.checked_add_signed(Duration::hours(2)).unwrap();
-    let User { settings, id, .. } = user.into();
-    let availability = settings.schedule;
-    let wsm = wsm.0.read().unwrap();
-    let wsm = wsm.get();
-    let res = wsm.find_all_users_matching_in_week(id, start, availability);
-    // TODO [optimization] users with same availability time can bee lots. Sort by affinity and keep only first n
+/// Filters for availability match, all optional
+#[derive(FromForm)]
+struct AvailabilityMatchQuery {
+    /// Hours from now when the search starts
+    start_hours: Option<u32>,
+    /// Days to search, from start
+    days: Option<usize>,
+    /// Language both users know, as in user settings (e.g. `c_plus_plus`)
+    language: Option<String>,
+    /// In [0..1]
+    min_affinity: Option<f64>,
+    min_session_minutes: Option<u32>,
+}
+
+impl AvailabilityMatchQuery {
+    /// Give some time to organize a session, by default
+    const DEFAULT_START_HOURS: u32 = 2;
+
+    fn filter(&self, now: DateTime<Utc>) -> MatchFilter {
+        let max_start_hours = (schedule_matcher::MAX_HORIZON_DAYS * 24) as u32;
+        let start_hours = self
+            .start_hours
+            .unwrap_or(Self::DEFAULT_START_HOURS)
+            .min(max_start_hours);
+        let start = now + Duration::hours(start_hours as i64);
+
+        let language = self.language.as_ref().and_then(|language| {
+            serde_json::from_value(serde_json::Value::String(language.clone())).ok()
+        });
+        let min_affinity = Affinity::from_number(self.min_affinity.unwrap_or(0.0));
+        let min_session = Duration::minutes(self.min_session_minutes.unwrap_or(0) as i64);
+
+        MatchFilter::new(start)
+            .with_days(self.days.unwrap_or(7))
+            .with_language(language)
+            .with_min_affinity(min_affinity)
+            .with_min_session(min_session)
+    }
+}
+
+/// Retrieve possible matching for the next days, considered user's schedule
+/// and affinities. Users in each slot are sorted by affinity.
+#[get("/availability-match?<query..>")]
+fn availability_match(
+    user: LoggedUser,
+    wsm: State<WeekScheduleMatrix>,
+    affinity_config: State<AffinityConfig>,
+    query: Form<AvailabilityMatchQuery>,
+) -> Json<AvailabilityMatch> {
+    let filter = query.filter(Utc::now());
+    let res = find_availability_match(user.into(), &wsm, &affinity_config, &filter);
     Json(res)
 }
 
+/// Same as `availability_match`, but consecutive slots with the same user
+/// are merged in a single session
+#[get("/availability-match/sessions?<query..>")]
+fn availability_match_sessions(
+    user: LoggedUser,
+    wsm: State<WeekScheduleMatrix>,
+    affinity_config: State<AffinityConfig>,
+    query: Form<AvailabilityMatchQuery>,
+) -> Json<Vec<MatchingSession>> {
+    let filter = query.filter(Utc::now());
+    let res = find_availability_match(user.into(), &wsm, &affinity_config, &filter);
+    Json(res.sessions(filter.min_session))
+}
+
+fn find_availability_match(
+    user: User,
+    wsm: &WeekScheduleMatrix,
+    affinity_config: &AffinityConfig,
+    filter: &MatchFilter,
+) -> AvailabilityMatch {
+    let profile = MatchingProfile::from(&user);
+    let User { settings, id, .. } = user;
+    let availability = settings.schedule;
+    let time_zone = *settings.time_zone;
+    with_projected_matrix(wsm, filter.start, filter.days, |wsm| {
+        wsm.find_all_users_matching(
+            id,
+            availability,
+            time_zone,
+            &profile,
+            affinity_config,
+            filter,
+        )
+    })
+}
+
+/// Call `f` with the matrix projected on the `days` days from `start`.
+/// Projections are cached, so a write lock is only needed when the period
+/// changes.
+fn with_projected_matrix<T>(
+    wsm: &WeekScheduleMatrix,
+    start: DateTime<Utc>,
+    days: usize,
+    f: impl FnOnce(&schedule_matcher::WeekScheduleMatrix) -> T,
+) -> T {
+    {
+        let cache = wsm.0.read().unwrap();
+        if let Some(matrix) = cache.get_projected(start, days) {
+            return f(matrix);
+        }
+    }
+
+    let mut cache = wsm.0.write().unwrap();
+    f(cache.get_period(start, days))
+}
+
+/// Filters for group slots. Either `users` or `language` is needed.
+#[derive(FromForm)]
+struct GroupQuery {
+    /// User ids separated by a dash, as in chat urls. The logged user is
+    /// always part of the group.
+    users: Option<String>,
+    /// Any user who knows this language, as in user settings
+    language: Option<String>,
+    /// By default, all `users` or MIN_GROUP_SIZE users who know `language`
+    min_size: Option<usize>,
+    start_hours: Option<u32>,
+    days: Option<usize>,
+    min_session_minutes: Option<u32>,
+}
+
+impl GroupQuery {
+    fn filter(&self, user: UserId, now: DateTime<Utc>) -> Option<GroupFilter> {
+        // Same defaults and limits of the availability match
+        let match_filter = AvailabilityMatchQuery {
+            start_hours: self.start_hours,
+            days: self.days,
+            language: None,
+            min_affinity: None,
+            min_session_minutes: self.min_session_minutes,
+        }
+        .filter(now);
+
+        let filter = if let Some(users) = &self.users {
+            let mut users: BTreeSet<UserId> = parse_members(users).into_iter().collect();
+            users.insert(user);
+            GroupFilter::new(users, match_filter.start)
+        } else {
+            let language = self.language.as_ref().and_then(|language| {
+                serde_json::from_value(serde_json::Value::String(language.clone())).ok()
+            })?;
+            GroupFilter::with_language(language, match_filter.start)
+        };
+
+        let filter = match self.min_size {
+            Some(min_size) => filter.with_min_size(min_size),
+            None => filter,
+        };
+
+        Some(
+            filter
+                .with_member(user)
+                .with_days(match_filter.days)
+                .with_min_session(match_filter.min_session),
+        )
+    }
+}
+
+/// Retrieve slots where the user and a group of other users are available
+/// together, for mob programming. Bigger groups come first, then longer
+/// slots.
+#[get("/availability-match/groups?<query..>")]
+fn availability_match_groups(
+    user: LoggedUser,
+    wsm: State<WeekScheduleMatrix>,
+    query: Form<GroupQuery>,
+) -> Result<Json<Vec<GroupSlot>>, Status> {
+    let filter = query
+        .filter(user.id, Utc::now())
+        .ok_or(Status::BadRequest)?;
+
+    let slots = with_projected_matrix(&wsm, filter.start, filter.days, |wsm| {
+        wsm.find_group_slots(&filter)
+    });
+    Ok(Json(slots))
+}
+
+/// Retrieve the secret url of user's calendar feed, if enabled
+#[get("/calendar-feed")]
+fn calendar_feed(
+    user: LoggedUser,
+    crypto_encoder: State<devand_crypto::Encoder>,
+) -> Json<Option<String>> {
+    Json(calendar_feed_url(&user.into(), &crypto_encoder))
+}
+
+/// Enable the calendar feed with a new url. Previous urls are not valid
+/// anymore.
+#[post("/calendar-feed")]
+fn calendar_feed_regenerate(
+    user: LoggedUser,
+    conn: PgDevandConn,
+    crypto_encoder: State<devand_crypto::Encoder>,
+) -> Option<Json<Option<String>>> {
+    let mut user: User = user.into();
+    user.settings.calendar_feed.regenerate();
+    let user = devand_db::save_user(user, &conn.0)?;
+    Some(Json(calendar_feed_url(&user, &crypto_encoder)))
+}
+
+/// Disable the calendar feed, so its url is not valid anymore
+#[delete("/calendar-feed")]
+fn calendar_feed_revoke(user: LoggedUser, conn: PgDevandConn) -> Option<Json<Option<String>>> {
+    let mut user: User = user.into();
+    user.settings.calendar_feed.revoke();
+    devand_db::save_user(user, &conn.0)?;
+    Some(Json(None))
+}
+
+/// The calendar feed of an user, in iCalendar format. Calendar clients
+/// cannot login, so the user is authenticated by the signed token in the
+/// url (e.g. `/api/calendar/<token>.ics`).
+#[get("/calendar/<token>")]
+fn calendar(
+    token: String,
+    conn: PgDevandConn,
+    crypto_decoder: State<devand_crypto::Decoder>,
+    wsm: State<WeekScheduleMatrix>,
+    affinity_config: State<AffinityConfig>,
+) -> Option<Content<String>> {
+    let token: SignedToken = token.trim_end_matches(".ics").to_string().into();
+    let devand_crypto::CalendarFeed { user_id, key } =
+        devand_crypto::CalendarFeed::try_from_token(&token, &crypto_decoder)?;
+
+    let user = devand_db::load_user_by_id(UserId(user_id), &conn.0)?;
+    if !user.settings.calendar_feed.accepts(key) {
+        return None;
+    }
+
+    let now = Utc::now();
+    let user_id = user.id;
+
+    let mut events = calendar::availability_events(
+        user_id,
+        &user.settings.schedule,
+        *user.settings.time_zone,
+        now,
+        &Text::AvailableForPairing.to_string(),
+    );
+
+    let filter = MatchFilter::new(now);
+    let res = find_availability_match(user, &wsm, &affinity_config, &filter);
+    let sessions = res.sessions(filter.min_session);
+
+    let mut names = BTreeMap::new();
+    for session in sessions.iter() {
+        names.entry(session.user).or_insert_with(|| {
+            devand_db::load_user_by_id(session.user, &conn.0)
+                .map(|u| PublicUserProfile::from(u).full_name())
+                .unwrap_or_default()
+        });
+    }
+
+    events.extend(calendar::session_events(user_id, &sessions, |u| {
+        Text::PairingWith(&names[&u]).to_string()
+    }));
+
+    let ical = calendar::to_ical(&Text::CalendarName.to_string(), &events, now);
+    Some(Content(ContentType::new("text", "calendar"), ical))
+}
+
+fn calendar_feed_url(user: &User, crypto_encoder: &devand_crypto::Encoder) -> Option<String> {
+    let feed = user.settings.calendar_feed;
+
+    if !feed.enabled {
+        return None;
+    }
+
+    let data = devand_crypto::CalendarFeed {
+        user_id: user.id.0,
+        key: feed.key,
+    };
+    let token = data.sign(crypto_encoder);
+
+    Some(format!(
+        "{}/api/calendar/{}.ics",
+        BASE_URL.unwrap_or(DEFAULT_BASE_URL),
+        token
+    ))
+}
+
+/// Derive a weekly schedule from an iCalendar file, in user's time zone and
+/// with the resolution of user's schedule. Nothing is saved here: users
+/// preview the changes and then save them with their settings.
+#[post("/availability-import", data = "<ics>")]
+fn availability_import(user: LoggedUser, ics: Json<String>) -> Result<Json<WeekSchedule>, Status> {
+    let user: User = user.into();
+    let resolution = match &user.settings.schedule {
+        Availability::Weekly(week_schedule) => week_schedule.resolution(),
+        Availability::Never => Default::default(),
+    };
+
+    calendar::import_week_schedule(&ics.0, *user.settings.time_zone, resolution)
+        .map(Json)
+        .ok_or(Status::UnprocessableEntity)
+}
+
+/// Retrieve all sessions the user is member of, sorted by start
+#[get("/sessions")]
+fn sessions(user: LoggedUser, conn: PgDevandConn) -> Json<Vec<PairingSession>> {
+    Json(devand_db::load_sessions_by_member(user.id, &conn))
+}
+
+/// Retrieve a single session. Only members can see it.
+#[get("/sessions/<id>")]
+fn session(user: LoggedUser, id: String, conn: PgDevandConn) -> Option<Json<PairingSession>> {
+    let id: SessionId = id.parse().ok()?;
+    let session = devand_db::load_session_by_id(id, &conn)?;
+
+    if !session.is_member(user.id) {
+        return None;
+    }
+
+    Some(Json(session))
+}
+
+/// Propose a new session. The session must fit in the schedule of all
+/// members and must not overlap their other sessions. Otherwise, the
+/// conflicts are returned with a `409 Conflict` status.
+#[post("/sessions", data = "<proposal>")]
+fn session_post(
+    user: LoggedUser,
+    proposal: Json<SessionProposal>,
+    conn: PgDevandConn,
+) -> Result<Json<PairingSession>, status::Custom<Json<Vec<SessionConflict>>>> {
+    let proposal = proposal.0;
+
+    let mut members = vec![user.id];
+    members.extend(proposal.invitees.iter().copied());
+
+    let result = conn.transaction(|| {
+        // Members are locked, so concurrent proposals cannot both pass the
+        // check and book the same time
+        let users = devand_db::lock_users_by_id(&members, &conn)?;
+        let sessions = devand_db::load_sessions_by_members(&members, &conn);
+
+        let conflicts = proposal.conflicts(user.id, &users, &sessions, Utc::now());
+
+        if !conflicts.is_empty() {
+            return Err(SessionError::Conflicts(conflicts));
+        }
+
+        Ok(devand_db::add_session(user.id, proposal, &conn)?)
+    });
+
+    result.map(Json).map_err(|err| match err {
+        SessionError::Conflicts(conflicts) => status::Custom(Status::Conflict, Json(conflicts)),
+        err => {
+            log::error!("Cannot add session: {:?}", err);
+            status::Custom(Status::InternalServerError, Json(Vec::new()))
+        }
+    })
+}
+
+/// Accept, decline, cancel a session or mark it as done. A proposal cannot
+/// be accepted if, in the meantime, members have booked another session at
+/// the same time or are not available anymore.
+#[put("/sessions/<id>/status", data = "<new_status>")]
+fn session_status_put(
+    user: LoggedUser,
+    id: String,
+    new_status: Json<SessionStatus>,
+    conn: PgDevandConn,
+) -> Result<Json<PairingSession>, Status> {
+    set_session_status(user.id, &id, new_status.0, &conn)
+}
+
+/// Cancel a session
+#[delete("/sessions/<id>")]
+fn session_delete(
+    user: LoggedUser,
+    id: String,
+    conn: PgDevandConn,
+) -> Result<Json<PairingSession>, Status> {
+    set_session_status(user.id, &id, SessionStatus::Cancelled, &conn)
+}
+
+fn set_session_status(
+    user: UserId,
+    id: &str,
+    status: SessionStatus,
+    conn: &PgDevandConn,
+) -> Result<Json<PairingSession>, Status> {
+    let id: SessionId = id.parse().map_err(|_| Status::NotFound)?;
+    let session = devand_db::load_session_by_id(id, conn).ok_or(Status::NotFound)?;
+
+    if !session.is_member(user) {
+        return Err(Status::NotFound);
+    }
+
+    let members: Vec<UserId> = session.members().collect();
+
+    let result = conn.transaction(|| {
+        // Sessions of the members cannot change until the end of the
+        // transaction, so the session is loaded again
+        let users = devand_db::lock_users_by_id(&members, conn)?;
+        let session = devand_db::load_session_by_id(id, conn)
+            .ok_or(SessionError::Status(Status::NotFound))?;
+
+        if !session.can_set_status(user, status, Utc::now()) {
+            return Err(SessionError::Status(Status::Forbidden));
+        }
+
+        if status == SessionStatus::Accepted {
+            let sessions = devand_db::load_sessions_by_members(&members, conn);
+            let conflicts = session.conflicts(&users, &sessions);
+
+            if !conflicts.is_empty() {
+                return Err(SessionError::Conflicts(conflicts));
+            }
+        }
+
+        Ok(devand_db::set_session_status(id, status, conn)?)
+    });
+
+    result.map(Json).map_err(|err| match err {
+        SessionError::Status(status) => status,
+        SessionError::Conflicts(_) => Status::Conflict,
+        err => {
+            log::error!("Cannot set session status: {:?}", err);
+            Status::InternalServerError
+        }
+    })
+}
+
+/// Why a session cannot be added or changed. Its transaction is rolled
+/// back.
+#[derive(Debug)]
+enum SessionError {
+    Status(Status),
+    Conflicts(Vec<SessionConflict>),
+    Db(String),
+}
+
+impl From<devand_db::Error> for SessionError {
+    fn from(err: devand_db::Error) -> Self {
+        SessionError::Db(format!("{:?}", err))
+    }
+}
+
+impl From<diesel::result::Error> for SessionError {
+    fn from(err: diesel::result::Error) -> Self {
+        SessionError::Db(format!("{:?}", err))
+    }
+}
+
 /// Retrieve all chats
 #[get("/chats")]
 fn chats(user: LoggedUser, conn: PgDevandConn) -> Option<Json<devand_core::UserChats>> {
@@ -122,57 +654,455 @@ fn chats(user: LoggedUser, conn: PgDevandConn) -> Option<Json<devand_core::UserC
     Some(Json(result))
 }
 
-/// Retrieve all messages in a chat, given its members
-#[get("/chat/<members>/messages")]
+/// Retrieve a page of messages in a direct chat, given its members. Pages
+/// are relative to a message, given by id as `before` or `after`. Without
+/// them, the newest messages are retrieved.
+#[get("/chat/<members>/messages?<before>&<after>&<limit>")]
 fn chat_messages_get(
     user: LoggedUser,
     members: String,
+    before: Option<String>,
+    after: Option<String>,
+    limit: Option<usize>,
     conn: PgDevandConn,
-) -> Option<Json<Vec<devand_core::chat::ChatMessage>>> {
-    let members = parse_members(&members);
+    push: State<Push>,
+) -> Option<Json<Vec<ChatMessage>>> {
+    let chat = ChatRef::direct(&user, &members)?;
+    let cursor = history_cursor(before, after)?;
+    let limit = history_page_size(limit);
+    Some(Json(read_chat_messages(
+        &user, &chat, cursor, limit, &conn, &push,
+    )))
+}
 
-    // TODO [refactoring] Authorize using request guard
-    let authorized = members.contains(&user.id);
-    if !authorized {
-        return None;
+/// Retrieve info about a direct chat and its newest messages, given its
+/// members
+#[get("/chat/<members>")]
+fn chat(
+    user: LoggedUser,
+    members: String,
+    config: State<ChatConfig>,
+    conn: PgDevandConn,
+    push: State<Push>,
+) -> Option<Json<ChatInfo>> {
+    let chat = ChatRef::direct(&user, &members)?;
+    Some(Json(chat_info(&user, &chat, *config, &conn, &push)))
+}
+
+/// Post a new message in a direct chat
+#[post("/chat/<members>/messages", data = "<txt>")]
+fn chat_messages_post(
+    user: LoggedUser,
+    members: String,
+    txt: Json<String>,
+    mailer: State<Mailer>,
+    conn: PgDevandConn,
+    not_limiter: State<crate::NotificationLimiter>,
+    push: State<Push>,
+) -> Option<Json<Vec<ChatMessage>>> {
+    let chat = ChatRef::direct(&user, &members)?;
+    let new_message = post_chat_message(&user, chat, txt.0, &mailer, &conn, &not_limiter, &push)?;
+    Some(Json(vec![new_message]))
+}
+
+/// Retrieve messages of a direct chat, newer than the one with the given id.
+/// With `changed_after`, messages edited or deleted after that time follow,
+/// along with the cursor of the next poll.
+#[get("/chat/<members>/messages/poll/<after>?<changed_after>")]
+fn chat_messages_poll(
+    user: LoggedUser,
+    members: String,
+    after: String,
+    changed_after: Option<String>,
+    conn: PgDevandConn,
+    push: State<Push>,
+) -> Option<Json<ChatUpdates>> {
+    // Note: Rocket 0.4 does not support websocket, so new messages are
+    // pushed by a companion server (see `push`) and polled only when it is
+    // unavailable
+    let chat = ChatRef::direct(&user, &members)?;
+    let updates = poll_chat_messages(&user, &chat, after, changed_after, &conn, &push)?;
+    Some(Json(updates))
+}
+
+/// Create a group chat with the logged user and the given members
+#[post("/group-chats", data = "<new_chat>")]
+fn group_chat_post(
+    user: LoggedUser,
+    new_chat: Json<NewGroupChat>,
+    conn: PgDevandConn,
+) -> Result<Json<Chat>, Status> {
+    let name = new_chat.valid_name().ok_or(Status::UnprocessableEntity)?;
+    let members = new_chat
+        .valid_members(user.id)
+        .ok_or(Status::UnprocessableEntity)?;
+
+    let unknown_member = members
+        .iter()
+        .any(|&member| devand_db::load_user_by_id(member, &conn).is_none());
+    if unknown_member {
+        return Err(Status::UnprocessableEntity);
     }
 
-    let result = devand_db::load_chat_history_by_members(&members, &conn);
+    devand_db::add_group_chat(name, &members, &conn)
+        .map(Json)
+        .map_err(|err| {
+            log::error!("Cannot create group chat: {:?}", err);
+            Status::InternalServerError
+        })
+}
+
+/// Retrieve info about a group chat and its newest messages
+#[get("/group-chat/<id>")]
+fn group_chat(
+    user: LoggedUser,
+    id: String,
+    config: State<ChatConfig>,
+    conn: PgDevandConn,
+    push: State<Push>,
+) -> Option<Json<ChatInfo>> {
+    let chat = ChatRef::group(&user, &id, &conn)?;
+    Some(Json(chat_info(&user, &chat, *config, &conn, &push)))
+}
+
+/// Retrieve a page of messages in a group chat, as for direct chats
+#[get("/group-chat/<id>/messages?<before>&<after>&<limit>")]
+fn group_chat_messages_get(
+    user: LoggedUser,
+    id: String,
+    before: Option<String>,
+    after: Option<String>,
+    limit: Option<usize>,
+    conn: PgDevandConn,
+    push: State<Push>,
+) -> Option<Json<Vec<ChatMessage>>> {
+    let chat = ChatRef::group(&user, &id, &conn)?;
+    let cursor = history_cursor(before, after)?;
+    let limit = history_page_size(limit);
+    Some(Json(read_chat_messages(
+        &user, &chat, cursor, limit, &conn, &push,
+    )))
+}
+
+/// Post a new message in a group chat
+#[post("/group-chat/<id>/messages", data = "<txt>")]
+fn group_chat_messages_post(
+    user: LoggedUser,
+    id: String,
+    txt: Json<String>,
+    mailer: State<Mailer>,
+    conn: PgDevandConn,
+    not_limiter: State<crate::NotificationLimiter>,
+    push: State<Push>,
+) -> Option<Json<Vec<ChatMessage>>> {
+    let chat = ChatRef::group(&user, &id, &conn)?;
+    let new_message = post_chat_message(&user, chat, txt.0, &mailer, &conn, &not_limiter, &push)?;
+    Some(Json(vec![new_message]))
+}
+
+/// Retrieve messages of a group chat, as for direct chats
+#[get("/group-chat/<id>/messages/poll/<after>?<changed_after>")]
+fn group_chat_messages_poll(
+    user: LoggedUser,
+    id: String,
+    after: String,
+    changed_after: Option<String>,
+    conn: PgDevandConn,
+    push: State<Push>,
+) -> Option<Json<ChatUpdates>> {
+    let chat = ChatRef::group(&user, &id, &conn)?;
+    let updates = poll_chat_messages(&user, &chat, after, changed_after, &conn, &push)?;
+    Some(Json(updates))
+}
 
-    if !result.is_empty() {
-        devand_db::mark_messages_as_read_by(user.id, &result, &conn);
+/// Add a member to a group chat. Any member can add other users.
+#[put("/group-chat/<id>/members/<member>")]
+fn group_chat_member_put(
+    user: LoggedUser,
+    id: String,
+    member: i32,
+    conn: PgDevandConn,
+) -> Result<Json<Chat>, Status> {
+    let chat = match ChatRef::group(&user, &id, &conn) {
+        Some(ChatRef::Group(chat)) => chat,
+        _ => return Err(Status::NotFound),
+    };
+
+    let member = devand_db::load_user_by_id(UserId(member), &conn)
+        .ok_or(Status::UnprocessableEntity)?
+        .id;
+
+    if chat.members.contains(&member) {
+        return Ok(Json(chat));
     }
 
-    Some(Json(result))
+    devand_db::add_group_chat_member(chat.id, member, &conn)
+        .map(Json)
+        .map_err(|err| {
+            log::error!("Cannot add group chat member: {:?}", err);
+            Status::InternalServerError
+        })
 }
 
-/// Retrieve all messages in a chat, given its members
-#[get("/chat/<members>")]
-fn chat(
+/// Leave a group chat. Members cannot remove other members.
+#[delete("/group-chat/<id>/members/<member>")]
+fn group_chat_member_delete(
     user: LoggedUser,
-    members: String,
+    id: String,
+    member: i32,
+    conn: PgDevandConn,
+    push: State<Push>,
+) -> Result<Json<Chat>, Status> {
+    let chat = match ChatRef::group(&user, &id, &conn) {
+        Some(ChatRef::Group(chat)) => chat,
+        _ => return Err(Status::NotFound),
+    };
+
+    if UserId(member) != user.id {
+        return Err(Status::Forbidden);
+    }
+
+    let chat = devand_db::remove_group_chat_member(chat.id, user.id, &conn).map_err(|err| {
+        log::error!("Cannot remove group chat member: {:?}", err);
+        Status::InternalServerError
+    })?;
+
+    // Unread messages of the chat are gone
+    push.unread_messages(user.id, &conn);
+
+    Ok(Json(chat))
+}
+
+/// Edit a message. Only its author can, shortly after sending it.
+#[put("/chat-message/<id>", data = "<txt>")]
+fn chat_message_put(
+    user: LoggedUser,
+    id: String,
+    txt: Json<String>,
+    config: State<ChatConfig>,
     conn: PgDevandConn,
-) -> Option<Json<devand_core::chat::ChatInfo>> {
-    let members = parse_members(&members);
+    push: State<Push>,
+) -> Result<Json<ChatMessage>, Status> {
+    let txt = valid_edit(&txt.0).ok_or(Status::UnprocessableEntity)?;
+    let (chat, message) = changeable_message(&user, &id, *config, &conn)?;
+
+    let message = devand_db::edit_chat_message(message.id, txt, &conn).map_err(|err| {
+        log::error!("Cannot edit chat message: {:?}", err);
+        Status::InternalServerError
+    })?;
+
+    push.message_changed(&chat, &message, &conn);
+
+    Ok(Json(message))
+}
+
+/// Delete a message. Only its author can, shortly after sending it. The
+/// message stays in the chat without text.
+#[delete("/chat-message/<id>")]
+fn chat_message_delete(
+    user: LoggedUser,
+    id: String,
+    config: State<ChatConfig>,
+    conn: PgDevandConn,
+    push: State<Push>,
+) -> Result<Json<ChatMessage>, Status> {
+    let (chat, message) = changeable_message(&user, &id, *config, &conn)?;
+
+    let message = devand_db::delete_chat_message(message.id, &conn).map_err(|err| {
+        log::error!("Cannot delete chat message: {:?}", err);
+        Status::InternalServerError
+    })?;
+
+    push.message_changed(&chat, &message, &conn);
+
+    Ok(Json(message))
+}
+
+/// Retrieve previous texts of an edited message, oldest first
+#[get("/chat-message/<id>/edits")]
+fn chat_message_edits(
+    user: LoggedUser,
+    id: String,
+    conn: PgDevandConn,
+) -> Option<Json<Vec<ChatMessageEdit>>> {
+    let id = uuid::Uuid::parse_str(&id).ok()?;
+    let (chat, message) = devand_db::load_chat_message_by_id(id, &conn)?;
+
+    if chat.members.contains(&user.id) {
+        Some(Json(devand_db::load_chat_message_edits(message.id, &conn)))
+    } else {
+        None
+    }
+}
+
+/// A chat the logged user is member of. Direct chats are found by their
+/// members and are created with the first message. Group chats are found by
+/// id.
+enum ChatRef {
+    Direct(Vec<UserId>),
+    Group(Chat),
+}
 
+impl ChatRef {
+    /// Direct chats have two members, given as in urls, in any order
     // TODO [refactoring] Authorize using request guard
-    let authorized = members.contains(&user.id);
-    if !authorized {
-        return None;
+    fn direct(user: &LoggedUser, members: &str) -> Option<Self> {
+        let members = canonical_members(&parse_members(members));
+
+        if members.len() == 2 && members.contains(&user.id) {
+            Some(ChatRef::Direct(members))
+        } else {
+            None
+        }
     }
 
-    let messages = devand_db::load_chat_history_by_members(&members, &conn);
+    fn group(user: &LoggedUser, id: &str, conn: &PgDevandConn) -> Option<Self> {
+        let id: ChatId = id.parse().ok()?;
+        let chat = devand_db::load_chat_by_id(id, conn)?;
+
+        if chat.is_group() && chat.members.contains(&user.id) {
+            Some(ChatRef::Group(chat))
+        } else {
+            None
+        }
+    }
+
+    fn members(&self) -> &[UserId] {
+        match self {
+            ChatRef::Direct(members) => members,
+            ChatRef::Group(chat) => &chat.members,
+        }
+    }
+
+    fn load_history_page(
+        &self,
+        cursor: HistoryCursor,
+        limit: usize,
+        conn: &PgDevandConn,
+    ) -> Vec<ChatMessage> {
+        match self {
+            ChatRef::Direct(members) => {
+                devand_db::load_chat_history_page_by_members(members, cursor, limit, conn)
+            }
+            ChatRef::Group(chat) => {
+                devand_db::load_chat_history_page_by_id(chat.id, cursor, limit, conn)
+            }
+        }
+    }
+
+    fn load_changes(&self, after: DateTime<Utc>, conn: &PgDevandConn) -> Vec<ChatMessage> {
+        match self {
+            ChatRef::Direct(members) => {
+                devand_db::load_chat_changes_by_members(members, after, conn)
+            }
+            ChatRef::Group(chat) => devand_db::load_chat_changes_by_id(chat.id, after, conn),
+        }
+    }
+}
+
+/// Load a page of messages and mark them as read by the user
+fn read_chat_messages(
+    user: &LoggedUser,
+    chat: &ChatRef,
+    cursor: HistoryCursor,
+    limit: usize,
+    conn: &PgDevandConn,
+    push: &Push,
+) -> Vec<ChatMessage> {
+    let messages = chat.load_history_page(cursor, limit, conn);
 
     if !messages.is_empty() {
-        devand_db::mark_messages_as_read_by(user.id, &messages, &conn);
+        devand_db::mark_messages_as_read_by(user.id, &messages, conn);
+        push.unread_messages(user.id, conn);
     }
 
-    let members_info = members
+    messages
+}
+
+/// New messages after the given one, followed by the ones changed after the
+/// given time. Changed messages are already known by the client, which
+/// replaces them and polls again from the returned cursor.
+fn poll_chat_messages(
+    user: &LoggedUser,
+    chat: &ChatRef,
+    after: String,
+    changed_after: Option<String>,
+    conn: &PgDevandConn,
+    push: &Push,
+) -> Option<ChatUpdates> {
+    let cursor = history_cursor(None, Some(after))?;
+    let changed_after = match changed_after {
+        Some(changed_after) => Some(parse_time(&changed_after)?),
+        None => None,
+    };
+
+    let limit = MAX_HISTORY_PAGE_SIZE;
+    let mut messages = read_chat_messages(user, chat, cursor, limit, conn, push);
+
+    let changed_after = changed_after.map(|changed_after| {
+        let changes = chat.load_changes(changed_after, conn);
+        let cursor = next_changes_cursor(changed_after, &changes);
+        let changes: Vec<_> = changes
+            .into_iter()
+            .filter(|changed| !messages.iter().any(|m| m.id == changed.id))
+            .collect();
+        messages.extend(changes);
+        cursor
+    });
+
+    Some(ChatUpdates {
+        messages,
+        changed_after,
+    })
+}
+
+/// A message the user can edit or delete, with its chat
+fn changeable_message(
+    user: &LoggedUser,
+    id: &str,
+    config: ChatConfig,
+    conn: &PgDevandConn,
+) -> Result<(Chat, ChatMessage), Status> {
+    let id = uuid::Uuid::parse_str(id).map_err(|_| Status::NotFound)?;
+    let (chat, message) = devand_db::load_chat_message_by_id(id, conn).ok_or(Status::NotFound)?;
+
+    if !chat.members.contains(&user.id) {
+        return Err(Status::NotFound);
+    }
+
+    if message.can_be_changed_by(user.id, Utc::now(), config) {
+        Ok((chat, message))
+    } else {
+        Err(Status::Forbidden)
+    }
+}
+
+fn chat_info(
+    user: &LoggedUser,
+    chat: &ChatRef,
+    config: ChatConfig,
+    conn: &PgDevandConn,
+    push: &Push,
+) -> ChatInfo {
+    // Changes made while loading are polled again, not skipped
+    let changed_after = Utc::now();
+
+    let messages = read_chat_messages(
+        user,
+        chat,
+        HistoryCursor::Latest,
+        HISTORY_PAGE_SIZE,
+        conn,
+        push,
+    );
+
+    let members_info = chat
+        .members()
         .iter()
         .filter_map(|&user_id| {
             // TODO [optimization] do not load full user info, only needed
             // TODO [optimization] for multiple users, just do only one call to db
-            let user = devand_db::load_user_by_id(user_id, &conn)?;
+            let user = devand_db::load_user_by_id(user_id, conn)?;
 
             Some(devand_core::chat::ChatMemberInfo {
                 user_id,
@@ -181,77 +1111,66 @@ fn chat(
         })
         .collect();
 
-    let result = devand_core::chat::ChatInfo {
-        members_info,
-        messages,
+    let name = match chat {
+        ChatRef::Direct(_) => None,
+        ChatRef::Group(chat) => chat.name.clone(),
     };
 
-    Some(Json(result))
+    ChatInfo {
+        name,
+        members_info,
+        messages,
+        config,
+        changed_after: Some(changed_after),
+    }
 }
 
-/// Post a new message in a chat
-#[post("/chat/<members>/messages", data = "<txt>")]
-fn chat_messages_post(
-    user: LoggedUser,
-    members: String,
-    txt: Json<String>,
-    mailer: State<Mailer>,
-    conn: PgDevandConn,
-    not_limiter: State<crate::NotificationLimiter>,
-) -> Option<Json<Vec<devand_core::chat::ChatMessage>>> {
-    let author = user.id;
-    let txt = txt.0;
-    let members = parse_members(&members);
-
-    // TODO [refactoring] Authorize using request guard
-    let authorized = members.contains(&user.id);
-    if !authorized {
-        return None;
-    }
+/// Add a message, notify other members by email and push it to all members
+fn post_chat_message(
+    user: &LoggedUser,
+    chat: ChatRef,
+    txt: String,
+    mailer: &Mailer,
+    conn: &PgDevandConn,
+    not_limiter: &crate::NotificationLimiter,
+    push: &Push,
+) -> Option<ChatMessage> {
+    let chat = match chat {
+        ChatRef::Direct(members) => devand_db::find_or_create_direct_chat(&members, conn)
+            .map_err(|err| log::error!("Cannot create chat: {:?}", err))
+            .ok()?,
+        ChatRef::Group(chat) => chat,
+    };
 
     let mut not_limiter = not_limiter.0.write().unwrap();
 
     crate::notifications::notify_chat_members(
         BASE_URL.unwrap_or(DEFAULT_BASE_URL),
-        &mailer,
-        &conn,
-        &user,
-        &members,
+        mailer,
+        conn,
+        user,
+        &chat,
+        &txt,
         &mut not_limiter,
     );
 
-    devand_db::add_chat_message_by_members(&members, author, txt, &conn).map(|new_message| Json(vec![new_message]))
+    let new_message = devand_db::add_chat_message_by_id(chat.id, user.id, txt, conn).ok()?;
+    push.new_message(&chat, &new_message, conn);
+    Some(new_message)
 }
 
-/// Retrieve new messages
-#[get("/chat/<members>/messages/poll/<after>")]
-fn chat_messages_poll(
+/// Url of the push channel, where the UI receives chat events. The url
+/// contains a token which authenticates the user for an hour. Not found when
+/// the push server is disabled, so the UI polls for new messages.
+#[get("/push")]
+fn push(
     user: LoggedUser,
-    members: String,
-    after: i64,
-    conn: PgDevandConn,
-) -> Option<Json<Vec<devand_core::chat::ChatMessage>>> {
-    // Note: Rocket 0.4 does not support websocket, so we just poll for new messages
-    let members = parse_members(&members);
-
-    // TODO [refactoring] Authorize using request guard
-    let authorized = members.contains(&user.id);
-    if !authorized {
-        return None;
-    }
-
-    // TODO [optimization] It could be better loading from db only messages created after the
-    // threshold, instead of filtering here.
-    let result: Vec<_> = devand_db::load_chat_history_by_members(&members, &conn)
-        .into_iter()
-        .filter(|x| x.created_at.timestamp() > after)
-        .collect();
-
-    if !result.is_empty() {
-        devand_db::mark_messages_as_read_by(user.id, &result, &conn);
-    }
-
-    Some(Json(result))
+    push: State<Push>,
+    crypto_encoder: State<devand_crypto::Encoder>,
+) -> Option<Json<String>> {
+    let url = push.url.as_ref()?;
+    let token = devand_crypto::PushChannel { user_id: user.id.0 }.sign(&crypto_encoder);
+    Some(Json(format!("{}?token={}", url, token)))
 }
 
 /// Load user public profile, given the user id. Note that this api is
@@ -282,6 +1201,33 @@ fn user_public_profile(
     Some(Json(user.into()))
 }
 
+/// Retrieve all topics users are interested in, most popular first
+#[get("/topics")]
+fn topics(_user: LoggedUser, conn: PgDevandConn) -> Option<Json<Vec<TopicCount>>> {
+    let users = devand_db::load_users(&conn.0)?;
+    let topics = devand_core::count_topics(users.iter().map(|u| &u.settings.topics));
+    Some(Json(topics))
+}
+
+/// Retrieve public profiles of all users interested in a topic. The topic is
+/// normalized, so `/topics/Web%20Assembly` is the same as
+/// `/topics/web-assembly`
+#[get("/topics/<topic>")]
+fn topic_users(
+    _user: LoggedUser,
+    topic: String,
+    conn: PgDevandConn,
+) -> Option<Json<Vec<PublicUserProfile>>> {
+    let topic: Topic = topic.parse().ok()?;
+    let users = devand_db::load_users(&conn.0)?;
+    let users = users
+        .into_iter()
+        .filter(|u| u.settings.topics.contains(&topic))
+        .map(PublicUserProfile::from)
+        .collect();
+    Some(Json(users))
+}
+
 /// Check if user password is the right one. This enpoint is used when
 /// user is changing password.  It  just give an immediate feedback about
 /// password validity.
@@ -319,6 +1265,26 @@ fn password_edit(
         .map_err(|_| Status::InternalServerError)
 }
 
+/// Pages of chat history are relative to a message, either `before` or
+/// `after` it, but not both
+fn history_cursor(before: Option<String>, after: Option<String>) -> Option<HistoryCursor> {
+    let parse = |id: String| uuid::Uuid::parse_str(&id).ok();
+
+    match (before, after) {
+        (None, None) => Some(HistoryCursor::Latest),
+        (Some(before), None) => parse(before).map(HistoryCursor::Before),
+        (None, Some(after)) => parse(after).map(HistoryCursor::After),
+        (Some(_), Some(_)) => None,
+    }
+}
+
+/// Times in urls are in RFC 3339 format, e.g. `2020-10-01T10:00:00.123456Z`
+fn parse_time(s: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(s)
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
 /// Given a string with user ids separated by a dash, return a Vec of UserId
 fn parse_members(s: &str) -> Vec<UserId> {
     s.split('-')
@@ -338,6 +1304,35 @@ mod test {
     use rocket::local::Client;
     use serial_test::serial;
 
+    #[test]
+    fn history_cursor_ok() {
+        let id = "554119d7-554a-438a-ac33-7a0b1bf99437";
+        let uuid = uuid::Uuid::parse_str(id).unwrap();
+
+        assert_eq!(history_cursor(None, None), Some(HistoryCursor::Latest));
+        assert_eq!(
+            history_cursor(Some(id.into()), None),
+            Some(HistoryCursor::Before(uuid))
+        );
+        assert_eq!(
+            history_cursor(None, Some(id.into())),
+            Some(HistoryCursor::After(uuid))
+        );
+        assert_eq!(history_cursor(None, Some("1600000000".into())), None);
+        assert_eq!(history_cursor(Some(id.into()), Some(id.into())), None);
+    }
+
+    #[test]
+    fn parse_time_ok() {
+        let t = parse_time("2020-10-01T10:00:00.123456Z").unwrap();
+        assert_eq!(t, Utc.ymd(2020, 10, 1).and_hms_micro(10, 0, 0, 123_456));
+        assert_eq!(
+            parse_time("2020-10-01T12:00:00+02:00"),
+            Some(Utc.ymd(2020, 10, 1).and_hms(10, 0, 0))
+        );
+        assert_eq!(parse_time("1600000000"), None);
+    }
+
     #[test]
     fn parse_members_ok() {
         let members = parse_members("5-72");
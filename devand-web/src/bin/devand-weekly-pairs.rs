@@ -0,0 +1,53 @@
+//! Compute and save pairs of the coming week.
+//!
+//! Suggesting pairs needs all users, so it is too expensive to do for each
+//! request: this job is meant to run periodically, e.g. hourly by cron, and
+//! the api only loads the saved pairs. Pairs of a week are computed once, so
+//! running it again does nothing until the next week starts. It reads the
+//! database and the affinity config as devand-web does.
+
+#[path = "../affinity_config.rs"]
+mod affinity_config;
+
+use chrono::prelude::*;
+use devand_core::pairing;
+use env_logger::Env;
+use rocket_contrib::databases::database_config;
+use rocket_contrib::databases::diesel::{Connection, PgConnection};
+
+fn main() {
+    dotenv::dotenv().ok();
+
+    env_logger::from_env(Env::default().default_filter_or("devand_weekly_pairs=info")).init();
+
+    if let Err(err) = save_weekly_pairs() {
+        log::error!("Cannot save weekly pairs: {}", err);
+        std::process::exit(1);
+    }
+}
+
+fn save_weekly_pairs() -> Result<(), String> {
+    // Only to read the config, the server is not launched
+    let rocket = rocket::ignite();
+    let config = rocket.config();
+
+    let affinity_config = affinity_config::load(config)?;
+    let url = database_config("pg_devand", config).map_err(|err| format!("{:?}", err))?;
+    let conn = PgConnection::establish(url.url).map_err(|err| err.to_string())?;
+
+    let week = pairing::next_week(Utc::now());
+
+    if devand_db::has_weekly_pairs(week, &conn) {
+        log::info!("Pairs of week {} are already saved", week);
+        return Ok(());
+    }
+
+    let users = devand_db::load_users(&conn).ok_or("Cannot load users")?;
+    let pairs = pairing::suggest_weekly_pairs(&users, week, &affinity_config);
+
+    devand_db::save_weekly_pairs(week, &pairs, &conn).map_err(|err| format!("{:?}", err))?;
+
+    log::info!("Saved {} pairs of week {}", pairs.0.len(), week);
+
+    Ok(())
+}
@@ -3,12 +3,15 @@
 #[macro_use]
 extern crate rocket;
 
+mod affinity_config;
 mod api;
 mod auth;
 mod notifications;
 mod pages;
+mod push;
 mod state;
 
+use rocket::config::ConfigError;
 use rocket::fairing::AdHoc;
 use rocket::http::uri::Uri;
 use rocket::{Request, Rocket};
@@ -41,6 +44,46 @@ fn static_files(rocket: Rocket) -> Result<Rocket, Rocket> {
     Ok(rocket)
 }
 
+fn affinity_config(rocket: Rocket) -> Result<Rocket, Rocket> {
+    match affinity_config::load(rocket.config()) {
+        Ok(affinity_config) => Ok(rocket.manage(affinity_config)),
+        Err(err) => {
+            log::error!("{}", err);
+            Err(rocket)
+        }
+    }
+}
+
+/// Load chat config. Every key is optional and can be set in Rocket.toml or
+/// by env, e.g. `ROCKET_CHAT_EDIT_WINDOW_MINUTES=30`. A key with a wrong type
+/// is an error, it is not replaced by the default.
+fn chat_config(rocket: Rocket) -> Result<Rocket, Rocket> {
+    let default = devand_core::chat::ChatConfig::default();
+
+    let edit_window_minutes = match rocket.config().get_int("chat_edit_window_minutes") {
+        Ok(edit_window_minutes) => edit_window_minutes,
+        Err(ConfigError::Missing(_)) => default.edit_window_minutes,
+        Err(err) => {
+            log::error!("{}", err);
+            return Err(rocket);
+        }
+    };
+
+    let chat_config = devand_core::chat::ChatConfig {
+        edit_window_minutes,
+    };
+
+    if !chat_config.is_valid() {
+        log::error!(
+            "chat_edit_window_minutes must be between 0 and {}",
+            devand_core::chat::MAX_EDIT_WINDOW_MINUTES
+        );
+        return Err(rocket);
+    }
+
+    Ok(rocket.manage(chat_config))
+}
+
 fn run_db_migrations(rocket: Rocket) -> Result<Rocket, Rocket> {
     let conn = PgDevandConn::get_one(&rocket).expect("database connection");
     match devand_db::run_migrations(&*conn) {
@@ -118,6 +161,7 @@ fn ignite() -> rocket::Rocket {
 
     let secret = std::env::var("DEVAND_SECRET").expect("env var DEVAND_SECRET to be present");
     let secret = secret.as_bytes();
+    let push_secret = secret.to_vec();
 
     rocket::ignite()
         .manage(create_mailer())
@@ -130,7 +174,12 @@ fn ignite() -> rocket::Rocket {
         .attach(PgDevandConn::fairing())
         .attach(AdHoc::on_attach("Database Migrations", run_db_migrations))
         .attach(AdHoc::on_attach("Static files", static_files))
+        .attach(AdHoc::on_attach("Affinity config", affinity_config))
+        .attach(AdHoc::on_attach("Chat config", chat_config))
         .attach(AdHoc::on_attach("WeekScheduleMatrixCache", init_wsmc))
+        .attach(AdHoc::on_attach("Push server", move |rocket| {
+            push::attach(rocket, &push_secret)
+        }))
         .mount("/", pages::routes())
         .mount("/api", api::routes())
         .register(catchers![not_found, unauthorized])
@@ -1,29 +1,57 @@
 use crate::Mailer;
 use crate::PgDevandConn;
 use chrono::{DateTime, Utc};
+use devand_core::chat::{markdown, Chat};
 use devand_core::{User, UserId};
 use devand_crypto::SignedToken;
 use devand_db::load_user_by_id;
 use devand_mailer::CcnEmail;
 
+/// The message as plain text, indented so that the mailer, which converts
+/// the email from Markdown to HTML, shows it as it is
+fn quote_message(txt: &str) -> String {
+    markdown::to_plain_text(txt)
+        .lines()
+        .map(|line| format!("    {}", line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 // TODO Subject/Text from text template
 pub(crate) fn notify_chat_members(
     base_url: &str,
     mailer: &Mailer,
     conn: &PgDevandConn,
     from: &User,
-    to: &[UserId],
+    chat: &Chat,
+    txt: &str,
     limiter: &mut NotificationLimiter,
 ) {
-    // TODO Chat can have more than one user by design, but the url is for just two users
-    let chat_url = format!("{}/chat/{}", base_url, &from.username);
-
-    let subject = format!("DevAndDev - {} sent you a new message", &from.visible_name);
-
-    let text = format!(
-        "You have a message from {}. View on DevAndDev: {}",
-        &from.visible_name, chat_url
-    );
+    let message = quote_message(txt);
+
+    let (subject, text) = match &chat.name {
+        Some(name) => {
+            let chat_url = format!("{}/group-chat/{}", base_url, chat.id);
+            let subject = format!(
+                "DevAndDev - {} sent a new message in {}",
+                &from.visible_name, name
+            );
+            let text = format!(
+                "You have a message from {} in the group chat {}:\n\n{}\n\nView on DevAndDev: {}",
+                &from.visible_name, name, message, chat_url
+            );
+            (subject, text)
+        }
+        None => {
+            let chat_url = format!("{}/chat/{}", base_url, &from.username);
+            let subject = format!("DevAndDev - {} sent you a new message", &from.visible_name);
+            let text = format!(
+                "You have a message from {}:\n\n{}\n\nView on DevAndDev: {}",
+                &from.visible_name, message, chat_url
+            );
+            (subject, text)
+        }
+    };
 
     // Note: if an addres cannot be obtained, error is ignored and email is
     // just not sent.
@@ -31,7 +59,8 @@ pub(crate) fn notify_chat_members(
 
     let now = Utc::now();
 
-    let recipients: Vec<_> = to
+    let recipients: Vec<_> = chat
+        .members
         .iter()
         // Prevent sending messages to `from` user
         .filter(|&&u| u != from.id)
@@ -138,4 +167,10 @@ mod test {
         assert!(limiter.can_send(UserId(1), UserId(2), now));
         assert!(!limiter.can_send(UserId(1), UserId(2), now));
     }
+
+    #[test]
+    fn message_is_quoted_as_plain_text() {
+        let txt = "See **this**:\n```rust\nfn main() {}\n```";
+        assert_eq!(quote_message(txt), "    See this:\n    \n    fn main() {}");
+    }
 }
@@ -318,6 +318,11 @@ fn dashboard_chat(auth_data: AuthData, _username: String) -> Template {
     dashboard(auth_data)
 }
 
+#[get("/group-chat/<_id>")]
+fn dashboard_group_chat(auth_data: AuthData, _id: String) -> Template {
+    dashboard(auth_data)
+}
+
 #[get("/chat")]
 fn dashboard_chats(auth_data: AuthData) -> Template {
     dashboard(auth_data)
@@ -328,6 +333,16 @@ fn dashboard_schedule(auth_data: AuthData) -> Template {
     dashboard(auth_data)
 }
 
+#[get("/schedule/groups")]
+fn dashboard_group_schedule(auth_data: AuthData) -> Template {
+    dashboard(auth_data)
+}
+
+#[get("/sessions")]
+fn dashboard_sessions(auth_data: AuthData) -> Template {
+    dashboard(auth_data)
+}
+
 #[get("/settings/password")]
 fn dashboard_settings_password(auth_data: AuthData) -> Template {
     dashboard(auth_data)
@@ -338,6 +353,16 @@ fn dashboard_user_profile(auth_data: AuthData, _username: String) -> Template {
     dashboard(auth_data)
 }
 
+#[get("/topics")]
+fn dashboard_topics(auth_data: AuthData) -> Template {
+    dashboard(auth_data)
+}
+
+#[get("/topics/<_topic>")]
+fn dashboard_topic(auth_data: AuthData, _topic: String) -> Template {
+    dashboard(auth_data)
+}
+
 fn dashboard(_auth_data: AuthData) -> Template {
     #[derive(Serialize)]
     struct Context {
@@ -508,10 +533,15 @@ pub fn routes() -> Vec<Route> {
         dashboard_affinities,
         dashboard_code_now,
         dashboard_schedule,
+        dashboard_group_schedule,
+        dashboard_sessions,
         dashboard_chat,
+        dashboard_group_chat,
         dashboard_chats,
         dashboard_settings_password,
         dashboard_user_profile,
+        dashboard_topics,
+        dashboard_topic,
         privacy,
         code_of_conduct,
         help,
@@ -0,0 +1,397 @@
+//! Push chat events to the UI with Server-Sent Events.
+//!
+//! Rocket 0.4 buffers streamed responses, so it cannot send an event as soon
+//! as it happens. Instead, a small companion server listens on its own
+//! address, with a thread for each connected client. The login cookie cannot
+//! be read outside Rocket, so clients authenticate with a short lived token,
+//! obtained from `/api/push`. When the server is disabled or unreachable, the
+//! UI falls back to polling.
+
+use crate::PgDevandConn;
+use devand_core::chat::{Chat, ChatEvent, ChatMessage};
+use devand_core::UserId;
+use devand_crypto::{Decoder, PushChannel, Signable, SignedToken};
+use rocket::Rocket;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const BASE_URL: Option<&'static str> = option_env!("DEVAND_BASE_URL");
+const DEFAULT_BASE_URL: &str = "http://localhost:8000";
+
+/// Without events, a comment is sent periodically to detect closed
+/// connections and to prevent proxies from closing idle ones
+const KEEP_ALIVE_SECONDS: u64 = 15;
+
+/// Clients must send their request within this time
+const REQUEST_TIMEOUT_SECONDS: u64 = 10;
+
+/// Events are not sent to clients which do not receive them within this time
+const WRITE_TIMEOUT_SECONDS: u64 = 30;
+
+/// Requests with more header lines are rejected
+const MAX_HEADER_LINES: usize = 100;
+
+/// Requests with a longer head are rejected
+const MAX_REQUEST_BYTES: u64 = 8 * 1024;
+
+/// Each client has its own thread, so connections are limited
+const DEFAULT_MAX_CLIENTS: usize = 1000;
+
+/// Managed by Rocket. Without an url, the push server is disabled.
+pub struct Push {
+    pub url: Option<String>,
+    hub: Arc<PushHub>,
+}
+
+impl Push {
+    /// Send a new message to all chat members, including the author, who
+    /// may have the chat open somewhere else. The others also get their
+    /// count of unread messages.
+    pub(crate) fn new_message(&self, chat: &Chat, message: &ChatMessage, conn: &PgDevandConn) {
+        let event = ChatEvent::NewMessage {
+            chat: chat.clone(),
+            message: message.clone(),
+        };
+
+        for &member in &chat.members {
+            self.hub.send(member, &event);
+
+            if member != message.author {
+                self.unread_messages(member, conn);
+            }
+        }
+    }
+
+    /// Send an edited or deleted message to all chat members. A deleted
+    /// message is not unread anymore, so the others also get their count of
+    /// unread messages.
+    pub(crate) fn message_changed(&self, chat: &Chat, message: &ChatMessage, conn: &PgDevandConn) {
+        let event = ChatEvent::MessageChanged {
+            chat: chat.clone(),
+            message: message.clone(),
+        };
+
+        for &member in &chat.members {
+            self.hub.send(member, &event);
+
+            if message.is_deleted() && member != message.author {
+                self.unread_messages(member, conn);
+            }
+        }
+    }
+
+    /// Send the count of unread messages to the user, e.g. after they read
+    /// some of them
+    pub(crate) fn unread_messages(&self, user: UserId, conn: &PgDevandConn) {
+        // Do not load chats of users who cannot receive the event
+        if self.hub.is_connected(user) {
+            let unread_messages = devand_db::load_chats_by_member(user, conn);
+            let event = ChatEvent::UnreadMessages(unread_messages.total_unread_messages());
+            self.hub.send(user, &event);
+        }
+    }
+}
+
+/// Connected clients, to which events are sent
+#[derive(Default)]
+struct PushHub {
+    clients: Mutex<Vec<(usize, UserId, Sender<String>)>>,
+    next_id: AtomicUsize,
+}
+
+impl PushHub {
+    /// Add a client of the user, which is removed when the subscription is
+    /// dropped
+    fn subscribe(&self, user: UserId) -> Subscription<'_> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let (tx, rx) = channel();
+        self.clients.lock().unwrap().push((id, user, tx));
+        Subscription {
+            hub: self,
+            id,
+            events: rx,
+        }
+    }
+
+    fn is_connected(&self, user: UserId) -> bool {
+        let clients = self.clients.lock().unwrap();
+        clients.iter().any(|(_, u, _)| *u == user)
+    }
+
+    /// Send the event to all clients of the user. Clients which cannot
+    /// receive it anymore are removed.
+    fn send(&self, user: UserId, event: &ChatEvent) {
+        let data = match serde_json::to_string(event) {
+            Ok(data) => data,
+            Err(err) => {
+                log::error!("Cannot serialize chat event: {:?}", err);
+                return;
+            }
+        };
+
+        let mut clients = self.clients.lock().unwrap();
+        clients.retain(|(_, u, tx)| *u != user || tx.send(data.clone()).is_ok());
+    }
+}
+
+/// Events of a connected client
+struct Subscription<'a> {
+    hub: &'a PushHub,
+    id: usize,
+    events: Receiver<String>,
+}
+
+impl Drop for Subscription<'_> {
+    fn drop(&mut self) {
+        let mut clients = self.hub.clients.lock().unwrap();
+        clients.retain(|(id, _, _)| *id != self.id);
+    }
+}
+
+/// Start the push server, if `push_address` is set in Rocket.toml or by env,
+/// e.g. `ROCKET_PUSH_ADDRESS=0.0.0.0:8001`. The UI connects to `push_url`,
+/// which defaults to `http://<push_address>` and must be changed when the
+/// server is behind a proxy. At most `push_max_clients` are connected at
+/// the same time.
+pub(crate) fn attach(rocket: Rocket, secret: &[u8]) -> Result<Rocket, Rocket> {
+    let hub = Arc::new(PushHub::default());
+    let config = rocket.config();
+
+    let address = match config.get_str("push_address") {
+        Ok(address) => address.to_string(),
+        Err(_) => {
+            log::info!("Push server is disabled");
+            return Ok(rocket.manage(Push { url: None, hub }));
+        }
+    };
+
+    let url = config
+        .get_str("push_url")
+        .map(String::from)
+        .unwrap_or_else(|_| format!("http://{}", address));
+
+    let max_clients = match config.get_int("push_max_clients") {
+        Ok(max_clients) if max_clients > 0 => max_clients as usize,
+        Ok(_) => {
+            log::error!("push_max_clients must be positive");
+            return Err(rocket);
+        }
+        Err(_) => DEFAULT_MAX_CLIENTS,
+    };
+
+    let listener = match TcpListener::bind(&address) {
+        Ok(listener) => listener,
+        Err(err) => {
+            log::error!("Cannot start push server on {}: {}", address, err);
+            return Err(rocket);
+        }
+    };
+
+    let decoder = Arc::new(Decoder::new_from_secret(secret));
+    let server_hub = hub.clone();
+    std::thread::spawn(move || serve(listener, server_hub, decoder, max_clients));
+
+    log::info!("Push server listening on {}", address);
+
+    Ok(rocket.manage(Push {
+        url: Some(url),
+        hub,
+    }))
+}
+
+fn serve(listener: TcpListener, hub: Arc<PushHub>, decoder: Arc<Decoder>, max_clients: usize) {
+    let clients = Arc::new(AtomicUsize::new(0));
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(mut stream) => {
+                // Clients are counted from the connection, before they are
+                // authenticated, because each one holds a thread
+                if clients.fetch_add(1, Ordering::SeqCst) >= max_clients {
+                    clients.fetch_sub(1, Ordering::SeqCst);
+                    log::warn!("Push server is full, a client is rejected");
+                    let _ = stream.write_all(
+                        b"HTTP/1.1 503 Service Unavailable\r\n\
+                        Content-Length: 0\r\n\
+                        Connection: close\r\n\r\n",
+                    );
+                    continue;
+                }
+
+                let hub = hub.clone();
+                let decoder = decoder.clone();
+                let clients = clients.clone();
+                std::thread::spawn(move || {
+                    if let Err(err) = handle_client(stream, &hub, &decoder) {
+                        log::debug!("Push client disconnected: {}", err);
+                    }
+                    clients.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+            Err(err) => log::error!("Push server cannot accept a client: {}", err),
+        }
+    }
+}
+
+fn handle_client(mut stream: TcpStream, hub: &PushHub, decoder: &Decoder) -> std::io::Result<()> {
+    stream.set_write_timeout(Some(Duration::from_secs(WRITE_TIMEOUT_SECONDS)))?;
+
+    let deadline = Instant::now() + Duration::from_secs(REQUEST_TIMEOUT_SECONDS);
+    let request = RequestReader {
+        stream: &stream,
+        deadline,
+    };
+    let user = read_request_target(request).and_then(|target| authenticate(&target, decoder));
+
+    let user = match user {
+        Some(user) => user,
+        None => {
+            return stream.write_all(
+                b"HTTP/1.1 401 Unauthorized\r\n\
+                Content-Length: 0\r\n\
+                Connection: close\r\n\r\n",
+            );
+        }
+    };
+
+    // The UI is served by Rocket, from another origin
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\n\
+        Content-Type: text/event-stream\r\n\
+        Cache-Control: no-cache\r\n\
+        Access-Control-Allow-Origin: {}\r\n\r\n",
+        site_origin()
+    )?;
+    stream.flush()?;
+
+    // Unsubscribed on return, even on errors
+    let subscription = hub.subscribe(user);
+    let keep_alive = Duration::from_secs(KEEP_ALIVE_SECONDS);
+
+    loop {
+        match subscription.events.recv_timeout(keep_alive) {
+            Ok(data) => write!(stream, "data: {}\n\n", data)?,
+            Err(RecvTimeoutError::Timeout) => stream.write_all(b": keep-alive\n\n")?,
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+        stream.flush()?;
+    }
+}
+
+/// The origin of the UI, e.g. `https://devand.dev`
+fn site_origin() -> &'static str {
+    BASE_URL.unwrap_or(DEFAULT_BASE_URL).trim_end_matches('/')
+}
+
+/// Reads the request until the deadline. The read timeout of the stream
+/// only applies to each read, so a slow client could keep sending a byte at
+/// a time.
+struct RequestReader<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl Read for RequestReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let timeout = self
+            .deadline
+            .checked_duration_since(Instant::now())
+            .filter(|timeout| *timeout > Duration::from_millis(0))
+            .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::TimedOut))?;
+        self.stream.set_read_timeout(Some(timeout))?;
+        self.stream.read(buf)
+    }
+}
+
+/// Read the request head and return its target, e.g. `/?token=...`. Only
+/// GET requests are accepted and headers are ignored.
+fn read_request_target(request: impl Read) -> Option<String> {
+    let mut reader = BufReader::new(request.take(MAX_REQUEST_BYTES));
+    let mut line = String::new();
+
+    reader.read_line(&mut line).ok()?;
+    let mut request_line = line.split_whitespace();
+    let target = match (request_line.next(), request_line.next()) {
+        (Some("GET"), Some(target)) => target.to_string(),
+        _ => return None,
+    };
+
+    for _ in 0..MAX_HEADER_LINES {
+        line.clear();
+        match reader.read_line(&mut line) {
+            Ok(0) | Err(_) => return None,
+            Ok(_) if line.trim_end().is_empty() => return Some(target),
+            Ok(_) => {}
+        }
+    }
+
+    None
+}
+
+/// Decode the user from the `token` parameter of the query
+fn authenticate(target: &str, decoder: &Decoder) -> Option<UserId> {
+    let (_, query) = target.split_once('?')?;
+    let token = query
+        .split('&')
+        .find_map(|param| param.strip_prefix("token="))?;
+    let token: SignedToken = token.to_string().into();
+    let PushChannel { user_id } = PushChannel::try_from_token(&token, decoder)?;
+    Some(UserId(user_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use devand_crypto::Encoder;
+
+    #[test]
+    fn authenticate_with_token_in_query() {
+        let encoder = Encoder::new_from_secret(b"secret");
+        let decoder = Decoder::new_from_secret(b"secret");
+        let token = PushChannel { user_id: 42 }.sign(&encoder);
+
+        let target = format!("/events?v=1&token={}", token);
+        assert_eq!(authenticate(&target, &decoder), Some(UserId(42)));
+
+        assert_eq!(authenticate("/events", &decoder), None);
+        assert_eq!(authenticate("/events?token=invalid", &decoder), None);
+
+        let other_decoder = Decoder::new_from_secret(b"other");
+        assert_eq!(authenticate(&target, &other_decoder), None);
+    }
+
+    #[test]
+    fn request_head_is_limited() {
+        let request = "GET /?token=abc HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let target = read_request_target(request.as_bytes());
+        assert_eq!(target, Some("/?token=abc".to_string()));
+
+        let header = "a".repeat(MAX_REQUEST_BYTES as usize);
+        let request = format!("GET / HTTP/1.1\r\nX-Long: {}\r\n\r\n", header);
+        assert_eq!(read_request_target(request.as_bytes()), None);
+
+        let request = "POST / HTTP/1.1\r\n\r\n";
+        assert_eq!(read_request_target(request.as_bytes()), None);
+    }
+
+    #[test]
+    fn events_are_sent_only_to_connected_members() {
+        let hub = PushHub::default();
+        let first = hub.subscribe(UserId(1));
+        let second = hub.subscribe(UserId(2));
+
+        hub.send(UserId(1), &ChatEvent::UnreadMessages(3));
+        assert_eq!(first.events.try_recv().unwrap(), r#"{"unread_messages":3}"#);
+        assert!(second.events.try_recv().is_err());
+
+        // Removed as soon as the client disconnects
+        drop(second);
+        assert!(!hub.is_connected(UserId(2)));
+        assert!(hub.is_connected(UserId(1)));
+    }
+}
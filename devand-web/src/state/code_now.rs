@@ -4,7 +4,7 @@ use std::time::{Duration, Instant};
 
 #[derive(Clone)]
 pub struct CodeNowUsersMap {
-    users: BTreeMap<UserId, (Instant, PublicUserProfile)>,
+    users: BTreeMap<UserId, (Instant, User)>,
     last_clear: Instant,
 }
 
@@ -22,10 +22,8 @@ impl CodeNowUsersMap {
     const CLEAR_INTERVAL: Duration = Duration::from_secs(30);
 
     fn add(&mut self, u: User) {
-        let id = u.id;
-        let profile = PublicUserProfile::from(u);
         let now = Instant::now();
-        self.users.insert(id, (now, profile));
+        self.users.insert(u.id, (now, u));
     }
 
     pub fn touch(&mut self, u: User) -> bool {
@@ -64,17 +62,32 @@ impl CodeNowUsersMap {
             self.users.remove(&id);
         }
     }
-}
 
-impl From<CodeNowUsersMap> for CodeNowUsers {
-    fn from(m: CodeNowUsersMap) -> Self {
-        CodeNowUsers(m.users.into_iter().map(|(_k, v)| v.1).collect())
+    /// Users who can code now with `user`. Users who require a common
+    /// spoken language are left out if they have none with `user`, since
+    /// their settings are not public.
+    pub fn users_for(&self, user: &User) -> CodeNowUsers {
+        let spoken_languages = &user.settings.spoken_languages;
+
+        let users = self
+            .users
+            .values()
+            .map(|(_, u)| u)
+            .filter(|u| u.settings.accepts_spoken_languages(spoken_languages))
+            .map(|u| PublicUserProfile::from(u.clone()))
+            // Users on vacation are not available for coding now
+            .filter(|u| u.vacation.is_none())
+            .collect();
+
+        CodeNowUsers(users)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use devand_core::{SpokenLanguage, SpokenLanguages};
+    use std::collections::BTreeSet;
 
     #[test]
     fn add_user() {
@@ -83,4 +96,35 @@ mod tests {
         m.add(user.clone());
         assert!(m.contains(&user));
     }
+
+    #[test]
+    fn exclude_users_on_vacation() {
+        let mut m = CodeNowUsersMap::default();
+        let mut user = devand_core::mock::user();
+        user.settings.vacation_mode = true;
+        m.add(user.clone());
+        let users = m.users_for(&user);
+        assert!(users.0.is_empty());
+    }
+
+    fn spoken_language(language: SpokenLanguage) -> SpokenLanguages {
+        std::iter::once(language).collect::<BTreeSet<_>>().into()
+    }
+
+    #[test]
+    fn exclude_users_without_required_spoken_language() {
+        let mut m = CodeNowUsersMap::default();
+        let mut user = devand_core::mock::user();
+        user.settings.spoken_languages = spoken_language(SpokenLanguage::English);
+        user.settings.require_common_spoken_language = true;
+        m.add(user);
+
+        let mut other = devand_core::mock::user_with_username("other");
+        other.id = UserId(2);
+        other.settings.spoken_languages = spoken_language(SpokenLanguage::Italian);
+        assert!(m.users_for(&other).0.is_empty());
+
+        other.settings.spoken_languages = spoken_language(SpokenLanguage::English);
+        assert_eq!(m.users_for(&other).0.len(), 1);
+    }
 }
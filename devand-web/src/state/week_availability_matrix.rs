@@ -1,5 +1,6 @@
-use devand_core::schedule_matcher::WeekScheduleMatrix;
-use devand_core::{Availability, UserId};
+use chrono::{DateTime, Utc};
+use devand_core::schedule_matcher::{MatchingProfile, WeekScheduleMatrix};
+use devand_core::{Availability, User};
 use rocket_contrib::databases::diesel;
 
 #[derive(Default)]
@@ -8,34 +9,60 @@ pub struct WeekScheduleMatrixCache {
 }
 
 impl WeekScheduleMatrixCache {
-    pub fn get(&self) -> &WeekScheduleMatrix {
-        self.data.as_ref().unwrap()
-    }
-
     pub fn init(&mut self, conn: &diesel::PgConnection) {
         // TODO [optimization] load only needed data
         let users = devand_db::load_users(conn).expect("Cannot load users from database");
 
         let schedules: Vec<_> = users
-            .into_iter()
+            .iter()
+            .cloned()
             .filter_map(|u| {
                 let user_id = u.id;
                 let schedule = u.settings.schedule;
+                let time_zone = *u.settings.time_zone;
                 if let Availability::Weekly(week_schedule) = schedule {
-                    Some((user_id, week_schedule))
+                    Some((user_id, week_schedule, time_zone))
                 } else {
                     None
                 }
             })
             .collect();
 
-        let wsm = WeekScheduleMatrix::from(schedules);
+        let mut wsm = WeekScheduleMatrix::from_local(schedules, Utc::now());
+
+        for u in users {
+            if let Some(vacation) = u.settings.enabled_vacation() {
+                wsm.set_vacation(u.id, Some(vacation));
+            }
+            wsm.set_profile(u.id, MatchingProfile::from(&u));
+        }
+
         self.data = Some(wsm);
     }
 
-    pub fn update(&mut self, user: UserId, availability: &Availability) {
+    /// Get the matrix, if schedules are already projected on the `days`
+    /// days from `date`
+    pub fn get_projected(&self, date: DateTime<Utc>, days: usize) -> Option<&WeekScheduleMatrix> {
+        self.data
+            .as_ref()
+            .filter(|wsm| wsm.is_projected_on(date, days))
+    }
+
+    /// Get the matrix, with schedules projected on the `days` days from
+    /// `date`
+    pub fn get_period(&mut self, date: DateTime<Utc>, days: usize) -> &WeekScheduleMatrix {
+        let wsm = self.data.as_mut().unwrap();
+        wsm.set_period(date, days);
+        wsm
+    }
+
+    /// Update user's availability, vacation and matching profile
+    pub fn update(&mut self, user: &User) {
         if let Some(wsm) = &mut self.data {
-            wsm.update(user, availability);
+            let settings = &user.settings;
+            wsm.update(user.id, &settings.schedule, *settings.time_zone);
+            wsm.set_vacation(user.id, settings.enabled_vacation());
+            wsm.set_profile(user.id, MatchingProfile::from(user));
         }
     }
 }